@@ -0,0 +1,536 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Context, Result};
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use proc_macro2::{Ident, Literal, Span, TokenStream};
+use quote::{format_ident, quote};
+use scale_info::{form::PortableForm, PortableRegistry, Type, TypeDef, TypeDefPrimitive};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use utils::substrate::ContractArtifacts;
+
+/// Keywords that can be used as identifiers once raw, e.g. a field named `type`.
+const RAW_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop",
+    "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "static",
+    "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual",
+    "where", "while", "yield",
+];
+/// Keywords that cannot be raw identifiers, suffixed with `_` instead.
+const RESERVED_KEYWORDS: &[&str] = &["crate", "self", "Self", "super"];
+
+/// Generates a typed client of a contract from its `.contract` bundle or metadata
+/// `.json`
+///
+/// The client wraps a [`utils::substrate::ContractInstance`] with one async method
+/// per message, taking and returning Rust types generated from the metadata. From a
+/// build script:
+///
+/// ```ignore
+/// ink_queries_codegen::Generator::new("flipper.contract").write_to_out_dir("flipper.rs")?;
+/// ```
+///
+/// then `include!(concat!(env!("OUT_DIR"), "/flipper.rs"));` in the crate.
+pub struct Generator {
+    artifact: PathBuf,
+    client_name: Option<String>,
+    crate_path: String,
+}
+
+impl Generator {
+    pub fn new(artifact: impl Into<PathBuf>) -> Self {
+        Self {
+            artifact: artifact.into(),
+            client_name: None,
+            crate_path: "::utils".to_owned(),
+        }
+    }
+
+    /// Sets the name of the client struct, the artifact file name by default.
+    pub fn client_name(mut self, client_name: impl Into<String>) -> Self {
+        self.client_name = Some(client_name.into());
+        self
+    }
+
+    /// Sets the path of this library's query crate in the generated code, `::utils`
+    /// by default.
+    pub fn crate_path(mut self, crate_path: impl Into<String>) -> Self {
+        self.crate_path = crate_path.into();
+        self
+    }
+
+    /// Generates the client and the types of its messages.
+    pub fn generate(&self) -> Result<TokenStream> {
+        let artifacts = ContractArtifacts::from_manifest_or_file(None, Some(&self.artifact))?;
+        let transcoder = artifacts.contract_transcoder()?;
+        let metadata = transcoder.metadata();
+        let utils: TokenStream = self
+            .crate_path
+            .parse()
+            .map_err(|err| anyhow!("Invalid crate path {}: {err}", self.crate_path))?;
+        let typed = quote!(#utils::substrate::typed);
+
+        let client_name = match &self.client_name {
+            Some(name) => name.clone(),
+            None => file_stem(&self.artifact)?.to_upper_camel_case(),
+        };
+        let client = ident(&client_name);
+        let mut types = TypeGen::new(metadata.registry(), utils.clone());
+
+        let methods = metadata
+            .spec()
+            .messages()
+            .iter()
+            .map(|message| {
+                let label = message.label();
+                let method = ident(&label.replace("::", "_").to_snake_case());
+                let docs = message.docs();
+                let args = message
+                    .args()
+                    .iter()
+                    .map(|arg| ident(&arg.label().to_snake_case()))
+                    .collect::<Vec<_>>();
+                let arg_types = message
+                    .args()
+                    .iter()
+                    .map(|arg| types.rust_type(arg.ty().ty().id))
+                    .collect::<Vec<_>>();
+                let (ret, lang_result) = match message.return_type().opt_type() {
+                    Some(ty) => match lang_result_ok(metadata.registry(), ty.ty().id) {
+                        Some(ok) => (types.rust_type(ok), true),
+                        None => (types.rust_type(ty.ty().id), false),
+                    },
+                    None => (quote!(()), false),
+                };
+                quote! {
+                    #(#[doc = #docs])*
+                    pub async fn #method(&self, #(#args: #arg_types),*)
+                        -> ::std::result::Result<#ret, #utils::substrate::ErrorVariant>
+                    {
+                        #[allow(unused_imports)]
+                        use #typed::ToArg;
+                        #typed::query(
+                            &self.instance,
+                            #label,
+                            vec![#(#args.to_arg()),*],
+                            #lang_result,
+                        )
+                        .await
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let definitions = types.definitions;
+        let client_doc = format!("Typed client of the {client_name} contract.");
+        Ok(quote! {
+            #(#definitions)*
+
+            #[doc = #client_doc]
+            pub struct #client {
+                instance: #utils::substrate::ContractInstance,
+            }
+
+            impl #client {
+                pub fn new(instance: #utils::substrate::ContractInstance) -> Self {
+                    Self { instance }
+                }
+
+                pub fn instance(&self) -> &#utils::substrate::ContractInstance {
+                    &self.instance
+                }
+
+                #(#methods)*
+            }
+        })
+    }
+
+    /// Writes the generated code to `file_name` in `OUT_DIR`, from a build script.
+    pub fn write_to_out_dir(&self, file_name: &str) -> Result<PathBuf> {
+        let out_dir = std::env::var("OUT_DIR").context("OUT_DIR is only set in build scripts")?;
+        let path = Path::new(&out_dir).join(file_name);
+        fs::write(&path, self.generate()?.to_string())?;
+        println!("cargo:rerun-if-changed={}", self.artifact.display());
+        Ok(path)
+    }
+}
+
+/// Maps the types of the metadata to Rust types, generating structs and enums.
+struct TypeGen<'a> {
+    registry: &'a PortableRegistry,
+    utils: TokenStream,
+    names: HashMap<u32, Ident>,
+    used_names: HashSet<String>,
+    /// Types being generated, whose recursive uses fall back to `Value`.
+    in_progress: HashSet<u32>,
+    definitions: Vec<TokenStream>,
+}
+
+impl<'a> TypeGen<'a> {
+    fn new(registry: &'a PortableRegistry, utils: TokenStream) -> Self {
+        Self {
+            registry,
+            utils,
+            names: HashMap::new(),
+            used_names: HashSet::new(),
+            in_progress: HashSet::new(),
+            definitions: Vec::new(),
+        }
+    }
+
+    fn value_type(&self) -> TokenStream {
+        let utils = &self.utils;
+        quote!(#utils::substrate::typed::Value)
+    }
+
+    fn rust_type(&mut self, id: u32) -> TokenStream {
+        let registry = self.registry;
+        let ty = match registry.resolve(id) {
+            Some(ty) => ty,
+            None => return self.value_type(),
+        };
+        let name = ty.path.segments.last().map(String::as_str);
+        let param = |index: usize| {
+            ty.type_params
+                .get(index)
+                .and_then(|param| param.ty.as_ref())
+                .map(|ty| ty.id)
+        };
+
+        match &ty.type_def {
+            TypeDef::Primitive(primitive) => match primitive {
+                TypeDefPrimitive::Bool => quote!(bool),
+                TypeDefPrimitive::Char => quote!(char),
+                TypeDefPrimitive::Str => quote!(String),
+                TypeDefPrimitive::U8 => quote!(u8),
+                TypeDefPrimitive::U16 => quote!(u16),
+                TypeDefPrimitive::U32 => quote!(u32),
+                TypeDefPrimitive::U64 => quote!(u64),
+                TypeDefPrimitive::U128 => quote!(u128),
+                TypeDefPrimitive::I8 => quote!(i8),
+                TypeDefPrimitive::I16 => quote!(i16),
+                TypeDefPrimitive::I32 => quote!(i32),
+                TypeDefPrimitive::I64 => quote!(i64),
+                TypeDefPrimitive::I128 => quote!(i128),
+                TypeDefPrimitive::U256 | TypeDefPrimitive::I256 => self.value_type(),
+            },
+            TypeDef::Compact(compact) => self.rust_type(compact.type_param.id),
+            TypeDef::Sequence(sequence) => {
+                let elem = self.rust_type(sequence.type_param.id);
+                quote!(Vec<#elem>)
+            }
+            TypeDef::Array(array) => {
+                let elem = self.rust_type(array.type_param.id);
+                let len = Literal::usize_unsuffixed(array.len as usize);
+                quote!([#elem; #len])
+            }
+            TypeDef::Tuple(tuple) if tuple.fields.is_empty() => quote!(()),
+            TypeDef::Tuple(tuple) if (2..=4).contains(&tuple.fields.len()) => {
+                let elems = tuple
+                    .fields
+                    .iter()
+                    .map(|field| self.rust_type(field.id))
+                    .collect::<Vec<_>>();
+                quote!((#(#elems),*))
+            }
+            TypeDef::Variant(_) if name == Some("Option") => match param(0) {
+                Some(some) => {
+                    let some = self.rust_type(some);
+                    quote!(Option<#some>)
+                }
+                None => self.value_type(),
+            },
+            TypeDef::Variant(_) if name == Some("Result") => match (param(0), param(1)) {
+                (Some(ok), Some(err)) => {
+                    let ok = self.rust_type(ok);
+                    let err = self.rust_type(err);
+                    quote!(::std::result::Result<#ok, #err>)
+                }
+                _ => self.value_type(),
+            },
+            TypeDef::Composite(_) if name == Some("AccountId") => {
+                let utils = &self.utils;
+                quote!(#utils::substrate::AccountId)
+            }
+            TypeDef::Composite(_) if name == Some("Hash") => quote!([u8; 32]),
+            // Reshaped by the decoder, see `DecodeOptions`.
+            TypeDef::Composite(_) if matches!(name, Some("BTreeMap" | "BTreeSet" | "Mapping")) => {
+                self.value_type()
+            }
+            TypeDef::Composite(_) | TypeDef::Variant(_) => self.define(id, ty),
+            _ => self.value_type(),
+        }
+    }
+
+    /// Generates a struct or enum for the type `id`, returning its name.
+    fn define(&mut self, id: u32, ty: &Type<PortableForm>) -> TokenStream {
+        if let Some(name) = self.names.get(&id) {
+            return quote!(#name);
+        }
+        if !self.in_progress.insert(id) {
+            // Recursive types would need boxing.
+            return self.value_type();
+        }
+
+        let base = ty
+            .path
+            .segments
+            .last()
+            .map_or_else(|| "Type".to_owned(), |name| name.to_upper_camel_case());
+        let label = ty.path.segments.last().cloned().unwrap_or_default();
+        let type_name = if self.used_names.contains(&base) {
+            // Generic types instantiated with different parameters share a name.
+            format!("{base}{id}")
+        } else {
+            base
+        };
+        self.used_names.insert(type_name.clone());
+        let name = ident(&type_name);
+        let typed = {
+            let utils = &self.utils;
+            quote!(#utils::substrate::typed)
+        };
+        let docs = &ty.docs;
+
+        let definition = match &ty.type_def {
+            TypeDef::Variant(variant) => {
+                let mut variants = Vec::new();
+                let mut from_arms = Vec::new();
+                let mut into_arms = Vec::new();
+                for variant in &variant.variants {
+                    let variant_name = &variant.name;
+                    let variant_ident = ident(variant_name);
+                    let fields = self.fields(&variant.fields);
+                    variants.push(fields.declaration(&variant_ident, false));
+                    let from_value = fields.build_expr(quote!(Self::#variant_ident));
+                    from_arms.push(quote!(#variant_name => Ok(#from_value)));
+                    into_arms.push(fields.arg_arm(
+                        &typed,
+                        quote!(Self::#variant_ident),
+                        variant_name,
+                    ));
+                }
+                quote! {
+                    #(#[doc = #docs])*
+                    #[derive(Debug, Clone, PartialEq)]
+                    pub enum #name {
+                        #(#variants,)*
+                    }
+
+                    impl #typed::FromValue for #name {
+                        #[allow(unused_mut, unused_variables)]
+                        fn from_value(value: #typed::Value) -> #typed::Result<Self> {
+                            let (variant, mut fields) = #typed::Fields::variant(value, #type_name)?;
+                            match variant.as_str() {
+                                #(#from_arms,)*
+                                other => Err(#typed::Error::msg(format!(
+                                    "Unknown variant {} of {}",
+                                    other, #type_name
+                                ))),
+                            }
+                        }
+                    }
+
+                    impl #typed::ToArg for #name {
+                        fn to_arg(&self) -> String {
+                            match self {
+                                #(#into_arms,)*
+                            }
+                        }
+                    }
+                }
+            }
+            TypeDef::Composite(composite) => {
+                let fields = self.fields(&composite.fields);
+                let declaration = fields.declaration(&name, true);
+                let semicolon = match fields {
+                    StructFields::Named(_) => quote!(),
+                    _ => quote!(;),
+                };
+                let from_value = fields.build_expr(quote!(Self));
+                let to_arg = fields.arg_arm(&typed, quote!(Self), &label);
+                quote! {
+                    #(#[doc = #docs])*
+                    #[derive(Debug, Clone, PartialEq)]
+                    pub struct #declaration #semicolon
+
+                    impl #typed::FromValue for #name {
+                        #[allow(unused_mut, unused_variables)]
+                        fn from_value(value: #typed::Value) -> #typed::Result<Self> {
+                            let mut fields = #typed::Fields::new(value, #type_name)?;
+                            Ok(#from_value)
+                        }
+                    }
+
+                    impl #typed::ToArg for #name {
+                        fn to_arg(&self) -> String {
+                            match self {
+                                #to_arg,
+                            }
+                        }
+                    }
+                }
+            }
+            _ => unreachable!("only composites and variants are defined"),
+        };
+
+        self.in_progress.remove(&id);
+        self.names.insert(id, name.clone());
+        self.definitions.push(definition);
+        quote!(#name)
+    }
+
+    fn fields(&mut self, fields: &[scale_info::Field<PortableForm>]) -> StructFields {
+        if fields.is_empty() {
+            StructFields::Unit
+        } else if fields.iter().all(|field| field.name.is_some()) {
+            StructFields::Named(
+                fields
+                    .iter()
+                    .map(|field| {
+                        let name = field.name.clone().unwrap_or_default();
+                        (name, self.rust_type(field.ty.id))
+                    })
+                    .collect(),
+            )
+        } else {
+            StructFields::Unnamed(
+                fields
+                    .iter()
+                    .map(|field| self.rust_type(field.ty.id))
+                    .collect(),
+            )
+        }
+    }
+}
+
+/// Fields of a struct or enum variant, with their Rust types.
+enum StructFields {
+    Unit,
+    Named(Vec<(String, TokenStream)>),
+    Unnamed(Vec<TokenStream>),
+}
+
+impl StructFields {
+    /// Declaration of the struct or variant `name`, with `pub` fields for structs.
+    fn declaration(&self, name: &Ident, public: bool) -> TokenStream {
+        let vis = if public { quote!(pub) } else { quote!() };
+        match self {
+            StructFields::Unit => quote!(#name),
+            StructFields::Named(fields) => {
+                let idents = fields.iter().map(|(field, _)| field_ident(field));
+                let types = fields.iter().map(|(_, ty)| ty);
+                quote!(#name { #(#vis #idents: #types),* })
+            }
+            StructFields::Unnamed(types) => quote!(#name(#(#vis #types),*)),
+        }
+    }
+
+    /// Expression building `path` from the `fields` of a decoded value.
+    fn build_expr(&self, path: TokenStream) -> TokenStream {
+        match self {
+            StructFields::Unit => quote!(#path),
+            StructFields::Named(fields) => {
+                let idents = fields.iter().map(|(field, _)| field_ident(field));
+                let names = fields.iter().map(|(field, _)| field);
+                quote!(#path { #(#idents: fields.take(#names)?),* })
+            }
+            StructFields::Unnamed(types) => {
+                let next = types.iter().map(|_| quote!(fields.next_field()?));
+                quote!(#path(#(#next),*))
+            }
+        }
+    }
+
+    /// Match arm rendering the struct or variant at `path`, labelled `label`.
+    fn arg_arm(&self, typed: &TokenStream, path: TokenStream, label: &str) -> TokenStream {
+        match self {
+            StructFields::Unit => quote!(#path => #typed::tuple_arg(#label, &[])),
+            StructFields::Named(fields) => {
+                let idents = fields
+                    .iter()
+                    .map(|(field, _)| field_ident(field))
+                    .collect::<Vec<_>>();
+                let names = fields.iter().map(|(field, _)| field);
+                quote! {
+                    #path { #(#idents),* } => #typed::named_arg(
+                        #label,
+                        &[#((#names, #typed::ToArg::to_arg(#idents))),*],
+                    )
+                }
+            }
+            StructFields::Unnamed(types) => {
+                let bindings = (0..types.len())
+                    .map(|index| format_ident!("field{}", index))
+                    .collect::<Vec<_>>();
+                quote! {
+                    #path(#(#bindings),*) => #typed::tuple_arg(
+                        #label,
+                        &[#(#typed::ToArg::to_arg(#bindings)),*],
+                    )
+                }
+            }
+        }
+    }
+}
+
+/// Type of the `Ok` value of ink's `Result<T, LangError>` return type, if `ty` is one.
+fn lang_result_ok(registry: &PortableRegistry, ty: u32) -> Option<u32> {
+    let ty = registry.resolve(ty)?;
+    let variant = match &ty.type_def {
+        TypeDef::Variant(variant) if ty.path.segments.last()? == "Result" => variant,
+        _ => return None,
+    };
+    let field = |name: &str| {
+        variant
+            .variants
+            .iter()
+            .find(|variant| variant.name == name)
+            .and_then(|variant| variant.fields.first())
+            .map(|field| field.ty.id)
+    };
+    let err = registry.resolve(field("Err")?)?;
+    if err.path.segments.last()? != "LangError" {
+        return None;
+    }
+    field("Ok")
+}
+
+fn field_ident(name: &str) -> Ident {
+    ident(&name.to_snake_case())
+}
+
+fn ident(name: &str) -> Ident {
+    let name = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    if RAW_KEYWORDS.contains(&name.as_str()) {
+        Ident::new_raw(&name, Span::call_site())
+    } else if RESERVED_KEYWORDS.contains(&name.as_str()) {
+        format_ident!("{}_", name)
+    } else {
+        Ident::new(&name, Span::call_site())
+    }
+}
+
+fn file_stem(path: &Path) -> Result<&str> {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .with_context(|| format!("Invalid artifact path {}", path.display()))
+}
@@ -1,19 +1,47 @@
 use std::vec;
 
-/// Testing cli
+use utils::substrate::{BlockHash, CallOptions};
+
+/// Environment variable holding the secret key URI of the querying account.
+const SURI_ENV: &str = "SUBSTRATE_SURI";
 
+/// Testing cli
+///
+/// Pass `--debug` to print the debug messages of the contract, and `--at <block hash>`
+/// to query the state of a past block.
 fn main() {
-    let contract = utils::substrate::SubstrateContract::from_account(
-        "fix enable minimum debate purse act congress poet give alley inch town".to_string(), // sample seed, NEVER expose it in clear
-        None,
-    )
-    .unwrap();
+    let args: Vec<String> = std::env::args().collect();
+    let debug = args.iter().any(|arg| arg == "--debug");
+    let at = args.iter().position(|arg| arg == "--at").map(|i| {
+        args.get(i + 1)
+            .expect("--at expects a block hash")
+            .parse::<BlockHash>()
+            .expect("Invalid block hash")
+    });
+
+    let suri = std::env::var(SURI_ENV).unwrap_or_else(|_| {
+        panic!(
+            "Set {} to the secret key URI of the querying account",
+            SURI_ENV
+        )
+    });
+    let contract = utils::substrate::SubstrateContract::from_account(suri, None).unwrap();
+
+    let mut options = CallOptions::default();
+    if let Some(at) = at {
+        options = options.at(at);
+    }
 
     // Prepare for dummy phala call
     let nonce = [1; 32];
-    let value = contract
+    let result = contract
         .instance
-        .call_msg("get", vec![], Some(nonce))
+        .query_msg("get", vec![], Some(nonce), options)
         .unwrap();
-    println!("{}", value);
+    println!("{}", result.value);
+
+    if debug {
+        println!("Debug messages:\n{}", result.debug_message);
+        println!("Timings: {:?}", result.timings);
+    }
 }
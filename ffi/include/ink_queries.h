@@ -0,0 +1,55 @@
+#ifndef INK_QUERIES_H
+#define INK_QUERIES_H
+
+/* Generated by cbindgen, do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Queries a message of an ink or Phala contract.
+ *
+ * `request` is a JSON object with the fields `artifact`, `address`, `message`, and
+ * optionally `url`, `chain`, `args`, `suri`, `password`, `origin` and `nonce`.
+ * Returns `{"value": .., "debug_message": ..}` or `{"error": ..}`.
+ *
+ * # Safety
+ *
+ * `request` must be a valid nul-terminated string. The returned string must be
+ * released with [`ink_queries_free`].
+ */
+char *ink_queries_call(const char *request);
+
+/**
+ * Decodes an event emitted by an ink contract.
+ *
+ * `request` is a JSON object with the fields `artifact`, `topics` and `data`, the
+ * topics and data being 0x-hex. Returns `{"value": ..}` or `{"error": ..}`.
+ *
+ * # Safety
+ *
+ * `request` must be a valid nul-terminated string. The returned string must be
+ * released with [`ink_queries_free`].
+ */
+char *ink_queries_decode_event(const char *request);
+
+/**
+ * Releases a string returned by this library.
+ *
+ * # Safety
+ *
+ * `response` must have been returned by this library and not released yet.
+ */
+void ink_queries_free(char *response);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* INK_QUERIES_H */
@@ -0,0 +1,184 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value as JsonValue};
+use sp_core::{Bytes, H256};
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr;
+use std::str::FromStr;
+
+use utils::substrate::address::Address;
+use utils::substrate::chain::Chain;
+use utils::substrate::signer::{ReadOnlySigner, SignerProvider};
+use utils::substrate::{
+    json_value, AccountId, CallOptions, ContractArtifacts, ContractInstance, InkMeta,
+    SubstrateBaseConfig,
+};
+
+#[derive(Deserialize)]
+struct CallRequest {
+    /// Path of the `.contract` bundle or metadata `.json`.
+    artifact: PathBuf,
+    address: String,
+    /// Node url, the default url of the chain if not set.
+    url: Option<String>,
+    chain: Option<String>,
+    message: String,
+    #[serde(default)]
+    args: Vec<String>,
+    /// Secret key URI of the querying account, required by Phala queries.
+    suri: Option<String>,
+    password: Option<String>,
+    /// Account dry-running ink messages when no `suri` is set.
+    origin: Option<String>,
+    /// Hex nonce of Phala queries, random if not set.
+    nonce: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DecodeEventRequest {
+    artifact: PathBuf,
+    topics: Vec<H256>,
+    data: Bytes,
+}
+
+/// Queries a message of an ink or Phala contract.
+///
+/// `request` is a JSON object with the fields `artifact`, `address`, `message`, and
+/// optionally `url`, `chain`, `args`, `suri`, `password`, `origin` and `nonce`.
+/// Returns `{"value": .., "debug_message": ..}` or `{"error": ..}`.
+///
+/// # Safety
+///
+/// `request` must be a valid nul-terminated string. The returned string must be
+/// released with [`ink_queries_free`].
+#[no_mangle]
+pub unsafe extern "C" fn ink_queries_call(request: *const c_char) -> *mut c_char {
+    respond(catch_panic(|| parse(request).and_then(call)))
+}
+
+/// Decodes an event emitted by an ink contract.
+///
+/// `request` is a JSON object with the fields `artifact`, `topics` and `data`, the
+/// topics and data being 0x-hex. Returns `{"value": ..}` or `{"error": ..}`.
+///
+/// # Safety
+///
+/// `request` must be a valid nul-terminated string. The returned string must be
+/// released with [`ink_queries_free`].
+#[no_mangle]
+pub unsafe extern "C" fn ink_queries_decode_event(request: *const c_char) -> *mut c_char {
+    respond(catch_panic(|| parse(request).and_then(decode_event)))
+}
+
+/// Releases a string returned by this library.
+///
+/// # Safety
+///
+/// `response` must have been returned by this library and not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn ink_queries_free(response: *mut c_char) {
+    if !response.is_null() {
+        drop(CString::from_raw(response));
+    }
+}
+
+unsafe fn parse<T: for<'de> Deserialize<'de>>(request: *const c_char) -> Result<T> {
+    if request.is_null() {
+        return Err(anyhow!("Request is null"));
+    }
+    let request = CStr::from_ptr(request)
+        .to_str()
+        .context("Request is not valid UTF-8")?;
+    serde_json::from_str(request).context("Invalid request")
+}
+
+/// Turns a panic into an error, as unwinding across the C boundary is undefined.
+fn catch_panic(f: impl FnOnce() -> Result<JsonValue>) -> Result<JsonValue> {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_owned());
+        Err(anyhow!("Panicked: {message}"))
+    })
+}
+
+fn respond(response: Result<JsonValue>) -> *mut c_char {
+    let response = response.unwrap_or_else(|err| json!({ "error": format!("{err:#}") }));
+    // Serialized JSON escapes nul characters, so the conversion cannot fail.
+    CString::new(response.to_string()).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+fn call(request: CallRequest) -> Result<JsonValue> {
+    let chain = match &request.chain {
+        Some(chain) => Chain::from_str(chain)?,
+        None => Chain::default(),
+    };
+    let address = Address::parse_on(&chain, &request.address)?;
+    let url = request
+        .url
+        .or_else(|| chain.default_url().map(str::to_owned))
+        .context("Missing node url")?;
+
+    let signer: Box<dyn SignerProvider> = match (request.suri, &request.origin) {
+        (Some(suri), _) => Box::new(SubstrateBaseConfig::new(suri, request.password).signer()?),
+        (None, Some(origin)) => {
+            let origin = AccountId::from_str(origin)
+                .map_err(|err| anyhow!("Invalid origin {origin}: {err:?}"))?;
+            Box::new(ReadOnlySigner::new(origin))
+        }
+        (None, None) => anyhow::bail!("Either suri or origin must be set"),
+    };
+
+    let nonce = match &request.nonce {
+        Some(nonce) => {
+            let nonce = H256::from_str(nonce.trim_start_matches("0x"))
+                .map_err(|err| anyhow!("Invalid nonce: {err}"))?;
+            Some(nonce.0)
+        }
+        None => Some(rand::random()),
+    };
+
+    let meta = InkMeta::new(request.artifact, url, chain, address);
+    let contract = ContractInstance::new(meta, signer);
+    let result = contract
+        .query_msg(
+            &request.message,
+            request.args,
+            nonce,
+            CallOptions::default(),
+        )
+        .map_err(|err| anyhow!("{err}"))?;
+
+    Ok(json!({
+        "value": json_value(&result.value),
+        "debug_message": result.debug_message,
+    }))
+}
+
+fn decode_event(request: DecodeEventRequest) -> Result<JsonValue> {
+    let artifacts = ContractArtifacts::from_manifest_or_file(None, Some(&request.artifact))?;
+    let value = artifacts
+        .event_decoder()?
+        .decode_contract_event(&request.topics, &request.data)?;
+
+    Ok(json!({ "value": json_value(&value) }))
+}
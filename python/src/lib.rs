@@ -0,0 +1,161 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Context};
+use contract_transcode::Value;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{IntoPyDict, PyBytes, PyDict, PyList, PyTuple};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use utils::substrate::address::Address;
+use utils::substrate::chain::Chain;
+use utils::substrate::signer::{ReadOnlySigner, SignerProvider};
+use utils::substrate::{
+    AccountId, BlockHash, CallOptions, ContractInstance, InkMeta, SubstrateBaseConfig,
+};
+
+/// A deployed ink or Phala contract, queried with the messages of its artifact.
+///
+/// Phala queries are signed with `suri`, while ink queries can instead be
+/// dry-run as a read-only `origin`.
+#[pyclass(name = "Contract")]
+struct Contract {
+    instance: ContractInstance,
+}
+
+#[pymethods]
+impl Contract {
+    #[new]
+    #[pyo3(signature = (url, artifact, address, *, suri = None, password = None, origin = None, chain = None))]
+    fn new(
+        url: String,
+        artifact: PathBuf,
+        address: &str,
+        suri: Option<String>,
+        password: Option<String>,
+        origin: Option<&str>,
+        chain: Option<&str>,
+    ) -> PyResult<Self> {
+        let chain = match chain {
+            Some(chain) => Chain::from_str(chain)?,
+            None => Chain::default(),
+        };
+        let address = Address::parse_on(&chain, address)?;
+        let signer: Box<dyn SignerProvider> = match (suri, origin) {
+            (Some(suri), _) => Box::new(SubstrateBaseConfig::new(suri, password).signer()?),
+            (None, Some(origin)) => {
+                let origin = AccountId::from_str(origin)
+                    .map_err(|err| anyhow!("Invalid origin {origin}: {err:?}"))?;
+                Box::new(ReadOnlySigner::new(origin))
+            }
+            (None, None) => {
+                return Err(PyValueError::new_err("Either suri or origin must be set"));
+            }
+        };
+
+        let meta = InkMeta::new(artifact, url, chain, address);
+        Ok(Self {
+            instance: ContractInstance::new(meta, signer),
+        })
+    }
+
+    /// Queries the message `name` with `args`, returning its decoded value.
+    #[pyo3(signature = (name, args = Vec::new()))]
+    fn query(&self, py: Python<'_>, name: &str, args: Vec<String>) -> PyResult<PyObject> {
+        let nonce = Some(rand::random());
+        let result = py
+            .allow_threads(|| {
+                self.instance
+                    .query_msg(name, args, nonce, CallOptions::default())
+            })
+            .map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+        to_py(py, &result.value)
+    }
+
+    /// Decodes the events emitted by the contract in the hex block hash `block`, or
+    /// the latest block.
+    #[pyo3(signature = (block = None))]
+    fn events(&self, py: Python<'_>, block: Option<&str>) -> PyResult<Vec<PyObject>> {
+        let block = block
+            .map(|block| BlockHash::from_str(block.trim_start_matches("0x")))
+            .transpose()
+            .map_err(|err| PyValueError::new_err(format!("Invalid block hash: {err}")))?;
+        let events = py
+            .allow_threads(|| self.instance.events_at(block))
+            .context("Failed to read contract events")?;
+        events.iter().map(|event| to_py(py, event)).collect()
+    }
+}
+
+/// Converts a decoded value to Python, sequences becoming lists, and structs and
+/// enum variants with fields `{name: fields}` dicts, named fields being dicts, so
+/// that e.g. `Ok { .. }` and `Err { .. }` can be told apart.
+fn to_py(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
+    let object = match value {
+        Value::Bool(value) => (*value).into_py(py),
+        Value::Char(value) => (*value).into_py(py),
+        Value::UInt(value) => (*value).into_py(py),
+        Value::Int(value) => (*value).into_py(py),
+        Value::String(value) | Value::Literal(value) => value.as_str().into_py(py),
+        Value::Hex(hex) => PyBytes::new(py, hex.bytes()).into_py(py),
+        Value::Unit => py.None(),
+        Value::Seq(seq) => {
+            let elems = seq
+                .elems()
+                .iter()
+                .map(|elem| to_py(py, elem))
+                .collect::<PyResult<Vec<_>>>()?;
+            PyList::new(py, elems).into_py(py)
+        }
+        Value::Map(map) => {
+            let dict = PyDict::new(py);
+            for (key, value) in map.iter() {
+                let key = match key {
+                    Value::String(name) => name.as_str().into_py(py),
+                    key => to_py(py, key)?,
+                };
+                dict.set_item(key, to_py(py, value)?)?;
+            }
+            match map.ident() {
+                // Maps are named after their type, which tells nothing.
+                Some(name) if name != "BTreeMap" => [(name, dict)].into_py_dict(py).into_py(py),
+                _ => dict.into_py(py),
+            }
+        }
+        Value::Tuple(tuple) => {
+            let values = tuple
+                .values()
+                .map(|value| to_py(py, value))
+                .collect::<PyResult<Vec<_>>>()?;
+            let fields = match values.len() {
+                1 => values[0].clone_ref(py),
+                _ => PyTuple::new(py, values).into_py(py),
+            };
+            match tuple.ident() {
+                Some(variant) if tuple.values().count() == 0 => variant.into_py(py),
+                Some(variant) => [(variant, fields)].into_py_dict(py).into_py(py),
+                None => fields,
+            }
+        }
+    };
+    Ok(object)
+}
+
+#[pymodule]
+fn ink_queries(_py: Python<'_>, module: &PyModule) -> PyResult<()> {
+    module.add_class::<Contract>()?;
+    Ok(())
+}
@@ -0,0 +1,54 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use utils::substrate::fixtures::FixtureManifest;
+use utils::substrate::testing::TestNode;
+
+const DEFAULT_URL: &str = "ws://127.0.0.1:9944";
+const DEFAULT_OUT_PATH: &str = "fixtures.toml";
+
+/// Deploys the contracts of a fixture manifest to a development node
+///
+/// `deploy-fixtures <manifest> [--url <node url>] [--out <path>]` deploys each
+/// contract after the ones whose addresses its constructor takes, see
+/// `FixtureManifest`, and writes their addresses to `fixtures.toml` by default, one
+/// table per contract as read by `InkMeta::from_toml`.
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let option = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .map(|i| {
+                args.get(i + 1)
+                    .with_context(|| format!("{name} expects a value"))
+            })
+            .transpose()
+    };
+    let manifest = args
+        .get(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .context("Usage: deploy-fixtures <manifest> [--url <node url>] [--out <path>]")?;
+    let url = option("--url")?.map_or(DEFAULT_URL, String::as_str);
+    let out = PathBuf::from(option("--out")?.map_or(DEFAULT_OUT_PATH, String::as_str));
+
+    let manifest = FixtureManifest::load(Path::new(manifest))?;
+    let node = TestNode::connect(url)?;
+    let deployed = manifest.deploy(&node)?;
+    deployed.write(&out)?;
+    println!("{}", deployed.to_toml()?);
+    Ok(())
+}
@@ -0,0 +1,94 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Context, Result};
+
+use utils::substrate::secrets::SecretResolver;
+use utils::substrate::{CallOptions, ConfigFile, ContractInstance, SubstrateBaseConfig};
+
+/// Environment variable holding the secret key URI of the querying account, for
+/// profiles without `suri`.
+const SURI_ENV: &str = "SUBSTRATE_SURI";
+const MAX_GAS_INCREASE_FLAG: &str = "--max-gas-increase";
+const USAGE: &str = "Usage: dry-run-diff <profiles> <before> <after> <message> [args..] \
+    [--max-gas-increase <percent>]";
+
+/// Compares dry-runs of a message on two contracts of a profiles file
+///
+/// `before` and `after` name tables of the profiles file, in the format of the query
+/// server: typically the same contract deployed with its current and upgraded code,
+/// or on two nodes. Prints the differences in return value, gas consumed and
+/// storage deposit, and exits with an error if the gas consumed grew by more than
+/// `--max-gas-increase` percent.
+fn main() -> Result<()> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let max_gas_increase = match args.iter().position(|arg| arg == MAX_GAS_INCREASE_FLAG) {
+        Some(i) => {
+            let percent = args
+                .get(i + 1)
+                .with_context(|| format!("{MAX_GAS_INCREASE_FLAG} expects a percentage"))?
+                .parse::<f64>()
+                .context("Invalid gas increase percentage")?;
+            args.drain(i..i + 2);
+            Some(percent)
+        }
+        None => None,
+    };
+    if args.len() < 4 {
+        anyhow::bail!(USAGE);
+    }
+    let message_args = args.split_off(4);
+    let (path, before, after, message) = (&args[0], &args[1], &args[2], &args[3]);
+
+    let suri = std::env::var(SURI_ENV).ok();
+    let resolver = SecretResolver::new();
+    let profiles = ConfigFile::load_profiles(path.as_ref())?;
+    let contract = |name: &str| -> Result<ContractInstance> {
+        let profile = profiles
+            .get(name)
+            .with_context(|| format!("No profile {name} in {path}"))?;
+        let base_config = match profile.base_config(&resolver)? {
+            Some(base_config) => base_config,
+            None => SubstrateBaseConfig::new(
+                suri.clone().with_context(|| {
+                    format!("Set {SURI_ENV} or the suri of profile {name} to the querying account")
+                })?,
+                None,
+            ),
+        };
+        let signer = base_config.signer()?;
+        let meta = profile.clone().into_meta()?;
+        Ok(ContractInstance::new(meta, Box::new(signer)))
+    };
+
+    let diff = contract(before)?
+        .diff_dry_run(
+            &contract(after)?,
+            message,
+            message_args,
+            CallOptions::default(),
+        )
+        .map_err(|err| anyhow!("Dry-run of {message} failed: {err}"))?;
+    println!("{diff}");
+
+    if let Some(max_gas_increase) = max_gas_increase {
+        if diff.exceeds_gas_budget(max_gas_increase) {
+            anyhow::bail!(
+                "Gas consumed by {message} grew by {:.2}%, over the {max_gas_increase}% budget",
+                diff.gas_delta_percent()
+            );
+        }
+    }
+    Ok(())
+}
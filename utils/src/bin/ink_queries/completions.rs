@@ -0,0 +1,187 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Result;
+
+use super::contract::Signature;
+
+/// Subcommands and their flags.
+const SUBCOMMANDS: &[(&str, &[&str])] = &[
+    (
+        "decode",
+        &["--artifact", "--metadata-dir", "--data", "--topic"],
+    ),
+    ("selector", &[]),
+    ("selectors", &["--artifact"]),
+    ("gen-vectors", &["--artifact", "--cases", "--seed"]),
+    ("schema", &["--artifact", "--typescript"]),
+    (
+        "query",
+        &[
+            "--artifact",
+            "--address",
+            "--url",
+            "--chain",
+            "--origin",
+            "--args",
+            "--arg",
+            "--allow-mutating-dry-run",
+        ],
+    ),
+    (
+        "watch",
+        &[
+            "--artifact",
+            "--address",
+            "--dir",
+            "--interval",
+            "--url",
+            "--chain",
+            "--origin",
+            "--args",
+            "--arg",
+            "--allow-mutating-dry-run",
+        ],
+    ),
+    (
+        "repl",
+        &[
+            "--artifact",
+            "--address",
+            "--url",
+            "--chain",
+            "--origin",
+            "--allow-mutating-dry-run",
+        ],
+    ),
+    ("completions", &["--artifact"]),
+    ("verify-build", &["--bundle", "--project", "--local"]),
+];
+/// Flags without a value.
+const SWITCHES: &[&str] = &["--typescript", "--local", "--allow-mutating-dry-run"];
+/// Flags whose value is a path.
+const PATH_FLAGS: &[&str] = &["--artifact", "--bundle", "--project", "--dir"];
+
+/// The completion script of `shell`, completing the messages of `messages` after
+/// `ink-queries query`.
+pub fn script(shell: &str, messages: &[Signature]) -> Result<String> {
+    match shell {
+        "bash" => Ok(bash(messages)),
+        // zsh runs bash completions through its compatibility layer.
+        "zsh" => Ok(format!(
+            "autoload -U +X compinit && compinit\nautoload -U +X bashcompinit && bashcompinit\n\n{}",
+            bash(messages)
+        )),
+        "fish" => Ok(fish(messages)),
+        _ => anyhow::bail!("Unsupported shell {shell}, expected bash, zsh or fish"),
+    }
+}
+
+/// Messages complete to their whole call, e.g. `transfer --args <AccountId> <Balance>`.
+fn bash(messages: &[Signature]) -> String {
+    let names: Vec<&str> = SUBCOMMANDS.iter().map(|(name, _)| *name).collect();
+    let calls: Vec<String> = messages
+        .iter()
+        .map(|message| {
+            format!("{} {}", message.label, message.placeholders())
+                .trim_end()
+                .to_owned()
+        })
+        .collect();
+    let mut flags = String::new();
+    for (name, subcommand_flags) in SUBCOMMANDS {
+        if !subcommand_flags.is_empty() {
+            flags.push_str(&format!(
+                "            {name}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")) ;;\n",
+                subcommand_flags.join(" ")
+            ));
+        }
+    }
+
+    format!(
+        r#"_ink_queries_messages={messages}
+
+_ink_queries() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    if [ "$COMP_CWORD" -eq 1 ]; then
+        COMPREPLY=($(compgen -W "{names}" -- "$cur"))
+    elif [ "${{COMP_WORDS[1]}}" = query ] && [ "$COMP_CWORD" -eq 2 ]; then
+        local IFS=$'\n'
+        COMPREPLY=($(compgen -W "$_ink_queries_messages" -- "$cur"))
+    elif [[ "$cur" == -* ]]; then
+        case "${{COMP_WORDS[1]}}" in
+{flags}        esac
+    else
+        COMPREPLY=($(compgen -f -- "$cur"))
+    fi
+}}
+
+complete -F _ink_queries ink-queries
+"#,
+        messages = bash_quoted(&calls.join("\n")),
+        names = names.join(" "),
+    )
+}
+
+/// Messages complete to their name, described by their arguments and whether they
+/// mutate the contract.
+fn fish(messages: &[Signature]) -> String {
+    let names: Vec<&str> = SUBCOMMANDS.iter().map(|(name, _)| *name).collect();
+    let mut out = format!(
+        "complete -c ink-queries -f\ncomplete -c ink-queries -n __fish_use_subcommand -a {}\n",
+        fish_quoted(&names.join(" "))
+    );
+    for (name, flags) in SUBCOMMANDS {
+        for flag in *flags {
+            let mut line = format!(
+                "complete -c ink-queries -n '__fish_seen_subcommand_from {name}' -l {}",
+                flag.trim_start_matches('-')
+            );
+            if !SWITCHES.contains(flag) {
+                line.push_str(" -r");
+            }
+            if PATH_FLAGS.contains(flag) {
+                line.push_str(" -F");
+            }
+            out.push_str(&line);
+            out.push('\n');
+        }
+    }
+    for message in messages {
+        let mut line = format!(
+            "complete -c ink-queries -n '__fish_seen_subcommand_from query; and test (count (commandline -opc)) -eq 2' -a {}",
+            fish_quoted(&message.label)
+        );
+        let mut description = message.placeholders();
+        if message.mutates {
+            description.push_str(" (mutates)");
+        }
+        if !description.trim().is_empty() {
+            line.push_str(&format!(" -d {}", fish_quoted(description.trim())));
+        }
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+/// `s` single quoted for bash, which reads no escapes between single quotes.
+fn bash_quoted(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// `s` single quoted for fish, which reads `\\` and `\'` between single quotes.
+fn fish_quoted(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
@@ -0,0 +1,194 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use utils::substrate::address::Address;
+use utils::substrate::chain::Chain;
+use utils::substrate::signer::{DevAccount, ReadOnlySigner, SignerProvider};
+use utils::substrate::{
+    AccountId, CallOptions, ContractArtifacts, ContractInstance, InkMeta, InkTranscoder,
+    SubstrateBaseConfig,
+};
+
+use super::USAGE;
+
+/// Environment variable holding the secret key URI of the querying account.
+const SURI_ENV: &str = "SUBSTRATE_SURI";
+
+/// Flags selecting a deployed contract and the account dry-running its messages.
+#[derive(Default)]
+pub struct ContractFlags {
+    artifact: Option<PathBuf>,
+    url: Option<String>,
+    address: Option<String>,
+    chain: Chain,
+    origin: Option<AccountId>,
+}
+
+impl ContractFlags {
+    /// Reads `flag` if it is one of these flags, returning whether it was.
+    pub fn parse(&mut self, flag: &str, value: &str) -> Result<bool> {
+        match flag {
+            "--artifact" => self.artifact = Some(PathBuf::from(value)),
+            "--url" => self.url = Some(value.to_owned()),
+            "--address" => self.address = Some(value.to_owned()),
+            "--chain" => self.chain = Chain::from_str(value)?,
+            "--origin" => {
+                let account = AccountId::from_str(value)
+                    .map_err(|err| anyhow::anyhow!("Invalid origin {value}: {err:?}"))?;
+                self.origin = Some(account);
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    pub fn artifact(&self) -> Option<&Path> {
+        self.artifact.as_deref()
+    }
+
+    /// The contract and its transcoder.
+    ///
+    /// Queries are signed with the key in `SUBSTRATE_SURI` if set, and otherwise
+    /// dry-run as `--origin`, Alice by default.
+    pub fn open(self) -> Result<(ContractInstance, InkTranscoder)> {
+        let artifact = self.artifact.context(USAGE)?;
+        let address = Address::parse_on(&self.chain, &self.address.context(USAGE)?)?;
+        let url = match self.url {
+            Some(url) => url,
+            None => self
+                .chain
+                .default_url()
+                .context("--url is required for chains without a default endpoint")?
+                .to_owned(),
+        };
+        let transcoder = ContractArtifacts::from_manifest_or_file(None, Some(&artifact))?
+            .contract_transcoder()?;
+
+        let signer: Box<dyn SignerProvider> = match std::env::var(SURI_ENV) {
+            Ok(suri) => Box::new(SubstrateBaseConfig::new(suri, None).signer()?),
+            Err(_) => Box::new(ReadOnlySigner::new(
+                self.origin
+                    .unwrap_or_else(|| DevAccount::Alice.account_id()),
+            )),
+        };
+        let meta = InkMeta::new(artifact, url, self.chain, address);
+        Ok((ContractInstance::new(meta, signer), transcoder))
+    }
+}
+
+/// A message of a contract and the types of its arguments.
+pub struct Signature {
+    pub label: String,
+    /// Labels and type names of the arguments.
+    pub args: Vec<(String, String)>,
+    pub mutates: bool,
+}
+
+impl Signature {
+    pub fn of_messages(transcoder: &InkTranscoder) -> Vec<Self> {
+        transcoder
+            .metadata()
+            .spec()
+            .messages()
+            .iter()
+            .map(|message| Signature {
+                label: message.label().to_owned(),
+                args: message
+                    .args()
+                    .iter()
+                    .map(|arg| {
+                        (
+                            arg.label().to_owned(),
+                            arg.ty().display_name().segments.join("::"),
+                        )
+                    })
+                    .collect(),
+                mutates: message.mutates(),
+            })
+            .collect()
+    }
+
+    /// The arguments as `label: Type`.
+    #[cfg(feature = "repl")]
+    pub fn hint(&self) -> String {
+        let mut hint = self
+            .args
+            .iter()
+            .map(|(label, ty)| format!("{label}: {ty}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if self.mutates {
+            hint.push_str("  (mutates)");
+        }
+        hint
+    }
+
+    /// The arguments of `ink-queries query`, e.g. `--args <AccountId> <Balance>`.
+    pub fn placeholders(&self) -> String {
+        if self.args.is_empty() {
+            return String::new();
+        }
+        let types: Vec<String> = self.args.iter().map(|(_, ty)| format!("<{ty}>")).collect();
+        format!("--args {}", types.join(" "))
+    }
+}
+
+/// Dry-runs `message` and prints its outcome, refusing messages mutating the
+/// contract state unless `allow_mutating`, as their changes would silently be lost.
+pub fn dry_run(
+    contract: &ContractInstance,
+    message: &str,
+    args: Vec<String>,
+    allow_mutating: bool,
+) -> Result<()> {
+    let mutates = contract
+        .mutates(message, &args)
+        .map_err(|err| anyhow::anyhow!("{err}"))?;
+    if mutates && !allow_mutating {
+        anyhow::bail!(
+            "{message} mutates the contract state, which a dry-run does not persist, pass \
+             --allow-mutating-dry-run to dry-run it anyway"
+        );
+    }
+    let diagnostics = contract
+        .validate_args(message, &args)
+        .map_err(|err| anyhow::anyhow!("{err}"))?;
+    if !diagnostics.is_empty() {
+        for diagnostic in &diagnostics {
+            eprintln!("error: {diagnostic}");
+        }
+        anyhow::bail!("Invalid arguments of {message}");
+    }
+    let outcome = contract
+        .dry_run(message, args, CallOptions::default())
+        .map_err(|err| anyhow::anyhow!("{err}"))?;
+    match &outcome.value {
+        Ok(value) if outcome.reverted => println!("reverted: {value:#}"),
+        Ok(value) => println!("{value:#}"),
+        Err(err) => println!("failed: {err}"),
+    }
+    println!(
+        "gas consumed {}/{}, required {}/{}, storage deposit {}",
+        outcome.gas_consumed.ref_time(),
+        outcome.gas_consumed.proof_size(),
+        outcome.gas_required.ref_time(),
+        outcome.gas_required.proof_size(),
+        outcome.storage_deposit
+    );
+    Ok(())
+}
@@ -0,0 +1,436 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use sp_core::H256;
+use std::path::PathBuf;
+use std::time::Duration;
+
+mod completions;
+mod contract;
+#[cfg(feature = "repl")]
+mod repl;
+
+use contract::{ContractFlags, Signature};
+use utils::substrate::watch::ArtifactWatcher;
+use utils::substrate::{
+    compute_selector, BuildRunner, BuildVerifier, CallOptions, ContractArtifacts, EventDecoder,
+    MetadataRegistry, SchemaGenerator, SelectorTable, VectorGenerator,
+};
+
+const USAGE: &str = "Usage:
+    ink-queries decode (--artifact <contract.json> | --metadata-dir <dir>) --data <0x..> \
+        [--topic <0x..>]..
+    ink-queries selector <label>..
+    ink-queries selectors --artifact <contract.json>
+    ink-queries gen-vectors --artifact <contract.json> [--cases <n>] [--seed <n>]
+    ink-queries schema --artifact <contract.json> [--typescript]
+    ink-queries query <message> --artifact <contract.json> --address <address> \
+        [--url <ws://..>] [--chain <chain>] [--origin <ss58>] [--args <arg>..] \
+        [--arg <name>=<value>].. [--allow-mutating-dry-run]
+    ink-queries watch <message> --artifact <contract.json> --address <address> \
+        [--dir <target/ink>] [--interval <ms>] [--url <ws://..>] [--chain <chain>] \
+        [--origin <ss58>] [--args <arg>..] [--arg <name>=<value>].. \
+        [--allow-mutating-dry-run]
+    ink-queries repl --artifact <contract.json> --address <address> [--url <ws://..>] \
+        [--chain <chain>] [--origin <ss58>] [--allow-mutating-dry-run]
+    ink-queries verify-build --bundle <contract.contract> --project <dir> [--local]
+    ink-queries completions <bash|zsh|fish> [--artifact <contract.json>]";
+
+/// Tools for ink! contracts
+///
+/// `decode` prints the message call, constructor call or event encoded in `--data`,
+/// e.g. copied from a block explorer, detecting which of them it is. The topics of
+/// an event, the signature topic first, are passed with `--topic`. With
+/// `--metadata-dir`, the data is decoded with the first of the contracts in the
+/// directory it belongs to, e.g. the callee of a cross-contract call.
+///
+/// `selector` prints the selectors ink! derives from labels, e.g. `flip` or
+/// `PSP22::transfer`, and `selectors` lists those of a contract, flagging custom
+/// selectors and selectors shared by several messages or constructors.
+///
+/// `gen-vectors` prints JSON test vectors of the messages of a contract, arguments
+/// and their SCALE encoding, to check other SDKs against this crate's encoder.
+///
+/// `schema` prints the JSON Schema of the JSON the messages and events of a contract
+/// decode to, or with `--typescript` the same types as TypeScript declarations.
+///
+/// `query` dry-runs a message call to a deployed contract and prints its outcome.
+/// Its arguments follow `--args`, up to the next flag, and may be named, e.g.
+/// `--arg to=5Grw.. --arg value=10` or `--args '{ to: 5Grw.., value: 10 }'`.
+/// Omitted `Option` arguments are `None`. Messages mutating the contract state are
+/// refused, their changes being lost, unless `--allow-mutating-dry-run` is passed,
+/// as for `watch` and `repl`.
+///
+/// `watch` queries a message like `query`, then again each time the artifacts in
+/// `--dir`, by default the directory of `--artifact`, change, printing each result.
+/// Leave it running next to `cargo contract build` while developing a contract.
+///
+/// `repl` opens a prompt dry-running each line as a message call to a deployed
+/// contract, e.g. `get_balance 5Grw..`, with tab-completion of message names and
+/// hints of their arguments. It requires the `repl` feature.
+///
+/// `verify-build` rebuilds a project as recorded in the `build_info` of a bundle, in
+/// its verifiable build image or with `--local` the local toolchain, prints the
+/// verdict as JSON and fails if the code hashes differ.
+///
+/// `completions` prints a completion script for a shell. With `--artifact`, it
+/// completes the messages of that contract after `query`, with placeholders of
+/// their arguments, e.g. `transfer --args <AccountId> <Balance>`.
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("decode") => decode(&args[1..]),
+        Some("selector") if args.len() > 1 => {
+            for label in &args[1..] {
+                println!("0x{} {label}", hex::encode(compute_selector(label)));
+            }
+            Ok(())
+        }
+        Some("selectors") => selectors(&args[1..]),
+        Some("gen-vectors") => gen_vectors(&args[1..]),
+        Some("schema") => schema(&args[1..]),
+        Some("query") => query(&args[1..]),
+        Some("watch") => watch(&args[1..]),
+        #[cfg(feature = "repl")]
+        Some("repl") => repl::run(&args[1..]),
+        #[cfg(not(feature = "repl"))]
+        Some("repl") => anyhow::bail!("ink-queries was built without the repl feature"),
+        Some("verify-build") => verify_build(&args[1..]),
+        Some("completions") => print_completions(&args[1..]),
+        _ => anyhow::bail!(USAGE),
+    }
+}
+
+fn decode(args: &[String]) -> Result<()> {
+    let mut artifact = None;
+    let mut metadata_dir = None;
+    let mut data = None;
+    let mut topics = Vec::new();
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .with_context(|| format!("{flag} expects a value\n{USAGE}"))?;
+        match flag.as_str() {
+            "--artifact" => artifact = Some(PathBuf::from(value)),
+            "--metadata-dir" => metadata_dir = Some(PathBuf::from(value)),
+            "--data" => data = Some(parse_hex(value).context("Invalid --data")?),
+            "--topic" => {
+                let topic = parse_hex(value).context("Invalid --topic")?;
+                if topic.len() != 32 {
+                    anyhow::bail!("Topics are 32 bytes, got {}", topic.len());
+                }
+                topics.push(H256::from_slice(&topic));
+            }
+            _ => anyhow::bail!("Unknown flag {flag}\n{USAGE}"),
+        }
+    }
+    let data = data.context(USAGE)?;
+
+    if let Some(dir) = metadata_dir {
+        let registry = MetadataRegistry::load_dir(&dir)?;
+        for (code_hash, name) in registry.code_hashes() {
+            let decoder = registry
+                .event_decoder_by_code_hash(code_hash)
+                .context("Registered code hash without metadata")?;
+            if let Ok(decoded) = decode_data(decoder, &topics, &data) {
+                println!("{name} {decoded}");
+                return Ok(());
+            }
+        }
+        anyhow::bail!(
+            "Data is neither a message, a constructor nor an event of the contracts in {}",
+            dir.display()
+        );
+    }
+
+    let artifact = artifact.context(USAGE)?;
+    let artifacts = ContractArtifacts::from_manifest_or_file(None, Some(&artifact))?;
+    println!(
+        "{}",
+        decode_data(&artifacts.event_decoder()?, &topics, &data)?
+    );
+    Ok(())
+}
+
+/// Decodes `data` as a message call, a constructor call or an event of the contract.
+fn decode_data(decoder: &EventDecoder, topics: &[H256], data: &[u8]) -> Result<String> {
+    let transcoder = decoder.transcoder();
+
+    // Messages and constructors start with their selector and must be consumed
+    // entirely, so that data of another kind sharing the first bytes is not
+    // mistaken for them.
+    let mut input = data;
+    if let Ok(value) = transcoder.decode_contract_message(&mut input) {
+        if input.is_empty() {
+            return Ok(format!("Message {value:#}"));
+        }
+    }
+    let mut input = data;
+    if let Ok(value) = transcoder.decode_contract_constructor(&mut input) {
+        if input.is_empty() {
+            return Ok(format!("Constructor {value:#}"));
+        }
+    }
+    let event = decoder
+        .decode_contract_event(topics, data)
+        .context("Data is neither a message, a constructor nor an event of the contract")?;
+    Ok(format!("Event {event:#}"))
+}
+
+fn selectors(args: &[String]) -> Result<()> {
+    let artifact = match args {
+        [flag, path] if flag == "--artifact" => PathBuf::from(path),
+        _ => anyhow::bail!(USAGE),
+    };
+    let artifacts = ContractArtifacts::from_manifest_or_file(None, Some(&artifact))?;
+    let table = SelectorTable::from_transcoder(&artifacts.contract_transcoder()?);
+    for entry in &table.entries {
+        println!("{entry}");
+    }
+    let collisions = table.collisions();
+    for collision in &collisions {
+        eprintln!(
+            "Collision: {:?}s {} share the selector 0x{}",
+            collision.kind,
+            collision.labels.join(", "),
+            hex::encode(collision.selector)
+        );
+    }
+    if !collisions.is_empty() {
+        anyhow::bail!("{} selector collisions", collisions.len());
+    }
+    Ok(())
+}
+
+fn gen_vectors(args: &[String]) -> Result<()> {
+    let mut artifact = None;
+    let mut cases = None;
+    let mut seed = 0;
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .with_context(|| format!("{flag} expects a value\n{USAGE}"))?;
+        match flag.as_str() {
+            "--artifact" => artifact = Some(PathBuf::from(value)),
+            "--cases" => cases = Some(value.parse().context("Invalid --cases")?),
+            "--seed" => seed = value.parse().context("Invalid --seed")?,
+            _ => anyhow::bail!("Unknown flag {flag}\n{USAGE}"),
+        }
+    }
+    let artifact = artifact.context(USAGE)?;
+
+    let artifacts = ContractArtifacts::from_manifest_or_file(None, Some(&artifact))?;
+    let transcoder = artifacts.contract_transcoder()?;
+    let mut generator = VectorGenerator::new(&transcoder).seed(seed);
+    if let Some(cases) = cases {
+        generator = generator.cases(cases);
+    }
+    println!("{}", serde_json::to_string_pretty(&generator.generate()?)?);
+    Ok(())
+}
+
+fn schema(args: &[String]) -> Result<()> {
+    let mut artifact = None;
+    let mut typescript = false;
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--artifact" => {
+                let value = args
+                    .next()
+                    .with_context(|| format!("{flag} expects a value\n{USAGE}"))?;
+                artifact = Some(PathBuf::from(value));
+            }
+            "--typescript" => typescript = true,
+            _ => anyhow::bail!("Unknown flag {flag}\n{USAGE}"),
+        }
+    }
+    let artifact = artifact.context(USAGE)?;
+
+    let artifacts = ContractArtifacts::from_manifest_or_file(None, Some(&artifact))?;
+    let transcoder = artifacts.contract_transcoder()?;
+    let generator = SchemaGenerator::new(&transcoder);
+    if typescript {
+        print!("{}", generator.typescript());
+    } else {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&generator.json_schema())?
+        );
+    }
+    Ok(())
+}
+
+fn query(args: &[String]) -> Result<()> {
+    let (message, args) = args.split_first().context(USAGE)?;
+    let mut flags = ContractFlags::default();
+    let mut message_args = Vec::new();
+    let mut allow_mutating = false;
+    let mut args = args.iter().peekable();
+    while let Some(flag) = args.next() {
+        if flag == "--args" {
+            while let Some(arg) = args.next_if(|arg| !arg.starts_with("--")) {
+                message_args.push(arg.clone());
+            }
+            continue;
+        }
+        if flag == "--allow-mutating-dry-run" {
+            allow_mutating = true;
+            continue;
+        }
+        let value = args
+            .next()
+            .with_context(|| format!("{flag} expects a value\n{USAGE}"))?;
+        match flag.as_str() {
+            "--arg" => message_args.push(named_arg(value)?),
+            _ if flags.parse(flag, value)? => {}
+            _ => anyhow::bail!("Unknown flag {flag}\n{USAGE}"),
+        }
+    }
+    let (instance, _) = flags.open()?;
+    contract::dry_run(&instance, message, message_args, allow_mutating)
+}
+
+fn watch(args: &[String]) -> Result<()> {
+    let (message, args) = args.split_first().context(USAGE)?;
+    let mut flags = ContractFlags::default();
+    let mut message_args = Vec::new();
+    let mut dir = None;
+    let mut interval = None;
+    let mut allow_mutating = false;
+    let mut args = args.iter().peekable();
+    while let Some(flag) = args.next() {
+        if flag == "--args" {
+            while let Some(arg) = args.next_if(|arg| !arg.starts_with("--")) {
+                message_args.push(arg.clone());
+            }
+            continue;
+        }
+        if flag == "--allow-mutating-dry-run" {
+            allow_mutating = true;
+            continue;
+        }
+        let value = args
+            .next()
+            .with_context(|| format!("{flag} expects a value\n{USAGE}"))?;
+        match flag.as_str() {
+            "--arg" => message_args.push(named_arg(value)?),
+            "--dir" => dir = Some(PathBuf::from(value)),
+            "--interval" => interval = Some(Duration::from_millis(value.parse()?)),
+            _ if flags.parse(flag, value)? => {}
+            _ => anyhow::bail!("Unknown flag {flag}\n{USAGE}"),
+        }
+    }
+    let dir = match dir {
+        Some(dir) => dir,
+        None => match flags.artifact().context(USAGE)?.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_owned(),
+            _ => PathBuf::from("."),
+        },
+    };
+    let mut watcher = ArtifactWatcher::new(dir);
+    if let Some(interval) = interval {
+        watcher = watcher.interval(interval);
+    }
+    let (instance, _) = flags.open()?;
+    if instance
+        .mutates(message, &message_args)
+        .map_err(|err| anyhow::anyhow!("{err}"))?
+        && !allow_mutating
+    {
+        anyhow::bail!(
+            "{message} mutates the contract state, which a query does not persist, pass \
+             --allow-mutating-dry-run to watch it anyway"
+        );
+    }
+    watcher.watch_query(
+        &instance,
+        message,
+        message_args,
+        CallOptions::default().allow_mutating(),
+        |result| {
+            match result {
+                Ok(result) => println!("{:#}", result.value),
+                Err(err) => eprintln!("error: {err}"),
+            }
+            true
+        },
+    )
+}
+
+/// Checks the value of `--arg`, which the encoder tells from positional arguments.
+fn named_arg(value: &str) -> Result<String> {
+    match value.split_once('=') {
+        Some((name, _)) if !name.trim().is_empty() => Ok(value.to_owned()),
+        _ => anyhow::bail!("--arg expects <name>=<value>, got {value}"),
+    }
+}
+
+fn print_completions(args: &[String]) -> Result<()> {
+    let messages = match args {
+        [_] => Vec::new(),
+        [_, flag, path] if flag == "--artifact" => {
+            let artifacts =
+                ContractArtifacts::from_manifest_or_file(None, Some(&PathBuf::from(path)))?;
+            Signature::of_messages(&artifacts.contract_transcoder()?)
+        }
+        _ => anyhow::bail!(USAGE),
+    };
+    print!("{}", completions::script(&args[0], &messages)?);
+    Ok(())
+}
+
+fn verify_build(args: &[String]) -> Result<()> {
+    let mut bundle = None;
+    let mut project = None;
+    let mut local = false;
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--local" => local = true,
+            "--bundle" | "--project" => {
+                let value = args
+                    .next()
+                    .with_context(|| format!("{flag} expects a value\n{USAGE}"))?;
+                match flag.as_str() {
+                    "--bundle" => bundle = Some(PathBuf::from(value)),
+                    _ => project = Some(PathBuf::from(value)),
+                }
+            }
+            _ => anyhow::bail!("Unknown flag {flag}\n{USAGE}"),
+        }
+    }
+
+    let mut verifier = BuildVerifier::new(bundle.context(USAGE)?, project.context(USAGE)?);
+    if local {
+        verifier = verifier.runner(BuildRunner::Toolchain);
+    }
+    let verification = verifier.verify()?;
+    println!("{}", serde_json::to_string_pretty(&verification)?);
+    if !verification.verified {
+        anyhow::bail!(
+            "Code hash {} of the rebuilt code does not match {} of the bundle",
+            verification.built_hash,
+            verification.expected_hash
+        );
+    }
+    Ok(())
+}
+
+fn parse_hex(value: &str) -> Result<Vec<u8>> {
+    Ok(hex::decode(value.strip_prefix("0x").unwrap_or(value))?)
+}
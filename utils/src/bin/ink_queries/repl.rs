@@ -0,0 +1,218 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Editor, Helper};
+
+use utils::substrate::ContractInstance;
+
+use super::contract::{dry_run, ContractFlags, Signature};
+use super::USAGE;
+
+const PROMPT: &str = "> ";
+const HELP: &str = "Enter a message and its arguments, e.g. `transfer 5Grw.. 100`, to dry-run it.
+Arguments are separated by spaces outside of brackets and quotes. Tab completes
+message names.
+    .messages    lists the messages of the contract
+    .help        prints this help
+    .quit        exits, as does Ctrl-D";
+
+/// Completes message names and hints their arguments.
+struct MessageHelper {
+    messages: Vec<Signature>,
+}
+
+impl Completer for MessageHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let prefix = &line[..pos];
+        if prefix.contains(char::is_whitespace) {
+            return Ok((pos, Vec::new()));
+        }
+        let candidates = self
+            .messages
+            .iter()
+            .filter(|message| message.label.starts_with(prefix))
+            .map(|message| Pair {
+                display: message.label.clone(),
+                replacement: format!("{} ", message.label),
+            })
+            .collect();
+        Ok((0, candidates))
+    }
+}
+
+impl Hinter for MessageHelper {
+    type Hint = String;
+
+    /// The arguments of the message being typed, or the rest of its name if only
+    /// one message starts with what was typed.
+    fn hint(&self, line: &str, pos: usize, _: &rustyline::Context<'_>) -> Option<String> {
+        if pos < line.len() {
+            return None;
+        }
+        let mut words = line.split_whitespace();
+        let label = words.next()?;
+        if words.next().is_some() {
+            return None;
+        }
+        if let Some(message) = self.messages.iter().find(|message| message.label == label) {
+            let hint = message.hint();
+            return match (hint.is_empty(), line.ends_with(' ')) {
+                (true, _) => None,
+                (false, true) => Some(hint),
+                (false, false) => Some(format!(" {hint}")),
+            };
+        }
+        let mut matching = self
+            .messages
+            .iter()
+            .filter(|message| message.label.starts_with(label));
+        match (matching.next(), matching.next()) {
+            (Some(message), None) => Some(message.label[label.len()..].to_owned()),
+            _ => None,
+        }
+    }
+}
+
+impl Highlighter for MessageHelper {}
+
+impl Validator for MessageHelper {}
+
+impl Helper for MessageHelper {}
+
+/// Runs `ink-queries repl`, dry-running each line as a message call.
+pub fn run(args: &[String]) -> Result<()> {
+    let mut flags = ContractFlags::default();
+    let mut allow_mutating = false;
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        if flag == "--allow-mutating-dry-run" {
+            allow_mutating = true;
+            continue;
+        }
+        let value = args
+            .next()
+            .with_context(|| format!("{flag} expects a value\n{USAGE}"))?;
+        if !flags.parse(flag, value)? {
+            anyhow::bail!("Unknown flag {flag}\n{USAGE}");
+        }
+    }
+    let (contract, transcoder) = flags.open()?;
+    let messages = Signature::of_messages(&transcoder);
+
+    let mut editor: Editor<MessageHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(MessageHelper { messages }));
+    println!(
+        "Connected to {} at {}, .help for help",
+        contract.address(),
+        contract.url()
+    );
+    loop {
+        let line = match editor.readline(PROMPT) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line)?;
+        match line {
+            ".quit" | ".exit" => return Ok(()),
+            ".help" => println!("{HELP}"),
+            ".messages" => {
+                let helper = editor.helper().expect("helper is set");
+                for message in &helper.messages {
+                    println!("{} {}", message.label, message.hint());
+                }
+            }
+            _ => {
+                if let Err(err) = call(&contract, line, allow_mutating) {
+                    eprintln!("error: {err:#}");
+                }
+            }
+        }
+    }
+}
+
+/// Dry-runs the message call of `line` and prints its outcome.
+fn call(contract: &ContractInstance, line: &str, allow_mutating: bool) -> Result<()> {
+    let mut words = split_args(line)?.into_iter();
+    let message = words.next().context("No message")?;
+    dry_run(contract, &message, words.collect(), allow_mutating)
+}
+
+/// Splits a line on whitespace outside of brackets and quotes, so that arguments
+/// such as `Some(1)`, `[1, 2]` or `"a b"` stay whole.
+fn split_args(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut escaped = false;
+    for c in line.chars() {
+        match quote {
+            Some(q) => {
+                word.push(c);
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None if c.is_whitespace() && depth == 0 => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            None => {
+                match c {
+                    '"' | '\'' => quote = Some(c),
+                    '(' | '[' | '{' => depth += 1,
+                    ')' | ']' | '}' => {
+                        depth = depth
+                            .checked_sub(1)
+                            .with_context(|| format!("Unbalanced {c} in {line}"))?;
+                    }
+                    _ => {}
+                }
+                word.push(c);
+            }
+        }
+    }
+    if quote.is_some() || depth > 0 {
+        anyhow::bail!("Unterminated argument in {line}");
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    Ok(words)
+}
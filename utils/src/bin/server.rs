@@ -0,0 +1,175 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use async_graphql::dynamic::Schema;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use utils::substrate::graphql;
+use utils::substrate::secrets::SecretResolver;
+use utils::substrate::{
+    json_value, BlockHash, CallOptions, ConfigFile, ContractInstance, SubstrateBaseConfig,
+};
+
+/// Environment variable holding the secret key URI signing the queries of profiles
+/// without `suri`.
+const SURI_ENV: &str = "SUBSTRATE_SURI";
+/// Environment variable holding the path of the contract profiles.
+const PROFILES_ENV: &str = "INK_QUERIES_PROFILES";
+const DEFAULT_PROFILES_PATH: &str = "contracts.toml";
+/// Environment variable holding the address the server listens on.
+const ADDR_ENV: &str = "INK_QUERIES_ADDR";
+const DEFAULT_ADDR: &str = "127.0.0.1:8080";
+
+type Contracts = BTreeMap<String, Arc<ContractInstance>>;
+
+struct AppState {
+    contracts: Contracts,
+    schema: Schema,
+}
+
+#[derive(Deserialize, Default)]
+struct QueryRequest {
+    #[serde(default)]
+    args: Vec<String>,
+    /// Block whose state is queried, the best block if not set.
+    at: Option<BlockHash>,
+}
+
+/// Serves queries of the contracts of a profiles file over HTTP
+///
+/// Each table of the profiles file is a contract, with the same fields as the
+/// config file:
+///
+/// ```toml
+/// [flipper]
+/// contract_path = "target/ink/flipper.contract"
+/// url = "ws://127.0.0.1:9944"
+/// ink_contract_id = "5C..."
+/// suri = "env:FLIPPER_SURI"
+/// ```
+///
+/// Profiles without `suri` are queried with the secret key URI in `SUBSTRATE_SURI`.
+///
+/// `POST /contracts/{name}/query/{message}` with `{"args": [..], "at": "0x.."}`
+/// returns `{"value": .., "debug_message": ..}`, and `GET /contracts` lists the
+/// contract names. `POST /graphql` runs GraphQL queries of the read-only messages,
+/// whose schema is served at `GET /graphql/schema`.
+#[tokio::main]
+async fn main() -> Result<()> {
+    let suri = std::env::var(SURI_ENV).ok();
+    let path = std::env::var(PROFILES_ENV).unwrap_or_else(|_| DEFAULT_PROFILES_PATH.to_owned());
+    let addr: SocketAddr = std::env::var(ADDR_ENV)
+        .unwrap_or_else(|_| DEFAULT_ADDR.to_owned())
+        .parse()
+        .context("Invalid listen address")?;
+
+    let contracts = load_profiles(&path, suri.as_deref())?;
+    let schema = graphql::build_schema(&contracts)?;
+    let app = Router::new()
+        .route("/contracts", get(list_contracts))
+        .route("/contracts/:name/query/:message", post(query))
+        .route("/graphql", post(graphql_query))
+        .route("/graphql/schema", get(graphql_schema))
+        .with_state(Arc::new(AppState { contracts, schema }));
+
+    axum::Server::bind(&addr)
+        .serve(app.into_make_service())
+        .await?;
+    Ok(())
+}
+
+fn load_profiles(path: &str, suri: Option<&str>) -> Result<Contracts> {
+    let resolver = SecretResolver::new();
+    ConfigFile::load_profiles(path.as_ref())?
+        .into_iter()
+        .map(|(name, profile)| {
+            let base_config = match profile.base_config(&resolver)? {
+                Some(base_config) => base_config,
+                None => SubstrateBaseConfig::new(
+                    suri.with_context(|| {
+                        format!("Set {SURI_ENV} or the suri of profile {name} to sign its queries")
+                    })?
+                    .to_owned(),
+                    None,
+                ),
+            };
+            let signer = base_config.signer()?;
+            let meta = profile.into_meta()?;
+            let contract = ContractInstance::new(meta, Box::new(signer));
+            Ok((name, Arc::new(contract)))
+        })
+        .collect()
+}
+
+async fn list_contracts(State(state): State<Arc<AppState>>) -> Json<Vec<String>> {
+    Json(state.contracts.keys().cloned().collect())
+}
+
+async fn query(
+    State(state): State<Arc<AppState>>,
+    Path((name, message)): Path<(String, String)>,
+    request: Option<Json<QueryRequest>>,
+) -> Response {
+    let contract = match state.contracts.get(&name) {
+        Some(contract) => contract.clone(),
+        None => return error(StatusCode::NOT_FOUND, format!("Unknown contract {name}")),
+    };
+    let Json(request) = request.unwrap_or_default();
+    let mut options = CallOptions::default();
+    if let Some(at) = request.at {
+        options = options.at(at);
+    }
+    let nonce = Some(rand::random());
+
+    // Queries block on the node and pRuntime, so they run off the async workers.
+    let result = tokio::task::spawn_blocking(move || {
+        contract.query_msg(&message, request.args, nonce, options)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(result)) => Json(json!({
+            "value": json_value(&result.value),
+            "debug_message": result.debug_message,
+        }))
+        .into_response(),
+        Ok(Err(err)) => error(StatusCode::UNPROCESSABLE_ENTITY, err.to_string()),
+        Err(err) => error(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
+    }
+}
+
+async fn graphql_query(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(state.schema.execute(request).await)
+}
+
+async fn graphql_schema(State(state): State<Arc<AppState>>) -> String {
+    state.schema.sdl()
+}
+
+fn error(status: StatusCode, message: String) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
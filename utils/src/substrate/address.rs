@@ -0,0 +1,318 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Result};
+use sp_core::hashing::keccak_256;
+use sp_core::H160;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+use subxt::utils::AccountId32;
+
+use super::chain::Chain;
+use super::contract::ink::try_decode_hex;
+use super::{AccountId, ContractId};
+
+/// Byte pallet-revive pads Ethereum addresses with to map them to accounts.
+const REVIVE_PADDING: u8 = 0xEE;
+/// Prefixes selecting the kind of an address, e.g. `phala:0x..`.
+const INK_PREFIX: &str = "ink:";
+const PHALA_PREFIX: &str = "phala:";
+const REVIVE_PREFIX: &str = "revive:";
+
+/// Address of a contract, on a substrate chain or in a Phala cluster.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Address {
+    /// Account of an ink contract, rendered in SS58.
+    Ink(AccountId),
+    /// Id of a Phat contract, rendered in hex.
+    Phala(ContractId),
+    /// Ethereum-style address of a pallet-revive contract, rendered in hex.
+    Revive(H160),
+}
+
+impl Address {
+    /// Parses an ink contract account from SS58, 0x-hex or an ink! `AccountId(..)` literal.
+    pub fn parse_ink(s: &str) -> Result<Self> {
+        let s = unwrap_literal(s.trim());
+        if s.starts_with("0x") {
+            return Ok(Address::Ink(bytes32(s)?.into()));
+        }
+        AccountId::from_str(s)
+            .map(Address::Ink)
+            .map_err(|err| anyhow!("Invalid contract address {s}: {err:?}"))
+    }
+
+    /// Parses a Phat contract id from 0x-hex.
+    pub fn parse_phala(s: &str) -> Result<Self> {
+        Ok(Address::Phala(ContractId::from(bytes32(s.trim())?)))
+    }
+
+    /// Parses a pallet-revive contract address from 20 bytes of 0x-hex.
+    pub fn parse_revive(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let bytes = try_decode_hex(s).map_err(|err| anyhow!("Invalid hex address {s}: {err}"))?;
+        if bytes.len() != H160::len_bytes() {
+            anyhow::bail!(
+                "Invalid address {s}: expected {} bytes, got {}",
+                H160::len_bytes(),
+                bytes.len()
+            );
+        }
+        Ok(Address::Revive(H160::from_slice(&bytes)))
+    }
+
+    /// Parses an address as [`Address::from_str`] does, reading unprefixed 32 bytes of
+    /// hex as a Phat contract id on Phala and as an ink contract account elsewhere.
+    pub fn parse_on(chain: &Chain, s: &str) -> Result<Self> {
+        let s = s.trim();
+        if *chain == Chain::Phala && s.starts_with("0x") && s.len() == 2 + 2 * 32 {
+            Self::parse_phala(s)
+        } else {
+            Self::from_str(s)
+        }
+    }
+
+    pub fn as_ink(&self) -> Option<&AccountId> {
+        match self {
+            Address::Ink(account) => Some(account),
+            Address::Phala(_) | Address::Revive(_) => None,
+        }
+    }
+
+    pub fn as_phala(&self) -> Option<&ContractId> {
+        match self {
+            Address::Phala(id) => Some(id),
+            Address::Ink(_) | Address::Revive(_) => None,
+        }
+    }
+
+    pub fn as_revive(&self) -> Option<&H160> {
+        match self {
+            Address::Revive(address) => Some(address),
+            Address::Ink(_) | Address::Phala(_) => None,
+        }
+    }
+
+    /// The raw 32 bytes of the address, the account pallet-revive maps them to for
+    /// revive contracts.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        match self {
+            Address::Ink(account) => account.0,
+            Address::Phala(id) => id.0,
+            Address::Revive(address) => revive_account_id(address).0,
+        }
+    }
+}
+
+impl FromStr for Address {
+    type Err = anyhow::Error;
+
+    /// Reads addresses prefixed with `ink:`, `phala:` or `revive:` as that kind of
+    /// address. Unprefixed, 20 bytes of hex are read as a revive contract address, and
+    /// 32 bytes of hex, SS58 or `AccountId(..)` literals as an ink contract account.
+    ///
+    /// Phat contract ids must be prefixed, or parsed with [`Address::parse_on`].
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Some(s) = s.strip_prefix(INK_PREFIX) {
+            Self::parse_ink(s)
+        } else if let Some(s) = s.strip_prefix(PHALA_PREFIX) {
+            Self::parse_phala(s)
+        } else if let Some(s) = s.strip_prefix(REVIVE_PREFIX) {
+            Self::parse_revive(s)
+        } else if s.starts_with("0x") && s.len() == 2 + 2 * H160::len_bytes() {
+            Self::parse_revive(s)
+        } else {
+            Self::parse_ink(s)
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Address::Ink(account) => write!(f, "{account}"),
+            Address::Phala(id) => write!(f, "0x{}", hex::encode(id)),
+            Address::Revive(address) => write!(f, "{address:?}"),
+        }
+    }
+}
+
+// Named concretely: coherence does not see through the `Config` projection of
+// `AccountId`, and would take this impl to overlap `From<Address>`.
+impl From<AccountId32> for Address {
+    fn from(account: AccountId32) -> Self {
+        Address::Ink(account)
+    }
+}
+
+impl From<ContractId> for Address {
+    fn from(id: ContractId) -> Self {
+        Address::Phala(id)
+    }
+}
+
+impl From<H160> for Address {
+    fn from(address: H160) -> Self {
+        Address::Revive(address)
+    }
+}
+
+/// Account pallet-revive maps the Ethereum-style `address` to: its 20 bytes padded
+/// with `0xEE`.
+pub fn revive_account_id(address: &H160) -> AccountId {
+    let mut account = [REVIVE_PADDING; 32];
+    account[..20].copy_from_slice(address.as_bytes());
+    account.into()
+}
+
+/// Ethereum-style address pallet-revive maps `account` to: the address it was padded
+/// from, or else the last 20 bytes of its Keccak-256 hash.
+///
+/// Accounts other than padded addresses must be mapped on chain with
+/// `Revive::map_account` before they can call contracts.
+pub fn revive_address(account: &AccountId) -> H160 {
+    let bytes = &account.0;
+    if bytes[20..].iter().all(|byte| *byte == REVIVE_PADDING) {
+        H160::from_slice(&bytes[..20])
+    } else {
+        H160::from_slice(&keccak_256(bytes)[12..])
+    }
+}
+
+/// Strips the `AccountId(..)` wrapper ink! uses when printing account ids.
+fn unwrap_literal(s: &str) -> &str {
+    s.strip_prefix("AccountId(")
+        .and_then(|inner| inner.strip_suffix(')'))
+        .map(str::trim)
+        .unwrap_or(s)
+}
+
+fn bytes32(s: &str) -> Result<[u8; 32]> {
+    let bytes = try_decode_hex(s).map_err(|err| anyhow!("Invalid hex address {s}: {err}"))?;
+    <[u8; 32]>::try_from(bytes.as_slice()).map_err(|_| {
+        anyhow!(
+            "Invalid address {s}: expected 32 bytes, got {}",
+            bytes.len()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+    const ALICE_HEX: &str = "0xd43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d";
+    const REVIVE: &str = "0x5fbdb2315678afecb367f032d93f642f64180aa3";
+
+    fn alice() -> Address {
+        Address::Ink(AccountId::from_str(ALICE).unwrap())
+    }
+
+    fn parse(s: &str) -> Address {
+        s.parse().unwrap()
+    }
+
+    fn parse_on(chain: &Chain, s: &str) -> Address {
+        Address::parse_on(chain, s).unwrap()
+    }
+
+    fn parse_err(s: &str) -> String {
+        s.parse::<Address>().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parses_prefixed_addresses() {
+        assert_eq!(parse(&format!("ink:{ALICE}")), alice());
+        assert_eq!(parse(&format!("ink:{ALICE_HEX}")), alice());
+        assert_eq!(parse(&format!("ink:AccountId({ALICE})")), alice());
+        assert_eq!(
+            parse(&format!("phala:{ALICE_HEX}")),
+            Address::Phala(ContractId::from_str(&ALICE_HEX[2..]).unwrap())
+        );
+        assert_eq!(
+            parse(&format!("revive:{REVIVE}")),
+            Address::Revive(H160::from_str(&REVIVE[2..]).unwrap())
+        );
+    }
+
+    #[test]
+    fn parses_unprefixed_addresses_by_format() {
+        assert_eq!(parse(ALICE), alice());
+        assert_eq!(parse(&format!("  {ALICE}\n")), alice());
+        assert_eq!(parse(&format!("AccountId( {ALICE} )")), alice());
+        // 32 bytes of hex are an ink contract account unless the chain is Phala.
+        assert_eq!(parse(ALICE_HEX), alice());
+        assert_eq!(parse_on(&Chain::Substrate, ALICE_HEX), alice());
+        assert_eq!(
+            parse_on(&Chain::Phala, ALICE_HEX),
+            parse(&format!("phala:{ALICE_HEX}"))
+        );
+        // 20 bytes of hex are a revive contract address on any chain.
+        assert_eq!(parse(REVIVE), parse(&format!("revive:{REVIVE}")));
+        assert_eq!(parse_on(&Chain::Phala, REVIVE), parse(REVIVE));
+    }
+
+    #[test]
+    fn rejects_addresses_of_the_wrong_length() {
+        assert_eq!(
+            parse_err(&format!("phala:{REVIVE}")),
+            format!("Invalid address {REVIVE}: expected 32 bytes, got 20")
+        );
+        assert_eq!(
+            parse_err(&format!("revive:{ALICE_HEX}")),
+            format!("Invalid address {ALICE_HEX}: expected 20 bytes, got 32")
+        );
+        let short = &ALICE_HEX[..64];
+        assert_eq!(
+            parse_err(short),
+            format!("Invalid address {short}: expected 32 bytes, got 31")
+        );
+        assert!(
+            parse_err("5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQZ")
+                .starts_with("Invalid contract address")
+        );
+    }
+
+    #[test]
+    fn displays_addresses_as_they_are_parsed() {
+        for address in [format!("ink:{ALICE}"), format!("revive:{REVIVE}")] {
+            let parsed = parse(&address);
+            assert_eq!(parse(&parsed.to_string()), parsed);
+        }
+        // Phat contract ids are displayed unprefixed, as Phala reads them.
+        let phala = parse(&format!("phala:{ALICE_HEX}"));
+        assert_eq!(phala.to_string(), ALICE_HEX);
+        assert_eq!(parse_on(&Chain::Phala, &phala.to_string()), phala);
+    }
+
+    #[test]
+    fn maps_revive_addresses_to_padded_accounts() {
+        let address = H160::from_str(&REVIVE[2..]).unwrap();
+        let account = revive_account_id(&address);
+        assert_eq!(&account.0[..20], address.as_bytes());
+        assert_eq!(account.0[20..], [REVIVE_PADDING; 12]);
+        assert_eq!(revive_address(&account), address);
+        assert_eq!(Address::Revive(address).to_bytes(), account.0);
+
+        // Other accounts are mapped to the end of their hash.
+        let alice = AccountId::from_str(ALICE).unwrap();
+        assert_eq!(
+            revive_address(&alice).as_bytes(),
+            &keccak_256(&alice.0)[12..]
+        );
+    }
+}
@@ -0,0 +1,176 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Context, Result};
+use std::convert::TryFrom;
+use std::str::FromStr;
+use subxt::dynamic::Value;
+use subxt::ext::scale_value::{Composite, ValueDef};
+
+use super::contract::info::{bytes, fetch, field};
+use super::contract::ink::try_decode_hex;
+use super::Client;
+
+const REGISTRY_PALLET: &str = "PhalaRegistry";
+const WORKERS: &str = "Workers";
+
+/// Who vouched for a worker's enclave when it registered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttestationProvider {
+    /// Registered by the chain's root, without remote attestation.
+    Root,
+    /// Intel Attestation Service (EPID).
+    Ias,
+    /// Intel DCAP quote verification.
+    Dcap,
+}
+
+impl FromStr for AttestationProvider {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "Root" => Ok(AttestationProvider::Root),
+            "Ias" => Ok(AttestationProvider::Ias),
+            "Dcap" => Ok(AttestationProvider::Dcap),
+            other => anyhow::bail!("Unknown attestation provider {other}"),
+        }
+    }
+}
+
+/// Requirements a pRuntime worker must meet before queries are sent to it.
+#[derive(Debug, Clone)]
+pub struct VerificationPolicy {
+    /// Chain holding the worker registry.
+    pub node_url: String,
+    /// Attestation providers accepted for the worker.
+    pub providers: Vec<AttestationProvider>,
+    /// Highest accepted SGX confidence level, 1 being a fully patched platform.
+    pub max_confidence_level: u8,
+}
+
+impl VerificationPolicy {
+    /// Accepts workers attested by IAS or DCAP at any confidence level.
+    pub fn new(node_url: impl Into<String>) -> Self {
+        Self {
+            node_url: node_url.into(),
+            providers: vec![AttestationProvider::Ias, AttestationProvider::Dcap],
+            max_confidence_level: 5,
+        }
+    }
+
+    pub fn providers(mut self, providers: Vec<AttestationProvider>) -> Self {
+        self.providers = providers;
+        self
+    }
+
+    pub fn max_confidence_level(mut self, max_confidence_level: u8) -> Self {
+        self.max_confidence_level = max_confidence_level;
+        self
+    }
+}
+
+/// Attestation of a worker, as recorded by the registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkerAttestation {
+    pub public_key: [u8; 32],
+    pub provider: Option<AttestationProvider>,
+    pub confidence_level: u8,
+}
+
+/// Checks that the worker behind `pruntime_url` is registered on chain with an
+/// attestation accepted by `policy`.
+///
+/// The worker's keys are compared with the registry, so a pRuntime impersonating a
+/// registered worker is rejected too. The quote itself is not re-verified: the
+/// registry only accepts workers whose quote was verified on registration.
+pub async fn verify_worker(
+    pruntime_url: &str,
+    policy: &VerificationPolicy,
+) -> Result<WorkerAttestation> {
+    let pruntime = phactory_api::pruntime_client::new_pruntime_client(pruntime_url.to_owned());
+    let system = pruntime
+        .get_info(())
+        .await?
+        .system
+        .context("Worker not initialized")?;
+    let public_key = key32(&system.public_key)?;
+    let ecdh_public_key = key32(&system.ecdh_public_key)?;
+
+    let client = Client::from_url(policy.node_url.as_str()).await?;
+    let info = fetch(&client, REGISTRY_PALLET, WORKERS, public_key)
+        .await?
+        .ok_or_else(|| anyhow!("Worker 0x{} is not registered", hex::encode(public_key)))?;
+
+    let registered_ecdh = field(&info, "ecdh_pubkey")
+        .and_then(bytes)
+        .context("Worker info has no ecdh_pubkey")?;
+    if registered_ecdh != ecdh_public_key {
+        anyhow::bail!(
+            "Worker 0x{} serves an ECDH key that does not match the registry",
+            hex::encode(public_key)
+        );
+    }
+
+    let provider = field(&info, "attestation_provider")
+        .and_then(option_variant)
+        .map(AttestationProvider::from_str)
+        .transpose()?;
+    let confidence_level = field(&info, "confidence_level")
+        .and_then(Value::as_u128)
+        .and_then(|level| u8::try_from(level).ok())
+        .context("Worker info has no confidence_level")?;
+
+    match provider {
+        Some(provider) if policy.providers.contains(&provider) => {}
+        provider => anyhow::bail!(
+            "Worker 0x{} is attested by {provider:?}, expected one of {:?}",
+            hex::encode(public_key),
+            policy.providers
+        ),
+    }
+    if confidence_level > policy.max_confidence_level {
+        anyhow::bail!(
+            "Worker 0x{} has confidence level {confidence_level}, at most {} is accepted",
+            hex::encode(public_key),
+            policy.max_confidence_level
+        );
+    }
+
+    Ok(WorkerAttestation {
+        public_key,
+        provider,
+        confidence_level,
+    })
+}
+
+/// Name of the variant wrapped in an `Option`, `None` for `None`.
+fn option_variant<T>(value: &Value<T>) -> Option<&str> {
+    let inner = match &value.value {
+        ValueDef::Variant(option) if option.name == "Some" => match &option.values {
+            Composite::Unnamed(values) => values.first()?,
+            Composite::Named(values) => &values.first()?.1,
+        },
+        _ => return None,
+    };
+    match &inner.value {
+        ValueDef::Variant(variant) => Some(variant.name.as_str()),
+        _ => None,
+    }
+}
+
+fn key32(key: &str) -> Result<[u8; 32]> {
+    let key = try_decode_hex(key)?;
+    <[u8; 32]>::try_from(key.as_slice()).map_err(|_| anyhow!("Invalid worker key length"))
+}
@@ -0,0 +1,255 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Context, Result};
+use jsonrpsee::core::client::ClientT;
+use jsonrpsee::rpc_params;
+use jsonrpsee::ws_client::WsClientBuilder;
+use serde_json::Value;
+use std::convert::TryFrom;
+
+use super::Balance;
+
+/// Unit of raw balances, the smallest amount of a token.
+pub const PLANCK: &str = "planck";
+
+/// Native token of a chain, as advertised by `system_properties`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenMetadata {
+    pub decimals: u8,
+    pub symbol: String,
+}
+
+impl TokenMetadata {
+    /// Fetches the token decimals and symbol of the chain at `url`.
+    pub async fn query(url: &str) -> Result<Self> {
+        let client = WsClientBuilder::default().build(url).await?;
+        let properties: Value = client.request("system_properties", rpc_params![]).await?;
+
+        // Multi-token chains advertise arrays, the first entry is the native token.
+        let first = |value: &Value| match value {
+            Value::Array(values) => values.first().cloned(),
+            value => Some(value.clone()),
+        };
+        let decimals = properties
+            .get("tokenDecimals")
+            .and_then(first)
+            .and_then(|value| value.as_u64())
+            .context("Chain does not advertise tokenDecimals")?;
+        let symbol = properties
+            .get("tokenSymbol")
+            .and_then(first)
+            .and_then(|value| value.as_str().map(str::to_owned))
+            .context("Chain does not advertise tokenSymbol")?;
+
+        Ok(Self {
+            decimals: u8::try_from(decimals)?,
+            symbol,
+        })
+    }
+
+    /// Parses an amount of this token into plancks.
+    ///
+    /// The unit is required, as `1` could be read as a token or as a planck: amounts
+    /// are decimal in the token symbol with an optional SI prefix, `1.5 DOT`, `250mDOT`,
+    /// `2kDOT`, or integer in [`PLANCK`], `1000000000000 planck`. Underscores are
+    /// accepted as separators.
+    pub fn parse(&self, input: &str) -> Result<Balance> {
+        let input = input.trim().replace('_', "");
+        let split = input
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(input.len());
+        let (amount, unit) = input.split_at(split);
+        let unit = unit.trim();
+
+        if amount.is_empty() {
+            anyhow::bail!("Invalid balance `{input}`: missing amount");
+        }
+
+        if unit.is_empty() {
+            anyhow::bail!(
+                "Invalid balance `{input}`: missing unit, expected {} or {PLANCK}",
+                self.symbol
+            );
+        }
+        if unit == PLANCK {
+            return amount
+                .parse::<Balance>()
+                .map_err(|_| anyhow!("Invalid balance `{input}`: not a whole number of plancks"));
+        }
+
+        let prefix = unit.strip_suffix(self.symbol.as_str()).ok_or_else(|| {
+            anyhow!(
+                "Invalid balance `{input}`: unknown unit, expected {} or {PLANCK}",
+                self.symbol
+            )
+        })?;
+        let exponent = i32::from(self.decimals) + si_exponent(prefix)?;
+        if exponent < 0 {
+            anyhow::bail!("Invalid balance `{input}`: unit is smaller than a planck");
+        }
+
+        scale_decimal(amount, exponent as u32)
+            .ok_or_else(|| anyhow!("Invalid balance `{input}`: not representable in plancks"))
+    }
+
+    /// Formats a planck amount as a decimal amount of tokens.
+    pub fn format(&self, balance: Balance) -> String {
+        // A unit beyond `u128` is larger than any balance, all of it being fraction.
+        let (integer, fraction) = match 10u128.checked_pow(u32::from(self.decimals)) {
+            Some(unit) => (balance / unit, balance % unit),
+            None => (0, balance),
+        };
+        if fraction == 0 {
+            return format!("{} {}", integer, self.symbol);
+        }
+        let fraction = format!("{:0width$}", fraction, width = self.decimals as usize);
+        format!(
+            "{}.{} {}",
+            integer,
+            fraction.trim_end_matches('0'),
+            self.symbol
+        )
+    }
+}
+
+fn si_exponent(prefix: &str) -> Result<i32> {
+    let exponent = match prefix {
+        "" => 0,
+        "k" => 3,
+        "M" => 6,
+        "G" => 9,
+        "T" => 12,
+        "m" => -3,
+        "u" | "μ" => -6,
+        "n" => -9,
+        "p" => -12,
+        other => anyhow::bail!("Unknown unit prefix `{other}`"),
+    };
+    Ok(exponent)
+}
+
+/// Multiplies a decimal literal by `10^exponent`, failing if a fraction remains.
+fn scale_decimal(amount: &str, exponent: u32) -> Option<Balance> {
+    let (integer, fraction) = match amount.split_once('.') {
+        Some((integer, fraction)) => (integer, fraction.trim_end_matches('0')),
+        None => (amount, ""),
+    };
+    if fraction.len() as u32 > exponent || fraction.contains('.') {
+        return None;
+    }
+
+    let integer = if integer.is_empty() {
+        0
+    } else {
+        integer.parse::<Balance>().ok()?
+    };
+    // Only a whole token needs its unit, which overflows with more than 38 decimals.
+    let integer = match integer {
+        0 => 0,
+        integer => integer.checked_mul(10u128.checked_pow(exponent)?)?,
+    };
+    let fraction = if fraction.is_empty() {
+        0
+    } else {
+        fraction
+            .parse::<Balance>()
+            .ok()?
+            .checked_mul(10u128.checked_pow(exponent - fraction.len() as u32)?)?
+    };
+
+    integer.checked_add(fraction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dot() -> TokenMetadata {
+        TokenMetadata {
+            decimals: 10,
+            symbol: "DOT".to_owned(),
+        }
+    }
+
+    #[test]
+    fn parses_amounts_with_their_unit() {
+        let dot = dot();
+        let cases = [
+            ("1 DOT", 10_000_000_000),
+            ("1.0 DOT", 10_000_000_000),
+            ("1DOT", 10_000_000_000),
+            ("1.5 DOT", 15_000_000_000),
+            (".5 DOT", 5_000_000_000),
+            ("250mDOT", 2_500_000_000),
+            ("2kDOT", 20_000_000_000_000),
+            ("1_000 DOT", 10_000_000_000_000),
+            ("0.0000000001 DOT", 1),
+            ("1 planck", 1),
+            ("1_000 planck", 1_000),
+            (" 0 planck ", 0),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(dot.parse(input).unwrap(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_amounts_without_unit_or_below_a_planck() {
+        let dot = dot();
+        for input in [
+            "1",
+            "1.0",
+            "",
+            "DOT",
+            "1 KSM",
+            "1 xDOT",
+            "0.00000000001 DOT",
+            "1 pDOT",
+            "1.5 planck",
+            "1.2.3 DOT",
+            "340282366920938463463374607431768211456 planck",
+            "1000000000000000000000000000000 DOT",
+        ] {
+            assert!(dot.parse(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn formats_amounts_in_tokens() {
+        let dot = dot();
+        assert_eq!(dot.format(0), "0 DOT");
+        assert_eq!(dot.format(10_000_000_000), "1 DOT");
+        assert_eq!(dot.format(15_000_000_000), "1.5 DOT");
+        assert_eq!(dot.format(1), "0.0000000001 DOT");
+        for balance in [0, 1, 15_000_000_000, u128::MAX] {
+            assert_eq!(dot.parse(&dot.format(balance)).unwrap(), balance);
+        }
+    }
+
+    #[test]
+    fn formats_amounts_of_tokens_with_more_decimals_than_a_balance_holds() {
+        let token = TokenMetadata {
+            decimals: 40,
+            symbol: "WEI".to_owned(),
+        };
+        assert_eq!(token.format(0), "0 WEI");
+        assert_eq!(token.format(1), format!("0.{}1 WEI", "0".repeat(39)));
+        assert_eq!(
+            token.format(u128::MAX),
+            "0.0340282366920938463463374607431768211455 WEI"
+        );
+        assert_eq!(token.parse(&token.format(u128::MAX)).unwrap(), u128::MAX);
+    }
+}
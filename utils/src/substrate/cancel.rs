@@ -0,0 +1,153 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use futures::future::{self, Either};
+use futures::pin_mut;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use super::runtime;
+
+/// Aborts the operations it is passed to, e.g. queries through
+/// [`CallOptions::cancel_on`](super::CallOptions::cancel_on), once cancelled from
+/// any thread or task.
+///
+/// Clones share their state, cancelling one cancels all of them. Aborted operations
+/// drop their connections and fail with [`Interrupted::Cancelled`].
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<TokenState>,
+}
+
+#[derive(Default)]
+struct TokenState {
+    cancelled: AtomicBool,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        let wakers = std::mem::take(&mut *self.inner.wakers.lock().unwrap());
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Completes once the token is cancelled.
+    pub fn cancelled(&self) -> Cancelled<'_> {
+        Cancelled { token: self }
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+/// Future of [`CancellationToken::cancelled`].
+pub struct Cancelled<'a> {
+    token: &'a CancellationToken,
+}
+
+impl Future for Cancelled<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.token.is_cancelled() {
+            return Poll::Ready(());
+        }
+        {
+            let mut wakers = self.token.inner.wakers.lock().unwrap();
+            if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                wakers.push(cx.waker().clone());
+            }
+        }
+        // Cancelled between the first check and the registration of the waker.
+        if self.token.is_cancelled() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Why an operation was aborted by [`run_until`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupted {
+    Cancelled,
+    TimedOut(Duration),
+}
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Interrupted::Cancelled => f.write_str("Operation cancelled"),
+            Interrupted::TimedOut(timeout) => write!(f, "Operation timed out after {timeout:?}"),
+        }
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+/// Runs `future` until it completes, unless `token` is cancelled or `timeout`
+/// elapses first, in which case the future is dropped, closing the connections it
+/// opened.
+pub async fn run_until<F: Future>(
+    future: F,
+    token: Option<&CancellationToken>,
+    timeout: Option<Duration>,
+) -> Result<F::Output, Interrupted> {
+    if token.is_none() && timeout.is_none() {
+        return Ok(future.await);
+    }
+    let cancelled = async {
+        match token {
+            Some(token) => token.cancelled().await,
+            None => future::pending().await,
+        }
+        Interrupted::Cancelled
+    };
+    let timed_out = async {
+        match timeout {
+            Some(timeout) => {
+                runtime::sleep(timeout).await;
+                Interrupted::TimedOut(timeout)
+            }
+            None => future::pending().await,
+        }
+    };
+    pin_mut!(future, cancelled, timed_out);
+    let interrupted = future::select(cancelled, timed_out);
+    match future::select(future, interrupted).await {
+        Either::Left((output, _)) => Ok(output),
+        Either::Right((interrupted, _)) => Err(interrupted.factor_first().0),
+    }
+}
@@ -0,0 +1,220 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Result};
+use sp_core::crypto::{AccountId32 as SpAccountId32, Ss58AddressFormat, Ss58Codec};
+use sp_core::hashing::{blake2_256, keccak_256, sha2_256};
+use std::str::FromStr;
+use subxt::config::{Config, DefaultExtrinsicParams};
+
+use super::weight::{WeightDefaults, PARACHAIN_MAX_PROOF_SIZE};
+use super::{AccountId, BlockHash, Client, DefaultConfig};
+
+/// Pallet of PolkaVM contracts, whose code hashes are Keccak-256 hashes.
+const REVIVE_PALLET: &str = "Revive";
+
+/// subxt [`Config`] of the runtime of a chain, which extrinsics are built and
+/// submitted with.
+///
+/// The chains of [`Chain`] all run with [`DefaultConfig`]. Chains with other header,
+/// address or asset types provide their own config, see
+/// [`ContractInstance::with_config`](super::ContractInstance::with_config), as long
+/// as their accounts and signatures are the substrate ones this crate signs with.
+pub trait ChainConfig:
+    Config<
+    Hash = BlockHash,
+    AccountId = AccountId,
+    Signature = <DefaultConfig as Config>::Signature,
+    ExtrinsicParams = DefaultExtrinsicParams<Self>,
+>
+{
+}
+
+impl<T> ChainConfig for T where
+    T: Config<
+        Hash = BlockHash,
+        AccountId = AccountId,
+        Signature = <DefaultConfig as Config>::Signature,
+        ExtrinsicParams = DefaultExtrinsicParams<T>,
+    >
+{
+}
+
+/// Chains with contract support known to this crate.
+///
+/// They all run with [`DefaultConfig`], so a chain only changes how addresses are
+/// rendered and which endpoint is used by default. See [`ChainConfig`] for chains
+/// whose runtime types differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Chain {
+    /// Any substrate chain using the generic address format.
+    #[default]
+    Substrate,
+    /// A local development node.
+    Local,
+    Astar,
+    Shiden,
+    Shibuya,
+    AlephZero,
+    AlephZeroTestnet,
+    Phala,
+    /// A chain not listed here, identified by its SS58 prefix.
+    Custom {
+        ss58_prefix: u16,
+    },
+}
+
+impl Chain {
+    pub fn ss58_prefix(&self) -> u16 {
+        match self {
+            Chain::Substrate | Chain::Local => 42,
+            Chain::Astar | Chain::Shiden | Chain::Shibuya => 5,
+            Chain::AlephZero | Chain::AlephZeroTestnet => 42,
+            Chain::Phala => 30,
+            Chain::Custom { ss58_prefix } => *ss58_prefix,
+        }
+    }
+
+    /// Public RPC endpoint used when the config does not provide a url.
+    pub fn default_url(&self) -> Option<&'static str> {
+        match self {
+            Chain::Substrate | Chain::Custom { .. } => None,
+            Chain::Local => Some("ws://127.0.0.1:9944"),
+            Chain::Astar => Some("wss://rpc.astar.network"),
+            Chain::Shiden => Some("wss://rpc.shiden.astar.network"),
+            Chain::Shibuya => Some("wss://rpc.shibuya.astar.network"),
+            Chain::AlephZero => Some("wss://ws.azero.dev"),
+            Chain::AlephZeroTestnet => Some("wss://ws.test.azero.dev"),
+            Chain::Phala => Some("wss://api.phala.network/ws"),
+        }
+    }
+
+    /// How contract call weights are limited on the chain.
+    pub fn weight_defaults(&self) -> WeightDefaults {
+        match self {
+            // Parachains, whose blocks are bounded by the proof size of the relay chain.
+            Chain::Astar | Chain::Shiden | Chain::Shibuya | Chain::Phala => WeightDefaults {
+                max_proof_size: PARACHAIN_MAX_PROOF_SIZE,
+                ..WeightDefaults::default()
+            },
+            Chain::Substrate
+            | Chain::Local
+            | Chain::AlephZero
+            | Chain::AlephZeroTestnet
+            | Chain::Custom { .. } => WeightDefaults::default(),
+        }
+    }
+
+    /// Hash function of the contract code hashes of the chain, `None` if it depends
+    /// on the runtime, see [`HashAlgorithm::detect`].
+    pub fn code_hash_algorithm(&self) -> Option<HashAlgorithm> {
+        match self {
+            // pallet-contracts chains.
+            Chain::Astar
+            | Chain::Shiden
+            | Chain::Shibuya
+            | Chain::AlephZero
+            | Chain::AlephZeroTestnet
+            | Chain::Phala => Some(HashAlgorithm::Blake2_256),
+            Chain::Substrate | Chain::Local | Chain::Custom { .. } => None,
+        }
+    }
+
+    /// Renders an account in the address format of the chain.
+    pub fn format_account(&self, account: &AccountId) -> String {
+        SpAccountId32::from(account.0)
+            .to_ss58check_with_version(Ss58AddressFormat::custom(self.ss58_prefix()))
+    }
+
+    /// Parses an SS58 address, rejecting addresses of another chain.
+    pub fn parse_account(&self, address: &str) -> Result<AccountId> {
+        let (account, format) = SpAccountId32::from_ss58check_with_version(address)
+            .map_err(|err| anyhow!("Invalid address {address}: {err:?}"))?;
+        if format.prefix() != self.ss58_prefix() {
+            anyhow::bail!(
+                "Address {address} has SS58 prefix {}, expected {} for {self:?}",
+                format.prefix(),
+                self.ss58_prefix()
+            );
+        }
+        Ok(account.into())
+    }
+}
+
+impl FromStr for Chain {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let chain = match s.to_ascii_lowercase().replace(['-', '_', ' '], "").as_str() {
+            "substrate" => Chain::Substrate,
+            "local" | "dev" => Chain::Local,
+            "astar" => Chain::Astar,
+            "shiden" => Chain::Shiden,
+            "shibuya" => Chain::Shibuya,
+            "alephzero" | "azero" => Chain::AlephZero,
+            "alephzerotestnet" | "azerotestnet" => Chain::AlephZeroTestnet,
+            "phala" => Chain::Phala,
+            other => match other.parse::<u16>() {
+                Ok(ss58_prefix) => Chain::Custom { ss58_prefix },
+                Err(_) => anyhow::bail!("Unknown chain {s}"),
+            },
+        };
+        Ok(chain)
+    }
+}
+
+/// Hash function of contract code hashes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    /// BLAKE2b-256, used by pallet-contracts.
+    Blake2_256,
+    /// Keccak-256, used by pallet-revive for PolkaVM contracts.
+    Keccak256,
+    Sha256,
+}
+
+impl HashAlgorithm {
+    pub fn hash(&self, data: &[u8]) -> [u8; 32] {
+        match self {
+            HashAlgorithm::Blake2_256 => blake2_256(data),
+            HashAlgorithm::Keccak256 => keccak_256(data),
+            HashAlgorithm::Sha256 => sha2_256(data),
+        }
+    }
+
+    /// Hash function of the contracts of the runtime at `url`, Keccak-256 if it runs
+    /// pallet-revive and BLAKE2 otherwise.
+    pub async fn detect(url: &str) -> Result<Self> {
+        let client = Client::from_url(url).await?;
+        if client.metadata().pallet_by_name(REVIVE_PALLET).is_some() {
+            Ok(HashAlgorithm::Keccak256)
+        } else {
+            Ok(HashAlgorithm::Blake2_256)
+        }
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let algorithm = match s.to_ascii_lowercase().replace(['-', '_'], "").as_str() {
+            "blake2" | "blake2b" | "blake2256" => HashAlgorithm::Blake2_256,
+            "keccak" | "keccak256" => HashAlgorithm::Keccak256,
+            "sha256" | "sha2256" => HashAlgorithm::Sha256,
+            _ => anyhow::bail!("Unknown hash algorithm {s}, expected blake2, keccak or sha256"),
+        };
+        Ok(algorithm)
+    }
+}
@@ -0,0 +1,175 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Context, Result};
+use phactory_api::prpc;
+use sp_core::H256;
+use subxt::dynamic::Value;
+use subxt::ext::scale_value::ValueDef;
+
+use super::contract::info::{account_id, bytes, fetch, field, h256};
+use super::{AccountId, Client, ContractId};
+
+const PHAT_CONTRACTS_PALLET: &str = "PhalaPhatContracts";
+const CONTRACTS: &str = "Contracts";
+const CLUSTERS: &str = "Clusters";
+/// Cluster workers are stored apart from the cluster info on newer runtimes.
+const CLUSTER_WORKERS: &str = "ClusterWorkers";
+
+/// Registration of a Phat contract on chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhatContractInfo {
+    pub id: ContractId,
+    pub deployer: AccountId,
+    pub cluster_id: H256,
+}
+
+/// A cluster of workers running Phat contracts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClusterInfo {
+    pub id: H256,
+    pub owner: AccountId,
+    /// The system contract of the cluster, hosting the cluster-wide services.
+    pub system_contract: ContractId,
+    /// Public keys of the workers serving the cluster.
+    pub workers: Vec<[u8; 32]>,
+}
+
+impl ClusterInfo {
+    pub fn has_worker(&self, public_key: &[u8]) -> bool {
+        self.workers.iter().any(|worker| worker[..] == *public_key)
+    }
+}
+
+/// State of the sidevm attached to a Phat contract, as reported by a worker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SidevmStatus {
+    /// The contract has no sidevm.
+    None,
+    Running,
+    /// The sidevm stopped, with the reason reported by the worker.
+    Stopped(String),
+    /// Any other state reported by the worker.
+    Other(String),
+}
+
+/// Reads the registration of the Phat contract `id` from the chain at `url`.
+pub async fn contract_info(url: &str, id: &ContractId) -> Result<PhatContractInfo> {
+    let client = Client::from_url(url).await?;
+    let info = fetch(&client, PHAT_CONTRACTS_PALLET, CONTRACTS, id.0)
+        .await?
+        .ok_or_else(|| anyhow!("Phala contract {id:?} is not deployed"))?;
+
+    let deployer = field(&info, "deployer")
+        .and_then(bytes)
+        .context("Phat contract info has no deployer")?;
+    let cluster_id = field(&info, "cluster_id")
+        .and_then(bytes)
+        .context("Phat contract info has no cluster_id")?;
+
+    Ok(PhatContractInfo {
+        id: *id,
+        deployer: account_id(&deployer)?,
+        cluster_id: h256(&cluster_id)?,
+    })
+}
+
+/// Reads the cluster `id` and its workers from the chain at `url`.
+pub async fn cluster_info(url: &str, id: &H256) -> Result<ClusterInfo> {
+    let client = Client::from_url(url).await?;
+    let info = fetch(&client, PHAT_CONTRACTS_PALLET, CLUSTERS, id.0)
+        .await?
+        .ok_or_else(|| anyhow!("Cluster {id:?} does not exist"))?;
+
+    let owner = field(&info, "owner")
+        .and_then(bytes)
+        .context("Cluster info has no owner")?;
+    let system_contract = field(&info, "system_contract")
+        .and_then(bytes)
+        .context("Cluster info has no system_contract")?;
+    let workers = match field(&info, "workers") {
+        Some(workers) => workers_of(workers)?,
+        None => match fetch(&client, PHAT_CONTRACTS_PALLET, CLUSTER_WORKERS, id.0).await? {
+            Some(workers) => workers_of(&workers)?,
+            None => Vec::new(),
+        },
+    };
+
+    Ok(ClusterInfo {
+        id: *id,
+        owner: account_id(&owner)?,
+        system_contract: h256(&system_contract)?,
+        workers,
+    })
+}
+
+/// Resolves the cluster running the Phat contract `id`, failing if the contract is
+/// not deployed or its cluster has no worker.
+pub async fn resolve_cluster(url: &str, id: &ContractId) -> Result<ClusterInfo> {
+    let contract = contract_info(url, id).await?;
+    let cluster = cluster_info(url, &contract.cluster_id).await?;
+    if cluster.workers.is_empty() {
+        anyhow::bail!(
+            "Cluster {:?} of Phala contract {id:?} has no worker",
+            cluster.id
+        );
+    }
+    Ok(cluster)
+}
+
+/// Public key of the worker behind the pRuntime at `pruntime_url`.
+pub async fn worker_public_key(pruntime_url: &str) -> Result<Vec<u8>> {
+    let client = phactory_api::pruntime_client::new_pruntime_client(pruntime_url.to_owned());
+    let info = client.get_info(()).await?;
+    let public_key = info.system.context("Worker not initialized")?.public_key;
+    Ok(super::contract::ink::try_decode_hex(&public_key)?)
+}
+
+/// Asks the worker at `pruntime_url` for the sidevm state of the Phat contract `id`.
+pub async fn sidevm_status(pruntime_url: &str, id: &ContractId) -> Result<SidevmStatus> {
+    let client = phactory_api::pruntime_client::new_pruntime_client(pruntime_url.to_owned());
+    let response = client
+        .get_contract_info(prpc::GetContractInfoRequest {
+            contracts: vec![format!("{id:?}")],
+        })
+        .await?;
+    let contract = response
+        .contracts
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("Worker does not run Phala contract {id:?}"))?;
+
+    let status = match contract.sidevm {
+        None => SidevmStatus::None,
+        Some(sidevm) => match sidevm.state.as_str() {
+            "running" => SidevmStatus::Running,
+            "stopped" => SidevmStatus::Stopped(sidevm.stop_reason),
+            other => SidevmStatus::Other(other.to_owned()),
+        },
+    };
+    Ok(status)
+}
+
+fn workers_of(workers: &Value<u32>) -> Result<Vec<[u8; 32]>> {
+    match &workers.value {
+        ValueDef::Composite(workers) => workers
+            .values()
+            .map(|worker| {
+                let worker = bytes(worker).context("Invalid worker public key")?;
+                Ok(h256(&worker)?.0)
+            })
+            .collect(),
+        _ => anyhow::bail!("Invalid cluster worker list"),
+    }
+}
@@ -13,8 +13,7 @@
 // limitations under the License.
 
 use super::{ink::InkMeta, ContractInstance};
-use crate::substrate;
-use crate::substrate::{PairSigner, SubstrateBaseConfig};
+use crate::substrate::{signer::SignerProvider, SubstrateBaseConfig};
 use anyhow::Result;
 
 pub struct NotInitialized;
@@ -24,7 +23,7 @@ pub struct Initialized {
 }
 
 pub struct Signed {
-    signer: PairSigner,
+    signer: Box<dyn SignerProvider>,
 }
 
 pub struct ContractBuilder<T> {
@@ -45,15 +44,23 @@ impl ContractBuilder<NotInitialized> {
             state: Initialized { config },
         }
     }
+
+    pub fn with_signer(self, signer: impl SignerProvider + 'static) -> ContractBuilder<Signed> {
+        ContractBuilder {
+            state: Signed {
+                signer: Box::new(signer),
+            },
+        }
+    }
 }
 
 impl ContractBuilder<Initialized> {
     pub fn sign(self) -> Result<ContractBuilder<Signed>> {
-        let pair = self.state.config.signer()?;
+        let keypair = self.state.config.signer()?;
 
         Ok(ContractBuilder {
             state: Signed {
-                signer: substrate::pair_signer(pair),
+                signer: Box::new(keypair),
             },
         })
     }
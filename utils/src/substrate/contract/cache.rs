@@ -0,0 +1,117 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sp_core::H256;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::query::CallResult;
+
+/// Contract address, message label and arguments of a query.
+pub(crate) type QueryKey = ([u8; 32], String, Vec<String>);
+
+struct CacheEntry {
+    /// Block the query read, `None` for Phala queries.
+    block: Option<H256>,
+    /// Whether the block was requested by the caller rather than the best block.
+    pinned: bool,
+    stored: Instant,
+    result: CallResult,
+}
+
+/// Results of successful queries, reused while the chain stays on the same block.
+///
+/// Ink queries are keyed by the block they read: each query first looks up the best
+/// block and pins the dry-run to it, and results of earlier best blocks are dropped
+/// once a new one is seen. Results of queries made `at` a given block never change
+/// and are kept until [`QueryCache::clear`]. With a TTL, any result younger than the
+/// TTL is reused without looking up the best block, which also caches Phala queries.
+///
+/// Queries overriding the origin, value or limits are not cached.
+#[derive(Default)]
+pub struct QueryCache {
+    ttl: Option<Duration>,
+    entries: Mutex<HashMap<QueryKey, Vec<CacheEntry>>>,
+}
+
+impl QueryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// Result of `key` stored within the TTL, whatever the block it read.
+    pub(crate) fn recent(&self, key: &QueryKey) -> Option<CallResult> {
+        let ttl = self.ttl?;
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(key)?
+            .iter()
+            .filter(|entry| entry.stored.elapsed() < ttl)
+            .max_by_key(|entry| entry.stored)
+            .map(|entry| entry.result.clone())
+    }
+
+    /// Result of `key` read at `block`.
+    pub(crate) fn get(&self, key: &QueryKey, block: H256) -> Option<CallResult> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(key)?
+            .iter()
+            .find(|entry| entry.block == Some(block))
+            .map(|entry| entry.result.clone())
+    }
+
+    /// Stores the result of `key` read at `block`.
+    ///
+    /// Storing a result of a new best block evicts the unpinned results of other
+    /// blocks, which can no longer be hit unless within the TTL.
+    pub(crate) fn insert(
+        &self,
+        key: QueryKey,
+        block: Option<H256>,
+        pinned: bool,
+        result: CallResult,
+    ) {
+        let ttl = self.ttl;
+        let mut entries = self.entries.lock().unwrap();
+        if let (Some(block), false) = (block, pinned) {
+            for key_entries in entries.values_mut() {
+                key_entries.retain(|entry| {
+                    entry.pinned
+                        || entry.block == Some(block)
+                        || ttl.is_some_and(|ttl| entry.stored.elapsed() < ttl)
+                });
+            }
+            entries.retain(|_, key_entries| !key_entries.is_empty());
+        }
+        let key_entries = entries.entry(key).or_default();
+        key_entries.retain(|entry| entry.block != block);
+        key_entries.push(CacheEntry {
+            block,
+            pinned,
+            stored: Instant::now(),
+            result,
+        });
+    }
+}
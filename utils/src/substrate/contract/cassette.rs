@@ -0,0 +1,214 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Context, Result};
+use scale::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+use sp_core::H256;
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use super::ink::try_decode_hex;
+use super::transport::{RpcTransport, TransportFuture};
+use crate::substrate::phala::QueryError;
+use crate::substrate::{Balance, ContractId};
+
+/// Channel of the Phala queries recorded in a cassette.
+const PINK_QUERY: &str = "pink_query";
+/// Channel of the best block lookups recorded in a cassette.
+const BEST_BLOCK: &str = "chain_getBlockHash";
+
+/// Whether a [`Cassette`] captures live traffic or answers from a previous capture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    Record,
+    Replay,
+}
+
+/// A request and its response, SCALE encoded and rendered in hex.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Interaction {
+    /// Runtime API function, `pink_query` for Phala queries or `chain_getBlockHash`.
+    channel: String,
+    request: String,
+    response: String,
+}
+
+/// Node and pRuntime traffic of contract queries, saved to a JSON file.
+///
+/// A recording cassette forwards every request and rewrites its file after each
+/// response. A replaying cassette answers from the file without any network access,
+/// each recorded interaction being used once, in order.
+///
+/// Phala queries are recorded decrypted, keyed by contract, message and transfer,
+/// since their encryption keys and nonce change with every query.
+#[derive(Debug)]
+pub struct Cassette {
+    path: PathBuf,
+    mode: CassetteMode,
+    interactions: Mutex<Vec<Interaction>>,
+}
+
+impl Cassette {
+    /// Starts recording to `path`, overwriting any previous recording.
+    pub fn record(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            mode: CassetteMode::Record,
+            interactions: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Loads a recording made with [`Cassette::record`].
+    pub fn replay(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read cassette {}", path.display()))?;
+        let interactions = serde_json::from_str(&content)
+            .with_context(|| format!("Invalid cassette {}", path.display()))?;
+        Ok(Self {
+            path,
+            mode: CassetteMode::Replay,
+            interactions: Mutex::new(interactions),
+        })
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Records or replays a Phala query, `live` sending it to the pRuntime worker.
+    pub(crate) async fn pink_query<F>(
+        &self,
+        id: ContractId,
+        call_data: &[u8],
+        transfer: Balance,
+        live: F,
+    ) -> Result<Result<Vec<u8>, QueryError>>
+    where
+        F: Future<Output = Result<Result<Vec<u8>, QueryError>>>,
+    {
+        let request = (id, call_data, transfer).encode();
+        match self.mode {
+            CassetteMode::Replay => {
+                let response = self.take(PINK_QUERY, &request)?;
+                Ok(Decode::decode(&mut response.as_slice())?)
+            }
+            CassetteMode::Record => {
+                let response = live.await?;
+                self.push(PINK_QUERY, &request, &response.encode())?;
+                Ok(response)
+            }
+        }
+    }
+
+    /// Removes and returns the first recorded response to `request` on `channel`.
+    fn take(&self, channel: &str, request: &[u8]) -> Result<Vec<u8>> {
+        let request = hex::encode(request);
+        let mut interactions = self.interactions.lock().unwrap();
+        let position = interactions
+            .iter()
+            .position(|interaction| {
+                interaction.channel == channel && interaction.request == request
+            })
+            .ok_or_else(|| {
+                anyhow!(
+                    "No recorded {channel} request 0x{request} left in cassette {}",
+                    self.path.display()
+                )
+            })?;
+        let interaction = interactions.remove(position);
+        Ok(try_decode_hex(&interaction.response)?)
+    }
+
+    fn push(&self, channel: &str, request: &[u8], response: &[u8]) -> Result<()> {
+        let mut interactions = self.interactions.lock().unwrap();
+        interactions.push(Interaction {
+            channel: channel.to_owned(),
+            request: hex::encode(request),
+            response: hex::encode(response),
+        });
+        let content = serde_json::to_string_pretty(&*interactions)?;
+        fs::write(&self.path, content)
+            .with_context(|| format!("Failed to write cassette {}", self.path.display()))
+    }
+}
+
+/// Sends runtime API calls through a [`Cassette`], to `inner` while recording.
+pub struct CassetteTransport {
+    cassette: Arc<Cassette>,
+    inner: Arc<dyn RpcTransport>,
+}
+
+impl CassetteTransport {
+    pub fn new(cassette: Arc<Cassette>, inner: Arc<dyn RpcTransport>) -> Self {
+        Self { cassette, inner }
+    }
+}
+
+impl RpcTransport for CassetteTransport {
+    /// Calls pinned to a block are recorded with the block hash appended to `args`.
+    fn state_call<'a>(
+        &'a self,
+        func: &'a str,
+        args: Vec<u8>,
+        at: Option<H256>,
+    ) -> TransportFuture<'a> {
+        Box::pin(async move {
+            let mut request = args.clone();
+            if let Some(at) = at {
+                request.extend_from_slice(at.as_bytes());
+            }
+            match self.cassette.mode {
+                CassetteMode::Replay => self.cassette.take(func, &request),
+                CassetteMode::Record => {
+                    let response = self.inner.state_call(func, args, at).await?;
+                    self.cassette.push(func, &request, &response)?;
+                    Ok(response)
+                }
+            }
+        })
+    }
+
+    fn best_block(&self) -> TransportFuture<'_, H256> {
+        Box::pin(async move {
+            match self.cassette.mode {
+                CassetteMode::Replay => {
+                    let hash = self.cassette.take(BEST_BLOCK, &[])?;
+                    Ok(H256::decode(&mut hash.as_slice())?)
+                }
+                CassetteMode::Record => {
+                    let hash = self.inner.best_block().await?;
+                    self.cassette.push(BEST_BLOCK, &[], hash.as_bytes())?;
+                    Ok(hash)
+                }
+            }
+        })
+    }
+
+    /// Replays stay offline, so dispatch errors are not decoded with the runtime
+    /// metadata.
+    fn node_url(&self) -> Option<&str> {
+        match self.cassette.mode {
+            CassetteMode::Replay => None,
+            CassetteMode::Record => self.inner.node_url(),
+        }
+    }
+}
@@ -0,0 +1,112 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Result;
+use jsonrpsee::core::client::ClientT;
+use jsonrpsee::rpc_params;
+use jsonrpsee::ws_client::WsClientBuilder;
+use serde::Deserialize;
+
+use crate::substrate::Client;
+
+/// `blake2_64("ContractsApi")`, the id of the runtime API serving dry-runs.
+const CONTRACTS_API_ID: &str = "0x68b66ba122c93fa7";
+/// `ContractsApi` version whose `call` arguments match [`super::query::CallRequest`].
+const CONTRACTS_API_VERSION: u32 = 2;
+const CONTRACTS_PALLET: &str = "Contracts";
+const CONTRACTS_CALLS: [&str; 4] = [
+    "call",
+    "instantiate",
+    "instantiate_with_code",
+    "upload_code",
+];
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RuntimeVersion {
+    spec_name: String,
+    spec_version: u32,
+    apis: Vec<(String, u32)>,
+}
+
+/// Outcome of comparing a node's runtime with the calls this crate makes.
+#[derive(Debug, Clone)]
+pub struct CompatibilityReport {
+    pub spec_name: String,
+    pub spec_version: u32,
+    /// Version of the `ContractsApi` runtime API, if the runtime exposes it.
+    pub contracts_api_version: Option<u32>,
+    /// Index of the contracts pallet, if the runtime has one.
+    pub contracts_pallet_index: Option<u8>,
+    /// Human readable incompatibilities, empty if the runtime is supported.
+    pub issues: Vec<String>,
+}
+
+impl CompatibilityReport {
+    pub fn is_compatible(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Checks that the runtime at `url` serves the runtime API and pallet calls used by
+/// this crate.
+///
+/// Every call is built dynamically from the runtime metadata, so the report only
+/// flags differences that would change how the arguments are encoded.
+pub async fn check_compatibility(url: &str) -> Result<CompatibilityReport> {
+    let rpc = WsClientBuilder::default().build(url).await?;
+    let version: RuntimeVersion = rpc
+        .request("state_getRuntimeVersion", rpc_params![])
+        .await?;
+
+    let mut issues = Vec::new();
+
+    let contracts_api_version = version
+        .apis
+        .iter()
+        .find(|(id, _)| id == CONTRACTS_API_ID)
+        .map(|(_, version)| *version);
+    match contracts_api_version {
+        None => issues.push("Runtime does not expose the ContractsApi runtime API".to_owned()),
+        Some(api_version) if api_version != CONTRACTS_API_VERSION => issues.push(format!(
+            "ContractsApi version {api_version} is not supported, expected {CONTRACTS_API_VERSION}"
+        )),
+        Some(_) => {}
+    }
+
+    let client = Client::from_url(url).await?;
+    let metadata = client.metadata();
+    let contracts_pallet_index = match metadata.pallet_by_name(CONTRACTS_PALLET) {
+        Some(pallet) => {
+            for call in CONTRACTS_CALLS {
+                if pallet.call_variant_by_name(call).is_none() {
+                    issues.push(format!("Pallet {CONTRACTS_PALLET} has no `{call}` call"));
+                }
+            }
+            Some(pallet.index())
+        }
+        None => {
+            issues.push(format!("Runtime has no {CONTRACTS_PALLET} pallet"));
+            None
+        }
+    };
+
+    Ok(CompatibilityReport {
+        spec_name: version.spec_name,
+        spec_version: version.spec_version,
+        contracts_api_version,
+        contracts_pallet_index,
+        issues,
+    })
+}
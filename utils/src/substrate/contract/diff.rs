@@ -0,0 +1,133 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use contract_transcode::Value;
+use pallet_contracts_primitives::StorageDeposit;
+use sp_weights::Weight;
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::substrate::Balance;
+
+/// Result of dry-running a message, with the resources it used.
+#[derive(Debug, Clone)]
+pub struct DryRunOutcome {
+    /// Decoded return value, or the error the dry-run failed with.
+    pub value: Result<Value, String>,
+    /// Whether the contract reverted, its return value then being its error.
+    pub reverted: bool,
+    pub gas_consumed: Weight,
+    /// Gas limit needed for the call to succeed, which can exceed the gas consumed.
+    pub gas_required: Weight,
+    /// Deposit charged for the storage the call adds, negative if it frees storage.
+    pub storage_deposit: i128,
+}
+
+impl DryRunOutcome {
+    pub(crate) fn storage_deposit_of(deposit: &StorageDeposit<Balance>) -> i128 {
+        match deposit {
+            StorageDeposit::Charge(charge) => i128::try_from(*charge).unwrap_or(i128::MAX),
+            StorageDeposit::Refund(refund) => -i128::try_from(*refund).unwrap_or(i128::MAX),
+        }
+    }
+}
+
+/// Differences between dry-runs of the same message, e.g. against the current and
+/// upgraded code of a contract, or against two nodes.
+#[derive(Debug, Clone)]
+pub struct DryRunDiff {
+    pub message: String,
+    pub before: DryRunOutcome,
+    pub after: DryRunOutcome,
+}
+
+impl DryRunDiff {
+    pub fn value_changed(&self) -> bool {
+        self.before.value != self.after.value || self.before.reverted != self.after.reverted
+    }
+
+    /// Change of the consumed `ref_time`, positive if the call got more expensive.
+    pub fn gas_delta(&self) -> i128 {
+        i128::from(self.after.gas_consumed.ref_time())
+            - i128::from(self.before.gas_consumed.ref_time())
+    }
+
+    /// Change of the consumed `ref_time` relative to `before`, in percent.
+    pub fn gas_delta_percent(&self) -> f64 {
+        let before = self.before.gas_consumed.ref_time();
+        if before == 0 {
+            return 0.0;
+        }
+        self.gas_delta() as f64 * 100.0 / before as f64
+    }
+
+    pub fn storage_deposit_delta(&self) -> i128 {
+        self.after.storage_deposit - self.before.storage_deposit
+    }
+
+    /// Whether the call consumes more than `max_increase_percent` additional gas,
+    /// e.g. to fail a CI gas budget.
+    pub fn exceeds_gas_budget(&self, max_increase_percent: f64) -> bool {
+        self.gas_delta_percent() > max_increase_percent
+    }
+
+    pub fn has_changes(&self) -> bool {
+        self.value_changed() || self.gas_delta() != 0 || self.storage_deposit_delta() != 0
+    }
+}
+
+impl fmt::Display for DryRunDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}:", self.message)?;
+        if self.value_changed() {
+            writeln!(
+                f,
+                "  value: {} -> {}",
+                render_value(&self.before),
+                render_value(&self.after)
+            )?;
+        } else {
+            writeln!(f, "  value: {} (unchanged)", render_value(&self.after))?;
+        }
+        writeln!(
+            f,
+            "  gas consumed: {} -> {} ({:+}, {:+.2}%)",
+            self.before.gas_consumed.ref_time(),
+            self.after.gas_consumed.ref_time(),
+            self.gas_delta(),
+            self.gas_delta_percent()
+        )?;
+        writeln!(
+            f,
+            "  proof size: {} -> {}",
+            self.before.gas_consumed.proof_size(),
+            self.after.gas_consumed.proof_size()
+        )?;
+        write!(
+            f,
+            "  storage deposit: {} -> {} ({:+})",
+            self.before.storage_deposit,
+            self.after.storage_deposit,
+            self.storage_deposit_delta()
+        )
+    }
+}
+
+fn render_value(outcome: &DryRunOutcome) -> String {
+    match (&outcome.value, outcome.reverted) {
+        (Ok(value), false) => value.to_string(),
+        (Ok(value), true) => format!("reverted with {value}"),
+        (Err(err), _) => format!("failed: {err}"),
+    }
+}
@@ -12,28 +12,39 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use sp_runtime::DispatchError;
+use anyhow::Context;
+use sp_runtime::{ArithmeticError, DispatchError, TokenError};
 use std::fmt::{self, Debug, Display};
 
+use crate::substrate::redact;
+
 #[derive(serde::Serialize)]
 pub enum ErrorVariant {
     #[serde(rename = "module_error")]
     Module(ModuleError),
+    /// A fungible token operation failed, e.g. the account cannot afford a transfer.
+    #[serde(rename = "token_error")]
+    Token(RuntimeError),
+    /// A runtime computation overflowed, underflowed or divided by zero.
+    #[serde(rename = "arithmetic_error")]
+    Arithmetic(RuntimeError),
     #[serde(rename = "generic_error")]
     Generic(GenericError),
 }
 
 impl From<subxt::Error> for ErrorVariant {
     fn from(error: subxt::Error) -> Self {
+        use subxt::error::DispatchError as SubxtDispatchError;
+
         match error {
-            subxt::Error::Runtime(subxt::error::DispatchError::Module(module_err)) => module_err
+            subxt::Error::Runtime(SubxtDispatchError::Module(module_err)) => module_err
                 .details()
                 .map(|details| {
-                    ErrorVariant::Module(ModuleError {
-                        pallet: details.pallet().to_string(),
-                        error: details.error().to_string(),
-                        docs: details.docs().to_vec(),
-                    })
+                    ErrorVariant::Module(ModuleError::new(
+                        details.pallet.name(),
+                        &details.variant.name,
+                        details.variant.docs.clone(),
+                    ))
                 })
                 .unwrap_or_else(|err| {
                     ErrorVariant::Generic(GenericError::from_message(format!(
@@ -41,6 +52,22 @@ impl From<subxt::Error> for ErrorVariant {
                         err
                     )))
                 }),
+            subxt::Error::Runtime(SubxtDispatchError::Token(err)) => {
+                ErrorVariant::Token(RuntimeError {
+                    error: format!("{err:?}"),
+                    hint: runtime_token_error(&err)
+                        .as_ref()
+                        .and_then(token_error_hint),
+                })
+            }
+            subxt::Error::Runtime(SubxtDispatchError::Arithmetic(err)) => {
+                ErrorVariant::Arithmetic(RuntimeError {
+                    error: format!("{err:?}"),
+                    hint: runtime_arithmetic_error(&err)
+                        .as_ref()
+                        .and_then(arithmetic_error_hint),
+                })
+            }
             err => ErrorVariant::Generic(GenericError::from_message(err.to_string())),
         }
     }
@@ -63,6 +90,110 @@ pub struct ModuleError {
     pub pallet: String,
     pub error: String,
     pub docs: Vec<String>,
+    /// How to avoid the error, for the errors commonly hit when calling contracts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<&'static str>,
+}
+
+impl ModuleError {
+    pub fn new(pallet: &str, error: &str, docs: Vec<String>) -> Self {
+        ModuleError {
+            pallet: pallet.to_owned(),
+            error: error.to_owned(),
+            docs,
+            hint: module_error_hint(pallet, error),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct RuntimeError {
+    pub error: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<&'static str>,
+}
+
+fn module_error_hint(pallet: &str, error: &str) -> Option<&'static str> {
+    let hint = match (pallet, error) {
+        ("Contracts", "OutOfGas") => {
+            "Raise the gas limit, or leave it unset to use the dry-run estimate"
+        }
+        ("Contracts", "StorageDepositLimitExhausted") => {
+            "Raise the storage deposit limit, or leave it unset to use the dry-run estimate"
+        }
+        ("Contracts", "StorageDepositNotEnoughFunds") => {
+            "The caller cannot afford the storage deposit, fund the account"
+        }
+        ("Contracts", "TransferFailed") => {
+            "The value transferred would leave an account below the existential deposit"
+        }
+        ("Contracts", "ContractTrapped") => {
+            "The contract panicked, check its debug messages with a dry-run"
+        }
+        ("Contracts", "ContractReverted") => "The contract reverted, decode its return value",
+        ("Contracts", "ContractNotFound") => "No contract lives at this address on this chain",
+        ("Contracts", "CodeNotFound") => "Upload the code before instantiating it",
+        ("Contracts", "CodeTooLarge") => "Build the contract in release mode to shrink its code",
+        ("Contracts", "CodeRejected") => {
+            "The code uses features the chain does not support, rebuild it with cargo contract"
+        }
+        ("Contracts", "DuplicateContract") => "Instantiate with another salt",
+        ("Contracts", "MaxCallDepthReached") => "The call nests too many cross-contract calls",
+        ("Contracts", "Indeterministic") => "Upload the code with enforced determinism",
+        ("Balances", "InsufficientBalance") => "Fund the account before sending the call",
+        ("Balances", "ExistentialDeposit") => "Transfer at least the existential deposit",
+        _ => return None,
+    };
+    Some(hint)
+}
+
+fn token_error_hint(error: &TokenError) -> Option<&'static str> {
+    let hint = match error {
+        TokenError::FundsUnavailable => "The account cannot afford the transfer or fee",
+        TokenError::OnlyProvider => "The account would be reaped, keep some balance on it",
+        TokenError::BelowMinimum => "Transfer at least the existential deposit",
+        TokenError::CannotCreate => "The receiving account cannot be created",
+        TokenError::Frozen => "The funds are frozen, e.g. staked or locked by governance",
+        _ => return None,
+    };
+    Some(hint)
+}
+
+fn arithmetic_error_hint(error: &ArithmeticError) -> Option<&'static str> {
+    let hint = match error {
+        ArithmeticError::Underflow => "A balance or counter went below zero, check the amounts",
+        ArithmeticError::Overflow => "A balance or counter exceeded its maximum, check the amounts",
+        ArithmeticError::DivisionByZero => "A division by zero occurred in the runtime",
+    };
+    Some(hint)
+}
+
+/// The runtime error of a token error reported by subxt, for the variants with hints.
+fn runtime_token_error(error: &subxt::error::TokenError) -> Option<TokenError> {
+    use subxt::error::TokenError as SubxtTokenError;
+
+    let error = match error {
+        SubxtTokenError::FundsUnavailable => TokenError::FundsUnavailable,
+        SubxtTokenError::OnlyProvider => TokenError::OnlyProvider,
+        SubxtTokenError::BelowMinimum => TokenError::BelowMinimum,
+        SubxtTokenError::CannotCreate => TokenError::CannotCreate,
+        SubxtTokenError::Frozen => TokenError::Frozen,
+        _ => return None,
+    };
+    Some(error)
+}
+
+/// The runtime error of an arithmetic error reported by subxt.
+fn runtime_arithmetic_error(error: &subxt::error::ArithmeticError) -> Option<ArithmeticError> {
+    use subxt::error::ArithmeticError as SubxtArithmeticError;
+
+    let error = match error {
+        SubxtArithmeticError::Underflow => ArithmeticError::Underflow,
+        SubxtArithmeticError::Overflow => ArithmeticError::Overflow,
+        SubxtArithmeticError::DivisionByZero => ArithmeticError::DivisionByZero,
+        _ => return None,
+    };
+    Some(error)
 }
 
 #[derive(serde::Serialize)]
@@ -77,19 +208,47 @@ impl GenericError {
 }
 
 impl ErrorVariant {
+    /// The error with every occurrence of `values` replaced, see
+    /// [`SensitiveArgs`](crate::substrate::redact::SensitiveArgs).
+    ///
+    /// Only generic errors carry text derived from the caller's input.
+    pub(crate) fn blind(self, values: &[&str]) -> Self {
+        match self {
+            ErrorVariant::Generic(err) if !values.is_empty() => ErrorVariant::Generic(
+                GenericError::from_message(redact::blind(&err.error, values)),
+            ),
+            err => err,
+        }
+    }
+
     pub fn from_dispatch_error(
         error: &DispatchError,
         metadata: &subxt::Metadata,
     ) -> anyhow::Result<ErrorVariant> {
         match error {
             DispatchError::Module(err) => {
-                let details = metadata.error(err.index, err.error[0])?;
-                Ok(ErrorVariant::Module(ModuleError {
-                    pallet: details.pallet().to_owned(),
-                    error: details.error().to_owned(),
-                    docs: details.docs().to_owned(),
-                }))
+                let pallet = metadata
+                    .pallet_by_index(err.index)
+                    .with_context(|| format!("No pallet with index {}", err.index))?;
+                let variant = pallet
+                    .error_variant_by_index(err.error[0])
+                    .with_context(|| {
+                        format!("No error {} in pallet {}", err.error[0], pallet.name())
+                    })?;
+                Ok(ErrorVariant::Module(ModuleError::new(
+                    pallet.name(),
+                    &variant.name,
+                    variant.docs.clone(),
+                )))
             }
+            DispatchError::Token(err) => Ok(ErrorVariant::Token(RuntimeError {
+                error: format!("{err:?}"),
+                hint: token_error_hint(err),
+            })),
+            DispatchError::Arithmetic(err) => Ok(ErrorVariant::Arithmetic(RuntimeError {
+                error: format!("{err:?}"),
+                hint: arithmetic_error_hint(err),
+            })),
             err => Ok(ErrorVariant::Generic(GenericError::from_message(format!(
                 "DispatchError: {err:?}"
             )))),
@@ -106,11 +265,29 @@ impl Debug for ErrorVariant {
 impl Display for ErrorVariant {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            ErrorVariant::Module(err) => f.write_fmt(format_args!(
-                "ModuleError: {}::{}: {:?}",
-                err.pallet, err.error, err.docs
-            )),
+            ErrorVariant::Module(err) => {
+                f.write_fmt(format_args!(
+                    "ModuleError: {}::{}: {:?}",
+                    err.pallet, err.error, err.docs
+                ))?;
+                write_hint(f, err.hint)
+            }
+            ErrorVariant::Token(err) => {
+                write!(f, "TokenError: {}", err.error)?;
+                write_hint(f, err.hint)
+            }
+            ErrorVariant::Arithmetic(err) => {
+                write!(f, "ArithmeticError: {}", err.error)?;
+                write_hint(f, err.hint)
+            }
             ErrorVariant::Generic(err) => write!(f, "{}", err.error),
         }
     }
 }
+
+fn write_hint(f: &mut fmt::Formatter<'_>, hint: Option<&str>) -> fmt::Result {
+    match hint {
+        Some(hint) => write!(f, " (hint: {hint})"),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,245 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use sp_core::H256;
+use std::convert::TryFrom;
+use subxt::dynamic::{self, Value};
+use subxt::ext::scale_value::{Composite, Primitive, ValueDef};
+
+use crate::substrate::{AccountId, Balance, BlockHash, Client};
+
+const CONTRACTS_PALLET: &str = "Contracts";
+const CONTRACT_INFO_OF: &str = "ContractInfoOf";
+/// Code owners are stored in `CodeInfoOf` since pallet-contracts v10, `OwnerInfoOf` before.
+const CODE_INFO_ENTRIES: [&str; 2] = ["CodeInfoOf", "OwnerInfoOf"];
+/// Deposit fields of `ContractInfo`, split by kind on newer runtimes.
+const STORAGE_DEPOSIT_FIELDS: [&str; 4] = [
+    "storage_deposit",
+    "storage_byte_deposit",
+    "storage_item_deposit",
+    "storage_base_deposit",
+];
+
+/// On-chain details of an instantiated contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractInfo {
+    pub address: AccountId,
+    pub code_hash: H256,
+    /// Unique id of the contract's child trie.
+    pub trie_id: Vec<u8>,
+    /// Account holding the storage deposit, on runtimes that have one.
+    pub deposit_account: Option<AccountId>,
+    /// Deposit paid for the contract's storage.
+    pub storage_deposit: Balance,
+    /// Number of storage items, on runtimes that track it.
+    pub storage_items: Option<u32>,
+    /// Size of the contract's storage in bytes, on runtimes that track it.
+    pub storage_bytes: Option<u32>,
+    /// Account that uploaded the code.
+    pub code_owner: Option<AccountId>,
+    /// Deposit paid by the owner for the code.
+    pub code_deposit: Option<Balance>,
+}
+
+/// Reads the details of the contract at `address`, `None` if no contract lives there.
+pub async fn get_contract_info(url: &str, address: &AccountId) -> Result<Option<ContractInfo>> {
+    get_contract_info_at(url, address, None).await
+}
+
+/// Same as [`get_contract_info`], on the state of block `at` or else the latest block.
+///
+/// Nodes only keep the state of recent blocks unless run as archive nodes.
+pub async fn get_contract_info_at(
+    url: &str,
+    address: &AccountId,
+    at: Option<BlockHash>,
+) -> Result<Option<ContractInfo>> {
+    let client = Client::from_url(url).await?;
+    let info = match fetch_at(&client, CONTRACTS_PALLET, CONTRACT_INFO_OF, address.0, at).await? {
+        Some(info) => info,
+        None => return Ok(None),
+    };
+
+    let code_hash = field(&info, "code_hash")
+        .and_then(bytes)
+        .context("ContractInfoOf has no code_hash")?;
+    let code_hash = h256(&code_hash)?;
+    let trie_id = field(&info, "trie_id")
+        .and_then(bytes)
+        .context("ContractInfoOf has no trie_id")?;
+    let deposit_account = field(&info, "deposit_account")
+        .and_then(bytes)
+        .map(|account| account_id(&account))
+        .transpose()?;
+    let storage_deposit = STORAGE_DEPOSIT_FIELDS
+        .iter()
+        .filter_map(|name| field(&info, name).and_then(Value::as_u128))
+        .sum();
+    let count = |name: &str| {
+        field(&info, name)
+            .and_then(Value::as_u128)
+            .and_then(|count| u32::try_from(count).ok())
+    };
+
+    let mut code_info = None;
+    let metadata = client.metadata();
+    let storage = metadata
+        .pallet_by_name(CONTRACTS_PALLET)
+        .and_then(|pallet| pallet.storage())
+        .context("The runtime has no contracts pallet")?;
+    // Runtimes have either entry, only the one they have is read.
+    for entry in CODE_INFO_ENTRIES {
+        if storage.entry_by_name(entry).is_none() {
+            continue;
+        }
+        if let Some(info) = fetch_at(&client, CONTRACTS_PALLET, entry, code_hash.0, at).await? {
+            code_info = Some(info);
+            break;
+        }
+    }
+    let code_owner = code_info
+        .as_ref()
+        .and_then(|info| field(info, "owner"))
+        .and_then(bytes)
+        .map(|account| account_id(&account))
+        .transpose()?;
+    let code_deposit = code_info
+        .as_ref()
+        .and_then(|info| field(info, "deposit"))
+        .and_then(Value::as_u128);
+
+    Ok(Some(ContractInfo {
+        address: address.clone(),
+        code_hash,
+        trie_id,
+        deposit_account,
+        storage_deposit,
+        storage_items: count("storage_items"),
+        storage_bytes: count("storage_bytes"),
+        code_owner,
+        code_deposit,
+    }))
+}
+
+/// Code hash of the contract at `address`, `None` if no contract lives there.
+pub async fn code_hash_of(url: &str, address: &AccountId) -> Result<Option<H256>> {
+    Ok(get_contract_info(url, address)
+        .await?
+        .map(|info| info.code_hash))
+}
+
+/// Lists every contract instantiated from `code_hash`.
+///
+/// Walks the whole `ContractInfoOf` map, so it is slow on chains with many
+/// contracts.
+pub async fn find_contracts_by_code_hash(url: &str, code_hash: &H256) -> Result<Vec<AccountId>> {
+    let client = Client::from_url(url).await?;
+    let root = dynamic::storage(CONTRACTS_PALLET, CONTRACT_INFO_OF, Vec::<Value>::new());
+    let mut entries = client.storage().at_latest().await?.iter(root).await?;
+
+    let mut contracts = Vec::new();
+    while let Some(entry) = entries.next().await {
+        let (key, info) = entry?;
+        let info = info.to_value()?;
+        let matches = field(&info, "code_hash")
+            .and_then(bytes)
+            .is_some_and(|hash| hash == code_hash.as_bytes());
+        if matches {
+            // Keys are hashed with Twox64Concat, the account id ends the key.
+            let offset = key
+                .len()
+                .checked_sub(32)
+                .context("Storage key is too short")?;
+            contracts.push(account_id(&key[offset..])?);
+        }
+    }
+    Ok(contracts)
+}
+
+/// Reads a storage map entry keyed by 32 bytes, decoded with the runtime metadata.
+pub(crate) async fn fetch(
+    client: &Client,
+    pallet: &str,
+    entry: &str,
+    key: [u8; 32],
+) -> Result<Option<Value<u32>>> {
+    fetch_at(client, pallet, entry, key, None).await
+}
+
+/// Same as [`fetch`], on the state of block `at` or else the latest block.
+pub(crate) async fn fetch_at(
+    client: &Client,
+    pallet: &str,
+    entry: &str,
+    key: [u8; 32],
+    at: Option<BlockHash>,
+) -> Result<Option<Value<u32>>> {
+    let address = dynamic::storage(pallet, entry, vec![Value::from_bytes(key)]);
+    let storage = match at {
+        Some(at) => client.storage().at(at),
+        None => client.storage().at_latest().await?,
+    };
+    let value = storage.fetch(&address).await.with_context(|| match at {
+        Some(at) => format!("Failed to read {pallet}::{entry} at block {at:?}"),
+        None => format!("Failed to read {pallet}::{entry}"),
+    })?;
+    match value {
+        Some(value) => Ok(Some(value.to_value()?)),
+        None => Ok(None),
+    }
+}
+
+/// Named field of a composite value.
+pub(crate) fn field<'a, T>(value: &'a Value<T>, name: &str) -> Option<&'a Value<T>> {
+    match &value.value {
+        ValueDef::Composite(Composite::Named(fields)) => fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value),
+        _ => None,
+    }
+}
+
+/// Bytes of a byte array or sequence, unwrapping newtypes such as `AccountId32`.
+pub(crate) fn bytes<T>(value: &Value<T>) -> Option<Vec<u8>> {
+    match &value.value {
+        ValueDef::Composite(composite) => {
+            let values: Vec<_> = composite.values().collect();
+            match values.as_slice() {
+                [inner] if !matches!(inner.value, ValueDef::Primitive(_)) => bytes(inner),
+                values => values
+                    .iter()
+                    .map(|value| match value.value {
+                        ValueDef::Primitive(Primitive::U128(byte)) => u8::try_from(byte).ok(),
+                        _ => None,
+                    })
+                    .collect(),
+            }
+        }
+        _ => None,
+    }
+}
+
+pub(crate) fn h256(bytes: &[u8]) -> Result<H256> {
+    if bytes.len() != 32 {
+        anyhow::bail!("Expected a 32 byte hash, got {} bytes", bytes.len());
+    }
+    Ok(H256::from_slice(bytes))
+}
+
+pub(crate) fn account_id(bytes: &[u8]) -> Result<AccountId> {
+    let bytes = <[u8; 32]>::try_from(bytes).context("Expected a 32 byte account id")?;
+    Ok(bytes.into())
+}
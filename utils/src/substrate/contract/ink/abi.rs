@@ -0,0 +1,319 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Context, Result};
+use contract_transcode::{Hex, Tuple, Value};
+use ethabi::ethereum_types::U256;
+use ethabi::token::{LenientTokenizer, Tokenizer};
+use ethabi::{Function, StateMutability, Token};
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::args::{self, ArgDiagnostic, ArgOptions};
+use super::decode::{self, DecodeOptions};
+use super::transcoder::InkTranscoder;
+
+/// Encodes the calls and decodes the return values of a contract's messages.
+pub enum ContractAbi {
+    /// ink! metadata, also emitted by solang for contracts targeting
+    /// pallet-contracts, whose calls are SCALE encoded.
+    Ink(Box<InkTranscoder>),
+    /// Ethereum-style ABI JSON of a Solidity contract, whose calls are ABI encoded.
+    Solidity(SolidityAbi),
+}
+
+impl ContractAbi {
+    /// Encodes a call of `msg_name` with `args`, in the transcoder syntax for ink!
+    /// metadata or the `ethabi` lenient syntax for Solidity ABIs.
+    pub fn encode(&self, msg_name: &str, args: &[String], options: &ArgOptions) -> Result<Vec<u8>> {
+        match self {
+            ContractAbi::Ink(transcoder) => {
+                let args = args::prepare_message_args(transcoder, msg_name, args, options)?;
+                transcoder.encode(msg_name, &args)
+            }
+            ContractAbi::Solidity(abi) => abi.encode(msg_name, args),
+        }
+    }
+
+    /// Checks the arguments of a call of `msg_name` without encoding it, returning
+    /// a diagnostic per invalid argument, see [`args::validate_message_args`].
+    pub fn validate_args(
+        &self,
+        msg_name: &str,
+        args: &[String],
+        options: &ArgOptions,
+    ) -> Vec<ArgDiagnostic> {
+        match self {
+            ContractAbi::Ink(transcoder) => {
+                args::validate_message_args(transcoder, msg_name, args, options)
+            }
+            ContractAbi::Solidity(abi) => abi.validate_args(msg_name, args),
+        }
+    }
+
+    /// Decodes the return value of `msg_name`.
+    pub fn decode_return(
+        &self,
+        msg_name: &str,
+        data: &[u8],
+        options: &DecodeOptions,
+    ) -> Result<Value> {
+        match self {
+            ContractAbi::Ink(transcoder) => {
+                decode::decode_return(transcoder, msg_name, data, options)
+            }
+            ContractAbi::Solidity(abi) => abi.decode_return(msg_name, data),
+        }
+    }
+
+    /// Labels of the parameters of `msg_name`, `None` if it is unknown or an
+    /// overloaded Solidity function.
+    pub fn arg_labels(&self, msg_name: &str) -> Option<Vec<String>> {
+        match self {
+            ContractAbi::Ink(transcoder) => transcoder
+                .metadata()
+                .spec()
+                .messages()
+                .iter()
+                .find(|message| message.label() == msg_name)
+                .map(|message| {
+                    message
+                        .args()
+                        .iter()
+                        .map(|arg| arg.label().to_owned())
+                        .collect()
+                }),
+            ContractAbi::Solidity(abi) => abi.function(msg_name, None).ok().map(|function| {
+                function
+                    .inputs
+                    .iter()
+                    .map(|param| param.name.clone())
+                    .collect()
+            }),
+        }
+    }
+
+    /// Whether `msg_name` called with `args` may mutate the contract state, `None`
+    /// if it is unknown.
+    ///
+    /// Solidity functions mutate unless they are `view` or `pure`, overloads being
+    /// told apart by their number of arguments.
+    pub fn mutates(&self, msg_name: &str, args: &[String]) -> Option<bool> {
+        match self {
+            ContractAbi::Ink(transcoder) => transcoder
+                .metadata()
+                .spec()
+                .messages()
+                .iter()
+                .find(|message| message.label() == msg_name)
+                .map(|message| message.mutates()),
+            ContractAbi::Solidity(abi) => abi.mutates(msg_name, args.len()),
+        }
+    }
+
+    /// The ink! transcoder, for features relying on the ink! type registry.
+    pub fn as_ink(&self) -> Option<&InkTranscoder> {
+        match self {
+            ContractAbi::Ink(transcoder) => Some(&**transcoder),
+            ContractAbi::Solidity(_) => None,
+        }
+    }
+}
+
+impl From<InkTranscoder> for ContractAbi {
+    fn from(transcoder: InkTranscoder) -> Self {
+        ContractAbi::Ink(Box::new(transcoder))
+    }
+}
+
+/// Ethereum-style ABI of a Solidity contract, as written by `solc --abi` or solang.
+#[derive(Debug, Clone)]
+pub struct SolidityAbi {
+    contract: ethabi::Contract,
+}
+
+impl SolidityAbi {
+    /// Loads the ABI JSON file at `path`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read ABI {}", path.display()))?;
+        Self::from_json(&json)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        let contract =
+            ethabi::Contract::load(json.as_bytes()).context("Invalid Solidity ABI JSON")?;
+        Ok(Self { contract })
+    }
+
+    /// Names of the functions of the contract.
+    pub fn functions(&self) -> impl Iterator<Item = &str> {
+        self.contract
+            .functions()
+            .map(|function| function.name.as_str())
+    }
+
+    /// Encodes a call of `msg_name`, its 4 bytes selector followed by the
+    /// arguments.
+    ///
+    /// Overloaded functions are told apart by their number of arguments.
+    pub fn encode(&self, msg_name: &str, args: &[String]) -> Result<Vec<u8>> {
+        let function = self.function(msg_name, Some(args.len()))?;
+        let tokens = function
+            .inputs
+            .iter()
+            .zip(args)
+            .map(|(param, arg)| {
+                LenientTokenizer::tokenize(&param.kind, arg)
+                    .with_context(|| format!("Invalid argument {} of {msg_name}", param.name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(function.encode_input(&tokens)?)
+    }
+
+    /// Checks the arguments of a call of `msg_name` against the types of its
+    /// parameters, returning a diagnostic per invalid argument.
+    pub fn validate_args(&self, msg_name: &str, args: &[String]) -> Vec<ArgDiagnostic> {
+        let function = match self.function(msg_name, Some(args.len())) {
+            Ok(function) => function,
+            Err(err) => return vec![ArgDiagnostic::general(err.to_string())],
+        };
+        function
+            .inputs
+            .iter()
+            .zip(args)
+            .enumerate()
+            .filter_map(|(i, (param, arg))| {
+                let err = LenientTokenizer::tokenize(&param.kind, arg).err()?;
+                Some(ArgDiagnostic {
+                    index: Some(i),
+                    label: Some(param.name.clone()),
+                    expected: Some(param.kind.to_string()),
+                    path: Some(format!("{msg_name}.args[{i}]")),
+                    message: err.to_string(),
+                    position: None,
+                })
+            })
+            .collect()
+    }
+
+    /// Whether the overload of `msg_name` taking `arity` arguments may mutate the
+    /// contract state. If there is no such overload, `msg_name` mutates if any of
+    /// its overloads does.
+    pub fn mutates(&self, msg_name: &str, arity: usize) -> Option<bool> {
+        let mutates = |function: &Function| {
+            !matches!(
+                function.state_mutability,
+                StateMutability::View | StateMutability::Pure
+            )
+        };
+        match self.function(msg_name, Some(arity)) {
+            Ok(function) => Some(mutates(function)),
+            Err(_) => self
+                .contract
+                .functions_by_name(msg_name)
+                .ok()
+                .map(|functions| functions.iter().any(mutates)),
+        }
+    }
+
+    /// Decodes the return values of `msg_name`: the value itself for a single
+    /// return value, a tuple otherwise.
+    pub fn decode_return(&self, msg_name: &str, data: &[u8]) -> Result<Value> {
+        let function = self.function(msg_name, None)?;
+        let mut tokens = function
+            .decode_output(data)
+            .with_context(|| format!("Failed to decode the output of {msg_name}"))?;
+        if tokens.len() == 1 {
+            return token_to_value(tokens.remove(0));
+        }
+        let values = tokens
+            .into_iter()
+            .map(token_to_value)
+            .collect::<Result<_>>()?;
+        Ok(Value::Tuple(Tuple::new(None, values)))
+    }
+
+    fn function(&self, msg_name: &str, arity: Option<usize>) -> Result<&Function> {
+        let functions = self
+            .contract
+            .functions_by_name(msg_name)
+            .map_err(|_| anyhow!("No function {msg_name} in the ABI"))?;
+        match arity {
+            Some(arity) => functions
+                .iter()
+                .find(|function| function.inputs.len() == arity)
+                .with_context(|| format!("No function {msg_name} taking {arity} arguments")),
+            None => match functions.as_slice() {
+                [function] => Ok(function),
+                _ => anyhow::bail!("Function {msg_name} is overloaded"),
+            },
+        }
+    }
+}
+
+/// Converts a decoded ABI token to a transcoder value, integers beyond 128 bits
+/// becoming decimal literals.
+fn token_to_value(token: Token) -> Result<Value> {
+    let value = match token {
+        Token::Address(address) => hex_value(address.as_bytes())?,
+        Token::FixedBytes(bytes) | Token::Bytes(bytes) => hex_value(&bytes)?,
+        Token::Uint(value) => match u128::try_from(value) {
+            Ok(value) => Value::UInt(value),
+            Err(_) => Value::Literal(value.to_string()),
+        },
+        Token::Int(value) => int_value(value),
+        Token::Bool(value) => Value::Bool(value),
+        Token::String(value) => Value::String(value),
+        Token::FixedArray(tokens) | Token::Array(tokens) => Value::Seq(
+            tokens
+                .into_iter()
+                .map(token_to_value)
+                .collect::<Result<Vec<_>>>()?
+                .into(),
+        ),
+        Token::Tuple(tokens) => Value::Tuple(Tuple::new(
+            None,
+            tokens
+                .into_iter()
+                .map(token_to_value)
+                .collect::<Result<_>>()?,
+        )),
+    };
+    Ok(value)
+}
+
+/// Reads a two's complement `int256`.
+fn int_value(value: U256) -> Value {
+    let negative = value.bit(255);
+    let magnitude = if negative {
+        (!value).overflowing_add(U256::one()).0
+    } else {
+        value
+    };
+    match i128::try_from(magnitude) {
+        Ok(magnitude) if negative => Value::Int(-magnitude),
+        Ok(magnitude) => Value::Int(magnitude),
+        Err(_) if negative => Value::Literal(format!("-{magnitude}")),
+        Err(_) => Value::Literal(magnitude.to_string()),
+    }
+}
+
+fn hex_value(bytes: &[u8]) -> Result<Value> {
+    let hex = Hex::from_str(&format!("0x{}", hex::encode(bytes)))
+        .map_err(|err| anyhow!("Invalid hex value: {err:?}"))?;
+    Ok(Value::Hex(hex))
+}
@@ -0,0 +1,1018 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Result;
+use contract_transcode::Value;
+use scale_info::{form::PortableForm, Field, PortableRegistry, TypeDef, TypeDefPrimitive};
+use serde::Serialize;
+use std::fmt;
+
+use super::scon;
+use super::transcoder::InkTranscoder;
+use super::values::{byte_string_len, Collection};
+
+/// How integer arguments that do not fit their parameter type are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntegerOverflow {
+    /// Reject the argument, naming the parameter and its range.
+    #[default]
+    Error,
+    /// Clamp the argument to the closest bound of the parameter type.
+    Saturate,
+}
+
+/// Checks applied to message and constructor arguments before they are encoded.
+#[derive(Debug, Clone, Default)]
+pub struct ArgOptions {
+    pub overflow: IntegerOverflow,
+    /// Accepts quoted strings for `Vec<u8>` and `[u8; N]` parameters, encoded as
+    /// their UTF-8 bytes.
+    pub strings_as_bytes: bool,
+}
+
+impl ArgOptions {
+    pub fn overflow(mut self, overflow: IntegerOverflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    pub fn strings_as_bytes(mut self, strings_as_bytes: bool) -> Self {
+        self.strings_as_bytes = strings_as_bytes;
+        self
+    }
+}
+
+/// A message or constructor parameter, as declared in the metadata.
+struct Param<'a> {
+    label: &'a str,
+    ty: u32,
+    /// Type name used in the contract source, e.g. `Balance`.
+    display_name: String,
+}
+
+/// A problem with an argument, found by [`validate_message_args`] before the call is
+/// encoded, e.g. to be shown next to the input field of the argument.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ArgDiagnostic {
+    /// Position of the parameter, `None` for problems with the argument list as a
+    /// whole, such as too many arguments.
+    pub index: Option<usize>,
+    /// Label of the parameter, or the unknown name an argument was given.
+    pub label: Option<String>,
+    /// Type expected, e.g. `Balance (u128)`.
+    pub expected: Option<String>,
+    /// Breadcrumb to the offending value, e.g. `transfer.args[1].Foo.bar[3]`.
+    pub path: Option<String>,
+    pub message: String,
+    /// Offset in the argument, in bytes, at which it fails to parse, if known.
+    pub position: Option<usize>,
+}
+
+impl ArgDiagnostic {
+    pub(crate) fn general(message: impl Into<String>) -> Self {
+        Self {
+            index: None,
+            label: None,
+            expected: None,
+            path: None,
+            message: message.into(),
+            position: None,
+        }
+    }
+
+    fn of_param(index: usize, param: &Param, message: impl Into<String>) -> Self {
+        Self {
+            index: Some(index),
+            label: Some(param.label.to_owned()),
+            expected: (!param.display_name.is_empty()).then(|| param.display_name.clone()),
+            ..Self::general(message)
+        }
+    }
+}
+
+impl fmt::Display for ArgDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.label, &self.path) {
+            (Some(label), Some(path)) => write!(f, "Argument `{label}` at `{path}`: ")?,
+            (Some(label), None) => write!(f, "Argument `{label}`: ")?,
+            _ => {}
+        }
+        if let Some(expected) = &self.expected {
+            write!(f, "expected {expected}, ")?;
+        }
+        f.write_str(&self.message)?;
+        if let Some(position) = self.position {
+            write!(f, " at offset {position}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the arguments of a message against its parameter types, returning them
+/// ready for the transcoder.
+///
+/// Integer arguments are checked against the bounds of their type, including
+/// `Compact` ones, and nested values against the fields and variants of theirs, so
+/// errors name the parameter and the path to the offending value rather than a type
+/// id. [`IntegerOverflow::Saturate`] only applies to top-level integer arguments.
+/// `BTreeMap` arguments may be given as map literals, `{ key: value, .. }`.
+/// Arguments of unknown messages are returned as is, for the transcoder to report.
+///
+/// Arguments may also be named, as `name=value` or a map literal of all of them,
+/// `{ name: value, .. }`, and omitted `Option` arguments are `None`.
+pub fn prepare_message_args(
+    transcoder: &InkTranscoder,
+    msg_name: &str,
+    args: &[String],
+    options: &ArgOptions,
+) -> Result<Vec<String>> {
+    match message_params(transcoder, msg_name) {
+        Some(params) => prepare(
+            transcoder.metadata().registry(),
+            msg_name,
+            params,
+            args,
+            options,
+        ),
+        None => Ok(args.to_vec()),
+    }
+}
+
+/// Same as [`prepare_message_args`], for a constructor.
+#[cfg(feature = "testing")]
+pub fn prepare_constructor_args(
+    transcoder: &InkTranscoder,
+    name: &str,
+    args: &[String],
+    options: &ArgOptions,
+) -> Result<Vec<String>> {
+    match constructor_params(transcoder, name) {
+        Some(params) => prepare(
+            transcoder.metadata().registry(),
+            name,
+            params,
+            args,
+            options,
+        ),
+        None => Ok(args.to_vec()),
+    }
+}
+
+/// Checks the arguments of a message like [`prepare_message_args`] without encoding
+/// them, reporting every invalid argument rather than the first one, an empty list
+/// meaning the arguments are valid.
+///
+/// Arguments that fail to parse are reported with the parser error and, where it
+/// can be told, the offset of the offending character. Values the transcoder
+/// converts with custom encoders, such as SS58 addresses, are only checked when
+/// encoded.
+pub fn validate_message_args(
+    transcoder: &InkTranscoder,
+    msg_name: &str,
+    args: &[String],
+    options: &ArgOptions,
+) -> Vec<ArgDiagnostic> {
+    match message_params(transcoder, msg_name) {
+        Some(params) => validate(
+            transcoder.metadata().registry(),
+            msg_name,
+            &params,
+            args,
+            options,
+        ),
+        None => vec![ArgDiagnostic::general(format!("No message {msg_name}"))],
+    }
+}
+
+fn message_params<'a>(transcoder: &'a InkTranscoder, msg_name: &str) -> Option<Vec<Param<'a>>> {
+    let message = transcoder
+        .metadata()
+        .spec()
+        .messages()
+        .iter()
+        .find(|message| message.label() == msg_name)?;
+    Some(
+        message
+            .args()
+            .iter()
+            .map(|arg| Param {
+                label: arg.label(),
+                ty: arg.ty().ty().id,
+                display_name: arg.ty().display_name().segments.join("::"),
+            })
+            .collect(),
+    )
+}
+
+#[cfg(feature = "testing")]
+fn constructor_params<'a>(transcoder: &'a InkTranscoder, name: &str) -> Option<Vec<Param<'a>>> {
+    let constructor = transcoder
+        .metadata()
+        .spec()
+        .constructors()
+        .iter()
+        .find(|constructor| constructor.label() == name)?;
+    Some(
+        constructor
+            .args()
+            .iter()
+            .map(|arg| Param {
+                label: arg.label(),
+                ty: arg.ty().ty().id,
+                display_name: arg.ty().display_name().segments.join("::"),
+            })
+            .collect(),
+    )
+}
+
+fn prepare(
+    registry: &PortableRegistry,
+    name: &str,
+    params: Vec<Param>,
+    args: &[String],
+    options: &ArgOptions,
+) -> Result<Vec<String>> {
+    let args = resolve_named_args(registry, name, &params, args).map_err(|diagnostics| {
+        let problems: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        anyhow::anyhow!("Invalid arguments of {name}: {}", problems.join("; "))
+    })?;
+    if params.len() != args.len() {
+        return Ok(args);
+    }
+
+    params
+        .iter()
+        .zip(&args)
+        .enumerate()
+        .map(|(i, (param, arg))| {
+            check_arg(registry, format!("{name}.args[{i}]"), param, arg, options).map_err(
+                |mismatch| {
+                    anyhow::anyhow!(
+                        "Invalid argument `{}` at `{}`: expected {}, {}",
+                        param.label,
+                        mismatch.path,
+                        mismatch.expected,
+                        mismatch.reason
+                    )
+                },
+            )
+        })
+        .collect()
+}
+
+fn validate(
+    registry: &PortableRegistry,
+    name: &str,
+    params: &[Param],
+    args: &[String],
+    options: &ArgOptions,
+) -> Vec<ArgDiagnostic> {
+    let args = match resolve_named_args(registry, name, params, args) {
+        Ok(args) => args,
+        Err(diagnostics) => return diagnostics,
+    };
+    if params.len() != args.len() {
+        return vec![ArgDiagnostic::general(format!(
+            "{name} takes {} arguments, got {}",
+            params.len(),
+            args.len()
+        ))];
+    }
+
+    let mut diagnostics = Vec::new();
+    for (i, (param, arg)) in params.iter().zip(&args).enumerate() {
+        let root = format!("{name}.args[{i}]");
+        if integer_type(registry, param.ty).is_none() {
+            if let Err(err) = scon::parse_value(arg) {
+                diagnostics.push(ArgDiagnostic {
+                    path: Some(root),
+                    position: parse_error_position(arg),
+                    ..ArgDiagnostic::of_param(i, param, format!("`{arg}` is invalid: {err}"))
+                });
+                continue;
+            }
+        }
+        if let Err(mismatch) = check_arg(registry, root, param, arg, options) {
+            diagnostics.push(ArgDiagnostic {
+                expected: Some(mismatch.expected),
+                path: Some(mismatch.path),
+                ..ArgDiagnostic::of_param(i, param, mismatch.reason)
+            });
+        }
+    }
+    diagnostics
+}
+
+/// Checks the argument `arg` of `param`, at `root`, returning it as the transcoder
+/// should see it.
+fn check_arg(
+    registry: &PortableRegistry,
+    root: String,
+    param: &Param,
+    arg: &str,
+    options: &ArgOptions,
+) -> Result<String, Mismatch> {
+    match integer_type(registry, param.ty) {
+        Some(integer) => integer
+            .check(arg, options.overflow)
+            .map_err(|reason| Mismatch {
+                path: root,
+                expected: integer.describe(&param.display_name),
+                reason,
+            }),
+        None => match scon::parse_value(arg) {
+            Ok(Value::String(text)) if options.strings_as_bytes => {
+                match byte_string_len(registry, param.ty) {
+                    Some(Some(len)) if text.len() != len as usize => Err(Mismatch {
+                        path: root,
+                        expected: format!("{len} bytes"),
+                        reason: format!("\"{text}\" is {} bytes long", text.len()),
+                    }),
+                    Some(_) => Ok(format!("0x{}", hex::encode(text))),
+                    None => Ok(arg.to_owned()),
+                }
+            }
+            Ok(value) => ValueChecker::new(registry, root)
+                .check(param.ty, &value)
+                .map(|()| {
+                    map_literal(registry, param.ty, &value).unwrap_or_else(|| arg.to_owned())
+                }),
+            // Left for the transcoder to report with its parser error.
+            Err(_) => Ok(arg.to_owned()),
+        },
+    }
+}
+
+/// Offset of the first unbalanced delimiter or unterminated string of `arg`, the
+/// usual reasons a value fails to parse.
+fn parse_error_position(arg: &str) -> Option<usize> {
+    let mut open: Vec<(usize, char)> = Vec::new();
+    let mut string_start = None;
+    let mut escaped = false;
+    for (i, c) in arg.char_indices() {
+        if string_start.is_some() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => string_start = None,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => string_start = Some(i),
+            '(' | '[' | '{' => open.push((i, c)),
+            ')' | ']' | '}' => {
+                let expected = match c {
+                    ')' => '(',
+                    ']' => '[',
+                    _ => '{',
+                };
+                match open.pop() {
+                    Some((_, opener)) if opener == expected => {}
+                    _ => return Some(i),
+                }
+            }
+            _ => {}
+        }
+    }
+    string_start.or_else(|| open.last().map(|(i, _)| *i))
+}
+
+/// Orders named arguments by parameter and fills in omitted `Option` arguments with
+/// `None`, returning the arguments in positional form.
+///
+/// Arguments are named either as `name=value`, after any positional ones, or all
+/// at once as a map literal, `{ name: value, .. }`, unless the message takes a
+/// single struct or map the literal could be a value of. Missing arguments that are
+/// not `Option`s are reported by name.
+fn resolve_named_args(
+    registry: &PortableRegistry,
+    name: &str,
+    params: &[Param],
+    args: &[String],
+) -> Result<Vec<String>, Vec<ArgDiagnostic>> {
+    let labels: Vec<&str> = params.iter().map(|param| param.label).collect();
+    let mut positional = Vec::new();
+    let mut named: Vec<(String, String)> = Vec::new();
+    match named_map(registry, params, args) {
+        Some(map) => named = map,
+        None => {
+            for arg in args {
+                // Values never start with `identifier=`, which is no SCON syntax.
+                match arg.split_once('=') {
+                    Some((label, value)) if is_identifier(label.trim()) => {
+                        named.push((label.trim().to_owned(), value.trim().to_owned()))
+                    }
+                    _ if named.is_empty() => positional.push(arg.clone()),
+                    _ => {
+                        return Err(vec![ArgDiagnostic::general(format!(
+                            "Positional argument `{arg}` of {name} follows named arguments"
+                        ))])
+                    }
+                }
+            }
+        }
+    }
+    if named.is_empty() && positional.len() >= params.len() {
+        return Ok(positional);
+    }
+    if positional.len() > params.len() {
+        return Err(vec![ArgDiagnostic::general(format!(
+            "{name} takes {} arguments, got {}",
+            params.len(),
+            positional.len()
+        ))]);
+    }
+
+    let mut diagnostics = Vec::new();
+    let mut resolved: Vec<Option<String>> = positional.into_iter().map(Some).collect();
+    resolved.resize(params.len(), None);
+    for (label, value) in named {
+        match labels.iter().position(|known| *known == label) {
+            Some(i) if resolved[i].is_some() => diagnostics.push(ArgDiagnostic::of_param(
+                i,
+                &params[i],
+                "argument is given twice",
+            )),
+            Some(i) => resolved[i] = Some(value),
+            None => diagnostics.push(ArgDiagnostic {
+                label: Some(label),
+                ..ArgDiagnostic::general(format!(
+                    "unknown argument, expected one of `{}`",
+                    labels.join("`, `")
+                ))
+            }),
+        }
+    }
+    for (i, (param, arg)) in params.iter().zip(&mut resolved).enumerate() {
+        if arg.is_none() {
+            if is_option(registry, param.ty) {
+                *arg = Some("None".to_owned());
+            } else {
+                diagnostics.push(ArgDiagnostic::of_param(
+                    i,
+                    param,
+                    "required argument is missing",
+                ));
+            }
+        }
+    }
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+    Ok(resolved.into_iter().flatten().collect())
+}
+
+/// The entries of a lone map literal naming arguments, e.g. `{ to: .., value: .. }`.
+fn named_map(
+    registry: &PortableRegistry,
+    params: &[Param],
+    args: &[String],
+) -> Option<Vec<(String, String)>> {
+    let map = match args {
+        [arg] => match scon::parse_value(arg).ok()? {
+            Value::Map(map) if map.ident().is_none() => map,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    // The literal is the value of a lone struct or map parameter.
+    if let [param] = params {
+        let is_struct_or_map = match registry.resolve(param.ty) {
+            Some(resolved) => {
+                matches!(resolved.type_def, TypeDef::Composite(_))
+                    || Collection::of(registry, resolved).is_some()
+            }
+            None => true,
+        };
+        if is_struct_or_map {
+            return None;
+        }
+    }
+    map.iter()
+        .map(|(key, value)| match key {
+            Value::String(label) if params.iter().any(|param| param.label == label) => {
+                Some((label.clone(), value.to_string()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn is_identifier(label: &str) -> bool {
+    let mut chars = label.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn is_option(registry: &PortableRegistry, ty: u32) -> bool {
+    registry.resolve(ty).is_some_and(|resolved| {
+        matches!(resolved.type_def, TypeDef::Variant(_))
+            && resolved.path.ident().as_deref() == Some("Option")
+    })
+}
+
+/// Rewrites a map literal `{ key: value, .. }` given for a `BTreeMap` parameter as
+/// the sequence of tuples the transcoder encodes it from.
+fn map_literal(registry: &PortableRegistry, ty: u32, value: &Value) -> Option<String> {
+    let map = match value {
+        Value::Map(map) if map.ident().is_none_or(|ident| ident == "BTreeMap") => map,
+        _ => return None,
+    };
+    match Collection::of(registry, registry.resolve(ty)?)? {
+        Collection::Map { .. } => {
+            let entries: Vec<_> = map
+                .iter()
+                .map(|(key, value)| format!("({key}, {value})"))
+                .collect();
+            Some(format!("[{}]", entries.join(", ")))
+        }
+        Collection::Set { .. } => None,
+    }
+}
+
+/// Resolves the integer type of a parameter, looking through `Compact`.
+fn integer_type(registry: &PortableRegistry, mut ty: u32) -> Option<IntegerType> {
+    loop {
+        match &registry.resolve(ty)?.type_def {
+            TypeDef::Compact(compact) => ty = compact.type_param.id,
+            TypeDef::Primitive(primitive) => return IntegerType::from_primitive(primitive),
+            _ => return None,
+        }
+    }
+}
+
+/// An argument value that does not match the type expected at `path`.
+struct Mismatch {
+    /// Breadcrumb to the value, e.g. `transfer.args[1].Foo.bar[3]`.
+    path: String,
+    expected: String,
+    reason: String,
+}
+
+/// Checks a parsed argument against its type, recursing into nested values.
+///
+/// Only shapes the transcoder would reject are reported: values it converts with
+/// custom encoders, such as addresses given as SS58 literals, are left to it.
+struct ValueChecker<'a> {
+    registry: &'a PortableRegistry,
+    path: Vec<String>,
+}
+
+impl<'a> ValueChecker<'a> {
+    fn new(registry: &'a PortableRegistry, root: String) -> Self {
+        Self {
+            registry,
+            path: vec![root],
+        }
+    }
+
+    fn check(&mut self, ty: u32, value: &Value) -> Result<(), Mismatch> {
+        let registry = self.registry;
+        let resolved = match registry.resolve(ty) {
+            Some(resolved) => resolved,
+            None => return Ok(()),
+        };
+        match &resolved.type_def {
+            TypeDef::Compact(compact) => self.check(compact.type_param.id, value),
+            TypeDef::Primitive(primitive) => self.check_primitive(primitive, value),
+            TypeDef::Composite(composite) => {
+                let name = resolved.path.ident().unwrap_or_else(|| "struct".to_owned());
+                self.check_fields(&name, &composite.fields, value)
+            }
+            TypeDef::Variant(variant) => {
+                let ident = match value {
+                    Value::Map(map) => map.ident(),
+                    Value::Tuple(tuple) => tuple.ident(),
+                    _ => None,
+                };
+                let ident = match ident {
+                    Some(ident) => ident,
+                    None => return Ok(()),
+                };
+                let name = resolved.path.ident().unwrap_or_else(|| "enum".to_owned());
+                let matched = match variant.variants.iter().find(|v| v.name == ident) {
+                    Some(matched) => matched,
+                    None => {
+                        let names: Vec<_> =
+                            variant.variants.iter().map(|v| v.name.as_str()).collect();
+                        return Err(self.mismatch(
+                            name,
+                            format!(
+                                "unknown variant `{ident}`, expected one of {}",
+                                names.join(", ")
+                            ),
+                        ));
+                    }
+                };
+                self.path.push(format!(".{ident}"));
+                self.check_fields(&ident, &matched.fields, value)?;
+                self.path.pop();
+                Ok(())
+            }
+            TypeDef::Sequence(sequence) => match value {
+                Value::Seq(seq) => self.check_elements(sequence.type_param.id, seq.elems()),
+                _ => Ok(()),
+            },
+            TypeDef::Array(array) => match value {
+                Value::Seq(seq) if seq.len() != array.len as usize => Err(self.mismatch(
+                    format!("an array of {} elements", array.len),
+                    format!("got {} elements", seq.len()),
+                )),
+                Value::Seq(seq) => self.check_elements(array.type_param.id, seq.elems()),
+                _ => Ok(()),
+            },
+            TypeDef::Tuple(tuple) => match value {
+                Value::Tuple(values) if values.ident().is_none() => {
+                    let values: Vec<_> = values.values().collect();
+                    if values.len() != tuple.fields.len() {
+                        return Err(self.mismatch(
+                            format!("a tuple of {} elements", tuple.fields.len()),
+                            format!("got {} elements", values.len()),
+                        ));
+                    }
+                    for (i, (field, value)) in tuple.fields.iter().zip(values).enumerate() {
+                        self.path.push(format!(".{i}"));
+                        self.check(field.id, value)?;
+                        self.path.pop();
+                    }
+                    Ok(())
+                }
+                _ => Ok(()),
+            },
+            TypeDef::BitSequence(_) => Ok(()),
+        }
+    }
+
+    fn check_primitive(&self, primitive: &TypeDefPrimitive, value: &Value) -> Result<(), Mismatch> {
+        if let Some(integer) = IntegerType::from_primitive(primitive) {
+            let (negative, magnitude) = match value {
+                Value::UInt(value) => (false, *value),
+                Value::Int(value) => (*value < 0, value.unsigned_abs()),
+                other => return Err(self.mismatch(integer.name(), format!("got `{other}`"))),
+            };
+            if !integer.fits(negative, magnitude) {
+                return Err(self.mismatch(
+                    integer.name(),
+                    format!("{value} is out of range {}", integer.range()),
+                ));
+            }
+            return Ok(());
+        }
+        match (primitive, value) {
+            (TypeDefPrimitive::Bool, Value::Bool(_))
+            | (TypeDefPrimitive::Char, Value::Char(_))
+            | (TypeDefPrimitive::Str, Value::String(_)) => Ok(()),
+            (TypeDefPrimitive::Bool, other) => Err(self.mismatch("bool", format!("got `{other}`"))),
+            (TypeDefPrimitive::Char, other) => Err(self.mismatch("char", format!("got `{other}`"))),
+            (TypeDefPrimitive::Str, other) => {
+                Err(self.mismatch("a quoted string", format!("got `{other}`")))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the fields of a struct or enum variant named `name`.
+    fn check_fields(
+        &mut self,
+        name: &str,
+        fields: &[Field<PortableForm>],
+        value: &Value,
+    ) -> Result<(), Mismatch> {
+        match value {
+            Value::Map(map) => {
+                for field in fields {
+                    let field_name = match &field.name {
+                        Some(field_name) => field_name,
+                        None => return Ok(()),
+                    };
+                    let field_value = map.get_by_str(field_name).ok_or_else(|| {
+                        self.mismatch(name, format!("missing field `{field_name}`"))
+                    })?;
+                    self.path.push(format!(".{field_name}"));
+                    self.check(field.ty.id, field_value)?;
+                    self.path.pop();
+                }
+                Ok(())
+            }
+            Value::Tuple(tuple) if fields.iter().all(|field| field.name.is_none()) => {
+                let values: Vec<_> = tuple.values().collect();
+                if values.len() != fields.len() {
+                    return Err(self.mismatch(
+                        format!("{name} with {} fields", fields.len()),
+                        format!("got {}", values.len()),
+                    ));
+                }
+                for (i, (field, value)) in fields.iter().zip(values).enumerate() {
+                    self.path.push(format!(".{i}"));
+                    self.check(field.ty.id, value)?;
+                    self.path.pop();
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_elements(&mut self, ty: u32, elems: &[Value]) -> Result<(), Mismatch> {
+        for (i, elem) in elems.iter().enumerate() {
+            self.path.push(format!("[{i}]"));
+            self.check(ty, elem)?;
+            self.path.pop();
+        }
+        Ok(())
+    }
+
+    fn mismatch(&self, expected: impl Into<String>, reason: impl Into<String>) -> Mismatch {
+        Mismatch {
+            path: self.path.concat(),
+            expected: expected.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct IntegerType {
+    bits: u32,
+    signed: bool,
+}
+
+impl IntegerType {
+    fn from_primitive(primitive: &TypeDefPrimitive) -> Option<Self> {
+        let (bits, signed) = match primitive {
+            TypeDefPrimitive::U8 => (8, false),
+            TypeDefPrimitive::U16 => (16, false),
+            TypeDefPrimitive::U32 => (32, false),
+            TypeDefPrimitive::U64 => (64, false),
+            TypeDefPrimitive::U128 => (128, false),
+            TypeDefPrimitive::I8 => (8, true),
+            TypeDefPrimitive::I16 => (16, true),
+            TypeDefPrimitive::I32 => (32, true),
+            TypeDefPrimitive::I64 => (64, true),
+            TypeDefPrimitive::I128 => (128, true),
+            _ => return None,
+        };
+        Some(Self { bits, signed })
+    }
+
+    fn max(&self) -> u128 {
+        match (self.signed, self.bits) {
+            (true, bits) => (1 << (bits - 1)) - 1,
+            (false, 128) => u128::MAX,
+            (false, bits) => (1 << bits) - 1,
+        }
+    }
+
+    /// Magnitude of the smallest value.
+    fn min_magnitude(&self) -> u128 {
+        if self.signed {
+            1 << (self.bits - 1)
+        } else {
+            0
+        }
+    }
+
+    fn fits(&self, negative: bool, magnitude: u128) -> bool {
+        if negative {
+            magnitude <= self.min_magnitude()
+        } else {
+            magnitude <= self.max()
+        }
+    }
+
+    fn name(&self) -> String {
+        format!("{}{}", if self.signed { 'i' } else { 'u' }, self.bits)
+    }
+
+    fn describe(&self, display_name: &str) -> String {
+        let name = self.name();
+        if display_name.is_empty() || display_name == name {
+            name
+        } else {
+            format!("{display_name} ({name})")
+        }
+    }
+
+    fn range(&self) -> String {
+        if self.signed {
+            format!("-{}..={}", self.min_magnitude(), self.max())
+        } else {
+            format!("0..={}", self.max())
+        }
+    }
+
+    /// Checks an integer literal, returning it as the transcoder should see it.
+    ///
+    /// Literals are read as the transcoder reads them: decimal digits, optionally
+    /// grouped by `_`, or for unsigned types `0x` followed by an even number of hex
+    /// digits.
+    fn check(&self, literal: &str, overflow: IntegerOverflow) -> Result<String, String> {
+        let trimmed = literal.trim();
+        let not_an_integer = || format!("`{literal}` is not an integer");
+        let (negative, magnitude) = if let Some(digits) = trimmed.strip_prefix("0x") {
+            if self.signed {
+                return Err(format!(
+                    "`{literal}` is not an integer, hex literals are only read as unsigned"
+                ));
+            }
+            if digits.is_empty()
+                || digits.len() % 2 != 0
+                || !digits.bytes().all(|b| b.is_ascii_hexdigit())
+            {
+                return Err(not_an_integer());
+            }
+            (false, u128::from_str_radix(digits, 16))
+        } else {
+            let (negative, digits) = match trimmed.strip_prefix('-') {
+                Some(digits) => (true, digits),
+                None => (false, trimmed),
+            };
+            let grouped = digits
+                .split('_')
+                .all(|group| !group.is_empty() && group.bytes().all(|b| b.is_ascii_digit()));
+            if !grouped {
+                return Err(not_an_integer());
+            }
+            (negative, digits.replace('_', "").parse::<u128>())
+        };
+        // Literals that do not fit in u128 are out of range of every type.
+        let out_of_range = match magnitude {
+            Ok(magnitude) => !self.fits(negative, magnitude),
+            Err(_) => true,
+        };
+        let bound = match (negative, self.min_magnitude()) {
+            (false, _) => self.max().to_string(),
+            (true, 0) => "0".to_owned(),
+            (true, min) => format!("-{min}"),
+        };
+
+        match (out_of_range, overflow) {
+            (false, _) => Ok(literal.to_owned()),
+            (true, IntegerOverflow::Saturate) => Ok(bound),
+            (true, IntegerOverflow::Error) => {
+                Err(format!("{trimmed} is out of range {}", self.range()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn integer(bits: u32, signed: bool) -> IntegerType {
+        IntegerType { bits, signed }
+    }
+
+    #[test]
+    fn accepts_integers_up_to_each_bound() {
+        let cases = [
+            (integer(8, false), "0", "255", "256"),
+            (integer(16, false), "0", "65_535", "65_536"),
+            (integer(32, false), "0", "4294967295", "4294967296"),
+            (
+                integer(64, false),
+                "0",
+                "18446744073709551615",
+                "18446744073709551616",
+            ),
+            (
+                integer(128, false),
+                "0",
+                "340282366920938463463374607431768211455",
+                "340282366920938463463374607431768211456",
+            ),
+            (integer(8, true), "-128", "127", "128"),
+            (integer(16, true), "-32768", "32767", "32768"),
+            (
+                integer(32, true),
+                "-2_147_483_648",
+                "2147483647",
+                "2147483648",
+            ),
+            (
+                integer(64, true),
+                "-9223372036854775808",
+                "9223372036854775807",
+                "9223372036854775808",
+            ),
+            (
+                integer(128, true),
+                "-170141183460469231731687303715884105728",
+                "170141183460469231731687303715884105727",
+                "170141183460469231731687303715884105728",
+            ),
+        ];
+        for (ty, min, max, above) in cases {
+            let name = ty.name();
+            assert_eq!(
+                ty.check(min, IntegerOverflow::Error).as_deref(),
+                Ok(min),
+                "{name}"
+            );
+            assert_eq!(
+                ty.check(max, IntegerOverflow::Error).as_deref(),
+                Ok(max),
+                "{name}"
+            );
+            assert!(ty.check(above, IntegerOverflow::Error).is_err(), "{}", name);
+            let below = match min.strip_prefix('-') {
+                Some(magnitude) => {
+                    let magnitude: u128 = magnitude.replace('_', "").parse().unwrap();
+                    format!("-{}", magnitude + 1)
+                }
+                None => "-1".to_owned(),
+            };
+            assert!(
+                ty.check(&below, IntegerOverflow::Error).is_err(),
+                "{}",
+                name
+            );
+            assert_eq!(
+                ty.check(above, IntegerOverflow::Saturate),
+                Ok(ty.max().to_string()),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn saturates_to_the_minimum() {
+        let i8 = integer(8, true);
+        assert_eq!(
+            i8.check("-129", IntegerOverflow::Saturate).as_deref(),
+            Ok("-128")
+        );
+        let u8 = integer(8, false);
+        assert_eq!(
+            u8.check("-1", IntegerOverflow::Saturate).as_deref(),
+            Ok("0")
+        );
+    }
+
+    #[test]
+    fn reads_hex_literals_of_unsigned_integers() {
+        let u16 = integer(16, false);
+        assert_eq!(
+            u16.check("0xDEAD", IntegerOverflow::Error).as_deref(),
+            Ok("0xDEAD")
+        );
+        assert_eq!(
+            u16.check("0x00ff", IntegerOverflow::Error).as_deref(),
+            Ok("0x00ff")
+        );
+        assert!(u16.check("0x010000", IntegerOverflow::Error).is_err());
+        assert_eq!(
+            u16.check("0x010000", IntegerOverflow::Saturate).as_deref(),
+            Ok("65535")
+        );
+        let u128 = integer(128, false);
+        let max = format!("0x{}", "ff".repeat(16));
+        assert!(u128.check(&max, IntegerOverflow::Error).is_ok());
+        assert!(u128
+            .check(&format!("{max}ff"), IntegerOverflow::Error)
+            .is_err());
+        // The transcoder only reads hex as unsigned integers, and as whole bytes.
+        assert!(integer(16, true)
+            .check("0x01", IntegerOverflow::Error)
+            .is_err());
+        for literal in ["0x", "0x1", "0xfg", "0x_ff"] {
+            assert!(
+                u16.check(literal, IntegerOverflow::Error).is_err(),
+                "{}",
+                literal
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_misplaced_separators() {
+        let u32 = integer(32, false);
+        assert_eq!(
+            u32.check("1_000", IntegerOverflow::Error).as_deref(),
+            Ok("1_000")
+        );
+        for literal in ["", "-", "_1", "1_", "1__0", "1,000", "+1", "1e3", "--1"] {
+            assert!(
+                u32.check(literal, IntegerOverflow::Error).is_err(),
+                "{}",
+                literal
+            );
+        }
+    }
+}
@@ -0,0 +1,97 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use contract_metadata::{ContractMetadata, SourceWasm};
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::Path;
+
+use super::ContractBinary;
+
+/// Writing of contract metadata, e.g. to re-bundle artifacts once their code was
+/// verified by a reproducible build.
+///
+/// Metadata is written as canonical JSON: object keys sorted at every level and
+/// pretty-printed with a trailing newline, so that the same metadata always gives
+/// the same bytes whatever the order it was read in.
+pub trait MetadataExtension {
+    /// The metadata as canonical JSON.
+    fn to_canonical_json(&self) -> Result<String>;
+
+    /// Writes the metadata to `path` as canonical JSON, e.g. a `.json` metadata file
+    /// or, with its code, a `.contract` bundle.
+    fn save(&self, path: &Path) -> Result<()>;
+
+    /// The `.contract` bundle of this metadata and `code`, which must have the code
+    /// hash of the metadata.
+    fn to_contract_bundle(&self, code: &[u8]) -> Result<ContractMetadata>;
+
+    /// The metadata without its code, as in the `.json` file `cargo contract` writes
+    /// next to the bundle.
+    fn strip(&self) -> ContractMetadata;
+}
+
+impl MetadataExtension for ContractMetadata {
+    fn to_canonical_json(&self) -> Result<String> {
+        let value = serde_json::to_value(self).context("Failed to serialize contract metadata")?;
+        let mut json = serde_json::to_string_pretty(&canonical(value))?;
+        json.push('\n');
+        Ok(json)
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_canonical_json()?)
+            .with_context(|| format!("Failed to write metadata {}", path.display()))
+    }
+
+    fn to_contract_bundle(&self, code: &[u8]) -> Result<ContractMetadata> {
+        let hash = ContractBinary::new(code.to_vec()).code_hash();
+        if hash != self.source.hash.0 {
+            anyhow::bail!(
+                "Code hash 0x{} does not match the code hash 0x{} of the metadata",
+                hex::encode(hash),
+                hex::encode(self.source.hash.0)
+            );
+        }
+        let mut bundle = self.clone();
+        bundle.source.wasm = Some(SourceWasm::new(code.to_vec()));
+        Ok(bundle)
+    }
+
+    fn strip(&self) -> ContractMetadata {
+        let mut metadata = self.clone();
+        metadata.source.wasm = None;
+        metadata
+    }
+}
+
+/// `value` with the keys of its objects sorted, whether or not `serde_json` keeps
+/// them in insertion order.
+fn canonical(value: Value) -> Value {
+    match value {
+        Value::Object(object) => {
+            let mut entries: Vec<(String, Value)> = object.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, canonical(value)))
+                    .collect::<Map<_, _>>(),
+            )
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(canonical).collect()),
+        value => value,
+    }
+}
@@ -0,0 +1,346 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use toml::Value;
+
+use super::{InkMeta, HEALTH_CHECK_INTERVAL};
+use crate::substrate::address::Address;
+use crate::substrate::chain::{Chain, HashAlgorithm};
+use crate::substrate::endpoints::Endpoints;
+use crate::substrate::redact::SecretString;
+use crate::substrate::secrets::{SecretRef, SecretResolver};
+use crate::substrate::SubstrateBaseConfig;
+
+/// Keys of a contract table.
+const KEYS: &[&str] = &[
+    "contract_path",
+    "url",
+    "chain",
+    "ink_contract_id",
+    "phala_contract_id",
+    "revive_contract_id",
+    "code_hash_algorithm",
+    "suri",
+    "password",
+];
+/// Keys of the contract address, exactly one of which is set.
+const ADDRESS_KEYS: &[&str] = &["ink_contract_id", "phala_contract_id", "revive_contract_id"];
+const URL_SCHEMES: &[&str] = &["ws://", "wss://", "http://", "https://"];
+const CHAIN_FORMAT: &str =
+    "substrate, local, astar, shiden, shibuya, aleph-zero, aleph-zero-testnet, phala or an SS58 prefix";
+
+/// A contract table of a config file, as read by [`InkMeta::from_toml`].
+///
+/// ```toml
+/// contract_path = "target/ink/flipper.contract"
+/// url = ["wss://rpc.shibuya.astar.network", "wss://shibuya.public.blastapi.io"]
+/// chain = "shibuya"
+/// ink_contract_id = "5DAAnrj7VHTznn2AWBemMuyBwZWs6FNFjdyVXUeYum3PTXFy"
+/// suri = "env:CONTRACT_SURI"
+/// ```
+///
+/// `suri` and `password` are secret key URI and password of the querying account,
+/// best given as references resolved by a [`SecretResolver`], `env:<name>` or
+/// `keyring:<account>`, rather than written to the file.
+///
+/// Parsing only checks the types of the keys. [`ConfigFile::into_meta`] checks their
+/// values, reporting every invalid key at once, and warns of keys it does not know,
+/// e.g. misspelt ones that would otherwise be silently ignored.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConfigFile {
+    /// File or table the config was read from, named by errors.
+    #[serde(skip)]
+    source: String,
+    pub contract_path: Option<String>,
+    pub url: Option<Urls>,
+    pub chain: Option<String>,
+    pub ink_contract_id: Option<String>,
+    pub phala_contract_id: Option<String>,
+    pub revive_contract_id: Option<String>,
+    pub code_hash_algorithm: Option<String>,
+    pub suri: Option<SecretString>,
+    pub password: Option<SecretString>,
+    #[serde(flatten)]
+    unknown: BTreeMap<String, Value>,
+}
+
+/// Url of a node, or endpoints failed over to in order.
+#[derive(Debug, Clone)]
+pub enum Urls {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl<'de> Deserialize<'de> for Urls {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct UrlsVisitor;
+
+        impl<'de> Visitor<'de> for UrlsVisitor {
+            type Value = Urls;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a url or a list of urls")
+            }
+
+            fn visit_str<E: de::Error>(self, url: &str) -> Result<Urls, E> {
+                Ok(Urls::One(url.to_owned()))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Urls, A::Error> {
+                let mut urls = Vec::new();
+                while let Some(url) = seq.next_element()? {
+                    urls.push(url);
+                }
+                Ok(Urls::Many(urls))
+            }
+        }
+
+        deserializer.deserialize_any(UrlsVisitor)
+    }
+}
+
+impl ConfigFile {
+    /// Reads the contract table at the top level of the file at `path`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config {}", path.display()))?;
+        Self::parse(&content, &path.display().to_string())
+    }
+
+    /// Reads the contract tables of the file at `path`, by name.
+    pub fn load_profiles(path: &Path) -> Result<BTreeMap<String, Self>> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read profiles {}", path.display()))?;
+        let profiles: BTreeMap<String, Value> = toml::from_str(&content)
+            .with_context(|| format!("Invalid TOML in {}", path.display()))?;
+        profiles
+            .into_iter()
+            .map(|(name, profile)| {
+                let source = format!("profile {name} of {}", path.display());
+                Ok((name, Self::from_value(profile, &source)?))
+            })
+            .collect()
+    }
+
+    /// Parses a contract table, `source` naming where it was read from in errors.
+    pub fn parse(content: &str, source: &str) -> Result<Self> {
+        let value: Value =
+            toml::from_str(content).with_context(|| format!("Invalid TOML in {source}"))?;
+        Self::from_value(value, source)
+    }
+
+    pub fn from_value(value: Value, source: &str) -> Result<Self> {
+        if !value.is_table() {
+            anyhow::bail!(
+                "Invalid config {source}: expected a table, got {}",
+                value.type_str()
+            );
+        }
+        let mut config: Self = serde_path_to_error::deserialize(value).map_err(|err| {
+            anyhow::anyhow!(
+                "Invalid config {source}: key `{}`: {}",
+                err.path(),
+                err.inner()
+            )
+        })?;
+        config.source = source.to_owned();
+        Ok(config)
+    }
+
+    /// Keys that are not part of a contract table.
+    pub fn unknown_keys(&self) -> impl Iterator<Item = &str> {
+        self.unknown.keys().map(String::as_str)
+    }
+
+    /// Validates every key, returning the contract they describe.
+    ///
+    /// Endpoints listed in `url` are health checked in the background.
+    pub fn into_meta(self) -> Result<InkMeta> {
+        for key in self.unknown_keys() {
+            tracing::warn!(
+                source = %self.source,
+                key,
+                "Ignoring unknown config key, expected one of {}",
+                KEYS.join(", ")
+            );
+        }
+
+        let mut errors = Vec::new();
+        let file = match &self.contract_path {
+            Some(path) if !path.trim().is_empty() => Some(PathBuf::from(path)),
+            _ => {
+                errors.push(
+                    "`contract_path` is missing, expected the path or URI of a .contract, .json, \
+                     .wasm, .polkavm or .abi artifact"
+                        .to_owned(),
+                );
+                None
+            }
+        };
+        let address = self.address().map_err(|err| errors.push(err)).ok();
+        let chain = match &self.chain {
+            Some(chain) => Chain::from_str(chain)
+                .map_err(|err| errors.push(format!("`chain`: {err}, expected {CHAIN_FORMAT}")))
+                .ok(),
+            None => Some(Chain::default()),
+        };
+        // An invalid chain has no default url to report missing in its stead.
+        let urls = match (&self.url, chain) {
+            (None, None) => None,
+            (_, chain) => self
+                .urls(chain.and_then(|chain| chain.default_url()))
+                .map_err(|err| errors.push(err))
+                .ok(),
+        };
+        let code_hash_algorithm = match &self.code_hash_algorithm {
+            Some(algorithm) => HashAlgorithm::from_str(algorithm)
+                .map(Some)
+                .map_err(|err| errors.push(format!("`code_hash_algorithm`: {err}")))
+                .ok(),
+            None => Some(None),
+        };
+        for (key, secret) in [("suri", &self.suri), ("password", &self.password)] {
+            if let Some(Err(err)) = secret
+                .as_ref()
+                .map(|secret| SecretRef::parse(secret.expose_secret()))
+            {
+                errors.push(format!("`{key}`: {err}"));
+            }
+        }
+
+        match (file, address, chain, urls, code_hash_algorithm) {
+            (Some(file), Some(address), Some(chain), Some(urls), Some(code_hash_algorithm))
+                if errors.is_empty() =>
+            {
+                let (url, endpoints) = match urls {
+                    Urls::One(url) => (url, None),
+                    Urls::Many(urls) => {
+                        let endpoints = Arc::new(Endpoints::new(urls)?);
+                        endpoints.start_health_checks(HEALTH_CHECK_INTERVAL);
+                        (endpoints.urls()[0].clone(), Some(endpoints))
+                    }
+                };
+                Ok(InkMeta {
+                    file,
+                    url,
+                    endpoints,
+                    chain,
+                    address,
+                    code_hash_algorithm,
+                })
+            }
+            _ => anyhow::bail!(
+                "Invalid config {}:\n  - {}",
+                self.source,
+                errors.join("\n  - ")
+            ),
+        }
+    }
+
+    /// The account of `suri` and `password`, `None` if there is no `suri`.
+    pub fn base_config(&self, resolver: &SecretResolver) -> Result<Option<SubstrateBaseConfig>> {
+        let resolve = |key: &str, secret: &Option<SecretString>| {
+            secret
+                .as_ref()
+                .map(|secret| resolver.resolve(secret.expose_secret()))
+                .transpose()
+                .with_context(|| format!("Invalid config {}: key `{key}`", self.source))
+        };
+        let suri = match resolve("suri", &self.suri)? {
+            Some(suri) => suri,
+            None => return Ok(None),
+        };
+        let password = resolve("password", &self.password)?;
+        Ok(Some(SubstrateBaseConfig::from_secrets(suri, password)))
+    }
+
+    /// The address of the one contract id key set.
+    fn address(&self) -> Result<Address, String> {
+        let ids = [
+            &self.ink_contract_id,
+            &self.phala_contract_id,
+            &self.revive_contract_id,
+        ];
+        let set: Vec<(&str, &str)> = ADDRESS_KEYS
+            .iter()
+            .zip(ids)
+            .filter_map(|(key, id)| id.as_deref().map(|id| (*key, id)))
+            .collect();
+        let (key, id) = match set.as_slice() {
+            [one] => *one,
+            [] => {
+                return Err(format!(
+                    "The contract address is missing, expected one of `{}`",
+                    ADDRESS_KEYS.join("`, `")
+                ))
+            }
+            several => {
+                let keys: Vec<&str> = several.iter().map(|(key, _)| *key).collect();
+                return Err(format!(
+                    "Only one contract address may be set, got `{}`",
+                    keys.join("`, `")
+                ));
+            }
+        };
+        let (address, format) = match key {
+            "ink_contract_id" => (
+                Address::parse_ink(id),
+                "an SS58 address or 32 bytes of 0x-hex",
+            ),
+            "phala_contract_id" => (Address::parse_phala(id), "32 bytes of 0x-hex"),
+            _ => (Address::parse_revive(id), "20 bytes of 0x-hex"),
+        };
+        address.map_err(|err| format!("`{key}`: {err}, expected {format}"))
+    }
+
+    /// The urls, falling back to `default_url`, that of the chain, if there are none.
+    fn urls(&self, default_url: Option<&str>) -> Result<Urls, String> {
+        let urls =
+            match &self.url {
+                Some(urls) => urls.clone(),
+                None => return match default_url {
+                    Some(url) => Ok(Urls::One(url.to_owned())),
+                    None => Err(
+                        "`url` is missing, expected a ws(s):// or http(s):// url, as the chain \
+                         has no default endpoint"
+                            .to_owned(),
+                    ),
+                },
+            };
+        let list = match &urls {
+            Urls::One(url) => std::slice::from_ref(url),
+            Urls::Many(urls) if urls.is_empty() => {
+                return Err("`url` is an empty list, expected at least one url".to_owned())
+            }
+            Urls::Many(urls) => urls.as_slice(),
+        };
+        for url in list {
+            if !URL_SCHEMES.iter().any(|scheme| url.starts_with(scheme)) {
+                return Err(format!(
+                    "`url`: invalid url {url}, expected a ws(s):// or http(s):// url"
+                ));
+            }
+        }
+        Ok(urls)
+    }
+}
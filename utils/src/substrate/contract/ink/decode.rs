@@ -0,0 +1,491 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Context, Result};
+use contract_transcode::Value;
+use scale::Encode;
+use scale_info::{form::PortableForm, Field, PortableRegistry, TypeDef, TypeDefPrimitive};
+use std::convert::TryFrom;
+
+use super::transcoder::InkTranscoder;
+use super::values::Normalizer;
+
+/// Nesting depth of decoded values allowed by default.
+const DEFAULT_MAX_DEPTH: usize = 64;
+/// Number of elements in a decoded collection allowed by default.
+const DEFAULT_MAX_COLLECTION_LEN: usize = 1 << 20;
+/// Number of elements in all the collections of a decoded value allowed by default.
+const DEFAULT_MAX_ELEMENTS: usize = 1 << 22;
+
+/// Settings applied when decoding return values and events.
+#[derive(Debug, Clone)]
+pub struct DecodeOptions {
+    /// Deepest nesting of values, beyond which decoding fails instead of recursing.
+    pub max_depth: usize,
+    /// Most elements in a sequence, array, string or bit sequence.
+    pub max_collection_len: usize,
+    /// Most elements in all the collections of a value together, which bounds the
+    /// work of walking collections of zero-sized elements, e.g. `Vec<Vec<()>>`.
+    pub max_elements: usize,
+    /// Renders `Vec<u8>` and `[u8; N]` values holding UTF-8 text as strings, rather
+    /// than hex. Bytes that are not printable text are still rendered as hex.
+    pub bytes_as_strings: bool,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_collection_len: DEFAULT_MAX_COLLECTION_LEN,
+            max_elements: DEFAULT_MAX_ELEMENTS,
+            bytes_as_strings: false,
+        }
+    }
+}
+
+impl DecodeOptions {
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn max_collection_len(mut self, max_collection_len: usize) -> Self {
+        self.max_collection_len = max_collection_len;
+        self
+    }
+
+    pub fn max_elements(mut self, max_elements: usize) -> Self {
+        self.max_elements = max_elements;
+        self
+    }
+
+    pub fn bytes_as_strings(mut self, bytes_as_strings: bool) -> Self {
+        self.bytes_as_strings = bytes_as_strings;
+        self
+    }
+}
+
+/// Decodes the return value of a message.
+///
+/// The data is checked against the metadata and the limits of `options` before it
+/// reaches the transcoder, so data from an untrusted node cannot make it recurse or
+/// allocate without bound. Errors give the path to the value that failed, e.g.
+/// `get.return.Ok.items[3]`.
+pub fn decode_return(
+    transcoder: &InkTranscoder,
+    msg_name: &str,
+    data: &[u8],
+    options: &DecodeOptions,
+) -> Result<Value> {
+    let metadata = transcoder.metadata();
+    let message = metadata
+        .spec()
+        .messages()
+        .iter()
+        .find(|message| message.label() == msg_name);
+    let return_type = message.and_then(|message| message.return_type().opt_type());
+    if message.is_some() {
+        let root = format!("{msg_name}.return");
+        let mut walker = Walker::new(metadata.registry(), data, root, options);
+        if let Some(ty) = return_type {
+            walker.walk(ty.ty().id)?;
+        }
+        walker.finish()?;
+    }
+
+    let value = transcoder.decode_return(msg_name, &mut &data[..])?;
+    Ok(match return_type {
+        Some(ty) => Normalizer::new(transcoder, options).normalize_as(
+            ty.ty().id,
+            display_name(&ty.display_name().segments).as_deref(),
+            value,
+        ),
+        None => value,
+    })
+}
+
+/// Decodes an event prefixed with its index in the metadata.
+///
+/// The data is checked first, as [`decode_return`] does.
+pub fn decode_event(
+    transcoder: &InkTranscoder,
+    data: &[u8],
+    options: &DecodeOptions,
+) -> Result<Value> {
+    let metadata = transcoder.metadata();
+    let (index, fields) = data.split_first().context("Event data is empty")?;
+    let event = metadata
+        .spec()
+        .events()
+        .get(usize::from(*index))
+        .with_context(|| format!("No event with index {index} in the metadata"))?;
+    let root = event.label().to_owned();
+    let mut walker = Walker::new(metadata.registry(), fields, root, options);
+    for arg in event.args() {
+        walker.walk_field(&format!(".{}", arg.label()), arg.ty().ty().id)?;
+    }
+    walker.finish()?;
+
+    // The transcoder reads the data as the encoded `Vec<u8>` of the event record.
+    let value = transcoder.decode_contract_event(&mut &data.encode()[..])?;
+    Ok(
+        Normalizer::new(transcoder, options).normalize_map(value, |name| {
+            event
+                .args()
+                .iter()
+                .find(|arg| arg.label() == name)
+                .map(|arg| {
+                    (
+                        arg.ty().ty().id,
+                        display_name(&arg.ty().display_name().segments),
+                    )
+                })
+        }),
+    )
+}
+
+fn display_name(segments: &[String]) -> Option<String> {
+    if segments.is_empty() {
+        None
+    } else {
+        Some(segments.join("::"))
+    }
+}
+
+/// Walks SCALE encoded data along a type of the registry, without decoding it.
+pub(crate) struct Walker<'a> {
+    registry: &'a PortableRegistry,
+    data: &'a [u8],
+    path: Vec<String>,
+    options: &'a DecodeOptions,
+    depth: usize,
+    /// Elements left to walk before [`DecodeOptions::max_elements`] is reached.
+    elements_left: usize,
+}
+
+impl<'a> Walker<'a> {
+    pub(crate) fn new(
+        registry: &'a PortableRegistry,
+        data: &'a [u8],
+        root: String,
+        options: &'a DecodeOptions,
+    ) -> Self {
+        Self {
+            registry,
+            data,
+            path: vec![root],
+            options,
+            depth: 0,
+            elements_left: options.max_elements,
+        }
+    }
+
+    /// Advances past a value of type `ty`.
+    pub(crate) fn walk(&mut self, ty: u32) -> Result<()> {
+        if self.depth >= self.options.max_depth {
+            return Err(self.error(format!(
+                "Values nested deeper than {} levels",
+                self.options.max_depth
+            )));
+        }
+        self.depth += 1;
+        self.walk_type(ty)?;
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn walk_type(&mut self, ty: u32) -> Result<()> {
+        let registry = self.registry;
+        let resolved = registry
+            .resolve(ty)
+            .ok_or_else(|| self.error(format!("Unknown type id {ty}")))?;
+        match &resolved.type_def {
+            TypeDef::Primitive(primitive) => self.walk_primitive(primitive),
+            TypeDef::Compact(_) => self.compact().map(|_| ()),
+            TypeDef::Composite(composite) => self.walk_fields(&composite.fields),
+            TypeDef::Variant(variant) => {
+                let index = self.take(1)?[0];
+                let matched = variant
+                    .variants
+                    .iter()
+                    .find(|variant| variant.index == index)
+                    .ok_or_else(|| {
+                        let name = resolved.path.ident().unwrap_or_else(|| "enum".to_owned());
+                        self.error(format!("Invalid variant index {index} for {name}"))
+                    })?;
+                self.path.push(format!(".{}", matched.name));
+                self.walk_fields(&matched.fields)?;
+                self.path.pop();
+                Ok(())
+            }
+            TypeDef::Sequence(sequence) => {
+                let len = self.compact()?;
+                let len = self.length(len)?;
+                self.walk_elements(sequence.type_param.id, len)
+            }
+            TypeDef::Array(array) => {
+                let len = self.length(u128::from(array.len))?;
+                self.walk_elements(array.type_param.id, len)
+            }
+            TypeDef::Tuple(tuple) => {
+                for (i, field) in tuple.fields.iter().enumerate() {
+                    self.walk_field(&format!(".{i}"), field.id)?;
+                }
+                Ok(())
+            }
+            TypeDef::BitSequence(bits) => {
+                let store_size = match registry
+                    .resolve(bits.bit_store_type.id)
+                    .map(|ty| &ty.type_def)
+                {
+                    Some(TypeDef::Primitive(TypeDefPrimitive::U16)) => 2,
+                    Some(TypeDef::Primitive(TypeDefPrimitive::U32)) => 4,
+                    Some(TypeDef::Primitive(TypeDefPrimitive::U64)) => 8,
+                    _ => 1,
+                };
+                let len = self.compact()?;
+                let len = self.length(len)?;
+                let words = len.div_ceil(store_size * 8);
+                self.take(words * store_size).map(|_| ())
+            }
+        }
+    }
+
+    /// Fails if data is left after the walked values.
+    pub(crate) fn finish(&self) -> Result<()> {
+        if !self.data.is_empty() {
+            anyhow::bail!(
+                "{} bytes left after `{}`",
+                self.data.len(),
+                self.path.concat()
+            );
+        }
+        Ok(())
+    }
+
+    fn walk_field(&mut self, segment: &str, ty: u32) -> Result<()> {
+        self.path.push(segment.to_owned());
+        self.walk(ty)?;
+        self.path.pop();
+        Ok(())
+    }
+
+    fn walk_fields(&mut self, fields: &[Field<PortableForm>]) -> Result<()> {
+        for (i, field) in fields.iter().enumerate() {
+            let segment = match &field.name {
+                Some(name) => format!(".{name}"),
+                None => format!(".{i}"),
+            };
+            self.walk_field(&segment, field.ty.id)?;
+        }
+        Ok(())
+    }
+
+    fn walk_elements(&mut self, ty: u32, len: usize) -> Result<()> {
+        // Byte strings are skipped at once rather than element by element.
+        let registry = self.registry;
+        if let Some(TypeDef::Primitive(TypeDefPrimitive::U8)) =
+            registry.resolve(ty).map(|ty| &ty.type_def)
+        {
+            return self.take(len).map(|_| ());
+        }
+        // Elements taking no data would otherwise be walked for free.
+        self.elements_left = self.elements_left.checked_sub(len).ok_or_else(|| {
+            self.error(format!(
+                "More than {} elements in total",
+                self.options.max_elements
+            ))
+        })?;
+        for i in 0..len {
+            self.walk_field(&format!("[{i}]"), ty)?;
+        }
+        Ok(())
+    }
+
+    fn walk_primitive(&mut self, primitive: &TypeDefPrimitive) -> Result<()> {
+        let size = match primitive {
+            TypeDefPrimitive::Bool => {
+                let byte = self.take(1)?[0];
+                if byte > 1 {
+                    return Err(self.error(format!("Invalid bool {byte}")));
+                }
+                return Ok(());
+            }
+            TypeDefPrimitive::Str => {
+                let len = self.compact()?;
+                let len = self.length(len)?;
+                let bytes = self.take(len)?;
+                if std::str::from_utf8(bytes).is_err() {
+                    return Err(self.error("Invalid UTF-8 string"));
+                }
+                return Ok(());
+            }
+            TypeDefPrimitive::U8 | TypeDefPrimitive::I8 => 1,
+            TypeDefPrimitive::U16 | TypeDefPrimitive::I16 => 2,
+            TypeDefPrimitive::Char | TypeDefPrimitive::U32 | TypeDefPrimitive::I32 => 4,
+            TypeDefPrimitive::U64 | TypeDefPrimitive::I64 => 8,
+            TypeDefPrimitive::U128 | TypeDefPrimitive::I128 => 16,
+            TypeDefPrimitive::U256 | TypeDefPrimitive::I256 => 32,
+        };
+        self.take(size).map(|_| ())
+    }
+
+    /// Reads a SCALE compact integer.
+    fn compact(&mut self) -> Result<u128> {
+        let first = self.take(1)?[0];
+        let value = match first & 0b11 {
+            0b00 => u128::from(first >> 2),
+            0b01 => {
+                let bytes = self.take(1)?;
+                u128::from(u16::from_le_bytes([first, bytes[0]]) >> 2)
+            }
+            0b10 => {
+                let bytes = self.take(3)?;
+                u128::from(u32::from_le_bytes([first, bytes[0], bytes[1], bytes[2]]) >> 2)
+            }
+            _ => {
+                let len = usize::from(first >> 2) + 4;
+                if len > 16 {
+                    return Err(self.error(format!("Compact integer of {len} bytes")));
+                }
+                let mut buf = [0u8; 16];
+                buf[..len].copy_from_slice(self.take(len)?);
+                u128::from_le_bytes(buf)
+            }
+        };
+        Ok(value)
+    }
+
+    fn length(&self, len: u128) -> Result<usize> {
+        match usize::try_from(len) {
+            Ok(len) if len <= self.options.max_collection_len => Ok(len),
+            _ => Err(self.error(format!(
+                "Length {len} exceeds the limit of {} elements",
+                self.options.max_collection_len
+            ))),
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(self.error(format!(
+                "Unexpected end of data, {len} bytes expected but {} left",
+                self.data.len()
+            )));
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn error(&self, reason: impl std::fmt::Display) -> anyhow::Error {
+        anyhow!("{reason} at `{}`", self.path.concat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scale_info::{MetaType, Registry, TypeInfo};
+
+    fn registry_of<T: TypeInfo + 'static>() -> (PortableRegistry, u32) {
+        let mut registry = Registry::new();
+        let id = registry.register_type(&MetaType::new::<T>()).id;
+        (registry.into(), id)
+    }
+
+    fn walk<T: TypeInfo + 'static>(data: &[u8], options: &DecodeOptions) -> Result<()> {
+        let (registry, ty) = registry_of::<T>();
+        let mut walker = Walker::new(&registry, data, "get.return".to_owned(), options);
+        walker.walk(ty)?;
+        walker.finish()
+    }
+
+    #[test]
+    fn walks_sequences() {
+        let options = DecodeOptions::default();
+        walk::<Vec<u32>>(&[0x08, 1, 0, 0, 0, 2, 0, 0, 0], &options).unwrap();
+        // Byte strings are taken at once.
+        walk::<Vec<u8>>(&[0x0c, 0xaa, 0xbb, 0xcc], &options).unwrap();
+    }
+
+    #[test]
+    fn reports_the_path_of_truncated_data() {
+        let err =
+            walk::<Vec<u32>>(&[0x08, 1, 0, 0, 0, 2, 0], &DecodeOptions::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unexpected end of data, 4 bytes expected but 2 left at `get.return[1]`"
+        );
+    }
+
+    #[test]
+    fn rejects_trailing_data() {
+        let err = walk::<u16>(&[1, 0, 0], &DecodeOptions::default()).unwrap_err();
+        assert_eq!(err.to_string(), "1 bytes left after `get.return`");
+    }
+
+    #[test]
+    fn rejects_unknown_variants() {
+        let options = DecodeOptions::default();
+        walk::<Option<u8>>(&[0x01, 0x07], &options).unwrap();
+        let err = walk::<Option<u8>>(&[0x02], &options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid variant index 2 for Option at `get.return`"
+        );
+    }
+
+    #[test]
+    fn limits_collection_lengths() {
+        let options = DecodeOptions::default().max_collection_len(2);
+        let err = walk::<Vec<u32>>(&[0x0c], &options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Length 3 exceeds the limit of 2 elements at `get.return`"
+        );
+    }
+
+    #[test]
+    fn limits_depth() {
+        let options = DecodeOptions::default().max_depth(2);
+        walk::<Vec<u8>>(&[0x00], &options).unwrap();
+        let err = walk::<Vec<Vec<u32>>>(&[0x04, 0x04, 1, 0, 0, 0], &options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Values nested deeper than 2 levels at `get.return[0][0]`"
+        );
+    }
+
+    #[test]
+    fn limits_the_elements_of_zero_sized_collections() {
+        let options = DecodeOptions::default().max_elements(100);
+        // Two sequences of 49 units: 2 + 98 elements, no data besides the lengths.
+        walk::<Vec<Vec<()>>>(&[0x08, 49 << 2, 49 << 2], &options).unwrap();
+        let err = walk::<Vec<Vec<()>>>(&[0x08, 49 << 2, 50 << 2], &options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "More than 100 elements in total at `get.return[1]`"
+        );
+
+        // 2^20 sequences announcing 2^20 units each fail early by default.
+        let len = [0x02, 0x00, 0x40, 0x00];
+        let mut data = len.to_vec();
+        data.extend(len.repeat(1 << 20));
+        let err = walk::<Vec<Vec<()>>>(&data, &DecodeOptions::default()).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("More than 4194304 elements in total"));
+    }
+}
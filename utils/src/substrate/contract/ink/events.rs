@@ -0,0 +1,253 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use contract_transcode::Value;
+use serde_json::{Map, Value as JsonValue};
+use sp_core::H256;
+use std::convert::TryFrom;
+
+use super::decode::{self, DecodeOptions};
+use super::migration::{self, AbiVersion};
+use super::transcoder::InkTranscoder;
+
+/// Decodes events emitted by a contract, in any metadata version.
+///
+/// ink! 4 and older prefix the event data with the index of the event in the
+/// metadata. ink! 5 drops that prefix and identifies an event by its signature
+/// topic, which is emitted as the first topic.
+pub struct EventDecoder {
+    transcoder: InkTranscoder,
+    /// Signature topic of each event, in metadata order. Empty for metadata
+    /// older than ink! 5.
+    signature_topics: Vec<Option<H256>>,
+    options: DecodeOptions,
+}
+
+impl EventDecoder {
+    pub fn new(transcoder: InkTranscoder, signature_topics: Vec<Option<H256>>) -> Self {
+        Self {
+            transcoder,
+            signature_topics,
+            options: DecodeOptions::default(),
+        }
+    }
+
+    /// Sets the limits applied to event data, see [`DecodeOptions`].
+    pub fn with_options(mut self, options: DecodeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// The transcoder of the contract, to decode its messages and return values.
+    pub fn transcoder(&self) -> &InkTranscoder {
+        &self.transcoder
+    }
+
+    /// Decodes the event with the given topics and data.
+    ///
+    /// The event is matched by signature topic when the metadata provides them,
+    /// otherwise by the index prefixed to the data.
+    pub fn decode_contract_event(&self, topics: &[H256], data: &[u8]) -> Result<Value> {
+        if self.signature_topics.is_empty() {
+            return self.decode_indexed(data);
+        }
+
+        let index = match topics.first().and_then(|topic| self.event_index(topic)) {
+            Some(index) => index,
+            None => self.anonymous_event_index().context(
+                "No event matches the signature topic and the event cannot be anonymous",
+            )?,
+        };
+        let index = u8::try_from(index).context("Contract declares too many events")?;
+
+        // Restore the index prefix so the transcoder can pick the event spec.
+        let mut indexed = Vec::with_capacity(data.len() + 1);
+        indexed.push(index);
+        indexed.extend_from_slice(data);
+        self.decode_indexed(&indexed)
+    }
+
+    fn decode_indexed(&self, data: &[u8]) -> Result<Value> {
+        decode::decode_event(&self.transcoder, data, &self.options)
+    }
+
+    fn event_index(&self, topic: &H256) -> Option<usize> {
+        self.signature_topics
+            .iter()
+            .position(|signature| signature.as_ref() == Some(topic))
+    }
+
+    /// An event without signature topic can only be told apart if it is the only one.
+    fn anonymous_event_index(&self) -> Option<usize> {
+        let mut anonymous = self
+            .signature_topics
+            .iter()
+            .enumerate()
+            .filter(|(_, signature)| signature.is_none());
+        match (anonymous.next(), anonymous.next()) {
+            (Some((index, _)), None) => Some(index),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the signature topic of every event from raw metadata.
+///
+/// Returns an empty list for metadata older than ink! 5.
+pub fn signature_topics(abi: &Map<String, JsonValue>) -> Result<Vec<Option<H256>>> {
+    if migration::detect_version(abi)? != AbiVersion::V5 {
+        return Ok(Vec::new());
+    }
+
+    let events = abi
+        .get("spec")
+        .and_then(|spec| spec.get("events"))
+        .and_then(JsonValue::as_array)
+        .context("Metadata has no events")?;
+    events
+        .iter()
+        .map(|event| match event.get("signature_topic") {
+            None | Some(JsonValue::Null) => Ok(None),
+            Some(JsonValue::String(topic)) => {
+                let bytes = super::try_decode_hex(topic)
+                    .with_context(|| format!("Invalid signature topic {topic}"))?;
+                if bytes.len() != 32 {
+                    anyhow::bail!("Invalid signature topic {topic}: expected 32 bytes");
+                }
+                Ok(Some(H256::from_slice(&bytes)))
+            }
+            Some(other) => anyhow::bail!("Invalid signature topic {other}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use contract_metadata::ContractMetadata;
+    use serde_json::json;
+    use sp_core::hashing::blake2_256;
+
+    const FLIPPER_V4: &str = include_str!("config/flipper_v4.json");
+    const FLIPPER_V5: &str = include_str!("config/flipper_v5.json");
+
+    fn flipped() -> H256 {
+        H256(blake2_256(b"Flipped(AccountId,bool)"))
+    }
+
+    /// `Flipped` data, `by` then `new_value`.
+    fn flipped_data(new_value: bool) -> Vec<u8> {
+        let mut data = vec![0x11; 32];
+        data.push(new_value.into());
+        data
+    }
+
+    /// The flipper contract, with `events` appended to its `Flipped` event.
+    fn metadata(json: &str, events: &[JsonValue]) -> ContractMetadata {
+        let mut metadata: ContractMetadata = serde_json::from_str(json).unwrap();
+        let spec = metadata.abi["spec"]["events"].as_array_mut().unwrap();
+        spec.extend_from_slice(events);
+        metadata
+    }
+
+    fn decoder(mut metadata: ContractMetadata) -> Result<EventDecoder> {
+        let topics = signature_topics(&metadata.abi)?;
+        metadata.abi = migration::upgrade_abi(metadata.abi)?;
+        Ok(EventDecoder::new(
+            InkTranscoder::from_metadata(metadata)?,
+            topics,
+        ))
+    }
+
+    /// An anonymous event without fields.
+    fn anonymous(label: &str) -> JsonValue {
+        json!({
+            "args": [],
+            "docs": [],
+            "label": label,
+            "module_path": "flipper::flipper",
+            "signature_topic": null
+        })
+    }
+
+    #[test]
+    fn matches_events_by_signature_topic() {
+        let decoder = decoder(metadata(FLIPPER_V5, &[anonymous("Reset")])).unwrap();
+        let topics = [flipped(), H256([0x11; 32])];
+        let event = decoder
+            .decode_contract_event(&topics, &flipped_data(true))
+            .unwrap();
+        assert!(event.to_string().starts_with("Flipped {"), "{}", event);
+        assert!(
+            event.to_string().ends_with("new_value: true }"),
+            "{}",
+            event
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_only_anonymous_event() {
+        let decoder = decoder(metadata(FLIPPER_V5, &[anonymous("Reset")])).unwrap();
+        // Anonymous events may have no topics at all, or only indexed fields.
+        for topics in [vec![], vec![H256::repeat_byte(7)]] {
+            let event = decoder.decode_contract_event(&topics, &[]).unwrap();
+            assert_eq!(event.to_string(), "Reset");
+        }
+    }
+
+    #[test]
+    fn rejects_unmatched_topics_with_several_anonymous_events() {
+        let events = [anonymous("Reset"), anonymous("Cleared")];
+        let several = decoder(metadata(FLIPPER_V5, &events)).unwrap();
+        let err = several.decode_contract_event(&[], &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No event matches the signature topic and the event cannot be anonymous"
+        );
+        // Without anonymous events, an unknown topic is an error as well.
+        let none = decoder(metadata(FLIPPER_V5, &[])).unwrap();
+        assert!(none
+            .decode_contract_event(&[H256::zero()], &flipped_data(true))
+            .is_err());
+    }
+
+    #[test]
+    fn decodes_older_events_by_index() {
+        assert!(signature_topics(&metadata(FLIPPER_V4, &[]).abi)
+            .unwrap()
+            .is_empty());
+        let decoder = decoder(metadata(FLIPPER_V4, &[])).unwrap();
+        let indexed = [&[0][..], &flipped_data(false)].concat();
+        let event = decoder.decode_contract_event(&[], &indexed).unwrap();
+        assert!(
+            event.to_string().ends_with("new_value: false }"),
+            "{}",
+            event
+        );
+        assert!(decoder.decode_contract_event(&[], &[1]).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_signature_topics() {
+        let mut short = anonymous("Reset");
+        short["signature_topic"] = json!("0x1234");
+        assert_eq!(
+            signature_topics(&metadata(FLIPPER_V5, &[short]).abi)
+                .unwrap_err()
+                .to_string(),
+            "Invalid signature topic 0x1234: expected 32 bytes"
+        );
+    }
+}
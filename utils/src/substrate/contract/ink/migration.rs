@@ -0,0 +1,348 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use serde_json::{json, Map, Value};
+
+/// Version of the ink! ABI embedded in contract metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbiVersion {
+    /// ink! 3 metadata, nested under a `V3` key.
+    V3,
+    /// ink! 4 metadata, flattened with `"version": "4"`.
+    V4,
+    /// ink! 5 metadata, identifying events by their signature topic.
+    V5,
+}
+
+/// Detects the ABI version of raw metadata.
+pub fn detect_version(abi: &Map<String, Value>) -> Result<AbiVersion> {
+    if abi.contains_key("V3") {
+        return Ok(AbiVersion::V3);
+    }
+    // ink! 5 writes the version as a number, ink! 4 as a string.
+    let version = match abi.get("version") {
+        Some(Value::String(version)) => Some(version.clone()),
+        Some(Value::Number(version)) => Some(version.to_string()),
+        _ => None,
+    };
+    match version.as_deref() {
+        Some("4") => Ok(AbiVersion::V4),
+        Some("5") => Ok(AbiVersion::V5),
+        Some(version) => anyhow::bail!("Unsupported ink! metadata version {version}"),
+        None => match abi.keys().find(|key| key.starts_with('V')) {
+            Some(legacy) => anyhow::bail!("Unsupported legacy ink! metadata version {legacy}"),
+            None => anyhow::bail!("Unknown ink! metadata format"),
+        },
+    }
+}
+
+/// Upgrades raw metadata to the ink! 4 ABI understood by the transcoder.
+///
+/// ink! 3 storage layouts are not migrated: they are replaced by an empty root,
+/// which is enough to transcode messages, constructors and events. ink! 5 metadata
+/// only adds fields to the ink! 4 layout, so it is relabeled and the event signature
+/// topics are read separately by [`super::events::EventDecoder`].
+pub fn upgrade_abi(mut abi: Map<String, Value>) -> Result<Map<String, Value>> {
+    match detect_version(&abi)? {
+        AbiVersion::V4 => Ok(abi),
+        AbiVersion::V5 => {
+            abi.insert("version".to_owned(), json!("4"));
+            Ok(abi)
+        }
+        AbiVersion::V3 => {
+            let v3 = abi.remove("V3").context("Missing V3 metadata")?;
+            upgrade_v3(v3)
+        }
+    }
+}
+
+fn upgrade_v3(v3: Value) -> Result<Map<String, Value>> {
+    let mut v3 = match v3 {
+        Value::Object(v3) => v3,
+        _ => anyhow::bail!("V3 metadata is not an object"),
+    };
+
+    let mut types = match v3.remove("types") {
+        Some(Value::Array(types)) => types,
+        _ => anyhow::bail!("V3 metadata has no type registry"),
+    };
+    let mut spec = match v3.remove("spec") {
+        Some(Value::Object(spec)) => spec,
+        _ => anyhow::bail!("V3 metadata has no spec"),
+    };
+
+    // ink! 4 moved the environment types, which the transcoder finds by path.
+    for path in types
+        .iter_mut()
+        .filter_map(|ty| ty.pointer_mut("/type/path"))
+        .filter_map(Value::as_array_mut)
+    {
+        if let [krate, module, name] = &mut path[..] {
+            if krate == "ink_env" && module == "types" && (name == "AccountId" || name == "Hash") {
+                *krate = json!("ink_primitives");
+            }
+        }
+    }
+
+    let mut registry = Registry::new(&mut types);
+    let u8_ty = registry.add(json!({ "def": { "primitive": "u8" } }));
+    let bytes32 = registry.add(json!({ "def": { "array": { "len": 32, "type": u8_ty } } }));
+    let account_id = registry.add(json!({
+        "def": { "composite": { "fields": [{ "type": bytes32, "typeName": "[u8; 32]" }] } },
+        "path": ["ink_primitives", "types", "AccountId"]
+    }));
+    let hash = registry.add(json!({
+        "def": { "composite": { "fields": [{ "type": bytes32, "typeName": "[u8; 32]" }] } },
+        "path": ["ink_primitives", "types", "Hash"]
+    }));
+    let balance = registry.add(json!({ "def": { "primitive": "u128" } }));
+    let timestamp = registry.add(json!({ "def": { "primitive": "u64" } }));
+    let block_number = registry.add(json!({ "def": { "primitive": "u32" } }));
+    let chain_extension = registry.add(json!({
+        "def": { "variant": {} },
+        "path": ["ink_env", "types", "NoChainExtension"]
+    }));
+    let lang_error = registry.add(json!({
+        "def": { "variant": { "variants": [{ "index": 1, "name": "CouldNotReadInput" }] } },
+        "path": ["ink_primitives", "LangError"]
+    }));
+
+    for constructor in array_entries(&mut spec, "constructors") {
+        constructor.entry("returnType").or_insert(Value::Null);
+        constructor.entry("default").or_insert(Value::Bool(false));
+    }
+    for message in array_entries(&mut spec, "messages") {
+        message.entry("default").or_insert(Value::Bool(false));
+    }
+    spec.entry("events").or_insert_with(|| json!([]));
+    spec.entry("docs").or_insert_with(|| json!([]));
+    spec.insert(
+        "lang_error".to_owned(),
+        json!({ "type": lang_error, "displayName": ["ink", "LangError"] }),
+    );
+    spec.insert(
+        "environment".to_owned(),
+        json!({
+            "accountId": { "type": account_id, "displayName": ["AccountId"] },
+            "balance": { "type": balance, "displayName": ["Balance"] },
+            "hash": { "type": hash, "displayName": ["Hash"] },
+            "timestamp": { "type": timestamp, "displayName": ["Timestamp"] },
+            "blockNumber": { "type": block_number, "displayName": ["BlockNumber"] },
+            "chainExtension": { "type": chain_extension, "displayName": ["ChainExtension"] },
+            "maxEventTopics": 4
+        }),
+    );
+
+    let mut abi = Map::new();
+    abi.insert("version".to_owned(), json!("4"));
+    abi.insert("types".to_owned(), Value::Array(types));
+    abi.insert("spec".to_owned(), Value::Object(spec));
+    abi.insert(
+        "storage".to_owned(),
+        json!({
+            "root": {
+                "layout": { "struct": { "name": "Storage", "fields": [] } },
+                "root_key": "0x00000000"
+            }
+        }),
+    );
+    Ok(abi)
+}
+
+fn array_entries<'a>(
+    spec: &'a mut Map<String, Value>,
+    key: &str,
+) -> impl Iterator<Item = &'a mut Map<String, Value>> {
+    spec.get_mut(key)
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object_mut)
+}
+
+/// Appends types to a portable registry, after its highest id.
+struct Registry<'a> {
+    types: &'a mut Vec<Value>,
+    next_id: u64,
+}
+
+impl<'a> Registry<'a> {
+    fn new(types: &'a mut Vec<Value>) -> Self {
+        let next_id = types
+            .iter()
+            .filter_map(|ty| ty.get("id").and_then(Value::as_u64))
+            .max()
+            .map_or(types.len() as u64, |id| id + 1);
+        Self { types, next_id }
+    }
+
+    /// Adds `ty`, unless the registry has a type of the same path.
+    fn add(&mut self, ty: Value) -> u64 {
+        let existing = ty.get("path").and_then(|path| {
+            self.types
+                .iter()
+                .find(|known| known.pointer("/type/path") == Some(path))
+        });
+        if let Some(id) = existing.and_then(|known| known.get("id")?.as_u64()) {
+            return id;
+        }
+        let id = self.next_id;
+        self.types.push(json!({ "id": id, "type": ty }));
+        self.next_id += 1;
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::substrate::contract::ink::events::{self, EventDecoder};
+    use crate::substrate::contract::ink::selectors::{compute_selector, SelectorTable};
+    use crate::substrate::contract::ink::transcoder::InkTranscoder;
+    use contract_metadata::ContractMetadata;
+    use sp_core::{hashing::blake2_256, H256};
+
+    // The flipper example with a `Flipped` event, as written by each ink! version.
+    const FLIPPER_V3: &str = include_str!("config/flipper_v3.json");
+    const FLIPPER_V4: &str = include_str!("config/flipper_v4.json");
+    const FLIPPER_V5: &str = include_str!("config/flipper_v5.json");
+
+    fn load(json: &str) -> ContractMetadata {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn transcoder(json: &str) -> InkTranscoder {
+        let mut metadata = load(json);
+        metadata.abi = upgrade_abi(metadata.abi).unwrap();
+        InkTranscoder::from_metadata(metadata).unwrap()
+    }
+
+    fn event_decoder(json: &str) -> EventDecoder {
+        let topics = events::signature_topics(&load(json).abi).unwrap();
+        EventDecoder::new(transcoder(json), topics)
+    }
+
+    #[test]
+    fn detects_versions() {
+        assert_eq!(
+            detect_version(&load(FLIPPER_V3).abi).unwrap(),
+            AbiVersion::V3
+        );
+        assert_eq!(
+            detect_version(&load(FLIPPER_V4).abi).unwrap(),
+            AbiVersion::V4
+        );
+        assert_eq!(
+            detect_version(&load(FLIPPER_V5).abi).unwrap(),
+            AbiVersion::V5
+        );
+
+        let mut v2 = Map::new();
+        v2.insert("V2".to_owned(), json!({}));
+        assert_eq!(
+            detect_version(&v2).unwrap_err().to_string(),
+            "Unsupported legacy ink! metadata version V2"
+        );
+        let mut v6 = Map::new();
+        v6.insert("version".to_owned(), json!(6));
+        assert_eq!(
+            detect_version(&v6).unwrap_err().to_string(),
+            "Unsupported ink! metadata version 6"
+        );
+    }
+
+    #[test]
+    fn keeps_selectors_across_versions() {
+        let v4 = SelectorTable::from_transcoder(&transcoder(FLIPPER_V4));
+        let labels: Vec<_> = v4
+            .entries
+            .iter()
+            .map(|entry| entry.label.as_str())
+            .collect();
+        assert_eq!(labels, ["new", "default", "flip", "get"]);
+        assert!(v4.entries.iter().all(|entry| !entry.is_custom()));
+        assert_eq!(
+            v4.find(compute_selector("flip")).next().unwrap().selector,
+            [0x63, 0x3a, 0xa5, 0x51]
+        );
+
+        assert_eq!(SelectorTable::from_transcoder(&transcoder(FLIPPER_V3)), v4);
+        assert_eq!(SelectorTable::from_transcoder(&transcoder(FLIPPER_V5)), v4);
+    }
+
+    #[test]
+    fn reads_signature_topics_of_v5_events_only() {
+        assert!(events::signature_topics(&load(FLIPPER_V3).abi)
+            .unwrap()
+            .is_empty());
+        assert!(events::signature_topics(&load(FLIPPER_V4).abi)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            events::signature_topics(&load(FLIPPER_V5).abi).unwrap(),
+            [Some(H256(blake2_256(b"Flipped(AccountId,bool)")))]
+        );
+    }
+
+    #[test]
+    fn decodes_events_across_versions() {
+        let by = [0x11; 32];
+        let mut data = by.to_vec();
+        data.push(1);
+        // ink! 3 and 4 prefix the data with the index of the event.
+        let indexed = [&[0], &data[..]].concat();
+
+        let v4 = event_decoder(FLIPPER_V4)
+            .decode_contract_event(&[H256(by)], &indexed)
+            .unwrap();
+        let v3 = event_decoder(FLIPPER_V3)
+            .decode_contract_event(&[H256(by)], &indexed)
+            .unwrap();
+        assert_eq!(v3, v4);
+        assert_eq!(
+            v4.to_string(),
+            "Flipped { by: 5CT5jwBEAhveEjgiSCQbkaKcKcUyF3VJ8qNXM9rXsuQyn3Kd, new_value: true }"
+        );
+
+        let signature = H256(blake2_256(b"Flipped(AccountId,bool)"));
+        let v5 = event_decoder(FLIPPER_V5)
+            .decode_contract_event(&[signature, H256(by)], &data)
+            .unwrap();
+        assert_eq!(v5, v4);
+        assert!(event_decoder(FLIPPER_V5)
+            .decode_contract_event(&[H256(by)], &data)
+            .is_err());
+    }
+
+    #[test]
+    fn upgrades_v3_to_v4() {
+        let abi = upgrade_abi(load(FLIPPER_V3).abi).unwrap();
+        assert_eq!(detect_version(&abi).unwrap(), AbiVersion::V4);
+        assert_eq!(
+            abi["types"][1]["type"]["path"],
+            json!(["ink_primitives", "types", "AccountId"])
+        );
+        // The environment reuses the types of the contract, and appends the others.
+        let ids: Vec<_> = abi["types"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|ty| ty["id"].as_u64().unwrap())
+            .collect();
+        assert_eq!(ids, (0..ids.len() as u64).collect::<Vec<_>>());
+        assert_eq!(abi["spec"]["environment"]["accountId"]["type"], json!(1));
+        assert_eq!(abi["spec"]["messages"][0]["default"], json!(false));
+    }
+}
@@ -12,89 +12,157 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod abi;
+pub mod args;
+pub mod bundle;
+pub mod config;
+pub mod decode;
+pub mod events;
+pub mod migration;
+pub mod registry;
+pub mod remote;
+pub mod resolver;
+pub mod schema;
+pub(crate) mod scon;
+pub mod selectors;
+pub mod store;
+pub mod transcoder;
+pub mod validation;
+pub(crate) mod values;
+pub mod vectors;
+pub mod verify;
+
 use anyhow::{anyhow, Context, Ok, Result};
-use std::convert::TryFrom;
-use std::str::FromStr;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
 use std::{fs, path::PathBuf};
 use std::{option::Option, path::Path};
 use subxt::Config;
 use toml::Value;
 
-use crate::substrate::{DefaultConfig, ContractId};
-use contract_build::CrateMetadata;
-use contract_metadata::ContractMetadata;
-use contract_transcode::ContractMessageTranscoder;
-use scale::Decode;
+use self::abi::{ContractAbi, SolidityAbi};
+use self::config::ConfigFile;
+use self::resolver::MetadataResolver;
+use self::transcoder::{DisplayHooks, InkTranscoder, TranscoderBuilder};
+use super::info;
+use crate::substrate::endpoints::Endpoints;
+use crate::substrate::{
+    address::Address,
+    chain::{Chain, HashAlgorithm},
+    runtime, DefaultConfig,
+};
+use contract_build::{CrateMetadata, Target};
+use contract_metadata::{Compiler, ContractMetadata, Language};
 
 type AccountId = <DefaultConfig as Config>::AccountId;
 
-const CONFIG_PATH: &'static str = "utils/src/substrate/contract/ink/config/config.toml";
+const CONFIG_PATH: &str = "utils/src/substrate/contract/ink/config/config.toml";
+/// Interval of the health checks of endpoints read from the config.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// Directory, relative to the target directory, where `cargo contract` writes artifacts.
+const INK_ARTIFACTS_DIR: &str = "ink";
+/// Magic bytes starting a PolkaVM program blob.
+const POLKAVM_MAGIC: &[u8] = b"PVM\0";
 
 /// Arguments required for creating and sending an extrinsic to a substrate node.
+#[derive(Clone)]
 pub struct InkMeta {
-    /// Path to a contract build artifact file: a raw `.wasm` file, a `.contract` bundle,
-    /// or a `.json` metadata file.
+    /// Path to a contract build artifact file: a raw `.wasm` or `.polkavm` file, a
+    /// `.contract` bundle, a `.json` metadata file, or the `.abi` JSON of a Solidity
+    /// contract. Can also be an `https://` or `ipfs://` URI, see
+    /// [`remote::fetch_artifact`].
     file: PathBuf,
     /// Node Url
     pub url: String,
-    /// Address of the deployed contract
-    pub ink_contract_id: Option<AccountId>,
-    /// Phala contract id
-    pub phala_contract_id: Option<ContractId>,
-}
-
-enum Id {
-    InkId(String),
-    PhalaId(String),
+    /// Endpoints failed over to when `url` stalls, `url` being the first of them
+    pub endpoints: Option<Arc<Endpoints>>,
+    /// Chain the contract is deployed on
+    pub chain: Chain,
+    /// Address of the deployed ink or Phala contract
+    pub address: Address,
+    /// Hash function of code hashes, if neither the chain nor the code target tell
+    pub code_hash_algorithm: Option<HashAlgorithm>,
 }
 
 impl InkMeta {
-    pub fn from_config_file() -> Result<InkMeta> {
-        let config_content = fs::read_to_string(CONFIG_PATH)?;
-        let config: Value = toml::from_str(&config_content)?;
-
-        macro_rules! extract {
-            ($config:expr, $field:expr) => {
-                $config
-                    .get($field)
-                    .and_then(|value| value.as_str().map(|v| Some(v.to_owned())))
-                    .unwrap_or(None)
-            };
+    pub fn new(file: PathBuf, url: String, chain: Chain, address: Address) -> Self {
+        Self {
+            file,
+            url,
+            endpoints: None,
+            chain,
+            address,
+            code_hash_algorithm: None,
         }
+    }
 
-        let ink_contract_id = extract!(config, "ink_contract_id");
-        let phala_contract_id = extract!(config, "phala_contract_id");
+    pub fn with_code_hash_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.code_hash_algorithm = Some(algorithm);
+        self
+    }
 
-        let id = match (ink_contract_id, phala_contract_id) {
-            (Some(id), None) => Id::InkId(id),
-            (None, Some(id)) => Id::PhalaId(id),
-            _ => anyhow::bail!("Failed to load Contract Id"),
-        };
+    /// Hash function of the code hashes of the contract, `None` to follow the target
+    /// of its code
+    pub fn hash_algorithm(&self) -> Option<HashAlgorithm> {
+        self.code_hash_algorithm
+            .or_else(|| self.chain.code_hash_algorithm())
+    }
 
-        let (ink_contract_id, phala_contract_id) = match id {
-            Id::InkId(ink_id) => {
-                let contract_id = <DefaultConfig as Config>::AccountId::from_str(ink_id.as_str())?;
-                (Some(contract_id), None)
-            }
-            Id::PhalaId(phala_id) => {
-                let contract_id = decode_hex(phala_id.as_str());
-                let contract_id = ContractId::decode(&mut &contract_id[..])?;
-                (None, Some(contract_id))
-            }
-        };
+    /// Sends requests to the active endpoint of `endpoints` instead of `url`
+    pub fn with_endpoints(mut self, endpoints: Arc<Endpoints>) -> Self {
+        self.url = endpoints.urls()[0].clone();
+        self.endpoints = Some(endpoints);
+        self
+    }
 
-        let ink_meta = InkMeta {
-            file: PathBuf::from(extract!(config, "contract_path").context("Failed to load file")?),
-            url: extract!(config, "url").context("Failed to load url")?,
-            ink_contract_id,
-            phala_contract_id,
-        };
-        Ok(ink_meta)
+    /// Url requests are currently sent to, the active endpoint if there are several
+    pub fn url(&self) -> &str {
+        match &self.endpoints {
+            Some(endpoints) => endpoints.active(),
+            None => &self.url,
+        }
     }
 
-    /// Load contract artifacts.
+    pub fn from_config_file() -> Result<InkMeta> {
+        ConfigFile::load(Path::new(CONFIG_PATH))?.into_meta()
+    }
+
+    /// Reads the contract path, url, chain and contract id of a config table, one of
+    /// `ink_contract_id`, `phala_contract_id` and `revive_contract_id`.
+    ///
+    /// `url` may also be a list of endpoints, whose health is then checked in the
+    /// background. `code_hash_algorithm` is `blake2`, `keccak` or `sha256`. See
+    /// [`ConfigFile`] for how the table is validated.
+    pub fn from_toml(config: &Value) -> Result<InkMeta> {
+        ConfigFile::from_value(config.clone(), "table")?.into_meta()
+    }
+
+    /// Resolves the verified metadata of the ink contract at `address` through
+    /// `resolver`, so that only its address needs to be known.
+    pub fn resolve(
+        url: String,
+        chain: Chain,
+        address: AccountId,
+        resolver: &dyn MetadataResolver,
+    ) -> Result<InkMeta> {
+        let file = resolver::resolve_artifact(&url, &address, resolver)?;
+        Ok(InkMeta::new(file, url, chain, Address::Ink(address)))
+    }
+
+    /// Load contract artifacts, fetching them first if the file is a URI.
     pub fn contract_artifacts(&self) -> Result<ContractArtifacts> {
-        ContractArtifacts::from_manifest_or_file(None, Some(&self.file))
+        match remote::remote_uri(&self.file) {
+            Some(uri) => {
+                let path = remote::fetch_artifact(uri)?;
+                let artifacts = ContractArtifacts::from_manifest_or_file(None, Some(&path))?;
+                artifacts
+                    .verify_bundled_code()
+                    .with_context(|| format!("Invalid artifact {uri}"))?;
+                Ok(artifacts)
+            }
+            None => ContractArtifacts::from_manifest_or_file(None, Some(&self.file)),
+        }
     }
 }
 
@@ -107,8 +175,8 @@ pub struct ContractArtifacts {
     metadata_path: PathBuf,
     /// The deserialized contract metadata if the expected metadata file exists.
     metadata: Option<ContractMetadata>,
-    /// The Wasm code of the contract if available.
-    pub code: Option<WasmCode>,
+    /// The Wasm or RISC-V code of the contract if available.
+    pub code: Option<ContractBinary>,
 }
 
 impl ContractArtifacts {
@@ -140,22 +208,81 @@ impl ContractArtifacts {
         };
         Self::from_artifact_path(artifact_path.as_path())
     }
+
+    /// Discover the contract bundles built in a workspace.
+    ///
+    /// Scans `target/ink/` and its per-contract subdirectories for `.contract` bundles
+    /// and maps each contract name to its artifacts.
+    pub fn discover(workspace_root: &Path) -> Result<BTreeMap<String, ContractArtifacts>> {
+        let ink_dir = workspace_root.join("target").join(INK_ARTIFACTS_DIR);
+        if !ink_dir.is_dir() {
+            anyhow::bail!(
+                "No contract artifacts directory at {}. \n\
+                Run `cargo contract build --release` to generate the artifacts.",
+                ink_dir.display()
+            )
+        }
+
+        let mut bundles = Vec::new();
+        for entry in fs::read_dir(&ink_dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                for nested in fs::read_dir(&path)? {
+                    bundles.push(nested?.path());
+                }
+            } else {
+                bundles.push(path);
+            }
+        }
+
+        let mut artifacts = BTreeMap::new();
+        for path in bundles {
+            if path.extension().and_then(|ext| ext.to_str()) != Some("contract") {
+                continue;
+            }
+            let contract = Self::from_artifact_path(&path)?;
+            let name = match &contract.metadata {
+                Some(metadata) => metadata.contract.name.clone(),
+                None => path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .context("Contract bundle has unreadable name")?
+                    .to_owned(),
+            };
+            if let Some(existing) = artifacts.insert(name.clone(), contract) {
+                anyhow::bail!(
+                    "Found several bundles for contract {name}, including {}",
+                    existing.artifact_path().display()
+                )
+            }
+        }
+        Ok(artifacts)
+    }
+
     /// Given a contract artifact path, load the contract code and metadata where
     /// possible.
     fn from_artifact_path(path: &Path) -> Result<Self> {
         let (metadata_path, metadata, code) = match path.extension().and_then(|ext| ext.to_str()) {
             Some("contract") | Some("json") => {
                 let metadata = ContractMetadata::load(path)?;
-                let code = metadata.clone().source.wasm.map(|wasm| WasmCode(wasm.0));
+                let code = metadata
+                    .clone()
+                    .source
+                    .wasm
+                    .map(|code| ContractBinary::new(code.0));
                 (PathBuf::from(path), Some(metadata), code)
             }
-            Some("wasm") => {
+            Some(ext @ ("wasm" | "polkavm" | "riscv")) => {
                 let file_name = path
                     .file_stem()
-                    .context("WASM bundle file has unreadable name")?
+                    .context("Contract binary file has unreadable name")?
                     .to_str()
                     .context("Error parsing filename string")?;
-                let code = Some(WasmCode(fs::read(path)?));
+                let target = match ext {
+                    "wasm" => Target::Wasm,
+                    _ => Target::RiscV,
+                };
+                let code = Some(ContractBinary::with_target(fs::read(path)?, target));
                 let dir = path.parent().map_or_else(PathBuf::new, PathBuf::from);
                 let metadata_path = dir.join(format!("{file_name}.json"));
                 if !metadata_path.exists() {
@@ -165,12 +292,16 @@ impl ContractArtifacts {
                     (metadata_path, Some(metadata), code)
                 }
             }
+            // Ethereum-style ABI JSON, loaded by `contract_abi`.
+            Some("abi") => (PathBuf::from(path), None, None),
             Some(ext) => anyhow::bail!(
-                "Invalid artifact extension {ext}, expected `.contract`, `.json` or `.wasm`"
+                "Invalid artifact extension {ext}, expected `.contract`, `.json`, `.wasm`, \
+                `.polkavm` or `.abi`"
             ),
             None => {
                 anyhow::bail!(
-                    "Artifact path has no extension, expected `.contract`, `.json`, or `.wasm`"
+                    "Artifact path has no extension, expected `.contract`, `.json`, `.wasm`, \
+                    `.polkavm` or `.abi`"
                 )
             }
         };
@@ -201,29 +332,199 @@ impl ContractArtifacts {
         })
     }
 
-    /// Construct a [`ContractMessageTranscoder`] from contract metadata.
-    pub fn contract_transcoder(&self) -> Result<ContractMessageTranscoder> {
+    /// Construct an [`InkTranscoder`] from contract metadata.
+    ///
+    /// Legacy ink! 3 and newer ink! 5 metadata is converted to the ink! 4 ABI first.
+    pub fn contract_transcoder(&self) -> Result<InkTranscoder> {
+        self.transcoder_builder()?.build()
+    }
+
+    /// Same as [`Self::contract_transcoder`], returning the builder to register
+    /// display hooks with.
+    pub fn transcoder_builder(&self) -> Result<TranscoderBuilder> {
+        let mut metadata = self.metadata()?;
+        metadata.abi = migration::upgrade_abi(metadata.abi)?;
+        Ok(TranscoderBuilder::new(metadata))
+    }
+
+    /// Construct the [`ContractAbi`] of the contract.
+    ///
+    /// `.abi` files are Ethereum-style ABIs of Solidity contracts. Metadata written
+    /// by solang for pallet-contracts follows the ink! format and is transcoded as
+    /// such.
+    pub fn contract_abi(&self) -> Result<ContractAbi> {
+        self.contract_abi_with(&DisplayHooks::default())
+    }
+
+    /// Same as [`Self::contract_abi`], rendering the values of ink! contracts with
+    /// `display_hooks`.
+    pub(crate) fn contract_abi_with(&self, display_hooks: &DisplayHooks) -> Result<ContractAbi> {
+        if self.is_solidity_abi() {
+            return Ok(ContractAbi::Solidity(SolidityAbi::load(
+                &self.artifacts_path,
+            )?));
+        }
+        if let Some(metadata) = &self.metadata {
+            let source = &metadata.source;
+            if matches!(source.language.language, Language::Solidity)
+                && !matches!(source.compiler.compiler, Compiler::Solang)
+            {
+                anyhow::bail!(
+                    "Solidity metadata built by {} is not supported, use solang or an `.abi` file",
+                    source.compiler
+                );
+            }
+        }
+        let transcoder = self
+            .transcoder_builder()?
+            .display_hooks(display_hooks)
+            .build()?;
+        Ok(ContractAbi::Ink(Box::new(transcoder)))
+    }
+
+    fn is_solidity_abi(&self) -> bool {
+        self.artifacts_path.extension().and_then(|ext| ext.to_str()) == Some("abi")
+    }
+
+    /// Checks that the contract at `address` runs the code of these artifacts.
+    ///
+    /// Decoded results are only meaningful if the metadata matches the on-chain code.
+    pub fn verify_code_hash(&self, url: &str, address: &AccountId) -> Result<()> {
+        self.verify_code_hash_with(url, address, None)
+    }
+
+    /// Same as [`ContractArtifacts::verify_code_hash`], hashing the code with
+    /// `algorithm` rather than the one of its target.
+    pub fn verify_code_hash_with(
+        &self,
+        url: &str,
+        address: &AccountId,
+        algorithm: Option<HashAlgorithm>,
+    ) -> Result<()> {
+        let local = match algorithm {
+            Some(algorithm) => self.code_hash_with(algorithm)?,
+            None => self.code_hash()?,
+        };
+        let on_chain = runtime::block_on(info::code_hash_of(url, address))?
+            .with_context(|| format!("No contract found at {address}"))?;
+        if on_chain.0 != local {
+            anyhow::bail!(
+                "Code hash mismatch for {address}: on-chain 0x{}, artifacts 0x{}",
+                hex::encode(on_chain),
+                hex::encode(local)
+            );
+        }
+        Ok(())
+    }
+
+    /// Checks that the bundled code matches the code hash of the metadata, when both
+    /// are available.
+    pub fn verify_bundled_code(&self) -> Result<()> {
+        if let (Some(code), Some(metadata)) = (&self.code, &self.metadata) {
+            let hash = code.code_hash();
+            if hash != metadata.source.hash.0 {
+                anyhow::bail!(
+                    "Code hash 0x{} of the metadata does not match the bundled code (0x{})",
+                    hex::encode(metadata.source.hash.0),
+                    hex::encode(hash)
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Hash of the contract code, read from the contract binary or else the metadata.
+    pub fn code_hash(&self) -> Result<[u8; 32]> {
+        match &self.code {
+            Some(code) => Ok(code.code_hash()),
+            None => Ok(self.metadata()?.source.hash.0),
+        }
+    }
+
+    /// Same as [`ContractArtifacts::code_hash`], hashing the contract binary with
+    /// `algorithm`. The hash recorded in the metadata is returned as is.
+    pub fn code_hash_with(&self, algorithm: HashAlgorithm) -> Result<[u8; 32]> {
+        match &self.code {
+            Some(code) => Ok(code.code_hash_with(algorithm)),
+            None => Ok(self.metadata()?.source.hash.0),
+        }
+    }
+
+    /// Construct an [`EventDecoder`](events::EventDecoder) from contract metadata.
+    pub fn event_decoder(&self) -> Result<events::EventDecoder> {
+        self.event_decoder_with(&DisplayHooks::default())
+    }
+
+    /// Same as [`Self::event_decoder`], rendering the event values with
+    /// `display_hooks`.
+    pub(crate) fn event_decoder_with(
+        &self,
+        display_hooks: &DisplayHooks,
+    ) -> Result<events::EventDecoder> {
         let metadata = self.metadata()?;
-        ContractMessageTranscoder::try_from(metadata)
-            .context("Failed to deserialize ink project metadata from contract metadata")
+        let signature_topics = events::signature_topics(&metadata.abi)?;
+        let transcoder = self
+            .transcoder_builder()?
+            .display_hooks(display_hooks)
+            .build()?;
+        Ok(events::EventDecoder::new(transcoder, signature_topics))
     }
 }
 
-/// The Wasm code of a contract.
+/// The code of a contract, a Wasm module or a RISC-V PolkaVM program.
 #[derive(Debug)]
-pub struct WasmCode(Vec<u8>);
+pub struct ContractBinary {
+    code: Vec<u8>,
+    target: Target,
+}
+
+impl ContractBinary {
+    /// Wraps contract code, telling PolkaVM programs from Wasm modules by their
+    /// magic bytes, e.g. for the code bundled in a `.contract` file.
+    pub fn new(code: Vec<u8>) -> Self {
+        let target = if code.starts_with(POLKAVM_MAGIC) {
+            Target::RiscV
+        } else {
+            Target::Wasm
+        };
+        Self::with_target(code, target)
+    }
+
+    pub fn with_target(code: Vec<u8>, target: Target) -> Self {
+        Self { code, target }
+    }
 
-impl WasmCode {
     /// The hash of the contract code: uniquely identifies the contract code on-chain.
+    ///
+    /// Wasm code is hashed with BLAKE2 as pallet-contracts does, PolkaVM programs
+    /// with Keccak-256 as pallet-revive does.
     pub fn code_hash(&self) -> [u8; 32] {
-        contract_build::code_hash(&self.0)
+        self.code_hash_with(self.hash_algorithm())
+    }
+
+    /// The hash of the contract code with `algorithm`, e.g. the one of a chain whose
+    /// code hashes do not follow the target of the code.
+    pub fn code_hash_with(&self, algorithm: HashAlgorithm) -> [u8; 32] {
+        algorithm.hash(&self.code)
+    }
+
+    /// Hash function of code hashes for the target of the code.
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        match self.target {
+            Target::Wasm => HashAlgorithm::Blake2_256,
+            Target::RiscV => HashAlgorithm::Keccak256,
+        }
+    }
+
+    pub fn target(&self) -> Target {
+        self.target
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.code
     }
 }
 
 pub fn try_decode_hex(hex_str: &str) -> Result<Vec<u8>, hex::FromHexError> {
     hex::decode(hex_str.strip_prefix("0x").unwrap_or(hex_str))
 }
-
-pub fn decode_hex(hex_str: &str) -> Vec<u8> {
-    try_decode_hex(hex_str).expect("Failed to parse hex_data")
-}
@@ -0,0 +1,173 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use contract_transcode::Value;
+use sp_core::H256;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use super::{events::EventDecoder, transcoder::InkTranscoder, ContractArtifacts};
+use crate::substrate::contract::info;
+use crate::substrate::{runtime, AccountId};
+
+/// Metadata of several contracts, to decode data involving more than one of them.
+///
+/// Metadata is registered by code hash, so every contract running the same code
+/// shares it. Addresses are linked to a code hash explicitly with
+/// [`MetadataRegistry::register_address`], or looked up on-chain with
+/// [`MetadataRegistry::resolve`].
+#[derive(Default)]
+pub struct MetadataRegistry {
+    contracts: BTreeMap<H256, RegisteredContract>,
+    addresses: BTreeMap<AccountId, H256>,
+}
+
+struct RegisteredContract {
+    name: String,
+    decoder: EventDecoder,
+}
+
+impl MetadataRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the metadata of every `.contract` and `.json` artifact in `dir`
+    /// and its direct subdirectories.
+    ///
+    /// JSON files that are not contract metadata are skipped.
+    pub fn load_dir(dir: &Path) -> Result<Self> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir)
+            .with_context(|| format!("Failed to read metadata directory {}", dir.display()))?
+        {
+            let path = entry?.path();
+            if path.is_dir() {
+                for nested in fs::read_dir(&path)? {
+                    paths.push(nested?.path());
+                }
+            } else {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut registry = Self::new();
+        for path in paths {
+            match path.extension().and_then(|ext| ext.to_str()) {
+                Some("contract") => {
+                    let artifacts = ContractArtifacts::from_artifact_path(&path)?;
+                    registry.register(&artifacts)?;
+                }
+                Some("json") => {
+                    // Plain JSON files may sit next to the metadata, e.g. configs.
+                    if let Ok(artifacts) = ContractArtifacts::from_artifact_path(&path) {
+                        registry.register(&artifacts)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(registry)
+    }
+
+    /// Registers the metadata of a contract, returning its code hash.
+    ///
+    /// Replaces the metadata previously registered for the same code.
+    pub fn register(&mut self, artifacts: &ContractArtifacts) -> Result<H256> {
+        let metadata = artifacts.metadata()?;
+        let code_hash = H256(artifacts.code_hash()?);
+        let contract = RegisteredContract {
+            name: metadata.contract.name,
+            decoder: artifacts.event_decoder()?,
+        };
+        self.contracts.insert(code_hash, contract);
+        Ok(code_hash)
+    }
+
+    /// Links a contract address to the code hash of registered metadata.
+    pub fn register_address(&mut self, address: AccountId, code_hash: H256) -> Result<()> {
+        if !self.contracts.contains_key(&code_hash) {
+            anyhow::bail!("No metadata registered for code hash {code_hash:?}");
+        }
+        self.addresses.insert(address, code_hash);
+        Ok(())
+    }
+
+    /// Looks up the code hash of the contract at `address` on the chain at `url`,
+    /// linking the address to it.
+    ///
+    /// Returns `false` if no metadata is registered for the contract code.
+    pub fn resolve(&mut self, url: &str, address: &AccountId) -> Result<bool> {
+        if self.addresses.contains_key(address) {
+            return Ok(true);
+        }
+        let code_hash = runtime::block_on(info::code_hash_of(url, address))?
+            .with_context(|| format!("No contract found at {address}"))?;
+        if !self.contracts.contains_key(&code_hash) {
+            return Ok(false);
+        }
+        self.addresses.insert(address.clone(), code_hash);
+        Ok(true)
+    }
+
+    /// Code hashes of the registered metadata, with the contract names.
+    pub fn code_hashes(&self) -> impl Iterator<Item = (&H256, &str)> {
+        self.contracts
+            .iter()
+            .map(|(code_hash, contract)| (code_hash, contract.name.as_str()))
+    }
+
+    pub fn transcoder_by_code_hash(&self, code_hash: &H256) -> Option<&InkTranscoder> {
+        self.event_decoder_by_code_hash(code_hash)
+            .map(EventDecoder::transcoder)
+    }
+
+    pub fn event_decoder_by_code_hash(&self, code_hash: &H256) -> Option<&EventDecoder> {
+        self.contracts
+            .get(code_hash)
+            .map(|contract| &contract.decoder)
+    }
+
+    pub fn transcoder(&self, address: &AccountId) -> Option<&InkTranscoder> {
+        self.event_decoder(address).map(EventDecoder::transcoder)
+    }
+
+    pub fn event_decoder(&self, address: &AccountId) -> Option<&EventDecoder> {
+        self.addresses
+            .get(address)
+            .and_then(|code_hash| self.event_decoder_by_code_hash(code_hash))
+    }
+
+    /// Decodes an event emitted by the contract at `address`.
+    pub fn decode_contract_event(
+        &self,
+        address: &AccountId,
+        topics: &[H256],
+        data: &[u8],
+    ) -> Result<Value> {
+        self.event_decoder(address)
+            .with_context(|| format!("No metadata registered for contract {address}"))?
+            .decode_contract_event(topics, data)
+    }
+
+    /// Decodes the input of a message sent to the contract at `address`.
+    pub fn decode_contract_message(&self, address: &AccountId, mut input: &[u8]) -> Result<Value> {
+        self.transcoder(address)
+            .with_context(|| format!("No metadata registered for contract {address}"))?
+            .decode_contract_message(&mut input)
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use sp_core::hashing::blake2_256;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::substrate::runtime;
+
+/// Environment variable overriding the directory remote artifacts are cached in.
+const CACHE_DIR_ENV: &str = "INK_QUERIES_CACHE_DIR";
+/// Environment variable overriding the gateway `ipfs://` artifacts are fetched from.
+const IPFS_GATEWAY_ENV: &str = "IPFS_GATEWAY";
+const DEFAULT_IPFS_GATEWAY: &str = "https://ipfs.io/ipfs/";
+const HTTPS_SCHEME: &str = "https://";
+const IPFS_SCHEME: &str = "ipfs://";
+/// Artifact extensions kept in cached file names, so that they load as local files.
+const ARTIFACT_EXTENSIONS: [&str; 6] = ["contract", "json", "wasm", "polkavm", "riscv", "abi"];
+
+/// The URI of an artifact path given as `https://` or `ipfs://` URI.
+pub fn remote_uri(file: &Path) -> Option<&str> {
+    let file = file.to_str()?;
+    (file.starts_with(HTTPS_SCHEME) || file.starts_with(IPFS_SCHEME)).then_some(file)
+}
+
+/// Fetches the artifact at `uri`, returning the path of its local copy.
+///
+/// Artifacts are stored under the hash of their content, in `$INK_QUERIES_CACHE_DIR`
+/// or `~/.cache/ink-queries`, and the URI is only fetched again if its copy is
+/// missing or was altered.
+pub fn fetch_artifact(uri: &str) -> Result<PathBuf> {
+    let cache = ArtifactCache::new(cache_dir());
+    if let Some(path) = cache.lookup(uri)? {
+        return Ok(path);
+    }
+
+    let url = match uri.strip_prefix(IPFS_SCHEME) {
+        Some(cid) => {
+            let gateway =
+                std::env::var(IPFS_GATEWAY_ENV).unwrap_or_else(|_| DEFAULT_IPFS_GATEWAY.to_owned());
+            format!("{}/{cid}", gateway.trim_end_matches('/'))
+        }
+        None => uri.to_owned(),
+    };
+    tracing::info!(uri, "Fetching contract artifact");
+    let bytes = runtime::block_on(async {
+        let response = reqwest::get(&url).await?.error_for_status()?;
+        response.bytes().await
+    })
+    .with_context(|| format!("Failed to fetch artifact {uri}"))?;
+    cache.store(uri, &bytes)
+}
+
+fn cache_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var(CACHE_DIR_ENV) {
+        return PathBuf::from(dir);
+    }
+    match std::env::var("HOME") {
+        Ok(home) => Path::new(&home).join(".cache").join("ink-queries"),
+        Err(_) => std::env::temp_dir().join("ink-queries"),
+    }
+}
+
+/// Content-addressed copies of remote artifacts, with a reference from each URI to
+/// the hash of its content.
+struct ArtifactCache {
+    objects: PathBuf,
+    refs: PathBuf,
+}
+
+impl ArtifactCache {
+    fn new(root: PathBuf) -> Self {
+        Self {
+            objects: root.join("objects"),
+            refs: root.join("refs"),
+        }
+    }
+
+    /// Path of the cached copy of `uri`, if its content still matches its hash.
+    fn lookup(&self, uri: &str) -> Result<Option<PathBuf>> {
+        let hash = match fs::read_to_string(self.ref_path(uri)) {
+            Ok(hash) => hash,
+            Err(_) => return Ok(None),
+        };
+        let path = self.object_path(hash.trim(), uri);
+        match fs::read(&path) {
+            Ok(content) if hex::encode(blake2_256(&content)) == hash.trim() => Ok(Some(path)),
+            Ok(_) => {
+                tracing::warn!(uri, path = %path.display(), "Cached artifact was altered");
+                Ok(None)
+            }
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn store(&self, uri: &str, content: &[u8]) -> Result<PathBuf> {
+        fs::create_dir_all(&self.objects)?;
+        fs::create_dir_all(&self.refs)?;
+        let hash = hex::encode(blake2_256(content));
+        let path = self.object_path(&hash, uri);
+
+        // Written aside and renamed, so that concurrent loads never read a partial file.
+        let partial = path.with_extension("partial");
+        fs::write(&partial, content)?;
+        fs::rename(&partial, &path)?;
+        fs::write(self.ref_path(uri), &hash)?;
+        Ok(path)
+    }
+
+    fn object_path(&self, hash: &str, uri: &str) -> PathBuf {
+        let extension = uri
+            .rsplit('/')
+            .next()
+            .and_then(|name| name.rsplit_once('.'))
+            .map(|(_, extension)| extension)
+            .filter(|extension| ARTIFACT_EXTENSIONS.contains(extension))
+            // Bundles are the usual published artifacts, e.g. behind an IPFS CID.
+            .unwrap_or("contract");
+        self.objects.join(format!("{hash}.{extension}"))
+    }
+
+    fn ref_path(&self, uri: &str) -> PathBuf {
+        self.refs.join(hex::encode(blake2_256(uri.as_bytes())))
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Context, Result};
+use sp_core::H256;
+use std::path::{Path, PathBuf};
+
+use super::{remote, ContractArtifacts};
+use crate::substrate::contract::{info, ContractInstance};
+use crate::substrate::typed;
+use crate::substrate::{runtime, AccountId};
+
+/// Placeholder of the hex code hash, without `0x`, in [`VerifierService`] URLs.
+const CODE_HASH_PLACEHOLDER: &str = "{code_hash}";
+
+/// A service or registry publishing verified metadata by code hash.
+pub trait MetadataResolver {
+    /// `https://` or `ipfs://` URI of the metadata verified for `code_hash`, `None`
+    /// if none was published.
+    fn metadata_uri(&self, code_hash: &H256) -> Result<Option<String>>;
+}
+
+/// A verification service serving metadata at a URL derived from the code hash,
+/// e.g. `https://verifier.example/contracts/{code_hash}/metadata.json`.
+pub struct VerifierService {
+    template: String,
+}
+
+impl VerifierService {
+    pub fn new(template: impl Into<String>) -> Result<Self> {
+        let template = template.into();
+        if !template.contains(CODE_HASH_PLACEHOLDER) {
+            anyhow::bail!("Verifier URL {template} has no {CODE_HASH_PLACEHOLDER} placeholder");
+        }
+        Ok(Self { template })
+    }
+}
+
+impl MetadataResolver for VerifierService {
+    fn metadata_uri(&self, code_hash: &H256) -> Result<Option<String>> {
+        let code_hash = hex::encode(code_hash);
+        Ok(Some(
+            self.template.replace(CODE_HASH_PLACEHOLDER, &code_hash),
+        ))
+    }
+}
+
+/// A registry contract mapping code hashes to metadata URIs, through a message
+/// taking the code hash and returning `Option<String>`.
+pub struct RegistryContract {
+    contract: ContractInstance,
+    message: String,
+}
+
+impl RegistryContract {
+    pub fn new(contract: ContractInstance, message: impl Into<String>) -> Self {
+        Self {
+            contract,
+            message: message.into(),
+        }
+    }
+}
+
+impl MetadataResolver for RegistryContract {
+    fn metadata_uri(&self, code_hash: &H256) -> Result<Option<String>> {
+        let code_hash = format!("0x{}", hex::encode(code_hash));
+        runtime::block_on(typed::query::<Option<String>>(
+            &self.contract,
+            &self.message,
+            vec![code_hash],
+            false,
+        ))
+        .map_err(|err| anyhow!("Registry query {} failed: {err}", self.message))
+    }
+}
+
+/// Downloads the verified metadata of the contract at `address` on the chain at
+/// `url`, returning the path of its cached copy.
+///
+/// Fails unless the metadata was built from the code the contract runs.
+pub fn resolve_artifact(
+    url: &str,
+    address: &AccountId,
+    resolver: &dyn MetadataResolver,
+) -> Result<PathBuf> {
+    let code_hash = runtime::block_on(info::code_hash_of(url, address))?
+        .with_context(|| format!("No contract found at {address}"))?;
+    let uri = resolver
+        .metadata_uri(&code_hash)?
+        .with_context(|| format!("No verified metadata for code hash {code_hash:?}"))?;
+    let path = match remote::remote_uri(Path::new(&uri)) {
+        Some(uri) => remote::fetch_artifact(uri)?,
+        None => anyhow::bail!("Metadata URI {uri} is neither https:// nor ipfs://"),
+    };
+
+    let artifacts = ContractArtifacts::from_manifest_or_file(None, Some(&path))?;
+    artifacts.verify_bundled_code()?;
+    let resolved = H256(artifacts.code_hash()?);
+    if resolved != code_hash {
+        anyhow::bail!(
+            "Metadata at {uri} is for code hash {resolved:?}, the contract at {address} runs \
+            {code_hash:?}"
+        );
+    }
+    Ok(path)
+}
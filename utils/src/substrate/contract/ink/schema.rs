@@ -0,0 +1,397 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use scale_info::{form::PortableForm, Field, PortableRegistry, TypeDef, TypeDefPrimitive};
+use serde_json::{json, Map as JsonMap, Value as JsonValue};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use super::transcoder::InkTranscoder;
+use super::values::{byte_string_len, Collection};
+
+const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// JSON shape of a decoded value, as rendered by the JSON output of this crate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Shape {
+    Null,
+    Bool,
+    /// Integers that always fit in an `i64`, rendered as JSON numbers.
+    Int,
+    /// Integers rendered as numbers when they fit in an `i64` and as decimal
+    /// strings otherwise.
+    BigInt,
+    /// Strings, SS58 accounts and hex or text byte strings.
+    Text,
+    List(Box<Shape>),
+    Tuple(Vec<Shape>),
+    Object(Vec<(String, Shape)>),
+    /// A map, keyed by its rendered keys.
+    Dict(Box<Shape>),
+    /// An enum variant or struct without fields, rendered as its name.
+    Tag(String),
+    /// A value wrapped in an object keyed by the name of its variant or type.
+    Tagged(String, Box<Shape>),
+    Union(Vec<Shape>),
+    /// A named type, defined once so that recursive types terminate.
+    Ref(String),
+    Any,
+}
+
+/// Generates the types of the JSON the messages and events of a contract decode to,
+/// so that frontends reading the JSON output get static types.
+///
+/// The types describe the output of the default [`super::decode::DecodeOptions`]:
+/// display hooks may render values differently. Structs and enums are defined once,
+/// under the name of their type, suffixed with their type id if several types share
+/// it, e.g. the `Result`s of different messages.
+pub struct SchemaGenerator<'a> {
+    transcoder: &'a InkTranscoder,
+}
+
+impl<'a> SchemaGenerator<'a> {
+    pub fn new(transcoder: &'a InkTranscoder) -> Self {
+        Self { transcoder }
+    }
+
+    /// A JSON Schema of an object holding the return value of every message under
+    /// `messages` and every event under `events`, keyed by their labels.
+    pub fn json_schema(&self) -> JsonValue {
+        let (shapes, messages, events) = self.shapes();
+        let defs: JsonMap<String, JsonValue> = shapes
+            .defs
+            .iter()
+            .map(|(name, shape)| (name.clone(), schema(shape)))
+            .collect();
+        let properties = |entries: &[(String, Shape)]| {
+            let properties: JsonMap<String, JsonValue> = entries
+                .iter()
+                .map(|(label, shape)| (label.clone(), schema(shape)))
+                .collect();
+            json!({ "type": "object", "properties": properties })
+        };
+        json!({
+            "$schema": JSON_SCHEMA_DIALECT,
+            "type": "object",
+            "properties": {
+                "messages": properties(&messages),
+                "events": properties(&events),
+            },
+            "$defs": defs,
+        })
+    }
+
+    /// TypeScript declarations of the same types, with the return values of messages
+    /// in a `Messages` interface and events in an `Events` interface.
+    pub fn typescript(&self) -> String {
+        let (shapes, messages, events) = self.shapes();
+        let mut out = String::new();
+        for (name, shape) in &shapes.defs {
+            out.push_str(&format!("export type {name} = {};\n\n", typescript(shape)));
+        }
+        for (interface, entries) in [("Messages", &messages), ("Events", &events)] {
+            out.push_str(&format!("export interface {interface} {{\n"));
+            for (label, shape) in entries {
+                out.push_str(&format!(
+                    "  {}: {};\n",
+                    property_key(label),
+                    typescript(shape)
+                ));
+            }
+            out.push_str("}\n");
+            if interface == "Messages" {
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    /// Shapes of the return values of messages and of events, with the named types
+    /// they refer to.
+    fn shapes(&self) -> (Shapes<'_>, NamedShapes, NamedShapes) {
+        let metadata = self.transcoder.metadata();
+        let mut shapes = Shapes::new(metadata.registry());
+        let messages = metadata
+            .spec()
+            .messages()
+            .iter()
+            .map(|message| {
+                let shape = match message.return_type().opt_type() {
+                    Some(ty) => shapes.shape(ty.ty().id),
+                    None => Shape::Null,
+                };
+                (message.label().to_owned(), shape)
+            })
+            .collect();
+        let events = metadata
+            .spec()
+            .events()
+            .iter()
+            .map(|event| {
+                let fields = event
+                    .args()
+                    .iter()
+                    .map(|arg| (arg.label().to_owned(), shapes.shape(arg.ty().ty().id)))
+                    .collect();
+                (event.label().to_owned(), Shape::Object(fields))
+            })
+            .collect();
+        (shapes, messages, events)
+    }
+}
+
+/// Shapes of messages or events, by name.
+type NamedShapes = Vec<(String, Shape)>;
+
+/// Shapes of the types of a registry, following how the transcoder decodes them and
+/// how [`super::values::json_value`] renders them.
+struct Shapes<'a> {
+    registry: &'a PortableRegistry,
+    names: HashMap<u32, String>,
+    taken: HashSet<String>,
+    defs: BTreeMap<String, Shape>,
+}
+
+impl<'a> Shapes<'a> {
+    fn new(registry: &'a PortableRegistry) -> Self {
+        Self {
+            registry,
+            names: HashMap::new(),
+            taken: HashSet::new(),
+            defs: BTreeMap::new(),
+        }
+    }
+
+    fn shape(&mut self, id: u32) -> Shape {
+        if let Some(name) = self.names.get(&id) {
+            return Shape::Ref(name.clone());
+        }
+        let registry = self.registry;
+        let ty = match registry.resolve(id) {
+            Some(ty) => ty,
+            None => return Shape::Any,
+        };
+        let ident = ty.path.segments.last().cloned();
+
+        match &ty.type_def {
+            TypeDef::Primitive(primitive) => match primitive {
+                TypeDefPrimitive::Bool => Shape::Bool,
+                TypeDefPrimitive::Char | TypeDefPrimitive::Str => Shape::Text,
+                TypeDefPrimitive::U8
+                | TypeDefPrimitive::U16
+                | TypeDefPrimitive::U32
+                | TypeDefPrimitive::I8
+                | TypeDefPrimitive::I16
+                | TypeDefPrimitive::I32
+                | TypeDefPrimitive::I64 => Shape::Int,
+                _ => Shape::BigInt,
+            },
+            TypeDef::Compact(compact) => self.shape(compact.type_param.id),
+            TypeDef::Sequence(_) | TypeDef::Array(_) if byte_string_len(registry, id).is_some() => {
+                Shape::Text
+            }
+            TypeDef::Sequence(sequence) => {
+                Shape::List(Box::new(self.shape(sequence.type_param.id)))
+            }
+            TypeDef::Array(array) => Shape::List(Box::new(self.shape(array.type_param.id))),
+            TypeDef::Tuple(tuple) => {
+                let mut fields: Vec<Shape> = tuple
+                    .fields
+                    .iter()
+                    .map(|field| self.shape(field.id))
+                    .collect();
+                match fields.len() {
+                    0 => Shape::Null,
+                    1 => fields.remove(0),
+                    _ => Shape::Tuple(fields),
+                }
+            }
+            TypeDef::BitSequence(_) => Shape::Any,
+            TypeDef::Composite(_)
+                if matches!(
+                    ident.as_deref(),
+                    Some("AccountId" | "AccountId32" | "Hash" | "H256")
+                ) =>
+            {
+                Shape::Text
+            }
+            TypeDef::Composite(composite) => match (Collection::of(registry, ty), ident) {
+                (Some(Collection::Map { value, .. }), _) => {
+                    Shape::Dict(Box::new(self.shape(value)))
+                }
+                (Some(Collection::Set { elem }), _) => Shape::List(Box::new(self.shape(elem))),
+                (None, Some(ident)) => {
+                    let name = self.define(id, &ident);
+                    let shape = self.fields(&ident, &composite.fields);
+                    self.defs.insert(name.clone(), shape);
+                    Shape::Ref(name)
+                }
+                (None, None) => {
+                    let fields = self.field_shapes(&composite.fields);
+                    let named = composite.fields.iter().all(|field| field.name.is_some());
+                    if named && !fields.is_empty() {
+                        return Shape::Object(fields);
+                    }
+                    let mut values: Vec<Shape> =
+                        fields.into_iter().map(|(_, shape)| shape).collect();
+                    match values.len() {
+                        1 => values.remove(0),
+                        _ => Shape::Tuple(values),
+                    }
+                }
+            },
+            TypeDef::Variant(variant) => {
+                let ident = ident.unwrap_or_else(|| format!("Enum{id}"));
+                let name = self.define(id, &ident);
+                let variants = variant
+                    .variants
+                    .iter()
+                    .map(|variant| self.fields(&variant.name, &variant.fields))
+                    .collect();
+                self.defs.insert(name.clone(), Shape::Union(variants));
+                Shape::Ref(name)
+            }
+        }
+    }
+
+    /// Reserves the name of a named type before its fields are visited.
+    fn define(&mut self, id: u32, ident: &str) -> String {
+        let name = if self.taken.contains(ident) {
+            format!("{ident}{id}")
+        } else {
+            ident.to_owned()
+        };
+        self.taken.insert(name.clone());
+        self.names.insert(id, name.clone());
+        name
+    }
+
+    /// Shape of the fields of a struct or enum variant named `name`: an object for
+    /// named fields, else the fields keyed by the name.
+    fn fields(&mut self, name: &str, fields: &[Field<PortableForm>]) -> Shape {
+        if fields.is_empty() {
+            return Shape::Tag(name.to_owned());
+        }
+        let shapes = self.field_shapes(fields);
+        if fields.iter().all(|field| field.name.is_some()) {
+            return Shape::Object(shapes);
+        }
+        let mut values: Vec<Shape> = shapes.into_iter().map(|(_, shape)| shape).collect();
+        let inner = match values.len() {
+            1 => values.remove(0),
+            _ => Shape::Tuple(values),
+        };
+        Shape::Tagged(name.to_owned(), Box::new(inner))
+    }
+
+    fn field_shapes(&mut self, fields: &[Field<PortableForm>]) -> Vec<(String, Shape)> {
+        fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let name = field.name.clone().unwrap_or_else(|| i.to_string());
+                (name, self.shape(field.ty.id))
+            })
+            .collect()
+    }
+}
+
+fn schema(shape: &Shape) -> JsonValue {
+    match shape {
+        Shape::Null => json!({ "type": "null" }),
+        Shape::Bool => json!({ "type": "boolean" }),
+        Shape::Int => json!({ "type": "integer" }),
+        Shape::BigInt => json!({ "type": ["integer", "string"], "pattern": "^-?[0-9]+$" }),
+        Shape::Text => json!({ "type": "string" }),
+        Shape::List(elem) => json!({ "type": "array", "items": schema(elem) }),
+        Shape::Tuple(elems) => json!({
+            "type": "array",
+            "prefixItems": elems.iter().map(schema).collect::<Vec<_>>(),
+            "items": false,
+        }),
+        Shape::Object(fields) => {
+            let properties: JsonMap<String, JsonValue> = fields
+                .iter()
+                .map(|(name, shape)| (name.clone(), schema(shape)))
+                .collect();
+            let required: Vec<&String> = fields.iter().map(|(name, _)| name).collect();
+            json!({
+                "type": "object",
+                "properties": properties,
+                "required": required,
+                "additionalProperties": false,
+            })
+        }
+        Shape::Dict(value) => json!({ "type": "object", "additionalProperties": schema(value) }),
+        Shape::Tag(name) => json!({ "const": name }),
+        Shape::Tagged(name, inner) => {
+            let mut properties = JsonMap::new();
+            properties.insert(name.clone(), schema(inner));
+            json!({
+                "type": "object",
+                "properties": properties,
+                "required": [name],
+                "additionalProperties": false,
+            })
+        }
+        Shape::Union(shapes) => json!({ "oneOf": shapes.iter().map(schema).collect::<Vec<_>>() }),
+        Shape::Ref(name) => json!({ "$ref": format!("#/$defs/{name}") }),
+        Shape::Any => json!({}),
+    }
+}
+
+fn typescript(shape: &Shape) -> String {
+    match shape {
+        Shape::Null => "null".to_owned(),
+        Shape::Bool => "boolean".to_owned(),
+        Shape::Int => "number".to_owned(),
+        Shape::BigInt => "number | string".to_owned(),
+        Shape::Text => "string".to_owned(),
+        Shape::List(elem) => format!("Array<{}>", typescript(elem)),
+        Shape::Tuple(elems) => {
+            let elems: Vec<String> = elems.iter().map(typescript).collect();
+            format!("[{}]", elems.join(", "))
+        }
+        Shape::Object(fields) => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(name, shape)| format!("{}: {}", property_key(name), typescript(shape)))
+                .collect();
+            format!("{{ {} }}", fields.join("; "))
+        }
+        Shape::Dict(value) => format!("Record<string, {}>", typescript(value)),
+        Shape::Tag(name) => JsonValue::from(name.as_str()).to_string(),
+        Shape::Tagged(name, inner) => {
+            format!("{{ {}: {} }}", property_key(name), typescript(inner))
+        }
+        Shape::Union(shapes) => {
+            let shapes: Vec<String> = shapes.iter().map(typescript).collect();
+            shapes.join(" | ")
+        }
+        Shape::Ref(name) => name.clone(),
+        Shape::Any => "unknown".to_owned(),
+    }
+}
+
+/// `name` as a TypeScript property key, quoted unless it is an identifier.
+fn property_key(name: &str) -> String {
+    let is_identifier = matches!(name.chars().next(), Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if is_identifier {
+        name.to_owned()
+    } else {
+        JsonValue::from(name).to_string()
+    }
+}
@@ -0,0 +1,433 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, bail, Result};
+use contract_transcode::{Hex, Map, Tuple, Value};
+
+/// The length of `u128::MAX`, above which alphanumeric words are literals.
+const MAX_UINT_LEN: usize = 39;
+
+/// Parses a message argument the way the transcoder reads it, which contract-transcode
+/// keeps private.
+///
+/// Unlike the transcoder, the whole of `input` must be a single value.
+pub(crate) fn parse_value(input: &str) -> Result<Value> {
+    let mut parser = Parser { input, pos: 0 };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos != input.len() {
+        bail!("unexpected `{}` at {}", parser.rest(), parser.pos);
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start_matches([' ', '\t', '\r', '\n']).len();
+    }
+
+    /// Consumes `token`, after any whitespace, if it comes next.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(anyhow!("expected `{token}` at {}", self.pos))
+        }
+    }
+
+    /// Consumes the longest prefix of characters matching `f`.
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c| !f(c)).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        if rest.starts_with("()") {
+            self.pos += 2;
+            return Ok(Value::Unit);
+        }
+        if let Some(digits) = rest.strip_prefix("0x") {
+            let len = digits
+                .find(|c: char| !c.is_ascii_hexdigit())
+                .unwrap_or(digits.len());
+            if len > 0 {
+                self.pos += 2 + len;
+                return Ok(Value::Hex(digits[..len].parse::<Hex>()?));
+            }
+        }
+        match self.peek() {
+            Some('[') => self.seq(),
+            Some('(') | Some('{') => self.composite(None),
+            Some('"') => self.string().map(Value::String),
+            Some('\'') => self.char(),
+            Some(c) if c == '+' || c == '-' || c.is_ascii_digit() => self.integer(),
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => self.word(),
+            Some(c) => bail!("unexpected `{c}` at {}", self.pos),
+            None => bail!("expected a value at {}", self.pos),
+        }
+    }
+
+    fn seq(&mut self) -> Result<Value> {
+        self.expect("[")?;
+        let elems = self.list("]", Self::value)?;
+        Ok(Value::Seq(elems.into()))
+    }
+
+    /// Parses the values of a list closed by `close`, allowing a trailing comma.
+    fn list<T>(
+        &mut self,
+        close: &str,
+        mut item: impl FnMut(&mut Self) -> Result<T>,
+    ) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        while !self.eat(close) {
+            items.push(item(self)?);
+            if !self.eat(",") {
+                self.expect(close)?;
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    /// Parses a tuple, or a map in braces or parentheses, named by `ident`.
+    fn composite(&mut self, ident: Option<&str>) -> Result<Value> {
+        let start = self.pos;
+        if self.eat("(") {
+            let tuple_err = match self.list(")", Self::value) {
+                Ok(values) => return Ok(Value::Tuple(Tuple::new(ident, values))),
+                Err(err) => err,
+            };
+            // Not a tuple, maybe a map in parentheses such as `(a: 1)`.
+            self.pos = start;
+            self.expect("(")?;
+            let entries = self.list(")", Self::entry).map_err(|_| tuple_err)?;
+            return Ok(Value::Map(Map::new(ident, entries.into_iter().collect())));
+        }
+        self.expect("{")?;
+        let entries = self.list("}", Self::entry)?;
+        Ok(Value::Map(Map::new(ident, entries.into_iter().collect())))
+    }
+
+    /// Parses a map entry, whose key is a field name or any value, e.g. the hex of an
+    /// account or a tuple keying a `BTreeMap`.
+    fn entry(&mut self) -> Result<(Value, Value)> {
+        self.skip_whitespace();
+        let key = match self.peek() {
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                Value::String(self.ident().to_owned())
+            }
+            _ => self.value()?,
+        };
+        self.expect(":")?;
+        Ok((key, self.value()?))
+    }
+
+    fn ident(&mut self) -> &'a str {
+        self.take_while(|c| c.is_alphanumeric() || c == '_')
+    }
+
+    /// Parses a bool, a literal, or a tuple, map or unit variant named by an identifier.
+    fn word(&mut self) -> Result<Value> {
+        let rest = self.rest();
+        let alphanumeric = rest
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(rest.len());
+        let ident = self.ident();
+        self.skip_whitespace();
+        match self.peek() {
+            Some('(') | Some('{') => return self.composite(Some(ident)),
+            _ => {}
+        }
+        if alphanumeric > MAX_UINT_LEN {
+            self.pos = self.input.len() - rest.len() + alphanumeric;
+            return Ok(Value::Literal(rest[..alphanumeric].to_owned()));
+        }
+        Ok(match ident {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => Value::Tuple(Tuple::new(Some(ident), Vec::new())),
+        })
+    }
+
+    fn integer(&mut self) -> Result<Value> {
+        self.skip_whitespace();
+        let start = self.pos;
+        // A long enough word is a literal, such as an account in base58.
+        let literal = self.rest().find(|c: char| !c.is_ascii_alphanumeric());
+        if literal.unwrap_or(self.rest().len()) > MAX_UINT_LEN {
+            let literal = self.take_while(|c| c.is_ascii_alphanumeric());
+            return Ok(Value::Literal(literal.to_owned()));
+        }
+        let sign = self.take_while(|c| c == '+' || c == '-');
+        if sign.len() > 1 {
+            bail!("unexpected `{sign}` at {start}");
+        }
+        let digits = self.take_while(|c| c.is_ascii_digit() || c == '_');
+        if digits.is_empty() || digits.starts_with('_') || digits.ends_with('_') {
+            bail!("expected an integer at {start}");
+        }
+        let digits = digits.replace('_', "");
+        let value = match sign {
+            "" => Value::UInt(digits.parse()?),
+            _ => Value::Int(format!("{sign}{digits}").parse()?),
+        };
+        Ok(value)
+    }
+
+    fn char(&mut self) -> Result<Value> {
+        let start = self.pos;
+        self.expect("'")?;
+        let c = self
+            .peek()
+            .ok_or_else(|| anyhow!("unterminated char at {start}"))?;
+        self.pos += c.len_utf8();
+        if !self.rest().starts_with('\'') {
+            bail!("unterminated char at {start}");
+        }
+        self.pos += 1;
+        Ok(Value::Char(c))
+    }
+
+    /// Parses a string with the escapes of JSON.
+    fn string(&mut self) -> Result<String> {
+        let start = self.pos;
+        self.expect("\"")?;
+        let mut text = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Ok(text);
+                }
+                '\\' => {
+                    let escaped = match chars.next().map(|(_, c)| c) {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => unicode_escape(&mut chars)?,
+                        _ => bail!("invalid escape in the string at {start}"),
+                    };
+                    text.push(escaped);
+                }
+                c if (c as u32) < 0x20 => bail!("control character in the string at {start}"),
+                c => text.push(c),
+            }
+        }
+        bail!("unterminated string at {start}")
+    }
+}
+
+/// Reads the code point of a `\u` escape, pairing surrogates as JSON does.
+fn unicode_escape(chars: &mut std::str::CharIndices) -> Result<char> {
+    fn code_unit(chars: &mut std::str::CharIndices) -> Result<u32> {
+        let digits: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+        if digits.len() != 4 {
+            bail!("truncated unicode escape");
+        }
+        Ok(u32::from_str_radix(&digits, 16)?)
+    }
+    let high = code_unit(chars)?;
+    let code = if (0xD800..0xDC00).contains(&high) {
+        match (chars.next(), chars.next()) {
+            (Some((_, '\\')), Some((_, 'u'))) => {}
+            _ => bail!("unpaired surrogate in unicode escape"),
+        }
+        let low = code_unit(chars)?;
+        if !(0xDC00..0xE000).contains(&low) {
+            bail!("unpaired surrogate in unicode escape");
+        }
+        0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+    } else {
+        high
+    };
+    char::from_u32(code).ok_or_else(|| anyhow!("invalid unicode escape"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uint(value: u128) -> Value {
+        Value::UInt(value)
+    }
+
+    fn string(text: &str) -> Value {
+        Value::String(text.to_owned())
+    }
+
+    fn hex(digits: &str) -> Value {
+        Value::Hex(digits.parse().unwrap())
+    }
+
+    fn map(ident: Option<&str>, entries: Vec<(Value, Value)>) -> Value {
+        Value::Map(Map::new(ident, entries.into_iter().collect()))
+    }
+
+    /// The arguments contract-transcode encodes in its own tests, with the values its
+    /// parser reads them as.
+    #[test]
+    fn parses_the_transcoder_inputs() {
+        let alice = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+        let bob = "5FHneW46xGXgs5mUiveU4sbTyGBzmstUspZC92UhjJM694ty";
+        let accounts = format!("[{alice}, {bob}]");
+        let cases = vec![
+            ("true", Value::Bool(true)),
+            ("false", Value::Bool(false)),
+            ("()", Value::Unit),
+            ("'a'", Value::Char('a')),
+            ("\"ink!\"", string("ink!")),
+            ("255", uint(255)),
+            ("18_446_744_073_709_551_615", uint(u64::MAX.into())),
+            ("-128", Value::Int(i8::MIN.into())),
+            ("+127", Value::Int(i8::MAX.into())),
+            (
+                "-170141183460469231731687303715884105728",
+                Value::Int(i128::MIN),
+            ),
+            ("0x00", hex("00")),
+            ("0xDEADBEEF", hex("DEADBEEF")),
+            (alice, Value::Literal(alice.to_owned())),
+            (
+                &accounts,
+                Value::Seq(
+                    vec![
+                        Value::Literal(alice.to_owned()),
+                        Value::Literal(bob.to_owned()),
+                    ]
+                    .into(),
+                ),
+            ),
+            ("[1, 2]", Value::Seq(vec![uint(1), uint(2)].into())),
+            (
+                "[0xDE, 0xAD]",
+                Value::Seq(vec![hex("DE"), hex("AD")].into()),
+            ),
+            (
+                r#"(1, "ink!", 0xDEADBEEF)"#,
+                Value::Tuple(Tuple::new(
+                    None,
+                    vec![uint(1), string("ink!"), hex("DEADBEEF")],
+                )),
+            ),
+            (
+                r#"S(b: "ink!", a: 1, c: 0xDEADBEEF)"#,
+                map(
+                    Some("S"),
+                    // Entries keep the order they are written in.
+                    vec![
+                        (string("b"), string("ink!")),
+                        (string("a"), uint(1)),
+                        (string("c"), hex("DEADBEEF")),
+                    ],
+                ),
+            ),
+            (
+                "S { nested: Nested(33) }",
+                map(
+                    Some("S"),
+                    vec![(
+                        string("nested"),
+                        Value::Tuple(Tuple::new(Some("Nested"), vec![uint(33)])),
+                    )],
+                ),
+            ),
+            ("None", Value::Tuple(Tuple::new(Some("None"), Vec::new()))),
+            (
+                "Some(1,)",
+                Value::Tuple(Tuple::new(Some("Some"), vec![uint(1)])),
+            ),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(parse_value(input).unwrap(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn parses_map_keys_as_values() {
+        let cases = vec![
+            ("{ a: 1 }", vec![(string("a"), uint(1))]),
+            (r#"{ "a b": 1 }"#, vec![(string("a b"), uint(1))]),
+            ("{ 1: true }", vec![(uint(1), Value::Bool(true))]),
+            ("{ -1: true }", vec![(Value::Int(-1), Value::Bool(true))]),
+            ("{ 0xdead: 1 }", vec![(hex("dead"), uint(1))]),
+            (
+                "{ (1, 2): 3 }",
+                vec![(
+                    Value::Tuple(Tuple::new(None, vec![uint(1), uint(2)])),
+                    uint(3),
+                )],
+            ),
+            (
+                "{ [1]: 2, }",
+                vec![(Value::Seq(vec![uint(1)].into()), uint(2))],
+            ),
+        ];
+        for (input, entries) in cases {
+            assert_eq!(parse_value(input).unwrap(), map(None, entries), "{input}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        for input in [
+            "",
+            "1 2",
+            "--1",
+            "1_",
+            "[1, 2",
+            "{ a 1 }",
+            "{ : 1 }",
+            "\"unterminated",
+            "'ab'",
+            "\"\\ud800\"",
+        ] {
+            assert!(parse_value(input).is_err(), "{}", input);
+        }
+    }
+}
@@ -0,0 +1,215 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sp_core::hashing::blake2_256;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::transcoder::InkTranscoder;
+
+/// The selector ink! derives from a message or constructor label, the first four
+/// bytes of its BLAKE2b-256 hash. Trait messages are labelled `Trait::message`.
+pub fn compute_selector(label: &str) -> [u8; 4] {
+    let hash = blake2_256(label.as_bytes());
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SelectorKind {
+    Message,
+    Constructor,
+}
+
+/// A message or constructor of contract metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectorEntry {
+    pub kind: SelectorKind,
+    pub label: String,
+    pub selector: [u8; 4],
+}
+
+impl SelectorEntry {
+    /// Whether the selector is not the one derived from the label, e.g. set with
+    /// `#[ink(selector = ..)]` or a wildcard.
+    pub fn is_custom(&self) -> bool {
+        self.selector != compute_selector(&self.label)
+    }
+}
+
+impl fmt::Display for SelectorEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "0x{} {:?} {}",
+            hex::encode(self.selector),
+            self.kind,
+            self.label
+        )?;
+        if self.is_custom() {
+            write!(f, " (custom)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Selectors shared by several messages, or several constructors, only one of which
+/// can be called.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectorCollision {
+    pub kind: SelectorKind,
+    pub selector: [u8; 4],
+    pub labels: Vec<String>,
+}
+
+/// The selectors of the messages and constructors of contract metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectorTable {
+    pub entries: Vec<SelectorEntry>,
+}
+
+impl SelectorTable {
+    pub fn from_transcoder(transcoder: &InkTranscoder) -> Self {
+        let spec = transcoder.metadata().spec();
+        let constructors = spec.constructors().iter().map(|constructor| {
+            entry(
+                SelectorKind::Constructor,
+                constructor.label(),
+                constructor.selector().to_bytes(),
+            )
+        });
+        let messages = spec.messages().iter().map(|message| {
+            entry(
+                SelectorKind::Message,
+                message.label(),
+                message.selector().to_bytes(),
+            )
+        });
+        Self {
+            entries: constructors.chain(messages).collect(),
+        }
+    }
+
+    /// Entries with `selector`, e.g. read from the first bytes of call data.
+    pub fn find(&self, selector: [u8; 4]) -> impl Iterator<Item = &SelectorEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.selector == selector)
+    }
+
+    pub fn collisions(&self) -> Vec<SelectorCollision> {
+        let mut by_selector: BTreeMap<(SelectorKind, [u8; 4]), Vec<String>> = BTreeMap::new();
+        for entry in &self.entries {
+            by_selector
+                .entry((entry.kind, entry.selector))
+                .or_default()
+                .push(entry.label.clone());
+        }
+        by_selector
+            .into_iter()
+            .filter(|(_, labels)| labels.len() > 1)
+            .map(|((kind, selector), labels)| SelectorCollision {
+                kind,
+                selector,
+                labels,
+            })
+            .collect()
+    }
+}
+
+fn entry(kind: SelectorKind, label: &str, selector: &[u8]) -> SelectorEntry {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(selector);
+    SelectorEntry {
+        kind,
+        label: label.to_owned(),
+        selector: bytes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use contract_metadata::ContractMetadata;
+    use serde_json::json;
+
+    const FLIPPER: &str = include_str!("config/flipper_v4.json");
+
+    /// The flipper contract, with the selectors of its messages replaced.
+    fn flipper(selectors: &[(&str, &str)]) -> SelectorTable {
+        let mut metadata: ContractMetadata = serde_json::from_str(FLIPPER).unwrap();
+        let messages = metadata.abi["spec"]["messages"].as_array_mut().unwrap();
+        for (label, selector) in selectors {
+            let message = messages
+                .iter_mut()
+                .find(|message| message["label"] == *label)
+                .unwrap();
+            message["selector"] = json!(selector);
+        }
+        SelectorTable::from_transcoder(&InkTranscoder::from_metadata(metadata).unwrap())
+    }
+
+    #[test]
+    fn computes_selectors_of_labels() {
+        assert_eq!(compute_selector("flip"), [0x63, 0x3a, 0xa5, 0x51]);
+        assert_eq!(compute_selector("new"), [0x9b, 0xae, 0x9d, 0x5e]);
+        assert_eq!(
+            compute_selector("PSP22::transfer"),
+            [0xdb, 0x20, 0xf9, 0xf5]
+        );
+    }
+
+    #[test]
+    fn lists_selectors_of_metadata() {
+        let table = flipper(&[]);
+        let listed: Vec<_> = table.entries.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            listed,
+            [
+                "0x9bae9d5e Constructor new",
+                "0xed4b9d1b Constructor default",
+                "0x633aa551 Message flip",
+                "0x2f865bd9 Message get",
+            ]
+        );
+        assert!(table.collisions().is_empty());
+
+        let found: Vec<_> = table.find([0x2f, 0x86, 0x5b, 0xd9]).collect();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].label, "get");
+        assert_eq!(table.find([0; 4]).count(), 0);
+    }
+
+    #[test]
+    fn flags_custom_selectors() {
+        let table = flipper(&[("get", "0xcafebabe")]);
+        let get = table.find([0xca, 0xfe, 0xba, 0xbe]).next().unwrap();
+        assert!(get.is_custom());
+        assert_eq!(get.to_string(), "0xcafebabe Message get (custom)");
+    }
+
+    #[test]
+    fn reports_collisions() {
+        // A constructor sharing the selector of a message does not collide.
+        let table = flipper(&[("get", "0x633aa551")]);
+        assert_eq!(
+            table.collisions(),
+            [SelectorCollision {
+                kind: SelectorKind::Message,
+                selector: [0x63, 0x3a, 0xa5, 0x51],
+                labels: vec!["flip".to_owned(), "get".to_owned()],
+            }]
+        );
+        assert!(flipper(&[("get", "0x9bae9d5e")]).collisions().is_empty());
+    }
+}
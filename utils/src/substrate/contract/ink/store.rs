@@ -0,0 +1,161 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sp_core::hashing::blake2_256;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{ContractArtifacts, InkMeta};
+
+/// How an artifact that no longer matches its pinned hashes is handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StalePolicy {
+    /// Refuse to load the artifact.
+    #[default]
+    Error,
+    /// Log a warning and load the artifact, keeping the pinned hashes.
+    Warn,
+}
+
+/// Hashes of an artifact, as pinned in the lockfile.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct LockedArtifact {
+    /// Code hash, empty for artifacts without code or metadata, e.g. Solidity ABIs.
+    code_hash: String,
+    /// Hash of the ABI section of the metadata, or else of the artifact file.
+    abi_hash: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Lockfile {
+    #[serde(default)]
+    artifacts: BTreeMap<String, LockedArtifact>,
+}
+
+/// Loads artifacts, pinning their code and ABI hashes in a lockfile
+///
+/// The first load of an artifact records its hashes, and later loads check that
+/// the artifact still matches them, so that queries are never silently decoded
+/// with metadata rebuilt from other code. Call [`ArtifactStore::update`] after an
+/// intended upgrade.
+pub struct ArtifactStore {
+    lockfile: PathBuf,
+    locked: Lockfile,
+    policy: StalePolicy,
+}
+
+impl ArtifactStore {
+    /// Opens the lockfile at `lockfile`, created on the first pinned artifact.
+    pub fn open(lockfile: impl Into<PathBuf>) -> Result<Self> {
+        let lockfile = lockfile.into();
+        let locked = match fs::read_to_string(&lockfile) {
+            Ok(content) => toml::from_str(&content)
+                .with_context(|| format!("Invalid artifact lockfile {}", lockfile.display()))?,
+            Err(_) => Lockfile::default(),
+        };
+        Ok(Self {
+            lockfile,
+            locked,
+            policy: StalePolicy::default(),
+        })
+    }
+
+    pub fn policy(mut self, policy: StalePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Loads the artifact at `path`, checking it against its pinned hashes or
+    /// pinning them.
+    pub fn load(&mut self, path: &Path) -> Result<ContractArtifacts> {
+        let artifacts = ContractArtifacts::from_manifest_or_file(None, Some(&path.to_path_buf()))?;
+        self.check(&path.display().to_string(), &artifacts)?;
+        Ok(artifacts)
+    }
+
+    /// Same as [`ArtifactStore::load`], for the artifact of `meta`, which may be a
+    /// remote URI.
+    pub fn load_meta(&mut self, meta: &InkMeta) -> Result<ContractArtifacts> {
+        let artifacts = meta.contract_artifacts()?;
+        self.check(&meta.file.display().to_string(), &artifacts)?;
+        Ok(artifacts)
+    }
+
+    /// Pins the current hashes of the artifact at `path`, e.g. after an upgrade.
+    pub fn update(&mut self, path: &Path) -> Result<()> {
+        let artifacts = ContractArtifacts::from_manifest_or_file(None, Some(&path.to_path_buf()))?;
+        self.locked
+            .artifacts
+            .insert(path.display().to_string(), hashes(&artifacts)?);
+        self.save()
+    }
+
+    fn check(&mut self, key: &str, artifacts: &ContractArtifacts) -> Result<()> {
+        let current = hashes(artifacts)?;
+        let pinned = match self.locked.artifacts.get(key) {
+            Some(pinned) => pinned.clone(),
+            None => {
+                self.locked.artifacts.insert(key.to_owned(), current);
+                return self.save();
+            }
+        };
+        if pinned == current {
+            return Ok(());
+        }
+
+        let message = format!(
+            "Artifact {key} no longer matches {}: code hash {} (pinned {}), ABI hash {} \
+            (pinned {})",
+            self.lockfile.display(),
+            current.code_hash,
+            pinned.code_hash,
+            current.abi_hash,
+            pinned.abi_hash
+        );
+        match self.policy {
+            StalePolicy::Error => anyhow::bail!("{message}"),
+            StalePolicy::Warn => {
+                tracing::warn!("{message}");
+                Ok(())
+            }
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        fs::write(&self.lockfile, toml::to_string(&self.locked)?).with_context(|| {
+            format!(
+                "Failed to write artifact lockfile {}",
+                self.lockfile.display()
+            )
+        })
+    }
+}
+
+fn hashes(artifacts: &ContractArtifacts) -> Result<LockedArtifact> {
+    let abi = match &artifacts.metadata {
+        Some(metadata) => serde_json::to_vec(&metadata.abi)?,
+        None => fs::read(artifacts.artifact_path())?,
+    };
+    let code_hash = match (&artifacts.code, &artifacts.metadata) {
+        (None, None) => String::new(),
+        _ => format!("0x{}", hex::encode(artifacts.code_hash()?)),
+    };
+    Ok(LockedArtifact {
+        code_hash,
+        abi_hash: format!("0x{}", hex::encode(blake2_256(&abi))),
+    })
+}
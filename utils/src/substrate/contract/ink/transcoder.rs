@@ -0,0 +1,144 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use contract_metadata::ContractMetadata;
+use contract_transcode::{ContractMessageTranscoder, Value};
+use ink_metadata::InkProject;
+use serde_json::Value as JsonValue;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// Replaces a decoded value for display, e.g. to render a timestamp as a date.
+///
+/// Returning `None` keeps the decoded value.
+pub type DisplayHook = Arc<dyn Fn(&Value) -> Option<Value> + Send + Sync>;
+
+/// Display hooks by type path or type name, see [`TranscoderBuilder::display_hook`].
+#[derive(Clone, Default)]
+pub struct DisplayHooks(BTreeMap<String, DisplayHook>);
+
+impl DisplayHooks {
+    pub fn insert(
+        &mut self,
+        type_name: impl Into<String>,
+        hook: impl Fn(&Value) -> Option<Value> + Send + Sync + 'static,
+    ) {
+        self.0.insert(type_name.into(), Arc::new(hook));
+    }
+
+    pub(crate) fn get(&self, type_name: &str) -> Option<&DisplayHook> {
+        self.0.get(type_name)
+    }
+}
+
+impl fmt::Debug for DisplayHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
+/// Builds the [`InkTranscoder`] of a contract, with the hooks rendering its decoded
+/// values.
+pub struct TranscoderBuilder {
+    metadata: ContractMetadata,
+    display_hooks: DisplayHooks,
+}
+
+impl TranscoderBuilder {
+    /// Starts the transcoder of the ink! project in the ABI of `metadata`.
+    pub fn new(metadata: ContractMetadata) -> Self {
+        Self {
+            metadata,
+            display_hooks: DisplayHooks::default(),
+        }
+    }
+
+    /// Registers a hook rendering the decoded values of a type, in return values,
+    /// events and their JSON.
+    ///
+    /// `type_name` is either the full path of a type in the metadata, e.g.
+    /// `ink_primitives::types::AccountId`, its last segment, or the name a field or
+    /// event argument type is written with in the contract source, e.g. `Balance` or
+    /// `Timestamp`, which is the only way to tell aliases of primitive types apart.
+    pub fn display_hook(
+        mut self,
+        type_name: impl Into<String>,
+        hook: impl Fn(&Value) -> Option<Value> + Send + Sync + 'static,
+    ) -> Self {
+        self.display_hooks.insert(type_name, hook);
+        self
+    }
+
+    /// Registers all of `hooks`, replacing the hooks already registered for the same
+    /// types.
+    pub fn display_hooks(mut self, hooks: &DisplayHooks) -> Self {
+        self.display_hooks.0.extend(
+            hooks
+                .0
+                .iter()
+                .map(|(name, hook)| (name.clone(), hook.clone())),
+        );
+        self
+    }
+
+    pub fn build(self) -> Result<InkTranscoder> {
+        let abi = JsonValue::Object(self.metadata.abi);
+        // The project is not `Clone`, the transcoder gets its own copy.
+        let project = serde_json::from_value(abi.clone())
+            .context("Failed to deserialize ink project metadata from contract metadata")?;
+        let metadata = serde_json::from_value(abi)
+            .context("Failed to deserialize ink project metadata from contract metadata")?;
+        Ok(InkTranscoder {
+            transcoder: ContractMessageTranscoder::new(project),
+            metadata,
+            display_hooks: self.display_hooks,
+        })
+    }
+}
+
+/// A [`ContractMessageTranscoder`] with the ink! project it transcodes, which the
+/// transcoder does not expose.
+pub struct InkTranscoder {
+    transcoder: ContractMessageTranscoder,
+    metadata: InkProject,
+    display_hooks: DisplayHooks,
+}
+
+impl InkTranscoder {
+    /// Builds the transcoder of the ink! project in the ABI of `metadata`, see
+    /// [`TranscoderBuilder`] to render its values with display hooks.
+    pub fn from_metadata(metadata: ContractMetadata) -> Result<Self> {
+        TranscoderBuilder::new(metadata).build()
+    }
+
+    /// The ink! project, e.g. to look up the types of messages and events.
+    pub fn metadata(&self) -> &InkProject {
+        &self.metadata
+    }
+
+    pub(crate) fn display_hooks(&self) -> &DisplayHooks {
+        &self.display_hooks
+    }
+}
+
+impl Deref for InkTranscoder {
+    type Target = ContractMessageTranscoder;
+
+    fn deref(&self) -> &ContractMessageTranscoder {
+        &self.transcoder
+    }
+}
@@ -0,0 +1,196 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::migration::{self, AbiVersion};
+use super::ContractBinary;
+use contract_metadata::{Compiler, ContractMetadata, Language};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+
+const REQUIRED_ABI_FIELDS: [&str; 3] = ["spec", "storage", "types"];
+const REQUIRED_SPEC_FIELDS: [&str; 3] = ["constructors", "messages", "events"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The metadata cannot be trusted to decode contract data.
+    Error,
+    /// The metadata is usable but likely not what was intended.
+    Warning,
+}
+
+/// A problem found while validating contract metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// JSON path of the offending field, e.g. `spec.messages[2].selector`.
+    pub path: String,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn error(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+
+    fn warning(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} at `{}`: {}",
+            self.severity, self.path, self.message
+        )
+    }
+}
+
+/// Structural checks of contract metadata, run before deploying or publishing it.
+pub trait MetadataValidation {
+    /// Returns every problem found, an empty list meaning the metadata is valid.
+    fn validate(&self) -> Vec<Diagnostic>;
+}
+
+impl MetadataValidation for ContractMetadata {
+    fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        if self.contract.name.is_empty() {
+            diagnostics.push(Diagnostic::error("contract.name", "Contract name is empty"));
+        }
+
+        // solang writes ink! metadata for contracts targeting pallet-contracts.
+        let transcodable = match self.source.language.language {
+            Language::Ink => true,
+            Language::Solidity => matches!(self.source.compiler.compiler, Compiler::Solang),
+            Language::AssemblyScript => false,
+        };
+        if !transcodable {
+            diagnostics.push(Diagnostic::error(
+                "source.language",
+                format!(
+                    "Unsupported language {} built by {}, only ink! and solang metadata can be transcoded",
+                    self.source.language, self.source.compiler
+                ),
+            ));
+        }
+
+        match &self.source.wasm {
+            Some(wasm) => {
+                let hash = ContractBinary::new(wasm.0.clone()).code_hash();
+                if hash != self.source.hash.0 {
+                    diagnostics.push(Diagnostic::error(
+                        "source.hash",
+                        format!(
+                            "Code hash 0x{} does not match the bundled code (0x{})",
+                            hex::encode(self.source.hash.0),
+                            hex::encode(hash)
+                        ),
+                    ));
+                }
+            }
+            None => diagnostics.push(Diagnostic::warning(
+                "source.wasm",
+                "No code bundled, the code hash cannot be verified",
+            )),
+        }
+
+        let abi = match migration::detect_version(&self.abi) {
+            Ok(AbiVersion::V4 | AbiVersion::V5) => self.abi.clone(),
+            Ok(AbiVersion::V3) => {
+                diagnostics.push(Diagnostic::warning(
+                    "V3",
+                    "Legacy ink! 3 metadata, it is upgraded to the ink! 4 ABI when loaded",
+                ));
+                match migration::upgrade_abi(self.abi.clone()) {
+                    Ok(abi) => abi,
+                    Err(err) => {
+                        diagnostics.push(Diagnostic::error("V3", err.to_string()));
+                        return diagnostics;
+                    }
+                }
+            }
+            Err(err) => {
+                diagnostics.push(Diagnostic::error("version", err.to_string()));
+                return diagnostics;
+            }
+        };
+
+        for field in REQUIRED_ABI_FIELDS {
+            if !abi.contains_key(field) {
+                diagnostics.push(Diagnostic::error(field, "Required field is missing"));
+            }
+        }
+
+        if let Some(spec) = abi.get("spec") {
+            for field in REQUIRED_SPEC_FIELDS {
+                if !spec.get(field).is_some_and(Value::is_array) {
+                    diagnostics.push(Diagnostic::error(
+                        format!("spec.{field}"),
+                        "Required list is missing",
+                    ));
+                }
+            }
+            check_selectors(spec, &mut diagnostics);
+        }
+
+        diagnostics
+    }
+}
+
+/// Checks that every message and constructor has a selector, unique within its kind.
+fn check_selectors(spec: &Value, diagnostics: &mut Vec<Diagnostic>) {
+    for kind in ["constructors", "messages"] {
+        let entries = match spec.get(kind).and_then(Value::as_array) {
+            Some(entries) => entries,
+            None => continue,
+        };
+
+        let mut seen = HashMap::new();
+        for (i, entry) in entries.iter().enumerate() {
+            let path = format!("spec.{kind}[{i}]");
+            let label = entry
+                .get("label")
+                .and_then(Value::as_str)
+                .unwrap_or("<unnamed>");
+            let selector = match entry.get("selector").and_then(Value::as_str) {
+                Some(selector) => selector.to_lowercase(),
+                None => {
+                    diagnostics.push(Diagnostic::error(
+                        format!("{path}.selector"),
+                        format!("`{label}` has no selector"),
+                    ));
+                    continue;
+                }
+            };
+            if let Some(other) = seen.insert(selector.clone(), label) {
+                diagnostics.push(Diagnostic::error(
+                    format!("{path}.selector"),
+                    format!("`{label}` and `{other}` share the selector {selector}"),
+                ));
+            }
+        }
+    }
+}
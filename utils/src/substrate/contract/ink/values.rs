@@ -0,0 +1,338 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use contract_transcode::{Map, Tuple, Value};
+use scale_info::{form::PortableForm, Field, PortableRegistry, Type, TypeDef, TypeDefPrimitive};
+use serde_json::{json, Map as JsonMap, Value as JsonValue};
+use std::convert::TryFrom;
+
+use super::decode::DecodeOptions;
+use super::transcoder::{DisplayHooks, InkTranscoder};
+
+/// A standard collection type, which the transcoder handles as a plain composite.
+pub(crate) enum Collection {
+    /// A `BTreeMap`, encoded as a sequence of key and value tuples.
+    Map { key: u32, value: u32 },
+    /// A `BTreeSet`, encoded as a sequence of elements.
+    Set { elem: u32 },
+}
+
+impl Collection {
+    pub(crate) fn of(registry: &PortableRegistry, ty: &Type<PortableForm>) -> Option<Self> {
+        let name = ty.path.segments.last()?;
+        let field = match &ty.type_def {
+            TypeDef::Composite(composite) if composite.fields.len() == 1 => &composite.fields[0],
+            _ => return None,
+        };
+        let elem = match &registry.resolve(field.ty.id)?.type_def {
+            TypeDef::Sequence(sequence) => sequence.type_param.id,
+            _ => return None,
+        };
+        match name.as_str() {
+            "BTreeMap" => match &registry.resolve(elem)?.type_def {
+                TypeDef::Tuple(tuple) if tuple.fields.len() == 2 => Some(Collection::Map {
+                    key: tuple.fields[0].id,
+                    value: tuple.fields[1].id,
+                }),
+                _ => None,
+            },
+            "BTreeSet" => Some(Collection::Set { elem }),
+            _ => None,
+        }
+    }
+}
+
+/// Length of a byte string type: `None` for a `Vec<u8>`, `Some(n)` for a `[u8; n]`.
+pub(crate) fn byte_string_len(registry: &PortableRegistry, ty: u32) -> Option<Option<u32>> {
+    let (elem, len) = match &registry.resolve(ty)?.type_def {
+        TypeDef::Sequence(sequence) => (sequence.type_param.id, None),
+        TypeDef::Array(array) => (array.type_param.id, Some(array.len)),
+        _ => return None,
+    };
+    match registry.resolve(elem)?.type_def {
+        TypeDef::Primitive(TypeDefPrimitive::U8) => Some(len),
+        _ => None,
+    }
+}
+
+/// Renders bytes as a string if they are printable UTF-8 text.
+fn text(bytes: &[u8]) -> Option<String> {
+    let text = std::str::from_utf8(bytes).ok()?;
+    if text.chars().any(|c| c.is_control() && !c.is_whitespace()) {
+        return None;
+    }
+    Some(text.to_owned())
+}
+
+/// Reshapes decoded values along their type.
+///
+/// `BTreeMap`s become [`Value::Map`]s keyed by their decoded keys and `BTreeSet`s
+/// become sequences, rather than composites wrapping a sequence. ink! `Mapping`s
+/// hold no data in the values they are part of, their entries live in separate
+/// storage cells, so they are left as is. With
+/// [`DecodeOptions::bytes_as_strings`], byte strings holding UTF-8 text become
+/// strings. Display hooks apply last, to the reshaped value.
+pub(crate) struct Normalizer<'a> {
+    registry: &'a PortableRegistry,
+    options: &'a DecodeOptions,
+    display_hooks: &'a DisplayHooks,
+}
+
+impl<'a> Normalizer<'a> {
+    pub(crate) fn new(transcoder: &'a InkTranscoder, options: &'a DecodeOptions) -> Self {
+        Self {
+            registry: transcoder.metadata().registry(),
+            options,
+            display_hooks: transcoder.display_hooks(),
+        }
+    }
+
+    pub(crate) fn normalize(&self, ty: u32, value: Value) -> Value {
+        self.normalize_as(ty, None, value)
+    }
+
+    /// Normalizes a value whose type was written `type_name` in the contract source,
+    /// e.g. `Balance`, then applies the display hook registered for it or for its
+    /// type path.
+    pub(crate) fn normalize_as(&self, ty: u32, type_name: Option<&str>, value: Value) -> Value {
+        let value = self.reshape(ty, value);
+        let hook = type_name
+            .and_then(|type_name| self.display_hooks.get(type_name))
+            .or_else(|| {
+                let path = &self.registry.resolve(ty)?.path;
+                self.display_hooks
+                    .get(&path.segments.join("::"))
+                    .or_else(|| self.display_hooks.get(path.segments.last()?))
+            });
+        match hook {
+            Some(hook) => hook(&value).unwrap_or(value),
+            None => value,
+        }
+    }
+
+    fn reshape(&self, ty: u32, value: Value) -> Value {
+        if self.options.bytes_as_strings && byte_string_len(self.registry, ty).is_some() {
+            return self.bytes_to_string(value);
+        }
+
+        let resolved = match self.registry.resolve(ty) {
+            Some(resolved) => resolved,
+            None => return value,
+        };
+        if let Some(collection) = Collection::of(self.registry, resolved) {
+            return self.normalize_collection(collection, value);
+        }
+
+        match (&resolved.type_def, value) {
+            (TypeDef::Compact(compact), value) => self.normalize(compact.type_param.id, value),
+            (TypeDef::Composite(composite), value) => {
+                self.normalize_fields(&composite.fields, value)
+            }
+            (TypeDef::Variant(variant), value) => {
+                let ident = match &value {
+                    Value::Map(map) => map.ident(),
+                    Value::Tuple(tuple) => tuple.ident(),
+                    _ => None,
+                };
+                match variant
+                    .variants
+                    .iter()
+                    .find(|variant| Some(&variant.name) == ident.as_ref())
+                {
+                    Some(matched) => self.normalize_fields(&matched.fields, value),
+                    None => value,
+                }
+            }
+            (TypeDef::Sequence(sequence), Value::Seq(seq)) => {
+                self.normalize_elements(sequence.type_param.id, seq.elems())
+            }
+            (TypeDef::Array(array), Value::Seq(seq)) => {
+                self.normalize_elements(array.type_param.id, seq.elems())
+            }
+            (TypeDef::Tuple(tuple), Value::Tuple(values)) if values.ident().is_none() => {
+                let values = tuple
+                    .fields
+                    .iter()
+                    .zip(values.values())
+                    .map(|(field, value)| self.normalize(field.id, value.clone()))
+                    .collect();
+                Value::Tuple(Tuple::new(None, values))
+            }
+            (_, value) => value,
+        }
+    }
+
+    fn bytes_to_string(&self, value: Value) -> Value {
+        let rendered = match &value {
+            Value::Hex(hex) => text(hex.bytes()),
+            Value::Seq(seq) => seq
+                .elems()
+                .iter()
+                .map(|elem| match elem {
+                    Value::UInt(byte) => u8::try_from(*byte).ok(),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()
+                .and_then(|bytes| text(&bytes)),
+            _ => None,
+        };
+        rendered.map_or(value, Value::String)
+    }
+
+    fn normalize_collection(&self, collection: Collection, value: Value) -> Value {
+        let elems = match &value {
+            Value::Tuple(tuple) if tuple.values().count() == 1 => match tuple.values().next() {
+                Some(Value::Seq(seq)) => Some(seq.elems().to_vec()),
+                _ => None,
+            },
+            Value::Seq(seq) => Some(seq.elems().to_vec()),
+            _ => None,
+        };
+        let elems = match elems {
+            Some(elems) => elems,
+            None => return value,
+        };
+
+        match collection {
+            Collection::Map { key, value: val } => {
+                let mut entries = Vec::with_capacity(elems.len());
+                for elem in elems {
+                    let mut pair = match &elem {
+                        Value::Tuple(pair) if pair.values().count() == 2 => pair.values(),
+                        _ => return value,
+                    };
+                    let (k, v) = match (pair.next(), pair.next()) {
+                        (Some(k), Some(v)) => (k.clone(), v.clone()),
+                        _ => return value,
+                    };
+                    entries.push((self.normalize(key, k), self.normalize(val, v)));
+                }
+                Value::Map(Map::new(Some("BTreeMap"), entries.into_iter().collect()))
+            }
+            Collection::Set { elem } => self.normalize_elements(elem, &elems),
+        }
+    }
+
+    /// Normalizes the fields of a struct or event decoded as a map, looking up the
+    /// type of each field by name.
+    pub(crate) fn normalize_map(
+        &self,
+        value: Value,
+        field_type: impl Fn(&str) -> Option<(u32, Option<String>)>,
+    ) -> Value {
+        let map = match value {
+            Value::Map(map) => map,
+            value => return value,
+        };
+        let ident = map.ident();
+        let entries = map
+            .iter()
+            .map(|(key, field_value)| {
+                let ty = match key {
+                    Value::String(name) => field_type(name),
+                    _ => None,
+                };
+                let field_value = match ty {
+                    Some((ty, type_name)) => {
+                        self.normalize_as(ty, type_name.as_deref(), field_value.clone())
+                    }
+                    None => field_value.clone(),
+                };
+                (key.clone(), field_value)
+            })
+            .collect();
+        Value::Map(Map::new(ident.as_deref(), entries))
+    }
+
+    fn normalize_fields(&self, fields: &[Field<PortableForm>], value: Value) -> Value {
+        match value {
+            Value::Map(_) => self.normalize_map(value, |name| {
+                fields
+                    .iter()
+                    .find(|field| field.name.as_deref() == Some(name))
+                    .map(|field| (field.ty.id, field.type_name.clone()))
+            }),
+            Value::Tuple(tuple) if tuple.values().count() == fields.len() => {
+                let ident = tuple.ident();
+                let values = fields
+                    .iter()
+                    .zip(tuple.values())
+                    .map(|(field, value)| {
+                        self.normalize_as(field.ty.id, field.type_name.as_deref(), value.clone())
+                    })
+                    .collect();
+                Value::Tuple(Tuple::new(ident.as_deref(), values))
+            }
+            value => value,
+        }
+    }
+
+    fn normalize_elements(&self, ty: u32, elems: &[Value]) -> Value {
+        let elems: Vec<_> = elems
+            .iter()
+            .map(|elem| self.normalize(ty, elem.clone()))
+            .collect();
+        // `Seq` is not exported, but converts from the elements.
+        Value::Seq(elems.into())
+    }
+}
+
+/// Renders a decoded value without type information, enum variants with fields as
+/// `{variant: fields}`.
+pub fn json_value(value: &Value) -> JsonValue {
+    match value {
+        Value::Bool(value) => JsonValue::Bool(*value),
+        Value::UInt(value) => match i64::try_from(*value) {
+            Ok(value) => json!(value),
+            Err(_) => JsonValue::String(value.to_string()),
+        },
+        Value::Int(value) => match i64::try_from(*value) {
+            Ok(value) => json!(value),
+            Err(_) => JsonValue::String(value.to_string()),
+        },
+        Value::Char(value) => JsonValue::String(value.to_string()),
+        Value::String(value) | Value::Literal(value) => JsonValue::String(value.clone()),
+        Value::Hex(hex) => JsonValue::String(format!("0x{}", hex::encode(hex.bytes()))),
+        Value::Unit => JsonValue::Null,
+        Value::Seq(seq) => JsonValue::Array(seq.elems().iter().map(json_value).collect()),
+        Value::Map(map) => JsonValue::Object(
+            map.iter()
+                .map(|(key, value)| (key_string(key), json_value(value)))
+                .collect(),
+        ),
+        Value::Tuple(tuple) => {
+            let mut values = tuple.values().map(json_value).collect::<Vec<_>>();
+            let fields = match values.len() {
+                1 => values.remove(0),
+                _ => JsonValue::Array(values),
+            };
+            match tuple.ident() {
+                Some(variant) if tuple.values().count() == 0 => JsonValue::String(variant),
+                Some(variant) => {
+                    let mut object = JsonMap::new();
+                    object.insert(variant, fields);
+                    JsonValue::Object(object)
+                }
+                None => fields,
+            }
+        }
+    }
+}
+
+pub(crate) fn key_string(key: &Value) -> String {
+    match key {
+        Value::String(key) => key.clone(),
+        key => key.to_string(),
+    }
+}
@@ -0,0 +1,335 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use scale_info::{form::PortableForm, Field, PortableRegistry, TypeDef, TypeDefPrimitive};
+use serde::Serialize;
+use sp_core::crypto::{AccountId32, Ss58Codec};
+use sp_core::hashing::blake2_256;
+use std::convert::TryInto;
+
+use super::transcoder::InkTranscoder;
+
+const DEFAULT_CASES: usize = 8;
+/// Nesting beyond which a recursive type is given up on.
+const MAX_DEPTH: usize = 16;
+
+/// A message call in SCON, the syntax of the transcoder, and its SCALE encoding.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TestVector {
+    pub message: String,
+    pub args: Vec<String>,
+    /// Hex encoded call data, selector included.
+    pub encoded: String,
+}
+
+/// Generates test vectors of the messages of a contract, e.g. to check that another
+/// SDK encodes calls like this crate does.
+///
+/// Each message gets the same number of cases. The first ones pass edge values to
+/// every parameter: zero and empty values, then the minimums, then the maximums of
+/// integers. The others pass random values drawn from `seed`, so that the same
+/// metadata and seed always give the same vectors. Enums, options included, cycle
+/// through their variants across cases.
+pub struct VectorGenerator<'a> {
+    transcoder: &'a InkTranscoder,
+    cases: usize,
+    seed: u64,
+}
+
+impl<'a> VectorGenerator<'a> {
+    pub fn new(transcoder: &'a InkTranscoder) -> Self {
+        Self {
+            transcoder,
+            cases: DEFAULT_CASES,
+            seed: 0,
+        }
+    }
+
+    pub fn cases(mut self, cases: usize) -> Self {
+        self.cases = cases;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Vectors of every message, skipping those with parameters of types without
+    /// SCON syntax, such as bit sequences.
+    pub fn generate(&self) -> Result<Vec<TestVector>> {
+        let metadata = self.transcoder.metadata();
+        let mut vectors = Vec::new();
+        for message in metadata.spec().messages() {
+            let label = message.label();
+            let mut rng = SplitMix64::new(self.seed ^ label_seed(label));
+            for case in 0..self.cases {
+                let mut sampler = Sampler {
+                    registry: metadata.registry(),
+                    rng: &mut rng,
+                    case,
+                };
+                let args = message
+                    .args()
+                    .iter()
+                    .map(|arg| sampler.sample(arg.ty().ty().id, case, 0))
+                    .collect::<Result<Vec<_>>>();
+                let args = match args {
+                    Ok(args) => args,
+                    Err(err) => {
+                        tracing::warn!(message = %label, "No test vectors: {err:#}");
+                        break;
+                    }
+                };
+                let encoded = self
+                    .transcoder
+                    .encode(label, &args)
+                    .with_context(|| format!("Failed to encode {label}({})", args.join(", ")))?;
+                vectors.push(TestVector {
+                    message: label.to_owned(),
+                    args,
+                    encoded: format!("0x{}", hex::encode(encoded)),
+                });
+            }
+        }
+        Ok(vectors)
+    }
+}
+
+/// Draws the SCON value of a type for a case.
+struct Sampler<'a, 'r> {
+    registry: &'a PortableRegistry,
+    rng: &'r mut SplitMix64,
+    /// Case of the whole vector, deciding between edge and random values.
+    case: usize,
+}
+
+impl Sampler<'_, '_> {
+    /// A value of `ty`, where `variant` picks the variants of nested enums and the
+    /// lengths of nested sequences.
+    fn sample(&mut self, ty: u32, variant: usize, depth: usize) -> Result<String> {
+        if depth > MAX_DEPTH {
+            anyhow::bail!("type {ty} nests too deeply");
+        }
+        let registry = self.registry;
+        let resolved = registry
+            .resolve(ty)
+            .with_context(|| format!("Unknown type {ty}"))?;
+        match &resolved.type_def {
+            TypeDef::Compact(compact) => self.sample(compact.type_param.id, variant, depth + 1),
+            TypeDef::Primitive(primitive) => self.primitive(primitive),
+            TypeDef::Composite(composite) => {
+                if resolved.path.segments.last().map(String::as_str) == Some("AccountId") {
+                    let account = AccountId32::new(self.bytes(32).try_into().unwrap());
+                    return Ok(account.to_ss58check());
+                }
+                let name = resolved.path.ident().unwrap_or_default();
+                self.fields(&name, &composite.fields, variant, depth)
+            }
+            TypeDef::Variant(enumeration) => {
+                let variants = &enumeration.variants;
+                if variants.is_empty() {
+                    anyhow::bail!("enum {:?} has no variants", resolved.path.ident());
+                }
+                let picked = &variants[variant % variants.len()];
+                self.fields(
+                    &picked.name,
+                    &picked.fields,
+                    variant / variants.len(),
+                    depth,
+                )
+            }
+            TypeDef::Sequence(sequence) => {
+                let len = self.len(variant);
+                self.elements(sequence.type_param.id, len, variant, depth)
+            }
+            TypeDef::Array(array) => {
+                self.elements(array.type_param.id, array.len as usize, variant, depth)
+            }
+            TypeDef::Tuple(tuple) => {
+                let values = tuple
+                    .fields
+                    .iter()
+                    .enumerate()
+                    .map(|(i, field)| self.sample(field.id, variant + i, depth + 1))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(format!("({})", values.join(", ")))
+            }
+            TypeDef::BitSequence(_) => anyhow::bail!("bit sequences have no SCON syntax"),
+        }
+    }
+
+    fn primitive(&mut self, primitive: &TypeDefPrimitive) -> Result<String> {
+        let value = match primitive {
+            TypeDefPrimitive::Bool => match self.case {
+                0 => "false".to_owned(),
+                1 | 2 => "true".to_owned(),
+                _ => (self.rng.next() % 2 == 1).to_string(),
+            },
+            TypeDefPrimitive::Char => {
+                let c = match self.case {
+                    0 => 'a',
+                    1 => '0',
+                    2 => 'é',
+                    _ => self.alphanumeric(),
+                };
+                format!("'{c}'")
+            }
+            TypeDefPrimitive::Str => {
+                let text = match self.case {
+                    0 => String::new(),
+                    1 => "a".to_owned(),
+                    2 => "ink! ✓".to_owned(),
+                    _ => {
+                        let len = 1 + self.rng.next() % 16;
+                        (0..len).map(|_| self.alphanumeric()).collect()
+                    }
+                };
+                format!("\"{text}\"")
+            }
+            TypeDefPrimitive::U8 => self.unsigned(u8::MAX.into()),
+            TypeDefPrimitive::U16 => self.unsigned(u16::MAX.into()),
+            TypeDefPrimitive::U32 => self.unsigned(u32::MAX.into()),
+            TypeDefPrimitive::U64 => self.unsigned(u64::MAX.into()),
+            TypeDefPrimitive::U128 => self.unsigned(u128::MAX),
+            TypeDefPrimitive::I8 => self.signed(8),
+            TypeDefPrimitive::I16 => self.signed(16),
+            TypeDefPrimitive::I32 => self.signed(32),
+            TypeDefPrimitive::I64 => self.signed(64),
+            TypeDefPrimitive::I128 => self.signed(128),
+            TypeDefPrimitive::U256 | TypeDefPrimitive::I256 => {
+                anyhow::bail!("256-bit integers have no SCON syntax")
+            }
+        };
+        Ok(value)
+    }
+
+    fn unsigned(&mut self, max: u128) -> String {
+        let value = match self.case {
+            0 | 1 => 0,
+            2 => max,
+            _ => self.rng.next_u128() % max.saturating_add(1).max(1),
+        };
+        value.to_string()
+    }
+
+    fn signed(&mut self, bits: u32) -> String {
+        let max = (1u128 << (bits - 1)) - 1;
+        let min_magnitude = 1u128 << (bits - 1);
+        match self.case {
+            0 => "0".to_owned(),
+            1 => format!("-{min_magnitude}"),
+            2 => max.to_string(),
+            _ if self.rng.next() % 2 == 1 => {
+                format!("-{}", 1 + self.rng.next_u128() % min_magnitude)
+            }
+            _ => (self.rng.next_u128() % (max + 1)).to_string(),
+        }
+    }
+
+    /// Fields of a struct or enum variant named `name`.
+    fn fields(
+        &mut self,
+        name: &str,
+        fields: &[Field<PortableForm>],
+        variant: usize,
+        depth: usize,
+    ) -> Result<String> {
+        if fields.is_empty() {
+            return Ok(name.to_owned());
+        }
+        let mut values = Vec::with_capacity(fields.len());
+        for (i, field) in fields.iter().enumerate() {
+            let value = self.sample(field.ty.id, variant + i, depth + 1)?;
+            values.push(match &field.name {
+                Some(field_name) => format!("{field_name}: {value}"),
+                None => value,
+            });
+        }
+        let values = values.join(", ");
+        if fields.iter().all(|field| field.name.is_some()) {
+            Ok(format!("{name} {{ {values} }}"))
+        } else {
+            Ok(format!("{name}({values})"))
+        }
+    }
+
+    /// `len` elements of `ty`, hex encoded if they are bytes.
+    fn elements(&mut self, ty: u32, len: usize, variant: usize, depth: usize) -> Result<String> {
+        let is_byte = matches!(
+            self.registry.resolve(ty).map(|resolved| &resolved.type_def),
+            Some(TypeDef::Primitive(TypeDefPrimitive::U8))
+        );
+        if is_byte {
+            let bytes = match self.case {
+                0 | 1 => vec![0; len],
+                2 => vec![u8::MAX; len],
+                _ => self.bytes(len),
+            };
+            return Ok(format!("0x{}", hex::encode(bytes)));
+        }
+        let values = (0..len)
+            .map(|i| self.sample(ty, variant + i, depth + 1))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(format!("[{}]", values.join(", ")))
+    }
+
+    /// Length of a sequence: empty first, then growing with the cases.
+    fn len(&mut self, variant: usize) -> usize {
+        match self.case {
+            0 => 0,
+            1 | 2 => 1 + variant % 2,
+            _ => (self.rng.next() % 4) as usize,
+        }
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.rng.next() as u8).collect()
+    }
+
+    fn alphanumeric(&mut self) -> char {
+        const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+        CHARS[(self.rng.next() % CHARS.len() as u64) as usize] as char
+    }
+}
+
+/// Seed of a message, so that adding a message does not change the vectors of the
+/// others.
+fn label_seed(label: &str) -> u64 {
+    let hash = blake2_256(label.as_bytes());
+    u64::from_le_bytes(hash[..8].try_into().unwrap())
+}
+
+/// SplitMix64, a generator whose sequence is fixed by its seed, unlike those of
+/// `rand` which may change between releases.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn next_u128(&mut self) -> u128 {
+        (u128::from(self.next()) << 64) | u128::from(self.next())
+    }
+}
@@ -0,0 +1,243 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use contract_metadata::ContractMetadata;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::process::Command;
+
+use super::ContractArtifacts;
+
+/// Lines of the build output kept in errors.
+const MAX_LOG_LINES: usize = 20;
+/// Directory the project is mounted at in verifiable build images.
+const CONTAINER_PROJECT_DIR: &str = "/contract";
+
+/// How a build is reproduced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum BuildRunner {
+    /// In the verifiable build image recorded in the bundle, with Docker.
+    Container,
+    /// With the local `cargo contract`, pinned to the toolchain of the bundle.
+    Toolchain,
+}
+
+/// Build settings recorded in the `build_info` of a bundle.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BuildInfo {
+    pub rust_toolchain: Option<String>,
+    pub cargo_contract_version: Option<String>,
+    /// `Release` or `Debug`.
+    pub build_mode: Option<String>,
+    pub optimization_passes: Option<String>,
+    pub keep_debug_symbols: bool,
+    /// Verifiable build image, e.g. `paritytech/contracts-verifiable:3.0.1`.
+    pub image: Option<String>,
+}
+
+impl BuildInfo {
+    pub fn from_metadata(metadata: &ContractMetadata) -> Self {
+        let info = metadata.source.build_info.as_ref();
+        let field = |key: &str| {
+            info.and_then(|info| info.get(key))
+                .and_then(Value::as_str)
+                .map(str::to_owned)
+        };
+        let wasm_opt = info.and_then(|info| info.get("wasm_opt_settings"));
+        Self {
+            rust_toolchain: field("rust_toolchain"),
+            cargo_contract_version: field("cargo_contract_version"),
+            build_mode: field("build_mode"),
+            optimization_passes: wasm_opt
+                .and_then(|settings| settings.get("optimization_passes"))
+                .map(|passes| match passes {
+                    Value::String(passes) => passes.clone(),
+                    passes => passes.to_string(),
+                }),
+            keep_debug_symbols: wasm_opt
+                .and_then(|settings| settings.get("keep_debug_symbols"))
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+            // Metadata of cargo-contract 3 has no `image` field, the key is kept
+            // among the ABI entries.
+            image: metadata
+                .abi
+                .get("image")
+                .and_then(Value::as_str)
+                .map(str::to_owned),
+        }
+    }
+
+    fn is_release(&self) -> bool {
+        self.build_mode.as_deref() != Some("Debug")
+    }
+
+    /// Arguments of `cargo contract build` reproducing the recorded settings.
+    fn build_args(&self) -> Vec<String> {
+        let mut args = vec!["contract".to_owned(), "build".to_owned()];
+        if self.is_release() {
+            args.push("--release".to_owned());
+        }
+        if let Some(passes) = &self.optimization_passes {
+            args.push(format!("--optimization-passes={passes}"));
+        }
+        if self.keep_debug_symbols {
+            args.push("--keep-debug-symbols".to_owned());
+        }
+        args
+    }
+}
+
+/// Outcome of the verification of a bundle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BuildVerification {
+    pub verified: bool,
+    /// Code hash recorded in the bundle.
+    pub expected_hash: String,
+    /// Code hash of the rebuilt code.
+    pub built_hash: String,
+    pub runner: BuildRunner,
+    pub build_info: BuildInfo,
+}
+
+/// Checks that the code of a `.contract` bundle is built from the sources of a
+/// project, by rebuilding it as described in its `build_info` and comparing code
+/// hashes.
+///
+/// Bundles of verifiable builds are rebuilt in their image, others with the local
+/// `cargo contract`, which must be the version that built the bundle, on the
+/// recorded Rust toolchain through rustup.
+pub struct BuildVerifier {
+    bundle: PathBuf,
+    project: PathBuf,
+    runner: Option<BuildRunner>,
+}
+
+impl BuildVerifier {
+    /// Verifies `bundle` against the project in the directory `project`.
+    pub fn new(bundle: impl Into<PathBuf>, project: impl Into<PathBuf>) -> Self {
+        Self {
+            bundle: bundle.into(),
+            project: project.into(),
+            runner: None,
+        }
+    }
+
+    /// Forces how the build is reproduced, e.g. the toolchain of a CI image that
+    /// already matches the bundle.
+    pub fn runner(mut self, runner: BuildRunner) -> Self {
+        self.runner = Some(runner);
+        self
+    }
+
+    /// Rebuilds the project and compares its code hash with the bundle's.
+    ///
+    /// A mismatch is a verdict rather than an error, errors meaning the build could
+    /// not be reproduced at all.
+    pub fn verify(&self) -> Result<BuildVerification> {
+        let metadata = ContractMetadata::load(&self.bundle)
+            .with_context(|| format!("Failed to load bundle {}", self.bundle.display()))?;
+        let build_info = BuildInfo::from_metadata(&metadata);
+        let runner = match (self.runner, &build_info.image) {
+            (Some(runner), _) => runner,
+            (None, Some(_)) => BuildRunner::Container,
+            (None, None) => BuildRunner::Toolchain,
+        };
+
+        let mut command = match runner {
+            BuildRunner::Container => self.container_command(&build_info)?,
+            BuildRunner::Toolchain => self.toolchain_command(&build_info)?,
+        };
+        tracing::info!(command = ?command, "Reproducing contract build");
+        run(&mut command)?;
+
+        let manifest = self.project.join("Cargo.toml");
+        let built = ContractArtifacts::from_manifest_or_file(Some(&manifest), None)
+            .context("Failed to load the rebuilt artifacts")?;
+        let built_hash = built.code_hash()?;
+        let expected_hash = metadata.source.hash.0;
+        Ok(BuildVerification {
+            verified: built_hash == expected_hash,
+            expected_hash: format!("0x{}", hex::encode(expected_hash)),
+            built_hash: format!("0x{}", hex::encode(built_hash)),
+            runner,
+            build_info,
+        })
+    }
+
+    fn container_command(&self, build_info: &BuildInfo) -> Result<Command> {
+        let image = build_info
+            .image
+            .as_ref()
+            .context("The bundle was not built in a verifiable build image")?;
+        let project = self
+            .project
+            .canonicalize()
+            .with_context(|| format!("No project at {}", self.project.display()))?;
+        let mut command = Command::new("docker");
+        command
+            .args(["run", "--rm", "--mount"])
+            .arg(format!(
+                "type=bind,source={},target={CONTAINER_PROJECT_DIR}",
+                project.display()
+            ))
+            .args(["--workdir", CONTAINER_PROJECT_DIR, image.as_str(), "cargo"])
+            .args(build_info.build_args());
+        Ok(command)
+    }
+
+    fn toolchain_command(&self, build_info: &BuildInfo) -> Result<Command> {
+        if let Some(expected) = &build_info.cargo_contract_version {
+            let installed = cargo_contract_version()?;
+            if !installed.contains(expected.as_str()) {
+                anyhow::bail!(
+                    "The bundle was built with cargo-contract {expected}, but {installed} is \
+                    installed"
+                );
+            }
+        }
+        let mut command = Command::new("cargo");
+        if let Some(toolchain) = &build_info.rust_toolchain {
+            command.arg(format!("+{toolchain}"));
+        }
+        command
+            .args(build_info.build_args())
+            .arg("--manifest-path")
+            .arg(self.project.join("Cargo.toml"));
+        Ok(command)
+    }
+}
+
+fn cargo_contract_version() -> Result<String> {
+    let output = Command::new("cargo")
+        .args(["contract", "--version"])
+        .output()
+        .context("Failed to run cargo contract, is cargo-contract installed?")?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+fn run(command: &mut Command) -> Result<()> {
+    let output = command
+        .output()
+        .with_context(|| format!("Failed to run {command:?}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let lines: Vec<&str> = stderr.lines().collect();
+        let tail = lines[lines.len().saturating_sub(MAX_LOG_LINES)..].join("\n");
+        anyhow::bail!("Build failed with {}:\n{tail}", output.status);
+    }
+    Ok(())
+}
@@ -0,0 +1,34 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Measurements of a single contract query, reported to a [`MetricsHook`].
+#[derive(Debug, Clone)]
+pub struct QueryMetrics {
+    /// Label of the queried message.
+    pub message: String,
+    /// Time from encoding the arguments to decoding the return value.
+    pub latency: Duration,
+    /// Size of the encoded message, zero if the arguments could not be encoded.
+    pub request_bytes: usize,
+    /// Size of the encoded return value, zero if the query failed.
+    pub response_bytes: usize,
+    pub succeeded: bool,
+}
+
+/// Called after every query, e.g. to update Prometheus histograms and counters
+/// labelled by message.
+pub type MetricsHook = Arc<dyn Fn(&QueryMetrics) + Send + Sync>;
@@ -13,28 +13,301 @@
 // limitations under the License.
 
 pub mod builder;
-mod error;
+pub mod cache;
+pub mod cassette;
+pub mod compat;
+pub mod diff;
+pub mod error;
+pub mod info;
 pub mod ink;
+pub mod metrics;
 pub mod query;
+pub mod trace;
+pub mod transport;
 
 use self::{
+    cache::QueryCache,
+    cassette::{Cassette, CassetteTransport},
+    compat::CompatibilityReport,
+    diff::{DryRunDiff, DryRunOutcome},
     error::ErrorVariant,
-    ink::InkMeta,
-    query::{Query, QueryBuilder},
+    info::ContractInfo,
+    ink::{
+        abi::ContractAbi,
+        args::{ArgDiagnostic, ArgOptions},
+        decode::DecodeOptions,
+        registry::MetadataRegistry,
+        transcoder::DisplayHooks,
+        InkMeta,
+    },
+    metrics::{MetricsHook, QueryMetrics},
+    query::{CallOptions, CallResult, MessageOutcome, MutatingPolicy, Query, QueryBuilder},
+    trace::CallTrace,
+    transport::{RpcTransport, WsTransport},
 };
 
-use super::{Nonce, PairSigner};
-use anyhow::Result;
-use contract_transcode::{ContractMessageTranscoder, Value};
+#[cfg(feature = "light-client")]
+use self::transport::LightClientTransport;
+#[cfg(feature = "attestation")]
+use super::attestation::{self, VerificationPolicy};
+use super::{
+    address::Address,
+    chain::{Chain, ChainConfig},
+    cluster::{self, ClusterInfo},
+    extrinsic::{self, ContractEmitted, ExtrinsicOutcome, FeeEstimate},
+    phala::{self, CertificateCache, PayloadReader, PinkQuery, PinkResponseCache},
+    redact::{self, SensitiveArgs, MAX_LOGGED_LEN, REDACTED},
+    runtime,
+    signer::SignerProvider,
+    xcm::{XcmSender, XcmTransact},
+    AccountId, BlockHash, Client, DefaultConfig, Nonce,
+};
+use anyhow::{anyhow, Context, Result};
+use contract_transcode::Value;
+use futures::future::join_all;
+use sp_core::H256;
+use sp_weights::Weight;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use subxt::tx::DynamicPayload;
+use tracing::Instrument;
+
+/// Gas limit of Phala transactions when the call options do not set one.
+const DEFAULT_PINK_GAS_LIMIT: u64 = 1_000_000_000_000;
 
-pub struct ContractInstance {
-    pub signer: PairSigner,
+/// Handle to a deployed contract
+///
+/// Clones are cheap and share the signer, certificate cache and query cache, so a
+/// single instance can be cloned into concurrent tasks instead of being rebuilt for
+/// each of them.
+///
+/// Extrinsics are built with the subxt config `T` of the chain, see
+/// [`ContractInstance::with_config`].
+pub struct ContractInstance<T = DefaultConfig> {
+    pub signer: Arc<dyn SignerProvider>,
     meta: InkMeta,
+    /// Delegate certificates signing Phala queries on behalf of the signer.
+    certificates: Arc<CertificateCache>,
+    /// Checks the pRuntime worker must pass before each Phala query.
+    #[cfg(feature = "attestation")]
+    attestation: Option<VerificationPolicy>,
+    /// Checks applied to message arguments before encoding them.
+    arg_options: ArgOptions,
+    /// Limits applied to the data returned by queries.
+    decode_options: DecodeOptions,
+    /// Renders the values decoded from the contract.
+    display_hooks: DisplayHooks,
+    /// Carries the dry-runs of ink messages, a websocket to the node url by default.
+    transport: Arc<dyn RpcTransport>,
+    /// Records or replays Phala queries, ink queries going through the transport.
+    cassette: Option<Arc<Cassette>>,
+    /// Receives the measurements of every query.
+    metrics: Option<MetricsHook>,
+    /// Results reused by repeated queries.
+    cache: Option<Arc<QueryCache>>,
+    /// Responses reused by identical Phala queries.
+    pink_cache: Option<Arc<PinkResponseCache>>,
+    /// Arguments kept out of logs and errors.
+    sensitive_args: SensitiveArgs,
+    /// Handling of queries of messages mutating the contract state.
+    mutating_policy: MutatingPolicy,
+    config: PhantomData<fn() -> T>,
 }
 
+// Not derived, which would require the config to be `Clone`.
+impl<T> Clone for ContractInstance<T> {
+    fn clone(&self) -> Self {
+        Self {
+            signer: self.signer.clone(),
+            meta: self.meta.clone(),
+            certificates: self.certificates.clone(),
+            #[cfg(feature = "attestation")]
+            attestation: self.attestation.clone(),
+            arg_options: self.arg_options.clone(),
+            decode_options: self.decode_options.clone(),
+            display_hooks: self.display_hooks.clone(),
+            transport: self.transport.clone(),
+            cassette: self.cassette.clone(),
+            metrics: self.metrics.clone(),
+            cache: self.cache.clone(),
+            pink_cache: self.pink_cache.clone(),
+            sensitive_args: self.sensitive_args.clone(),
+            mutating_policy: self.mutating_policy,
+            config: PhantomData,
+        }
+    }
+}
+
+// Handles are cloned into concurrent tasks, e.g. by the query server.
+const _: fn() = || {
+    fn assert_shareable<T: Clone + Send + Sync + 'static>() {}
+    assert_shareable::<ContractInstance>();
+};
+
 impl ContractInstance {
-    pub fn new(meta: InkMeta, signer: PairSigner) -> Self {
-        Self { meta, signer }
+    pub fn new(meta: InkMeta, signer: Box<dyn SignerProvider>) -> Self {
+        Self {
+            transport: Arc::new(match &meta.endpoints {
+                Some(endpoints) => WsTransport::with_endpoints(endpoints.clone()),
+                None => WsTransport::new(meta.url.clone()),
+            }),
+            meta,
+            signer: Arc::from(signer),
+            certificates: Arc::new(CertificateCache::default()),
+            #[cfg(feature = "attestation")]
+            attestation: None,
+            arg_options: ArgOptions::default(),
+            decode_options: DecodeOptions::default(),
+            display_hooks: DisplayHooks::default(),
+            cassette: None,
+            metrics: None,
+            cache: None,
+            pink_cache: None,
+            sensitive_args: SensitiveArgs::default(),
+            mutating_policy: MutatingPolicy::default(),
+            config: PhantomData,
+        }
+    }
+}
+
+impl<T: ChainConfig> ContractInstance<T> {
+    /// Builds the extrinsics of the contract with the subxt config `U`, for chains
+    /// whose runtime types differ from [`DefaultConfig`]
+    pub fn with_config<U: ChainConfig>(self) -> ContractInstance<U> {
+        ContractInstance {
+            signer: self.signer,
+            meta: self.meta,
+            certificates: self.certificates,
+            #[cfg(feature = "attestation")]
+            attestation: self.attestation,
+            arg_options: self.arg_options,
+            decode_options: self.decode_options,
+            display_hooks: self.display_hooks,
+            transport: self.transport,
+            cassette: self.cassette,
+            metrics: self.metrics,
+            cache: self.cache,
+            pink_cache: self.pink_cache,
+            sensitive_args: self.sensitive_args,
+            mutating_policy: self.mutating_policy,
+            config: PhantomData,
+        }
+    }
+
+    /// Verifies the attestation of the pRuntime worker before sending it a query
+    #[cfg(feature = "attestation")]
+    pub fn with_attestation(mut self, policy: VerificationPolicy) -> Self {
+        self.attestation = Some(policy);
+        self
+    }
+
+    /// Sets how long the delegate certificate used for Phala queries stays valid
+    pub fn with_certificate_ttl(mut self, ttl: Duration) -> Self {
+        self.certificates = Arc::new(CertificateCache::new(ttl));
+        self
+    }
+
+    /// Sets how message arguments are checked before encoding them
+    pub fn with_arg_options(mut self, options: ArgOptions) -> Self {
+        self.arg_options = options;
+        self
+    }
+
+    /// Sets the limits applied to the data returned by queries
+    pub fn with_decode_options(mut self, options: DecodeOptions) -> Self {
+        self.decode_options = options;
+        self
+    }
+
+    /// Renders the decoded values of a type with `hook`, in query results and events,
+    /// see [`TranscoderBuilder::display_hook`](ink::transcoder::TranscoderBuilder::display_hook)
+    pub fn with_display_hook(
+        mut self,
+        type_name: impl Into<String>,
+        hook: impl Fn(&Value) -> Option<Value> + Send + Sync + 'static,
+    ) -> Self {
+        self.display_hooks.insert(type_name, hook);
+        self
+    }
+
+    /// Sends the dry-runs of ink messages through `transport`, e.g. a
+    /// [`transport::MockTransport`] in unit tests
+    pub fn with_transport(mut self, transport: Arc<dyn RpcTransport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Sends the dry-runs of ink messages through a light client of the chain
+    /// described by the JSON `chain_spec`, instead of the websocket of the node
+    #[cfg(feature = "light-client")]
+    pub fn with_light_client(self, chain_spec: &str) -> Result<Self> {
+        let transport = runtime::block_on(LightClientTransport::from_chain_spec(chain_spec))?;
+        Ok(self.with_transport(Arc::new(transport)))
+    }
+
+    /// Same as [`Self::with_light_client`] for a parachain, synced along the relay
+    /// chain described by the JSON `relay_chain_spec`
+    #[cfg(feature = "light-client")]
+    pub fn with_parachain_light_client(
+        self,
+        chain_spec: &str,
+        relay_chain_spec: &str,
+    ) -> Result<Self> {
+        let transport = runtime::block_on(LightClientTransport::from_parachain_spec(
+            chain_spec,
+            relay_chain_spec,
+        ))?;
+        Ok(self.with_transport(Arc::new(transport)))
+    }
+
+    /// Records the queries to the node and pRuntime, or replays them without network
+    /// access, depending on the mode of `cassette`
+    pub fn with_cassette(mut self, cassette: Cassette) -> Self {
+        let cassette = Arc::new(cassette);
+        self.transport = Arc::new(CassetteTransport::new(
+            cassette.clone(),
+            self.transport.clone(),
+        ));
+        self.cassette = Some(cassette);
+        self
+    }
+
+    /// Reports the latency, size and outcome of every query to `hook`
+    pub fn with_metrics(mut self, hook: impl Fn(&QueryMetrics) + Send + Sync + 'static) -> Self {
+        self.metrics = Some(Arc::new(hook));
+        self
+    }
+
+    /// Reuses the results of repeated queries, see [`QueryCache`]
+    ///
+    /// The cache is shared by the clones of this instance.
+    pub fn with_cache(mut self, cache: QueryCache) -> Self {
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Reuses the responses of identical Phala queries, so that side effects of the
+    /// contract, e.g. HTTP requests, are not repeated, see [`PinkResponseCache`]
+    ///
+    /// Unlike the [`QueryCache`], it applies to queries transferring value.
+    pub fn with_pink_cache(mut self, cache: PinkResponseCache) -> Self {
+        self.pink_cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Warns of, allows or rejects queries of messages mutating the contract state,
+    /// whose changes are never persisted.
+    pub fn with_mutating_policy(mut self, policy: MutatingPolicy) -> Self {
+        self.mutating_policy = policy;
+        self
+    }
+
+    /// Keeps the values of `args` out of query logs, error messages and serialized
+    /// errors.
+    pub fn with_sensitive_args(mut self, args: SensitiveArgs) -> Self {
+        self.sensitive_args = args;
+        self
     }
 
     /// Allows to call a substrate based ink smart contract
@@ -45,36 +318,888 @@ impl ContractInstance {
         args: Vec<String>,
         nonce: Option<Nonce>,
     ) -> Result<Value, ErrorVariant> {
-        let transcoder = self.get_transcoder()?;
-
-        let call_data = transcoder.encode(msg_name, &args)?;
-
-        let query = match (
-            self.meta.ink_contract_id.clone(),
-            self.meta.phala_contract_id,
-        ) {
-            (Some(ink_id), None) => Query::InkQuery(call_data, ink_id),
-            (None, Some(phala_id)) => {
-                let nonce = nonce.expect("Must provide nonce to call phala");
-                Query::PhalaQuery(call_data, phala_id, nonce)
+        self.call_msg_with(msg_name, args, nonce, CallOptions::default())
+    }
+
+    /// Same as [`ContractInstance::call_msg`], overriding the dry-run parameters
+    pub fn call_msg_with(
+        &self,
+        msg_name: &str,
+        args: Vec<String>,
+        nonce: Option<Nonce>,
+        options: CallOptions,
+    ) -> Result<Value, ErrorVariant> {
+        self.query_msg(msg_name, args, nonce, options)
+            .map(|result| result.value)
+    }
+
+    /// Same as [`ContractInstance::call_msg_with`], also returning the debug messages
+    /// printed by the contract
+    pub fn query_msg(
+        &self,
+        msg_name: &str,
+        args: Vec<String>,
+        nonce: Option<Nonce>,
+        options: CallOptions,
+    ) -> Result<CallResult, ErrorVariant> {
+        runtime::block_on(self.query_msg_async(msg_name, args, nonce, options))
+    }
+
+    /// Same as [`Self::query_msg`], to be awaited within an async runtime instead of
+    /// blocking on one.
+    pub async fn query_msg_async(
+        &self,
+        msg_name: &str,
+        args: Vec<String>,
+        nonce: Option<Nonce>,
+        options: CallOptions,
+    ) -> Result<CallResult, ErrorVariant> {
+        let span = tracing::info_span!("query_msg", message = msg_name);
+        let limits = options.clone();
+        limits
+            .bounded(self.instrumented_query(msg_name, args, nonce, options))
+            .instrument(span)
+            .await?
+    }
+
+    /// Sends the queries of `calls`, messages and their arguments, concurrently and
+    /// returns their results in the same order.
+    ///
+    /// Phala queries to a worker share its connections, so that many are in flight at
+    /// once, each with a fresh random nonce. The futures of
+    /// [`Self::query_msg_async`] can also be joined directly, e.g. with
+    /// `futures::future::join_all`.
+    pub fn query_many(
+        &self,
+        calls: Vec<(String, Vec<String>)>,
+        options: CallOptions,
+    ) -> Vec<Result<CallResult, ErrorVariant>> {
+        runtime::block_on(self.query_many_async(calls, options))
+    }
+
+    /// Same as [`Self::query_many`], to be awaited where blocking is not possible.
+    pub async fn query_many_async(
+        &self,
+        calls: Vec<(String, Vec<String>)>,
+        options: CallOptions,
+    ) -> Vec<Result<CallResult, ErrorVariant>> {
+        join_all(calls.into_iter().map(|(msg_name, args)| {
+            let options = options.clone();
+            async move {
+                let nonce = rand::random::<Nonce>();
+                self.query_msg_async(&msg_name, args, Some(nonce), options)
+                    .await
             }
+        }))
+        .await
+    }
+
+    async fn instrumented_query(
+        &self,
+        msg_name: &str,
+        args: Vec<String>,
+        nonce: Option<Nonce>,
+        options: CallOptions,
+    ) -> Result<CallResult, ErrorVariant> {
+        let started = Instant::now();
+        let mut request_bytes = 0;
+        let abi = if self.sensitive_args.is_empty() {
+            None
+        } else {
+            self.get_abi().ok()
+        };
+        let sensitive: Vec<String> = self
+            .sensitive_values(abi.as_ref(), msg_name, &args)
+            .into_iter()
+            .map(str::to_owned)
+            .collect();
+        let logged_args = args
+            .iter()
+            .map(|arg| {
+                if sensitive.contains(arg) {
+                    REDACTED.to_owned()
+                } else {
+                    redact::truncate(arg, MAX_LOGGED_LEN).into_owned()
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let sensitive: Vec<&str> = sensitive.iter().map(String::as_str).collect();
+        let result = self
+            .run_query(msg_name, args, nonce, options, &mut request_bytes)
+            .await
+            .map_err(|err| err.blind(&sensitive));
+
+        let outcome = match &result {
+            Ok(result) => redact::truncate(&result.value.to_string(), MAX_LOGGED_LEN).into_owned(),
+            Err(err) => redact::truncate(&err.to_string(), MAX_LOGGED_LEN).into_owned(),
+        };
+        tracing::debug!(
+            contract = %self.meta.address,
+            message = msg_name,
+            args = ?logged_args,
+            duration = ?started.elapsed(),
+            timings = ?result.as_ref().ok().map(|result| result.timings),
+            succeeded = result.is_ok(),
+            outcome = %outcome,
+            "Contract query"
+        );
+        if let Some(hook) = &self.metrics {
+            hook(&QueryMetrics {
+                message: msg_name.to_owned(),
+                latency: started.elapsed(),
+                request_bytes,
+                response_bytes: result.as_ref().map_or(0, |result| result.output_len),
+                succeeded: result.is_ok(),
+            });
+        }
+        result
+    }
+
+    async fn run_query(
+        &self,
+        msg_name: &str,
+        args: Vec<String>,
+        nonce: Option<Nonce>,
+        mut options: CallOptions,
+        request_bytes: &mut usize,
+    ) -> Result<CallResult, ErrorVariant> {
+        let cache = match &self.cache {
+            Some(cache) if options.is_cacheable() => cache,
             _ => {
-                return Err(ErrorVariant::from(
-                    "Contract Id Error: must provide only one contract address",
-                ))
+                return self
+                    .send_query(msg_name, args, nonce, options, request_bytes)
+                    .await
             }
         };
+        let key = (
+            self.meta.address.to_bytes(),
+            msg_name.to_owned(),
+            args.clone(),
+        );
+        if options.at.is_none() {
+            if let Some(result) = cache.recent(&key) {
+                return Ok(result.cached());
+            }
+        }
+
+        // Ink queries are pinned to a block, so that the cached result matches it.
+        let pinned = options.at.is_some();
+        let mut block = None;
+        if self.meta.address.as_phala().is_none() {
+            let at = match options.at {
+                Some(at) => at,
+                None => self.transport.best_block().await?,
+            };
+            if let Some(result) = cache.get(&key, at) {
+                return Ok(result.cached());
+            }
+            options.at = Some(at);
+            block = Some(at);
+        }
 
-        let contract_query = QueryBuilder::new(msg_name.to_string(), transcoder)
+        let result = self
+            .send_query(msg_name, args, nonce, options, request_bytes)
+            .await?;
+        cache.insert(key, block, pinned, result.clone());
+        Ok(result)
+    }
+
+    async fn send_query(
+        &self,
+        msg_name: &str,
+        args: Vec<String>,
+        nonce: Option<Nonce>,
+        options: CallOptions,
+        request_bytes: &mut usize,
+    ) -> Result<CallResult, ErrorVariant> {
+        let abi = self.get_abi()?;
+        if !options.allow_mutating && abi.mutates(msg_name, &args) == Some(true) {
+            self.check_mutating_query(msg_name)?;
+        }
+
+        let encoding = Instant::now();
+        let call_data =
+            tracing::debug_span!("encode").in_scope(|| self.encode_msg(&abi, msg_name, &args))?;
+        let encoded = encoding.elapsed();
+        *request_bytes = call_data.len();
+
+        let nonce = match &options.idempotency_key {
+            Some(key) => Some(phala::idempotency_nonce(key.as_bytes())),
+            None => nonce,
+        };
+        let query = Query::new(call_data, &self.meta.address, nonce)?;
+
+        #[cfg(feature = "attestation")]
+        if let (Query::Pink(..), Some(policy)) = (&query, &self.attestation) {
+            attestation::verify_worker(self.meta.url(), policy).await?;
+        }
+
+        let mut builder = QueryBuilder::new(msg_name.to_string(), abi)
             .query(query)
-            .build();
+            .options(options)
+            .decode_options(self.decode_options.clone())
+            .transport(self.transport.clone());
+        if let Some(cassette) = &self.cassette {
+            builder = builder.cassette(cassette.clone());
+        }
+        if let Some(cache) = &self.pink_cache {
+            builder = builder.pink_cache(cache.clone());
+        }
+        let contract_query = builder.build();
+
+        let mut result = contract_query
+            .call_async(
+                self.meta.url().to_owned(),
+                self.signer.as_ref(),
+                &self.certificates,
+            )
+            .await?;
+        result.timings.encode = encoded;
+        Ok(result)
+    }
+
+    /// Dry-runs a message of the configured ink or revive contract, tracing the calls
+    /// through which it reached other contracts
+    ///
+    /// See [`ContractInstance::trace_msg_with`], the messages of other contracts are
+    /// not decoded.
+    pub fn trace_msg(
+        &self,
+        msg_name: &str,
+        args: Vec<String>,
+        options: CallOptions,
+    ) -> Result<CallTrace, ErrorVariant> {
+        self.trace_msg_with(msg_name, args, options, &MetadataRegistry::new())
+    }
+
+    /// Dry-runs a message of the configured ink or revive contract, tracing the calls
+    /// through which it reached other contracts, and decodes the messages sent to the
+    /// contracts whose metadata is in `registry`
+    ///
+    /// Revive contracts are traced by pallet-revive, with the callee and input of
+    /// every call, see [`trace::CallFrame`]. Callees are linked in the registry by
+    /// the account pallet-revive maps their address to, see
+    /// [`MetadataRegistry::register_address`]. For ink contracts, calls are only
+    /// listed if the node logs host functions to the debug buffer, see
+    /// [`trace::HostCall`], and their callees are not known, see [`CallTrace`].
+    pub fn trace_msg_with(
+        &self,
+        msg_name: &str,
+        args: Vec<String>,
+        options: CallOptions,
+        registry: &MetadataRegistry,
+    ) -> Result<CallTrace, ErrorVariant> {
+        let abi = self.get_abi()?;
+        let transcoder = abi
+            .as_ink()
+            .context("Call tracing requires ink! metadata")?;
+        let call_data = self.encode_msg(&abi, msg_name, &args)?;
+        let message = transcoder.decode_contract_message(&mut &call_data[..])?;
+
+        let origin = options
+            .origin
+            .clone()
+            .unwrap_or_else(|| self.signer.account_id());
+        match &self.meta.address {
+            Address::Ink(dest) => {
+                let result = runtime::block_on(query::call_dry_run(
+                    self.transport.as_ref(),
+                    origin,
+                    &options,
+                    dest.clone(),
+                    call_data,
+                ))?;
+                let succeeded = matches!(&result.result, Ok(output) if !output.did_revert());
+
+                Ok(CallTrace::new(
+                    dest.clone(),
+                    message,
+                    &result.debug_message,
+                    succeeded,
+                ))
+            }
+            Address::Revive(dest) => {
+                let trace = runtime::block_on(trace::revive_trace_call(
+                    self.transport.as_ref(),
+                    &origin,
+                    &options,
+                    *dest,
+                    call_data,
+                ))?;
+                Ok(CallTrace::revive(
+                    *dest, message, trace, transcoder, registry,
+                ))
+            }
+            Address::Phala(_) => Err(ErrorVariant::from(
+                "Call tracing requires an ink or revive contract address",
+            )),
+        }
+    }
+
+    /// Dry-runs a message of the configured ink or revive contract, keeping the gas and storage
+    /// deposit it used
+    ///
+    /// Failed and reverted calls are outcomes rather than errors, so that they can
+    /// be compared.
+    pub fn dry_run(
+        &self,
+        msg_name: &str,
+        args: Vec<String>,
+        options: CallOptions,
+    ) -> Result<DryRunOutcome, ErrorVariant> {
+        let dest = &self.meta.address;
+        if dest.as_phala().is_some() {
+            return Err(ErrorVariant::from(
+                "Dry-runs require an ink or revive contract address",
+            ));
+        }
+        let abi = self.get_abi()?;
+        let call_data = self.encode_msg(&abi, msg_name, &args)?;
+        let origin = options
+            .origin
+            .clone()
+            .unwrap_or_else(|| self.signer.account_id());
+
+        runtime::block_on(options.bounded(async {
+            let result =
+                query::address_dry_run(self.transport.as_ref(), origin, &options, dest, call_data)
+                    .await?;
+            let (value, reverted) = match &result.result {
+                Ok(output) => {
+                    let value = abi
+                        .decode_return(msg_name, &output.data, &self.decode_options)
+                        .map_err(|err| err.to_string());
+                    (value, output.did_revert())
+                }
+                Err(err) => {
+                    let error = match self.transport.node_url() {
+                        Some(url) => {
+                            let metadata = Client::from_url(url).await?.metadata();
+                            ErrorVariant::from_dispatch_error(err, &metadata)?.to_string()
+                        }
+                        None => format!("DispatchError({err:?})"),
+                    };
+                    (Err(error), false)
+                }
+            };
+            Ok(DryRunOutcome {
+                value,
+                reverted,
+                gas_consumed: result.gas_consumed,
+                gas_required: result.gas_required,
+                storage_deposit: DryRunOutcome::storage_deposit_of(&result.storage_deposit),
+            })
+        }))?
+    }
+
+    /// Dry-runs a message on this contract and on `other`, e.g. the upgraded code of
+    /// the contract or the same contract on another node, and compares the outcomes
+    pub fn diff_dry_run(
+        &self,
+        other: &ContractInstance,
+        msg_name: &str,
+        args: Vec<String>,
+        options: CallOptions,
+    ) -> Result<DryRunDiff, ErrorVariant> {
+        let before = self.dry_run(msg_name, args.clone(), options.clone())?;
+        let after = other.dry_run(msg_name, args, options)?;
+        Ok(DryRunDiff {
+            message: msg_name.to_owned(),
+            before,
+            after,
+        })
+    }
+
+    /// Estimates the fee of sending a message to the configured ink contract
+    ///
+    /// The gas limit is set as in [`ContractInstance::call_payload`].
+    pub fn estimate_fee(
+        &self,
+        msg_name: &str,
+        args: Vec<String>,
+        options: CallOptions,
+    ) -> Result<FeeEstimate> {
+        runtime::block_on(async {
+            let call = self.call_payload_async(msg_name, &args, &options).await?;
+            extrinsic::estimate_fee::<T, _>(self.meta.url(), &call, self.signer.as_ref()).await
+        })
+    }
+
+    /// Builds the `Contracts::call` extrinsic sending a message to the configured ink
+    /// contract, e.g. to submit it in an [`extrinsic::batch_all`] or through a proxy
+    ///
+    /// The gas limit is taken from a dry-run, plus the margin of the chain's
+    /// [`WeightDefaults`](crate::substrate::WeightDefaults), unless set in the options.
+    pub fn call_payload(
+        &self,
+        msg_name: &str,
+        args: Vec<String>,
+        options: CallOptions,
+    ) -> Result<DynamicPayload> {
+        runtime::block_on(self.call_payload_async(msg_name, &args, &options))
+    }
+
+    /// Wraps the extrinsic of [`ContractInstance::call_payload`] in an XCM `Transact`,
+    /// for `sender` to call the contract on its parachain `para_id`, e.g. from a
+    /// governance chain
+    ///
+    /// The weight of the remote call is estimated by the node of the contract.
+    pub fn xcm_transact(
+        &self,
+        msg_name: &str,
+        args: Vec<String>,
+        options: CallOptions,
+        sender: XcmSender,
+        para_id: u32,
+    ) -> Result<XcmTransact> {
+        runtime::block_on(async {
+            let payload = self.call_payload_async(msg_name, &args, &options).await?;
+            XcmTransact::from_payload(sender, self.meta.url(), para_id, &payload)
+                .await?
+                .estimate_weight(self.meta.url())
+                .await
+        })
+    }
+
+    /// Encodes the call data of a message to the configured contract from its local
+    /// artifacts only, without network access
+    pub fn encode_call_data(&self, msg_name: &str, args: Vec<String>) -> Result<Vec<u8>> {
+        self.encode_msg(&self.get_abi()?, msg_name, &args)
+    }
+
+    /// Same as [`ContractInstance::call_payload`] without network access, e.g. to sign
+    /// it on an air-gapped machine with an [`OfflineChain`](crate::substrate::offline::OfflineChain)
+    ///
+    /// The gas limit cannot be taken from a dry-run and must be set in the options.
+    pub fn offline_call_payload(
+        &self,
+        msg_name: &str,
+        args: Vec<String>,
+        options: CallOptions,
+    ) -> Result<DynamicPayload> {
+        let gas_limit = options
+            .gas_limit
+            .extrinsic(&self.meta.chain.weight_defaults())
+            .context("Offline calls require a V2 gas limit on chains without a proof size bound")?;
+        let call_data = self.encode_call_data(msg_name, args)?;
+        self.call_extrinsic(&options, gas_limit, call_data)
+    }
+
+    /// `Contracts::call` or `Revive::call` of the configured contract
+    fn call_extrinsic(
+        &self,
+        options: &CallOptions,
+        gas_limit: Weight,
+        call_data: Vec<u8>,
+    ) -> Result<DynamicPayload> {
+        match &self.meta.address {
+            Address::Ink(dest) => Ok(extrinsic::contract_call(
+                dest,
+                options.value,
+                gas_limit,
+                options.storage_deposit_limit,
+                call_data,
+            )),
+            Address::Revive(dest) => Ok(extrinsic::revive_call(
+                dest,
+                options.value,
+                gas_limit,
+                options.storage_deposit_limit,
+                call_data,
+            )),
+            Address::Phala(_) => {
+                anyhow::bail!("Contract extrinsics require an ink or revive contract address")
+            }
+        }
+    }
+
+    async fn call_payload_async(
+        &self,
+        msg_name: &str,
+        args: &[String],
+        options: &CallOptions,
+    ) -> Result<DynamicPayload> {
+        if self.meta.address.as_phala().is_some() {
+            anyhow::bail!("Contract extrinsics require an ink or revive contract address");
+        }
+        let call_data = self.encode_msg(&self.get_abi()?, msg_name, args)?;
+
+        let defaults = self.meta.chain.weight_defaults();
+        let gas_limit = match options.gas_limit.extrinsic(&defaults) {
+            Some(gas_limit) => gas_limit,
+            None => {
+                let origin = self.signer.account_id();
+                let dry_run = query::address_dry_run(
+                    self.transport.as_ref(),
+                    origin,
+                    options,
+                    &self.meta.address,
+                    call_data.clone(),
+                )
+                .await?;
+                options
+                    .gas_limit
+                    .with_dry_run(&defaults, dry_run.gas_required)
+            }
+        };
+        self.call_extrinsic(options, gas_limit, call_data)
+    }
+
+    /// Sends a query to the sidevm of the configured Phala contract, reading its
+    /// response as a stream
+    pub fn sidevm_query(&self, payload: Vec<u8>, nonce: Nonce) -> Result<PayloadReader> {
+        let id = self
+            .meta
+            .address
+            .as_phala()
+            .context("Sidevm queries require a phala contract id")?;
+        let certificate = self.certificates.get_or_issue(self.signer.as_ref())?;
+        let reader = runtime::block_on(phala::pink_query_stream(
+            self.meta.url(),
+            *id,
+            PinkQuery::SidevmQuery(payload),
+            &certificate,
+            nonce,
+        ))??;
+        Ok(reader)
+    }
+
+    /// Sends a state-changing message to the configured contract as an extrinsic
+    /// submitted to the chain at `node_url`, and waits until it is finalized
+    ///
+    /// The gas limit of ink and revive contracts is taken from a dry-run, as for
+    /// [`ContractInstance::call_payload`], unless set in the options.
+    pub fn send_msg(
+        &self,
+        msg_name: &str,
+        args: Vec<String>,
+        node_url: &str,
+        options: CallOptions,
+    ) -> Result<ExtrinsicOutcome<T>, ErrorVariant> {
+        if options.origin.is_some() {
+            return Err(ErrorVariant::from(
+                "Transactions are always sent by the signer, origin cannot be overridden",
+            ));
+        }
+        let id = match &self.meta.address {
+            Address::Phala(id) => id,
+            Address::Ink(_) | Address::Revive(_) => {
+                return runtime::block_on(async {
+                    let call = self.call_payload_async(msg_name, &args, &options).await?;
+                    extrinsic::submit(node_url, &call, self.signer.as_ref()).await
+                });
+            }
+        };
+
+        let call_data = self.encode_msg(&self.get_abi()?, msg_name, &args)?;
+        let gas_limit = options
+            .gas_limit
+            .ref_time()
+            .unwrap_or(DEFAULT_PINK_GAS_LIMIT);
+
+        runtime::block_on(phala::pink_command_raw(
+            node_url,
+            *id,
+            call_data,
+            self.signer.as_ref(),
+            options.value,
+            gas_limit,
+            options.storage_deposit_limit,
+        ))
+    }
+
+    /// Queries `msg_name`, or sends it with [`ContractInstance::send_msg`] through the
+    /// node of the contract if it mutates the contract state
+    ///
+    /// Mutating messages are queried instead with [`CallOptions::allow_mutating`].
+    /// Phala contracts are sent through a node rather than their worker, so their
+    /// mutating messages must be sent with [`ContractInstance::send_msg`].
+    pub fn execute_msg(
+        &self,
+        msg_name: &str,
+        args: Vec<String>,
+        nonce: Option<Nonce>,
+        options: CallOptions,
+    ) -> Result<MessageOutcome<T>, ErrorVariant> {
+        if options.allow_mutating || !self.mutates(msg_name, &args)? {
+            return self
+                .query_msg(msg_name, args, nonce, options)
+                .map(MessageOutcome::Queried);
+        }
+        if self.meta.address.as_phala().is_some() {
+            return Err(ErrorVariant::from(anyhow!(
+                "{msg_name} mutates the contract state, send it through a node with `send_msg`"
+            )));
+        }
+        self.send_msg(msg_name, args, self.meta.url(), options)
+            .map(MessageOutcome::Sent)
+    }
+
+    /// Address of the contract
+    pub fn address(&self) -> &Address {
+        &self.meta.address
+    }
+
+    /// Url of the node the contract is queried through
+    pub fn url(&self) -> &str {
+        self.meta.url()
+    }
+
+    /// Chain the contract is deployed on, used to render and parse its addresses
+    pub fn chain(&self) -> Chain {
+        self.meta.chain
+    }
+
+    /// Checks that the node serves the runtime API and calls used for ink contracts
+    pub fn check_compatibility(&self) -> Result<CompatibilityReport> {
+        runtime::block_on(compat::check_compatibility(self.meta.url()))
+    }
+
+    /// Reads the on-chain details of the contract at `address`
+    pub fn get_contract_info(&self, address: &AccountId) -> Result<Option<ContractInfo>> {
+        runtime::block_on(info::get_contract_info(self.meta.url(), address))
+    }
+
+    /// Same as [`ContractInstance::get_contract_info`], on the state of block `at`
+    pub fn get_contract_info_at(
+        &self,
+        address: &AccountId,
+        at: BlockHash,
+    ) -> Result<Option<ContractInfo>> {
+        runtime::block_on(info::get_contract_info_at(
+            self.meta.url(),
+            address,
+            Some(at),
+        ))
+    }
+
+    /// Decodes the events emitted by the configured ink contract in block `at`, or
+    /// the latest block
+    pub fn events_at(&self, at: Option<BlockHash>) -> Result<Vec<Value>> {
+        let address = self
+            .meta
+            .address
+            .as_ink()
+            .context("Contract events require an ink contract address")?;
+        let decoder = self
+            .meta
+            .contract_artifacts()?
+            .event_decoder_with(&self.display_hooks)?
+            .with_options(self.decode_options.clone());
+
+        runtime::block_on(async {
+            let client = Client::from_url(self.meta.url()).await?;
+            let events = match at {
+                Some(at) => client.events().at(at).await?,
+                None => client.events().at_latest().await?,
+            };
+            let mut decoded = Vec::new();
+            for event in events.iter() {
+                if let Some(event) = ContractEmitted::from_event(&event?)? {
+                    if &event.contract == address {
+                        decoded.push(decoder.decode_contract_event(&event.topics, &event.data)?);
+                    }
+                }
+            }
+            Ok(decoded)
+        })
+    }
+
+    /// Decodes the events emitted by the configured ink contract among `events`, e.g.
+    /// the events of one call of an [`ExtrinsicOutcome::batch_contract_events`]
+    pub fn decode_events(&self, events: &[ContractEmitted]) -> Result<Vec<Value>> {
+        let address = self
+            .meta
+            .address
+            .as_ink()
+            .context("Contract events require an ink contract address")?;
+        let decoder = self
+            .meta
+            .contract_artifacts()?
+            .event_decoder_with(&self.display_hooks)?
+            .with_options(self.decode_options.clone());
+
+        events
+            .iter()
+            .filter(|event| &event.contract == address)
+            .map(|event| decoder.decode_contract_event(&event.topics, &event.data))
+            .collect()
+    }
+
+    /// Decodes the events among `events` emitted by the configured ink contract or by
+    /// a contract whose metadata is in `registry`, e.g. the contracts a call reached,
+    /// with the address of the contract emitting each of them
+    ///
+    /// Addresses not linked in the registry are looked up on-chain, and the events of
+    /// contracts without registered metadata are skipped.
+    pub fn decode_events_with(
+        &self,
+        events: &[ContractEmitted],
+        registry: &mut MetadataRegistry,
+    ) -> Result<Vec<(AccountId, Value)>> {
+        let address = self
+            .meta
+            .address
+            .as_ink()
+            .context("Contract events require an ink contract address")?;
+        let decoder = self
+            .meta
+            .contract_artifacts()?
+            .event_decoder_with(&self.display_hooks)?
+            .with_options(self.decode_options.clone());
+
+        let mut decoded = Vec::new();
+        for event in events {
+            let value = if &event.contract == address {
+                decoder.decode_contract_event(&event.topics, &event.data)?
+            } else if registry.resolve(self.meta.url(), &event.contract)? {
+                registry.decode_contract_event(&event.contract, &event.topics, &event.data)?
+            } else {
+                continue;
+            };
+            decoded.push((event.contract.clone(), value));
+        }
+        Ok(decoded)
+    }
+
+    /// Lists every contract instantiated from `code_hash`
+    pub fn find_contracts_by_code_hash(&self, code_hash: &H256) -> Result<Vec<AccountId>> {
+        runtime::block_on(info::find_contracts_by_code_hash(
+            self.meta.url(),
+            code_hash,
+        ))
+    }
+
+    /// Checks that the configured ink contract runs the code of the local artifacts
+    pub fn verify_code_hash(&self) -> Result<()> {
+        let address = self
+            .meta
+            .address
+            .as_ink()
+            .context("Code hash verification requires an ink contract address")?;
+        self.meta.contract_artifacts()?.verify_code_hash_with(
+            self.meta.url(),
+            address,
+            self.meta.hash_algorithm(),
+        )
+    }
+
+    /// Resolves the cluster of the configured Phala contract from the chain at
+    /// `node_url`, and checks that the configured pRuntime is one of its workers
+    pub fn check_phala_cluster(&self, node_url: &str) -> Result<ClusterInfo> {
+        let id = self
+            .meta
+            .address
+            .as_phala()
+            .context("Cluster lookup requires a phala contract id")?;
+        runtime::block_on(async {
+            let cluster = cluster::resolve_cluster(node_url, id).await?;
+            let worker = cluster::worker_public_key(self.meta.url()).await?;
+            if !cluster.has_worker(&worker) {
+                anyhow::bail!(
+                    "Worker 0x{} at {} does not serve cluster {:?} of Phala contract {id:?}",
+                    hex::encode(worker),
+                    self.meta.url(),
+                    cluster.id
+                );
+            }
+            Ok(cluster)
+        })
+    }
+
+    /// Whether `msg_name` called with `args` may mutate the contract state, in which
+    /// case querying it discards its changes. Unknown messages do not.
+    pub fn mutates(&self, msg_name: &str, args: &[String]) -> Result<bool, ErrorVariant> {
+        Ok(self.get_abi()?.mutates(msg_name, args).unwrap_or(false))
+    }
+
+    /// Checks the arguments of a call of `msg_name` without encoding or sending it,
+    /// an empty list meaning the arguments are valid.
+    ///
+    /// Sensitive arguments are blinded in the diagnostics, as in errors.
+    pub fn validate_args(
+        &self,
+        msg_name: &str,
+        args: &[String],
+    ) -> Result<Vec<ArgDiagnostic>, ErrorVariant> {
+        let abi = self.get_abi()?;
+        let sensitive = self.sensitive_values(Some(&abi), msg_name, args);
+        let mut diagnostics = abi.validate_args(msg_name, args, &self.arg_options);
+        if !sensitive.is_empty() {
+            for diagnostic in &mut diagnostics {
+                diagnostic.message = redact::blind(&diagnostic.message, &sensitive);
+            }
+        }
+        Ok(diagnostics)
+    }
+
+    /// Applies the [`MutatingPolicy`] to a query of the mutating message `msg_name`.
+    fn check_mutating_query(&self, msg_name: &str) -> Result<(), ErrorVariant> {
+        match self.mutating_policy {
+            MutatingPolicy::Allow => Ok(()),
+            MutatingPolicy::Warn => {
+                tracing::warn!(
+                    contract = %self.meta.address,
+                    message = msg_name,
+                    "Querying a message that mutates the contract state, its changes are \
+                     not persisted"
+                );
+                Ok(())
+            }
+            MutatingPolicy::Deny => Err(ErrorVariant::from(anyhow!(
+                "{msg_name} mutates the contract state, which a query does not \
+                     persist, send it with `send_msg` or `execute_msg`, or allow \
+                     mutating queries"
+            ))),
+        }
+    }
+
+    fn encode_msg(&self, abi: &ContractAbi, msg_name: &str, args: &[String]) -> Result<Vec<u8>> {
+        abi.encode(msg_name, args, &self.arg_options)
+            .map_err(|err| {
+                let sensitive = self.sensitive_values(Some(abi), msg_name, args);
+                if sensitive.is_empty() {
+                    err
+                } else {
+                    anyhow!(redact::blind(&format!("{err:?}"), &sensitive))
+                }
+            })
+    }
+
+    /// Values of the sensitive arguments among `args` of `msg_name`, all of them if
+    /// the parameters of the message cannot be read from `abi`.
+    fn sensitive_values<'a>(
+        &self,
+        abi: Option<&ContractAbi>,
+        msg_name: &str,
+        args: &'a [String],
+    ) -> Vec<&'a str> {
+        if self.sensitive_args.is_empty() {
+            return Vec::new();
+        }
+        let labels = abi.and_then(|abi| abi.arg_labels(msg_name));
+        self.sensitive_args
+            .mask(msg_name, labels.as_deref(), args.len())
+            .into_iter()
+            .zip(args)
+            .filter(|(sensitive, _)| *sensitive)
+            .map(|(_, arg)| arg.as_str())
+            .collect()
+    }
 
-        contract_query.call(self.meta.url.clone(), &self.signer)
+    pub(crate) fn get_abi(&self) -> Result<ContractAbi> {
+        self.meta
+            .contract_artifacts()?
+            .contract_abi_with(&self.display_hooks)
     }
 
-    fn get_transcoder(&self) -> Result<ContractMessageTranscoder> {
+    #[cfg(any(feature = "graphql", feature = "postgres"))]
+    pub(crate) fn get_transcoder(&self) -> Result<ink::transcoder::InkTranscoder> {
         let artifacts = self.meta.contract_artifacts()?;
-        let transcoder = artifacts.contract_transcoder()?;
-        Ok(transcoder)
+        artifacts
+            .transcoder_builder()?
+            .display_hooks(&self.display_hooks)
+            .build()
     }
 }
@@ -12,105 +12,285 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::substrate::{phala, Balance, Client, ContractId, DefaultConfig, Nonce, PairSigner};
+use crate::substrate::{
+    address::{self, Address},
+    cancel::{self, CancellationToken},
+    extrinsic::ExtrinsicOutcome,
+    phala::{self, CertificateCache, DelegateCertificate, PinkResponseCache},
+    signer::SignerProvider,
+    weight::WeightLimit,
+    AccountId, Balance, BlockHash, Client, ContractId, DefaultConfig, Nonce,
+};
 use anyhow::{anyhow, Context, Result};
-use contract_transcode::ContractMessageTranscoder;
 use contract_transcode::Value;
 use jsonrpsee::core::client::ClientT;
 use jsonrpsee::rpc_params;
 use jsonrpsee::ws_client::WsClientBuilder;
-use pallet_contracts_primitives::ContractExecResult;
+use pallet_contracts_primitives::{ExecReturnValue, StorageDeposit};
 use scale::{Decode, Encode};
-use sp_core::Bytes;
+use sp_core::{Bytes, H160};
+use sp_runtime::DispatchError;
 use sp_weights::Weight;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use subxt::Config;
+use tracing::Instrument;
 
+use super::cassette::Cassette;
 use super::error::ErrorVariant;
+use super::ink::abi::ContractAbi;
+use super::ink::decode::DecodeOptions;
+use super::transport::{RpcTransport, WsTransport};
 
-pub struct ContractQuery {
-    msg_name: String,
-    transcoder: ContractMessageTranscoder,
-    query: Query,
+/// Per-call overrides of the dry-run parameters.
+#[derive(Debug, Clone, Default)]
+pub struct CallOptions {
+    /// Caller of the message, the signer's account if not set.
+    ///
+    /// Only ink queries can impersonate another caller: Phala queries are always
+    /// made by the account signing the query certificate.
+    pub origin: Option<AccountId>,
+    /// Balance transferred to the contract, for payable messages.
+    ///
+    /// See [`crate::substrate::balance::TokenMetadata::parse`] to read it from user input.
+    pub value: Balance,
+    /// Maximum weight the call may consume.
+    pub gas_limit: WeightLimit,
+    /// Maximum storage deposit the dry-run may charge, unbounded if not set.
+    pub storage_deposit_limit: Option<Balance>,
+    /// Block whose state the dry-run reads, the best block if not set.
+    ///
+    /// Nodes only keep the state of recent blocks unless run as archive nodes.
+    pub at: Option<BlockHash>,
+    /// Identifies a logical Phala query across retries, its nonce being derived
+    /// from the key instead of the one passed to the call, see
+    /// [`phala::idempotency_nonce`]. Ignored by ink dry-runs, which have no effects.
+    pub idempotency_key: Option<String>,
+    /// Time the whole call, connecting and retries included, may take before it is
+    /// aborted, unbounded if not set.
+    pub timeout: Option<Duration>,
+    /// Aborts the call once cancelled, e.g. when its result is no longer needed.
+    pub cancellation: Option<CancellationToken>,
+    /// Queries the message even if it mutates the contract state, whatever the
+    /// [`MutatingPolicy`] of the contract.
+    pub allow_mutating: bool,
 }
 
-impl ContractQuery {
-    pub fn call(&self, url: String, signer: &PairSigner) -> Result<Value, ErrorVariant> {
-        self.query
-            .query(url, signer, &self.transcoder, self.msg_name.as_str())
+impl CallOptions {
+    pub fn origin(mut self, origin: AccountId) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    pub fn value(mut self, value: Balance) -> Self {
+        self.value = value;
+        self
+    }
+
+    pub fn gas_limit(mut self, gas_limit: impl Into<WeightLimit>) -> Self {
+        self.gas_limit = gas_limit.into();
+        self
+    }
+
+    pub fn storage_deposit_limit(mut self, storage_deposit_limit: Balance) -> Self {
+        self.storage_deposit_limit = Some(storage_deposit_limit);
+        self
+    }
+
+    pub fn at(mut self, block: BlockHash) -> Self {
+        self.at = Some(block);
+        self
+    }
+
+    pub fn idempotency_key(mut self, key: impl Into<String>) -> Self {
+        self.idempotency_key = Some(key.into());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn cancel_on(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    pub fn allow_mutating(mut self) -> Self {
+        self.allow_mutating = true;
+        self
+    }
+
+    /// Runs `future` within the timeout of the call, unless it is cancelled first.
+    pub(crate) async fn bounded<F: Future>(&self, future: F) -> Result<F::Output> {
+        cancel::run_until(future, self.cancellation.as_ref(), self.timeout)
+            .await
+            .map_err(anyhow::Error::from)
+    }
+
+    /// Whether any option only applicable to ink dry-runs is set.
+    fn overrides_dry_run(&self) -> bool {
+        self.origin.is_some()
+            || self.gas_limit != WeightLimit::Unlimited
+            || self.storage_deposit_limit.is_some()
+    }
+
+    /// Whether results of these options can be shared with plain queries.
+    pub(crate) fn is_cacheable(&self) -> bool {
+        !self.overrides_dry_run() && self.value == 0
     }
 }
 
-pub struct QueryBuilder {
-    msg_name: String,
-    transcoder: ContractMessageTranscoder,
-    query: Option<Query>,
+/// How queries of messages mutating the contract state are handled.
+///
+/// Queries are dry-runs, so the changes of such messages are discarded although the
+/// query succeeds. Dry-runs made on purpose, e.g. by
+/// [`ContractInstance::dry_run`](super::ContractInstance::dry_run) to estimate gas,
+/// are not affected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MutatingPolicy {
+    /// Logs a warning and queries the message.
+    #[default]
+    Warn,
+    /// Queries the message silently.
+    Allow,
+    /// Fails the query, unless [`CallOptions::allow_mutating`] is set.
+    Deny,
+}
+
+/// Outcome of a contract query.
+#[derive(Debug, Clone)]
+pub struct CallResult {
+    /// Decoded return value of the message.
+    pub value: Value,
+    /// Output of `ink::env::debug_println!` during the dry-run, empty if the contract
+    /// printed nothing or was built without debug output.
+    pub debug_message: String,
+    /// Size of the SCALE encoded return value, in bytes.
+    pub output_len: usize,
+    /// Time spent in each phase of the query, all zero for cached results.
+    pub timings: CallTimings,
 }
 
-impl QueryBuilder {
-    pub fn new(msg_name: String, transcoder: ContractMessageTranscoder) -> Self {
+impl CallResult {
+    fn new(value: Value, output_len: usize, debug_message: &[u8], timings: CallTimings) -> Self {
         Self {
-            msg_name,
-            transcoder,
-            query: None,
+            value,
+            output_len,
+            debug_message: String::from_utf8_lossy(debug_message).into_owned(),
+            timings,
         }
     }
 
-    pub fn query(mut self, query: Query) -> Self {
-        self.query = Some(query);
+    /// The result as returned from a cache, without the timings of the original query.
+    pub(crate) fn cached(mut self) -> Self {
+        self.timings = CallTimings::default();
         self
     }
+}
 
-    pub fn build(self) -> ContractQuery {
-        ContractQuery {
-            msg_name: self.msg_name,
-            transcoder: self.transcoder,
-            query: self.query.expect("Query is not set"),
-        }
+/// Outcome of [`ContractInstance::execute_msg`](crate::substrate::ContractInstance::execute_msg).
+pub enum MessageOutcome<T: Config = DefaultConfig> {
+    /// The message does not mutate the contract state and was queried.
+    Queried(CallResult),
+    /// The message mutates the contract state and was sent as an extrinsic.
+    Sent(ExtrinsicOutcome<T>),
+}
+
+/// Time spent in the phases of a query, to tell a slow node or Phala worker from
+/// slow local transcoding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CallTimings {
+    /// Encoding the message and its arguments.
+    pub encode: Duration,
+    /// Connecting to the pRuntime of the Phala worker and reading its key. Ink
+    /// dry-runs connect through their transport, as part of `rpc`.
+    pub connect: Duration,
+    /// Waiting for the node or the Phala worker to run the query.
+    pub rpc: Duration,
+    /// Encrypting the query to the Phala worker and decrypting its response, zero
+    /// for ink dry-runs.
+    pub decrypt: Duration,
+    /// Decoding the return value.
+    pub decode: Duration,
+}
+
+impl CallTimings {
+    pub fn total(&self) -> Duration {
+        self.encode + self.connect + self.rpc + self.decrypt + self.decode
     }
 }
 
-#[derive(Debug, Clone)]
-pub enum Query {
-    InkQuery(Vec<u8>, <DefaultConfig as Config>::AccountId),
-    PhalaQuery(Vec<u8>, ContractId, Nonce),
+pub struct ContractQuery {
+    msg_name: String,
+    abi: ContractAbi,
+    query: Query,
+    options: CallOptions,
+    decode_options: DecodeOptions,
+    transport: Option<Arc<dyn RpcTransport>>,
+    cassette: Option<Arc<Cassette>>,
+    pink_cache: Option<Arc<PinkResponseCache>>,
 }
 
-impl Query {
-    pub fn query(
+impl ContractQuery {
+    pub async fn call_async(
         &self,
         url: String,
-        signer: &PairSigner,
-        transcoder: &ContractMessageTranscoder,
-        msg_name: &str,
-    ) -> Result<Value, ErrorVariant> {
-        match self {
-            Query::InkQuery(message, id) => async_std::task::block_on(self.ink_query(
-                url,
-                signer,
-                transcoder,
-                msg_name,
-                id.clone(),
-                message.clone(),
-            )),
+        signer: &dyn SignerProvider,
+        certificates: &CertificateCache,
+    ) -> Result<CallResult, ErrorVariant> {
+        let transport = match &self.transport {
+            Some(transport) => transport.clone(),
+            None => Arc::new(WsTransport::new(url.clone())),
+        };
+        match &self.query {
+            Query::Ink(message, id) => {
+                let origin = self
+                    .options
+                    .origin
+                    .clone()
+                    .unwrap_or_else(|| signer.account_id());
 
-            Query::PhalaQuery(message, id, nonce) => {
-                let value = async_std::task::block_on(self.pink_query(
-                    url,
-                    signer,
-                    transcoder,
-                    msg_name,
-                    id.clone(),
+                self.ink_query(
+                    transport.as_ref(),
+                    origin,
+                    Address::Ink(id.clone()),
                     message.clone(),
-                    nonce.clone(),
-                ));
-
-                match value {
-                    Ok(res) => Ok(res),
-                    Err(err) => {
-                        let error = ErrorVariant::from(err);
-                        Err(error)
-                    }
-                }
+                )
+                .await
+            }
+
+            Query::Revive(message, address) => {
+                let origin = self
+                    .options
+                    .origin
+                    .clone()
+                    .unwrap_or_else(|| signer.account_id());
+
+                self.ink_query(
+                    transport.as_ref(),
+                    origin,
+                    Address::Revive(*address),
+                    message.clone(),
+                )
+                .await
+            }
+
+            Query::Pink(..) if self.options.overrides_dry_run() => Err(ErrorVariant::from(
+                "Origin and limit overrides are not supported for Phala queries",
+            )),
+
+            Query::Pink(..) if self.options.at.is_some() => Err(ErrorVariant::from(
+                "Phala queries always read the latest state of the worker",
+            )),
+
+            Query::Pink(message, id, nonce) => {
+                let certificate = certificates.get_or_issue(signer)?;
+                self.pink_query(url, &certificate, *id, message.clone(), *nonce)
+                    .await
+                    .map_err(ErrorVariant::from)
             }
         }
     }
@@ -118,87 +298,334 @@ impl Query {
     async fn pink_query(
         &self,
         url: String,
-        signer: &PairSigner,
-        transcoder: &ContractMessageTranscoder,
-        msg_name: &str,
+        certificate: &DelegateCertificate,
         id: ContractId,
         message: Vec<u8>,
         nonce: Nonce,
-    ) -> Result<Value> {
-        let payload = phala::pink_query_raw(&url, id, message, signer.signer(), nonce).await??;
+    ) -> Result<CallResult> {
+        let value = self.options.value;
+        let mut timings = CallTimings::default();
+        let live = phala::pink_query_timed(
+            &url,
+            id,
+            message.clone(),
+            certificate,
+            nonce,
+            value,
+            &mut timings,
+        )
+        .instrument(tracing::debug_span!("rpc", contract = %id));
+        let sent = async {
+            match self.cassette.as_deref() {
+                Some(cassette) => cassette.pink_query(id, &message, value, live).await,
+                None => live.await,
+            }
+        };
+        let payload = match self.pink_cache.as_deref() {
+            Some(cache) => cache.get_or_query(id, &message, value, sent).await??,
+            None => sent.await??,
+        };
 
-        let ref output =
-            pallet_contracts_primitives::ContractExecResult::<u128>::decode(&mut &payload[..])?
-                .result
-                .map_err(|err| anyhow::anyhow!("DispatchError({err:?})"))?;
+        let result = decode_exec_result(&payload)?;
+        let output = &result
+            .result
+            .map_err(|err| anyhow::anyhow!("DispatchError({err:?})"))?;
 
         if output.did_revert() {
             return Err(anyhow!("Contract execution reverted"));
         }
 
-        let value = transcoder
-            .decode_return(msg_name, &mut &output.data[..])
-            .context(format!("Failed to decode return value {:?}", &output))?;
+        let decoding = Instant::now();
+        let value = tracing::debug_span!("decode", bytes = output.data.len())
+            .in_scope(|| self.decode_return(&output.data))
+            .context(format!("Failed to decode return value {:?}", output))?;
+        timings.decode = decoding.elapsed();
 
-        Ok(value)
+        Ok(CallResult::new(
+            value,
+            output.data.len(),
+            &result.debug_message,
+            timings,
+        ))
     }
 
     async fn ink_query(
         &self,
-        url: String,
-        signer: &PairSigner,
-        transcoder: &ContractMessageTranscoder,
-        msg_name: &str,
-        id: <DefaultConfig as Config>::AccountId,
+        transport: &dyn RpcTransport,
+        origin: AccountId,
+        dest: Address,
         message: Vec<u8>,
-    ) -> Result<Value, ErrorVariant> {
-        let client = Client::from_url(url.clone()).await?;
-
-        let result = self.call_dry_run(url, signer, id, message).await?;
+    ) -> Result<CallResult, ErrorVariant> {
+        let mut timings = CallTimings::default();
+        let sending = Instant::now();
+        let result = address_dry_run(transport, origin, &self.options, &dest, message)
+            .instrument(tracing::debug_span!("rpc"))
+            .await?;
+        timings.rpc = sending.elapsed();
 
         match result.result {
             Ok(ref ret_val) => {
-                let value = transcoder
-                    .decode_return(msg_name, &mut &ret_val.data[..])
-                    .context(format!("Failed to decode return value {:?}", &ret_val))?;
+                let decoding = Instant::now();
+                let value = tracing::debug_span!("decode", bytes = ret_val.data.len())
+                    .in_scope(|| self.decode_return(&ret_val.data))
+                    .context(format!("Failed to decode return value {:?}", ret_val))?;
+                timings.decode = decoding.elapsed();
 
-                Ok(value)
-            }
-            Err(ref err) => {
-                let metadata = client.metadata();
-                let error = ErrorVariant::from_dispatch_error(err, &metadata)?;
-                Err(error)
+                Ok(CallResult::new(
+                    value,
+                    ret_val.data.len(),
+                    &result.debug_message,
+                    timings,
+                ))
             }
+            Err(ref err) => match transport.node_url() {
+                Some(url) => {
+                    let metadata = Client::from_url(url).await?.metadata();
+                    let error = ErrorVariant::from_dispatch_error(err, &metadata)?;
+                    Err(error)
+                }
+                None => Err(ErrorVariant::from(anyhow!("DispatchError({err:?})"))),
+            },
         }
     }
 
-    async fn call_dry_run(
-        &self,
-        url: String,
-        signer: &PairSigner,
-        dest: <DefaultConfig as Config>::AccountId,
-        input_data: Vec<u8>,
-    ) -> Result<ContractExecResult<Balance>> {
-        let call_request = CallRequest {
-            origin: signer.account_id().clone(),
-            dest,
-            value: 0,
-            gas_limit: None,
-            storage_deposit_limit: None,
-            input_data,
-        };
-        self.state_call(url.as_str(), "ContractsApi_call", call_request)
-            .await
+    fn decode_return(&self, data: &[u8]) -> Result<Value> {
+        self.abi
+            .decode_return(&self.msg_name, data, &self.decode_options)
+    }
+}
+
+/// Builder state before the query is set.
+pub struct NoQuery;
+
+/// Builds a [`ContractQuery`], which can only be built once its query is set.
+pub struct QueryBuilder<Q = NoQuery> {
+    msg_name: String,
+    abi: ContractAbi,
+    query: Q,
+    options: CallOptions,
+    decode_options: DecodeOptions,
+    transport: Option<Arc<dyn RpcTransport>>,
+    cassette: Option<Arc<Cassette>>,
+    pink_cache: Option<Arc<PinkResponseCache>>,
+}
+
+impl QueryBuilder<NoQuery> {
+    /// Builds a query of `msg_name`, decoded with `abi`, e.g. a
+    /// [`ContractMessageTranscoder`](contract_transcode::ContractMessageTranscoder).
+    pub fn new(msg_name: String, abi: impl Into<ContractAbi>) -> Self {
+        Self {
+            msg_name,
+            abi: abi.into(),
+            query: NoQuery,
+            options: CallOptions::default(),
+            decode_options: DecodeOptions::default(),
+            transport: None,
+            cassette: None,
+            pink_cache: None,
+        }
+    }
+}
+
+impl<Q> QueryBuilder<Q> {
+    pub fn query(self, query: Query) -> QueryBuilder<Query> {
+        QueryBuilder {
+            msg_name: self.msg_name,
+            abi: self.abi,
+            query,
+            options: self.options,
+            decode_options: self.decode_options,
+            transport: self.transport,
+            cassette: self.cassette,
+            pink_cache: self.pink_cache,
+        }
+    }
+
+    pub fn options(mut self, options: CallOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn decode_options(mut self, decode_options: DecodeOptions) -> Self {
+        self.decode_options = decode_options;
+        self
+    }
+
+    /// Sends ink queries through `transport` instead of a websocket to the node url.
+    pub fn transport(mut self, transport: Arc<dyn RpcTransport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Records or replays Phala queries with `cassette`.
+    ///
+    /// Ink queries are recorded by their transport, see [`super::cassette::CassetteTransport`].
+    pub fn cassette(mut self, cassette: Arc<Cassette>) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    /// Reuses the responses of identical Phala queries, see [`PinkResponseCache`].
+    pub fn pink_cache(mut self, cache: Arc<PinkResponseCache>) -> Self {
+        self.pink_cache = Some(cache);
+        self
+    }
+}
+
+impl QueryBuilder<Query> {
+    pub fn build(self) -> ContractQuery {
+        ContractQuery {
+            msg_name: self.msg_name,
+            abi: self.abi,
+            query: self.query,
+            options: self.options,
+            decode_options: self.decode_options,
+            transport: self.transport,
+            cassette: self.cassette,
+            pink_cache: self.pink_cache,
+        }
     }
+}
+
+#[derive(Debug, Clone)]
+pub enum Query {
+    Ink(Vec<u8>, <DefaultConfig as Config>::AccountId),
+    Pink(Vec<u8>, ContractId, Nonce),
+    /// Dry-run of a pallet-revive contract, through the `ReviveApi` runtime API.
+    Revive(Vec<u8>, H160),
+}
 
-    async fn state_call<A: Encode, R: Decode>(&self, url: &str, func: &str, args: A) -> Result<R> {
-        let client = WsClientBuilder::default().build(&url).await?;
-        let params = rpc_params![func, Bytes(args.encode())];
-        let bytes: Bytes = client.request("state_call", params).await?;
-        Ok(R::decode(&mut bytes.as_ref())?)
+impl Query {
+    /// Builds the query matching the kind of contract at `address`.
+    ///
+    /// Phala queries must provide a nonce.
+    pub fn new(message: Vec<u8>, address: &Address, nonce: Option<Nonce>) -> Result<Self> {
+        match address {
+            Address::Ink(id) => Ok(Query::Ink(message, id.clone())),
+            Address::Phala(id) => {
+                let nonce = nonce.context("Must provide nonce to call phala")?;
+                Ok(Query::Pink(message, *id, nonce))
+            }
+            Address::Revive(address) => Ok(Query::Revive(message, *address)),
+        }
     }
 }
 
+/// Result of a contract dry-run.
+///
+/// Runtimes append the events of the call since `pallet-contracts-primitives` 24,
+/// without bumping the `ContractsApi` version, and older ones end the result after
+/// `result`. The events are not decoded, see [`decode_exec_result`].
+pub type ContractExecResult<Balance> = pallet_contracts_primitives::ContractExecResult<Balance, ()>;
+
+/// Decodes the result of `ContractsApi_call`, skipping the events trailing it.
+pub(crate) fn decode_exec_result(bytes: &[u8]) -> Result<ContractExecResult<Balance>> {
+    let input = &mut &bytes[..];
+    Ok(ContractExecResult {
+        gas_consumed: Decode::decode(input)?,
+        gas_required: Decode::decode(input)?,
+        storage_deposit: Decode::decode(input)?,
+        debug_message: Decode::decode(input)?,
+        result: Decode::decode(input)?,
+        events: None,
+    })
+}
+
+/// Dry-runs a contract call through the `ContractsApi` runtime API.
+pub(crate) async fn call_dry_run(
+    transport: &dyn RpcTransport,
+    origin: AccountId,
+    options: &CallOptions,
+    dest: <DefaultConfig as Config>::AccountId,
+    input_data: Vec<u8>,
+) -> Result<ContractExecResult<Balance>> {
+    let call_request = CallRequest {
+        origin,
+        dest,
+        value: options.value,
+        gas_limit: options.gas_limit.dry_run(),
+        storage_deposit_limit: options.storage_deposit_limit,
+        input_data,
+    };
+    let bytes = transport
+        .state_call("ContractsApi_call", call_request.encode(), options.at)
+        .await
+        .with_context(|| match options.at {
+            Some(at) => format!(
+                "Dry-run at block {at:?} failed, reading pruned state requires an archive node"
+            ),
+            None => "Dry-run failed".to_owned(),
+        })?;
+    decode_exec_result(&bytes)
+}
+
+/// Dry-runs a call of the ink or revive contract at `dest`.
+pub(crate) async fn address_dry_run(
+    transport: &dyn RpcTransport,
+    origin: AccountId,
+    options: &CallOptions,
+    dest: &Address,
+    input_data: Vec<u8>,
+) -> Result<ContractExecResult<Balance>> {
+    match dest {
+        Address::Ink(account) => {
+            call_dry_run(transport, origin, options, account.clone(), input_data).await
+        }
+        Address::Revive(address) => {
+            revive_dry_run(transport, origin, options, *address, input_data).await
+        }
+        Address::Phala(_) => anyhow::bail!("Phala contracts cannot be dry-run on chain"),
+    }
+}
+
+/// Dry-runs a contract call through the `ReviveApi` runtime API of pallet-revive.
+///
+/// The origin must be mapped to an Ethereum-style address, see
+/// [`address::revive_address`]. The result has no debug message, which
+/// pallet-revive no longer collects.
+pub(crate) async fn revive_dry_run(
+    transport: &dyn RpcTransport,
+    origin: AccountId,
+    options: &CallOptions,
+    dest: H160,
+    input_data: Vec<u8>,
+) -> Result<ContractExecResult<Balance>> {
+    let call_request = ReviveCallRequest {
+        origin,
+        dest,
+        value: options.value,
+        gas_limit: options.gas_limit.dry_run(),
+        storage_deposit_limit: options.storage_deposit_limit,
+        input_data,
+    };
+    let bytes = transport
+        .state_call("ReviveApi_call", call_request.encode(), options.at)
+        .await
+        .with_context(|| {
+            format!(
+                "Dry-run of revive contract {dest:?} failed, is the origin mapped to {:?}?",
+                address::revive_address(&call_request.origin)
+            )
+        })?;
+    let result = ReviveExecResult::decode(&mut bytes.as_slice())?;
+    Ok(ContractExecResult {
+        gas_consumed: result.gas_consumed,
+        gas_required: result.gas_required,
+        storage_deposit: result.storage_deposit,
+        debug_message: Vec::new(),
+        result: result.result,
+        events: None,
+    })
+}
+
+/// Calls a runtime API function with SCALE encoded arguments.
+pub(crate) async fn state_call<A: Encode, R: Decode>(url: &str, func: &str, args: A) -> Result<R> {
+    let client = WsClientBuilder::default().build(&url).await?;
+    let params = rpc_params![func, Bytes(args.encode())];
+    let bytes: Bytes = client.request("state_call", params).await?;
+    Ok(R::decode(&mut bytes.as_ref())?)
+}
+
 /// A struct that encodes RPC parameters required for a call to a smart contract.
 ///
 /// Copied from `pallet-contracts-rpc-runtime-api`.
@@ -211,3 +638,24 @@ pub struct CallRequest {
     storage_deposit_limit: Option<Balance>,
     input_data: Vec<u8>,
 }
+
+/// Arguments of `ReviveApi_call`, as [`CallRequest`] with an Ethereum-style
+/// destination.
+#[derive(Encode)]
+struct ReviveCallRequest {
+    origin: AccountId,
+    dest: H160,
+    value: Balance,
+    gas_limit: Option<Weight>,
+    storage_deposit_limit: Option<Balance>,
+    input_data: Vec<u8>,
+}
+
+/// Result of `ReviveApi_call`, as `ContractExecResult` without its debug message.
+#[derive(Decode)]
+struct ReviveExecResult {
+    gas_consumed: Weight,
+    gas_required: Weight,
+    storage_deposit: StorageDeposit<Balance>,
+    result: Result<ExecReturnValue, DispatchError>,
+}
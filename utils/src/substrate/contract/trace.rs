@@ -0,0 +1,329 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Context, Result};
+use contract_transcode::Value;
+use scale::{Decode, Encode};
+use sp_core::{H160, H256, U256};
+
+use super::ink::{registry::MetadataRegistry, transcoder::InkTranscoder};
+use super::query::CallOptions;
+use super::transport::RpcTransport;
+use crate::substrate::address::{self, Address};
+use crate::substrate::AccountId;
+
+/// Host functions through which a contract calls or creates another contract.
+const CONTRACT_CALLS: [&str; 3] = ["call", "delegate_call", "instantiate"];
+
+/// A host function called by a contract, as logged in the debug buffer.
+///
+/// pallet-contracts logs every host function call to the debug buffer when the node
+/// runs with `-lruntime::contracts::strace=trace`, one line per call:
+/// `seal1::call(flags: 0, callee_ptr: 65536, ..) = Ok(Success)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostCall {
+    /// Host function module, e.g. `seal1`.
+    pub module: String,
+    pub name: String,
+    /// Arguments as logged, pointers into the contract memory for buffers.
+    pub args: Vec<(String, String)>,
+    /// Return value as logged, e.g. `Ok(Success)` or `Ok(CalleeReverted)`.
+    pub result: String,
+}
+
+impl HostCall {
+    /// Whether the call transfers control to another contract.
+    pub fn is_contract_call(&self) -> bool {
+        CONTRACT_CALLS.contains(&self.name.as_str())
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.result == "Ok(Success)"
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let (call, result) = line.rsplit_once(") = ")?;
+        let (function, args) = call.split_once('(')?;
+        let (module, name) = function.split_once("::")?;
+        if module.is_empty() || name.is_empty() || module.contains(char::is_whitespace) {
+            return None;
+        }
+        let args = args
+            .split(", ")
+            .filter(|arg| !arg.is_empty())
+            .filter_map(|arg| arg.split_once(": "))
+            .map(|(name, value)| (name.to_owned(), value.to_owned()))
+            .collect();
+
+        Some(Self {
+            module: module.to_owned(),
+            name: name.to_owned(),
+            args,
+            result: result.to_owned(),
+        })
+    }
+}
+
+/// Kind of a [`CallFrame`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum CallKind {
+    Call,
+    /// A call that cannot modify state.
+    StaticCall,
+    /// A call running the code of the callee on the storage of the caller.
+    DelegateCall,
+}
+
+/// A call of a pallet-revive contract, with the calls it made nested under it.
+#[derive(Debug, Clone)]
+pub struct CallFrame {
+    pub kind: CallKind,
+    pub from: H160,
+    /// Callee, or the contract whose code runs for delegate calls.
+    pub to: H160,
+    pub input: Vec<u8>,
+    pub output: Vec<u8>,
+    /// Message called, decoded with the metadata of the callee, or `None` if the
+    /// callee has no registered metadata or the input is none of its messages.
+    pub message: Option<Value>,
+    pub gas_used: U256,
+    /// Why the call failed, e.g. `execution reverted`.
+    pub error: Option<String>,
+    pub revert_reason: Option<String>,
+    pub calls: Vec<CallFrame>,
+}
+
+impl CallFrame {
+    fn decode_messages(
+        trace: RawCallTrace,
+        contract: &H160,
+        transcoder: &InkTranscoder,
+        registry: &MetadataRegistry,
+    ) -> Self {
+        let callee = if &trace.to == contract {
+            Some(transcoder)
+        } else {
+            registry.transcoder(&address::revive_account_id(&trace.to))
+        };
+        let message = callee.and_then(|transcoder| {
+            transcoder
+                .decode_contract_message(&mut &trace.input[..])
+                .ok()
+        });
+        let calls = trace
+            .calls
+            .into_iter()
+            .map(|call| Self::decode_messages(call, contract, transcoder, registry))
+            .collect();
+
+        Self {
+            kind: trace.call_type,
+            from: trace.from,
+            to: trace.to,
+            input: trace.input,
+            output: trace.output,
+            message,
+            gas_used: trace.gas_used,
+            error: trace.error,
+            revert_reason: trace.revert_reason,
+            calls,
+        }
+    }
+
+    /// Whether the call and every call it made succeeded.
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none() && self.calls.iter().all(CallFrame::succeeded)
+    }
+}
+
+/// Calls through which a dry-run reached other contracts.
+///
+/// pallet-revive traces the calls of revive contracts with their callees and
+/// inputs, see [`CallTrace::root`]. For ink contracts of pallet-contracts, only the
+/// host calls in the debug buffer are known: it logs pointers into the contract
+/// memory rather than their contents, so their callees and inputs are unknown, and
+/// the calls are listed in execution order without nesting them under their caller.
+#[derive(Debug, Clone)]
+pub struct CallTrace {
+    /// Contract the dry-run called.
+    pub contract: Address,
+    /// Message called, decoded with the contract metadata.
+    pub message: Value,
+    /// Calls and instantiations of other contracts made by ink contracts, in
+    /// execution order.
+    pub calls: Vec<HostCall>,
+    /// Call of a revive contract, with the calls it made.
+    pub root: Option<CallFrame>,
+    /// Debug buffer lines that are not host calls, e.g. `debug_println!` output.
+    pub debug_output: Vec<String>,
+    /// Whether the dry-run succeeded without reverting.
+    pub succeeded: bool,
+}
+
+impl CallTrace {
+    pub(crate) fn new(
+        contract: AccountId,
+        message: Value,
+        debug_message: &[u8],
+        succeeded: bool,
+    ) -> Self {
+        let debug_message = String::from_utf8_lossy(debug_message);
+        let mut calls = Vec::new();
+        let mut debug_output = Vec::new();
+        for line in debug_message.lines() {
+            match HostCall::parse(line) {
+                Some(call) if call.is_contract_call() => calls.push(call),
+                Some(_) => {}
+                None => debug_output.push(line.to_owned()),
+            }
+        }
+
+        Self {
+            contract: Address::Ink(contract),
+            message,
+            calls,
+            root: None,
+            debug_output,
+            succeeded,
+        }
+    }
+
+    /// Traces a call of the revive contract at `contract`, decoding the messages of
+    /// callees with the metadata linked to their account in `registry`, see
+    /// [`address::revive_account_id`].
+    pub(crate) fn revive(
+        contract: H160,
+        message: Value,
+        trace: RawCallTrace,
+        transcoder: &InkTranscoder,
+        registry: &MetadataRegistry,
+    ) -> Self {
+        let root = CallFrame::decode_messages(trace, &contract, transcoder, registry);
+        Self {
+            contract: Address::Revive(contract),
+            message,
+            calls: Vec::new(),
+            succeeded: root.error.is_none(),
+            root: Some(root),
+            debug_output: Vec::new(),
+        }
+    }
+}
+
+/// Dry-runs a call of the revive contract at `dest` with the call tracer of
+/// pallet-revive, through the `ReviveApi_trace_call` runtime API.
+///
+/// The call transfers no value: values of Ethereum transactions are in the
+/// Ethereum denomination, whose ratio to the native balance the runtime defines.
+pub(crate) async fn revive_trace_call(
+    transport: &dyn RpcTransport,
+    origin: &AccountId,
+    options: &CallOptions,
+    dest: H160,
+    input: Vec<u8>,
+) -> Result<RawCallTrace> {
+    if options.value != 0 {
+        anyhow::bail!("Tracing revive calls transferring a value is not supported");
+    }
+    let tx = GenericTransaction {
+        from: Some(address::revive_address(origin)),
+        input: InputOrData {
+            input: Some(input),
+            data: None,
+        },
+        to: Some(dest),
+        ..GenericTransaction::default()
+    };
+    let config = TracerType::CallTracer(Some(CallTracerConfig {
+        with_logs: false,
+        only_top_call: false,
+    }));
+    let bytes = transport
+        .state_call("ReviveApi_trace_call", (tx, config).encode(), options.at)
+        .await
+        .with_context(|| format!("Tracing a call of revive contract {dest:?} failed"))?;
+    match Result::<Trace, EthTransactError>::decode(&mut bytes.as_slice())? {
+        Ok(Trace::Call(trace)) => Ok(trace),
+        Err(EthTransactError::Data(data)) => {
+            Err(anyhow!("Traced call reverted with 0x{}", hex::encode(data)))
+        }
+        Err(EthTransactError::Message(message)) => Err(anyhow!("Traced call failed: {message}")),
+    }
+}
+
+/// `GenericTransaction` of pallet-revive, the Ethereum transaction it dry-runs.
+#[derive(Default, Encode)]
+struct GenericTransaction {
+    access_list: Option<Vec<(H160, Vec<H256>)>>,
+    blob_versioned_hashes: Vec<H256>,
+    blobs: Vec<Vec<u8>>,
+    chain_id: Option<U256>,
+    from: Option<H160>,
+    gas: Option<U256>,
+    gas_price: Option<U256>,
+    input: InputOrData,
+    max_fee_per_blob_gas: Option<U256>,
+    max_fee_per_gas: Option<U256>,
+    max_priority_fee_per_gas: Option<U256>,
+    nonce: Option<U256>,
+    to: Option<H160>,
+    r#type: Option<u8>,
+    value: Option<U256>,
+}
+
+#[derive(Default, Encode)]
+struct InputOrData {
+    input: Option<Vec<u8>>,
+    data: Option<Vec<u8>>,
+}
+
+#[derive(Encode)]
+enum TracerType {
+    CallTracer(Option<CallTracerConfig>),
+}
+
+#[derive(Encode)]
+struct CallTracerConfig {
+    with_logs: bool,
+    only_top_call: bool,
+}
+
+#[derive(Decode)]
+enum Trace {
+    Call(RawCallTrace),
+}
+
+#[derive(Decode)]
+enum EthTransactError {
+    /// Revert data of the call.
+    Data(Vec<u8>),
+    Message(String),
+}
+
+/// `CallTrace` of pallet-revive.
+#[derive(Decode)]
+pub(crate) struct RawCallTrace {
+    from: H160,
+    _gas: U256,
+    gas_used: U256,
+    to: H160,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    error: Option<String>,
+    revert_reason: Option<String>,
+    calls: Vec<RawCallTrace>,
+    _logs: Vec<(H160, Vec<H256>, Vec<u8>, u32)>,
+    _value: Option<U256>,
+    call_type: CallKind,
+}
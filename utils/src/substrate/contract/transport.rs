@@ -0,0 +1,486 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[cfg(feature = "light-client")]
+use anyhow::Context;
+use anyhow::{anyhow, Result};
+use jsonrpsee::core::client::ClientT;
+use jsonrpsee::core::params::ArrayParams;
+#[cfg(feature = "light-client")]
+use jsonrpsee::core::traits::ToRpcParams;
+use jsonrpsee::rpc_params;
+use jsonrpsee::ws_client::WsClientBuilder;
+use scale::Encode;
+use serde::de::DeserializeOwned;
+use sp_core::{Bytes, H256};
+use std::collections::VecDeque;
+use std::future::Future;
+#[cfg(feature = "light-client")]
+use std::num::NonZeroU32;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "light-client")]
+use subxt_lightclient::smoldot::{
+    AddChainConfig, AddChainConfigJsonRpc, ChainId, Client as SmoldotClient, DefaultPlatform,
+    JsonRpcResponses,
+};
+#[cfg(feature = "light-client")]
+use subxt_lightclient::{AddedChain, LightClientRpc};
+
+use super::query::ContractExecResult;
+use crate::substrate::endpoints::Endpoints;
+#[cfg(feature = "light-client")]
+use crate::substrate::runtime;
+use crate::substrate::{rate_limit, Balance};
+
+/// Future returned by [`RpcTransport`] calls.
+pub type TransportFuture<'a, T = Vec<u8>> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// Carries the runtime API calls of ink queries to a node.
+///
+/// Phala queries are sent to the pRuntime worker and do not go through the transport.
+pub trait RpcTransport: Send + Sync {
+    /// Calls the runtime API function `func` with SCALE encoded `args` on the state of
+    /// block `at`, or the best block, returning its SCALE encoded result.
+    fn state_call<'a>(
+        &'a self,
+        func: &'a str,
+        args: Vec<u8>,
+        at: Option<H256>,
+    ) -> TransportFuture<'a>;
+
+    /// Hash of the best block.
+    fn best_block(&self) -> TransportFuture<'_, H256>;
+
+    /// Url of the node whose runtime metadata decodes dispatch errors, if any.
+    fn node_url(&self) -> Option<&str>;
+}
+
+/// Sends runtime API calls to a node over a websocket.
+#[derive(Debug, Clone)]
+pub struct WsTransport {
+    url: String,
+    /// Endpoints failed over to, `url` being unused if set.
+    endpoints: Option<Arc<Endpoints>>,
+}
+
+impl WsTransport {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            endpoints: None,
+        }
+    }
+
+    /// Sends the calls to the active endpoint of `endpoints`, retrying a failed call
+    /// once on the endpoint it fails over to.
+    pub fn with_endpoints(endpoints: Arc<Endpoints>) -> Self {
+        Self {
+            url: endpoints.urls()[0].clone(),
+            endpoints: Some(endpoints),
+        }
+    }
+
+    fn url(&self) -> &str {
+        match &self.endpoints {
+            Some(endpoints) => endpoints.active(),
+            None => &self.url,
+        }
+    }
+
+    async fn request<R: DeserializeOwned>(&self, method: &str, params: ArrayParams) -> Result<R> {
+        let url = self.url();
+        match self.request_to(url, method, params.clone()).await {
+            Err(err) => match &self.endpoints {
+                Some(endpoints) if endpoints.report_failure(url, &format!("{err:#}")) => {
+                    self.request_to(endpoints.active(), method, params).await
+                }
+                _ => Err(err),
+            },
+            response => response,
+        }
+    }
+
+    async fn request_to<R: DeserializeOwned>(
+        &self,
+        url: &str,
+        method: &str,
+        params: ArrayParams,
+    ) -> Result<R> {
+        let _permit = rate_limit::acquire(url).await;
+        let mut builder = WsClientBuilder::default();
+        if let Some(endpoints) = &self.endpoints {
+            // Stalled endpoints are only detected if requests time out.
+            builder = builder
+                .connection_timeout(endpoints.request_timeout())
+                .request_timeout(endpoints.request_timeout());
+        }
+        let client = builder.build(url).await?;
+        Ok(client.request(method, params).await?)
+    }
+}
+
+impl RpcTransport for WsTransport {
+    fn state_call<'a>(
+        &'a self,
+        func: &'a str,
+        args: Vec<u8>,
+        at: Option<H256>,
+    ) -> TransportFuture<'a> {
+        Box::pin(async move {
+            let bytes: Bytes = self
+                .request("state_call", rpc_params![func, Bytes(args), at])
+                .await?;
+            Ok(bytes.0)
+        })
+    }
+
+    fn best_block(&self) -> TransportFuture<'_, H256> {
+        Box::pin(async move { self.request("chain_getBlockHash", rpc_params![]).await })
+    }
+
+    fn node_url(&self) -> Option<&str> {
+        Some(self.url())
+    }
+}
+
+/// Sends runtime API calls through an embedded smoldot light client, which verifies
+/// the state it reads against the chain's consensus instead of trusting an RPC
+/// provider.
+///
+/// Dispatch errors are not decoded with the runtime metadata, as there is no node
+/// url to fetch it from.
+#[cfg(feature = "light-client")]
+#[derive(Clone)]
+pub struct LightClientTransport {
+    rpc: LightClientRpc,
+}
+
+#[cfg(feature = "light-client")]
+impl LightClientTransport {
+    /// Syncs the chain described by the JSON `chain_spec`, e.g. the spec of a relay
+    /// chain exported with `build-spec`, from its boot nodes.
+    ///
+    /// A parachain only syncs along its relay chain, see [`Self::from_parachain_spec`].
+    pub async fn from_chain_spec(chain_spec: &str) -> Result<Self> {
+        Self::start(chain_spec, None)
+    }
+
+    /// Syncs the parachain described by the JSON `chain_spec`, whose blocks are
+    /// finalized by the relay chain described by `relay_chain_spec`.
+    ///
+    /// The relay chain is synced alongside the parachain but not queried.
+    pub async fn from_parachain_spec(chain_spec: &str, relay_chain_spec: &str) -> Result<Self> {
+        Self::start(chain_spec, Some(relay_chain_spec))
+    }
+
+    fn start(chain_spec: &str, relay_chain_spec: Option<&str>) -> Result<Self> {
+        // The light client spawns its background task with `tokio::spawn`.
+        let _runtime = runtime::enter_tokio();
+        let mut client = SmoldotClient::new(DefaultPlatform::new(
+            env!("CARGO_PKG_NAME").into(),
+            env!("CARGO_PKG_VERSION").into(),
+        ));
+        let relay_chain = match relay_chain_spec {
+            Some(spec) => Some(add_chain(&mut client, spec, None, None)?.0),
+            None => None,
+        };
+        let json_rpc = AddChainConfigJsonRpc::Enabled {
+            max_pending_requests: NonZeroU32::new(128).expect("not zero"),
+            max_subscriptions: 0,
+        };
+        let (chain_id, rpc_responses) =
+            add_chain(&mut client, chain_spec, Some(json_rpc), relay_chain)?;
+        let rpc_responses = rpc_responses.expect("JSON-RPC is enabled on the queried chain");
+        let rpc = LightClientRpc::new_from_client(
+            client,
+            std::iter::once(AddedChain {
+                chain_id,
+                rpc_responses,
+            }),
+        )
+        .for_chain(chain_id);
+        Ok(Self { rpc })
+    }
+
+    /// Syncs the chain whose spec and boot nodes are read from the node at `url`,
+    /// which must then be trusted to serve the right chain.
+    pub async fn from_url(url: &str) -> Result<Self> {
+        let client = WsClientBuilder::default().build(url).await?;
+        let chain_spec: serde_json::Value = client
+            .request("sync_state_genSyncSpec", rpc_params![true])
+            .await
+            .with_context(|| format!("Failed to read the chain spec from {url}"))?;
+        Self::from_chain_spec(&chain_spec.to_string()).await
+    }
+
+    async fn request<R: DeserializeOwned>(&self, method: &str, params: ArrayParams) -> Result<R> {
+        let params = match params.to_rpc_params()? {
+            Some(params) => params.get().to_owned(),
+            None => "[]".to_owned(),
+        };
+        let response = self
+            .rpc
+            .method_request(method.to_owned(), params)
+            .map_err(|_| anyhow!("The light client has stopped"))?
+            .await
+            .map_err(|_| anyhow!("The light client has stopped"))??;
+        Ok(serde_json::from_str(response.get())?)
+    }
+}
+
+/// Adds the chain described by `spec` to `client`, returning its id and its JSON-RPC
+/// responses, which are disabled if `json_rpc` is not set.
+#[cfg(feature = "light-client")]
+fn add_chain(
+    client: &mut SmoldotClient<Arc<DefaultPlatform>>,
+    spec: &str,
+    json_rpc: Option<AddChainConfigJsonRpc>,
+    relay_chain: Option<ChainId>,
+) -> Result<(ChainId, Option<JsonRpcResponses>)> {
+    let chain = client
+        .add_chain(AddChainConfig {
+            specification: spec,
+            json_rpc: json_rpc.unwrap_or(AddChainConfigJsonRpc::Disabled),
+            potential_relay_chains: relay_chain.into_iter(),
+            database_content: "",
+            user_data: (),
+        })
+        .map_err(|err| anyhow!("Failed to start the light client: {err}"))?;
+    Ok((chain.chain_id, chain.json_rpc_responses))
+}
+
+#[cfg(feature = "light-client")]
+impl RpcTransport for LightClientTransport {
+    fn state_call<'a>(
+        &'a self,
+        func: &'a str,
+        args: Vec<u8>,
+        at: Option<H256>,
+    ) -> TransportFuture<'a> {
+        Box::pin(async move {
+            let bytes: Bytes = self
+                .request("state_call", rpc_params![func, Bytes(args), at])
+                .await?;
+            Ok(bytes.0)
+        })
+    }
+
+    fn best_block(&self) -> TransportFuture<'_, H256> {
+        Box::pin(async move { self.request("chain_getBlockHash", rpc_params![]).await })
+    }
+
+    fn node_url(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// Replays canned responses in order, recording the calls it receives.
+///
+/// Dispatch errors are not decoded with the runtime metadata, as there is no node to
+/// fetch it from.
+#[derive(Debug, Default)]
+pub struct MockTransport {
+    responses: Mutex<VecDeque<Vec<u8>>>,
+    calls: Mutex<Vec<(String, Vec<u8>)>>,
+    best_block: Mutex<H256>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues the result of the next contract dry-run.
+    pub fn push_result(&self, result: &ContractExecResult<Balance>) {
+        self.push_raw(result.encode());
+    }
+
+    /// Queues the SCALE encoded result of the next runtime API call.
+    pub fn push_raw(&self, bytes: Vec<u8>) {
+        self.responses.lock().unwrap().push_back(bytes);
+    }
+
+    /// Sets the hash returned as the best block, zero by default.
+    pub fn set_best_block(&self, hash: H256) {
+        *self.best_block.lock().unwrap() = hash;
+    }
+
+    /// Runtime API calls received so far, with their SCALE encoded arguments.
+    pub fn calls(&self) -> Vec<(String, Vec<u8>)> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+impl RpcTransport for MockTransport {
+    fn state_call<'a>(
+        &'a self,
+        func: &'a str,
+        args: Vec<u8>,
+        _at: Option<H256>,
+    ) -> TransportFuture<'a> {
+        self.calls.lock().unwrap().push((func.to_owned(), args));
+        let response = self.responses.lock().unwrap().pop_front();
+        Box::pin(
+            async move { response.ok_or_else(|| anyhow!("No mocked response left for {func}")) },
+        )
+    }
+
+    fn best_block(&self) -> TransportFuture<'_, H256> {
+        let hash = *self.best_block.lock().unwrap();
+        Box::pin(async move { Ok(hash) })
+    }
+
+    fn node_url(&self) -> Option<&str> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::substrate::address::Address;
+    use crate::substrate::chain::Chain;
+    use crate::substrate::contract::ink::InkMeta;
+    use crate::substrate::signer::DevAccount;
+    use crate::substrate::{runtime, CallOptions, ContractInstance, MutatingPolicy, QueryCache};
+    use pallet_contracts_primitives::{ExecReturnValue, ReturnFlags, StorageDeposit};
+    use sp_core::Pair;
+    use sp_runtime::DispatchError;
+    use sp_weights::Weight;
+    use std::path::PathBuf;
+
+    /// The flipper contract, whose `get` message has selector `0x2f865bd9`.
+    const FLIPPER: &str = "src/substrate/contract/ink/config/testcontract.contract";
+    const GET_SELECTOR: [u8; 4] = [0x2f, 0x86, 0x5b, 0xd9];
+
+    fn flipper(transport: &Arc<MockTransport>) -> ContractInstance {
+        let meta = InkMeta::new(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(FLIPPER),
+            "ws://127.0.0.1:9944".to_owned(),
+            Chain::Local,
+            Address::Ink([0x11; 32].into()),
+        );
+        ContractInstance::new(meta, Box::new(DevAccount::Alice.pair()))
+            .with_transport(transport.clone())
+    }
+
+    fn exec_result(result: Result<Vec<u8>, DispatchError>) -> ContractExecResult<Balance> {
+        ContractExecResult {
+            gas_consumed: Weight::from_parts(1_000, 10),
+            gas_required: Weight::from_parts(2_000, 20),
+            storage_deposit: StorageDeposit::Charge(0),
+            debug_message: b"flipper".to_vec(),
+            result: result.map(|data| ExecReturnValue {
+                flags: ReturnFlags::empty(),
+                data,
+            }),
+            events: None,
+        }
+    }
+
+    /// `Ok(value)` of the `MessageResult<bool>` returned by `get`.
+    fn returns(value: bool) -> ContractExecResult<Balance> {
+        exec_result(Ok(Ok::<_, ()>(value).encode()))
+    }
+
+    #[test]
+    fn dry_runs_messages_through_the_transport() {
+        let transport = Arc::new(MockTransport::new());
+        transport.push_result(&returns(true));
+        let result = flipper(&transport)
+            .query_msg("get", vec![], None, CallOptions::default())
+            .unwrap();
+        assert_eq!(result.value.to_string(), "Ok(true)");
+        assert_eq!(result.debug_message, "flipper");
+        assert_eq!(result.output_len, 2);
+
+        let calls = transport.calls();
+        assert_eq!(calls.len(), 1);
+        let (func, args) = &calls[0];
+        assert_eq!(func, "ContractsApi_call");
+        // The call data ends the request, as a length-prefixed byte string.
+        assert!(args.ends_with(&[&[4 << 2][..], &GET_SELECTOR[..]].concat()));
+        assert!(args.starts_with(&DevAccount::Alice.pair().public().0));
+    }
+
+    #[test]
+    fn fails_without_a_response() {
+        let transport = Arc::new(MockTransport::new());
+        let err = flipper(&transport)
+            .call_msg("get", vec![], None)
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("No mocked response left for ContractsApi_call"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn reports_dispatch_errors() {
+        let transport = Arc::new(MockTransport::new());
+        transport.push_result(&exec_result(Err(DispatchError::BadOrigin)));
+        let err = flipper(&transport)
+            .call_msg("get", vec![], None)
+            .unwrap_err();
+        assert!(
+            err.to_string().starts_with("DispatchError(BadOrigin)"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn denies_mutating_queries_before_sending_them() {
+        let transport = Arc::new(MockTransport::new());
+        let contract = flipper(&transport).with_mutating_policy(MutatingPolicy::Deny);
+        assert!(contract.call_msg("flip", vec![], None).is_err());
+        assert!(transport.calls().is_empty());
+
+        transport.push_result(&exec_result(Ok(Ok::<_, ()>(()).encode())));
+        let result = contract
+            .call_msg_with(
+                "flip",
+                vec![],
+                None,
+                CallOptions::default().allow_mutating(),
+            )
+            .unwrap();
+        assert_eq!(result.to_string(), "Ok()");
+    }
+
+    #[test]
+    fn caches_results_at_the_best_block() {
+        let transport = Arc::new(MockTransport::new());
+        let contract = flipper(&transport).with_cache(QueryCache::new());
+        transport.set_best_block(H256::repeat_byte(1));
+        transport.push_result(&returns(true));
+        transport.push_result(&returns(false));
+
+        let get = || contract.call_msg("get", vec![], None).unwrap().to_string();
+        assert_eq!(get(), "Ok(true)");
+        assert_eq!(get(), "Ok(true)");
+        assert_eq!(transport.calls().len(), 1);
+
+        // Concurrent queries of the same message share the result.
+        let results = runtime::block_on(contract.query_many_async(
+            vec![("get".to_owned(), vec![]), ("get".to_owned(), vec![])],
+            CallOptions::default(),
+        ));
+        assert!(results.iter().all(|result| result.is_ok()));
+        assert_eq!(transport.calls().len(), 1);
+    }
+}
@@ -0,0 +1,382 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use jsonrpsee::core::client::ClientT;
+use jsonrpsee::rpc_params;
+use jsonrpsee::ws_client::WsClientBuilder;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::runtime;
+
+/// Blocks an endpoint may lag behind the most advanced one and still be used.
+const DEFAULT_MAX_LAG: u32 = 5;
+/// Time after which a request to an endpoint is considered stalled.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Health of an endpoint at its last check.
+#[derive(Debug, Clone)]
+pub struct EndpointHealth {
+    pub url: String,
+    /// Round trip of the best block request, `None` if it failed.
+    pub latency: Option<Duration>,
+    pub best_block: Option<u32>,
+    /// Blocks behind the most advanced endpoint.
+    pub lag: u32,
+    pub error: Option<String>,
+}
+
+impl EndpointHealth {
+    fn unchecked(url: &str) -> Self {
+        Self {
+            url: url.to_owned(),
+            latency: None,
+            best_block: None,
+            lag: 0,
+            error: None,
+        }
+    }
+}
+
+/// Switch of the active endpoint, reported to a [`FailoverHook`].
+#[derive(Debug, Clone)]
+pub struct FailoverEvent {
+    pub from: String,
+    pub to: String,
+    /// Why `from` was abandoned, e.g. the error of a stalled request.
+    pub reason: String,
+}
+
+/// Called after every failover, e.g. to alert or log which node is in use.
+pub type FailoverHook = Arc<dyn Fn(&FailoverEvent) + Send + Sync>;
+
+/// Interchangeable RPC endpoints of a chain, one of which is active.
+///
+/// Requests failing on the active endpoint, e.g. after a timeout, switch it to the
+/// healthiest other one, and periodic health checks, see
+/// [`Endpoints::start_health_checks`], switch away from endpoints that stopped
+/// following the chain.
+pub struct Endpoints {
+    urls: Vec<String>,
+    active: AtomicUsize,
+    health: Mutex<Vec<EndpointHealth>>,
+    max_lag: u32,
+    timeout: Duration,
+    hook: Option<FailoverHook>,
+}
+
+impl std::fmt::Debug for Endpoints {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Endpoints")
+            .field("urls", &self.urls)
+            .field("active", &self.active())
+            .finish()
+    }
+}
+
+impl Endpoints {
+    /// Endpoints of `urls`, the first one being active.
+    pub fn new(urls: Vec<String>) -> Result<Self> {
+        if urls.is_empty() {
+            anyhow::bail!("At least one endpoint is required");
+        }
+        Ok(Self {
+            health: Mutex::new(
+                urls.iter()
+                    .map(|url| EndpointHealth::unchecked(url))
+                    .collect(),
+            ),
+            urls,
+            active: AtomicUsize::new(0),
+            max_lag: DEFAULT_MAX_LAG,
+            timeout: DEFAULT_TIMEOUT,
+            hook: None,
+        })
+    }
+
+    pub fn max_lag(mut self, max_lag: u32) -> Self {
+        self.max_lag = max_lag;
+        self
+    }
+
+    /// Sets the time after which requests and health checks are considered stalled.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn on_failover(mut self, hook: FailoverHook) -> Self {
+        self.hook = Some(hook);
+        self
+    }
+
+    pub fn urls(&self) -> &[String] {
+        &self.urls
+    }
+
+    /// Url requests are currently sent to.
+    pub fn active(&self) -> &str {
+        &self.urls[self.active.load(Ordering::Acquire)]
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Health of the endpoints at the last check.
+    pub fn health(&self) -> Vec<EndpointHealth> {
+        self.health.lock().unwrap().clone()
+    }
+
+    /// Reports a failed request to `url`, switching to another endpoint if it is
+    /// still the active one. Returns whether requests should be retried on the new
+    /// active endpoint.
+    pub fn report_failure(&self, url: &str, reason: &str) -> bool {
+        let from = match self.urls.iter().position(|candidate| candidate == url) {
+            Some(from) => from,
+            None => return false,
+        };
+        if let Some(health) = self.health.lock().unwrap().get_mut(from) {
+            health.error = Some(reason.to_owned());
+            health.latency = None;
+        }
+        match self.fail_over(from, reason) {
+            Some(_) => true,
+            // Another request already switched away from `url`.
+            None => self.active() != url,
+        }
+    }
+
+    /// Checks the latency and best block of every endpoint, switching away from the
+    /// active one if it failed or lags behind.
+    pub async fn check(&self) -> Vec<EndpointHealth> {
+        let mut health = Vec::with_capacity(self.urls.len());
+        for url in &self.urls {
+            let mut endpoint = EndpointHealth::unchecked(url);
+            match self.probe(url).await {
+                Ok((best_block, latency)) => {
+                    endpoint.best_block = Some(best_block);
+                    endpoint.latency = Some(latency);
+                }
+                Err(err) => endpoint.error = Some(format!("{err:#}")),
+            }
+            health.push(endpoint);
+        }
+        let best = health
+            .iter()
+            .filter_map(|endpoint| endpoint.best_block)
+            .max()
+            .unwrap_or_default();
+        for endpoint in &mut health {
+            endpoint.lag = endpoint
+                .best_block
+                .map_or(0, |block| best.saturating_sub(block));
+        }
+        *self.health.lock().unwrap() = health.clone();
+
+        let active = self.active.load(Ordering::Acquire);
+        if let Some(reason) = self.unhealthy(&health[active]) {
+            self.fail_over(active, &reason);
+        }
+        health
+    }
+
+    /// Checks the endpoints every `interval` in the background, until they are
+    /// dropped.
+    pub fn start_health_checks(self: &Arc<Self>, interval: Duration) {
+        let endpoints = Arc::downgrade(self);
+        runtime::spawn(async move {
+            loop {
+                match endpoints.upgrade() {
+                    Some(endpoints) => drop(endpoints.check().await),
+                    None => return,
+                }
+                runtime::sleep(interval).await;
+            }
+        });
+    }
+
+    fn unhealthy(&self, health: &EndpointHealth) -> Option<String> {
+        if let Some(error) = &health.error {
+            return Some(error.clone());
+        }
+        if health.lag > self.max_lag {
+            return Some(format!("{} blocks behind", health.lag));
+        }
+        None
+    }
+
+    /// Switches from the endpoint `from` to the healthiest other one, returning it
+    /// unless `from` was no longer active.
+    fn fail_over(&self, from: usize, reason: &str) -> Option<usize> {
+        let to = {
+            let health = self.health.lock().unwrap();
+            let mut candidates: Vec<usize> = (1..self.urls.len())
+                .map(|offset| (from + offset) % self.urls.len())
+                .collect();
+            // Healthy endpoints first, the fastest first, then in order.
+            candidates.sort_by_key(|&candidate| {
+                let health = &health[candidate];
+                (
+                    self.unhealthy(health).is_some(),
+                    health.latency.unwrap_or(Duration::MAX),
+                )
+            });
+            *candidates.first()?
+        };
+        self.active
+            .compare_exchange(from, to, Ordering::AcqRel, Ordering::Acquire)
+            .ok()?;
+
+        let event = FailoverEvent {
+            from: self.urls[from].clone(),
+            to: self.urls[to].clone(),
+            reason: reason.to_owned(),
+        };
+        tracing::warn!(from = %event.from, to = %event.to, reason, "RPC endpoint failover");
+        if let Some(hook) = &self.hook {
+            hook(&event);
+        }
+        Some(to)
+    }
+
+    async fn probe(&self, url: &str) -> Result<(u32, Duration)> {
+        let started = Instant::now();
+        let client = WsClientBuilder::default()
+            .connection_timeout(self.timeout)
+            .request_timeout(self.timeout)
+            .build(url)
+            .await?;
+        let header: serde_json::Value = client.request("chain_getHeader", rpc_params![]).await?;
+        let latency = started.elapsed();
+        let number = header["number"]
+            .as_str()
+            .context("Block header without number")?;
+        let number = u32::from_str_radix(number.trim_start_matches("0x"), 16)?;
+        Ok((number, latency))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::substrate::{RpcTransport, WsTransport};
+
+    /// Endpoints nothing listens on, refusing connections at once.
+    fn unreachable(count: u16) -> Vec<String> {
+        (1..=count)
+            .map(|port| format!("ws://127.0.0.1:{port}"))
+            .collect()
+    }
+
+    /// Endpoints of `urls`, recording their failovers.
+    fn recorded(urls: Vec<String>) -> (Endpoints, Arc<Mutex<Vec<FailoverEvent>>>) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        let endpoints = Endpoints::new(urls)
+            .unwrap()
+            .timeout(Duration::from_secs(1))
+            .on_failover(Arc::new(move |event: &FailoverEvent| {
+                recorded.lock().unwrap().push(event.clone())
+            }));
+        (endpoints, events)
+    }
+
+    #[test]
+    fn requires_an_endpoint() {
+        assert!(Endpoints::new(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn fails_over_from_the_active_endpoint_once() {
+        let urls = unreachable(3);
+        let (endpoints, events) = recorded(urls.clone());
+        assert_eq!(endpoints.active(), urls[0]);
+
+        assert!(endpoints.report_failure(&urls[0], "timed out"));
+        assert_eq!(endpoints.active(), urls[1]);
+        assert_eq!(endpoints.health()[0].error.as_deref(), Some("timed out"));
+        // A concurrent request failing on the same endpoint retries on the new one.
+        assert!(endpoints.report_failure(&urls[0], "timed out"));
+        assert_eq!(endpoints.active(), urls[1]);
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            (events[0].from.as_str(), events[0].to.as_str()),
+            (urls[0].as_str(), urls[1].as_str())
+        );
+        assert_eq!(events[0].reason, "timed out");
+    }
+
+    #[test]
+    fn fails_over_to_healthy_endpoints_first() {
+        let urls = unreachable(3);
+        let (endpoints, _) = recorded(urls.clone());
+        endpoints.report_failure(&urls[0], "down");
+        // The endpoint after the active one is skipped as it failed last.
+        endpoints.report_failure(&urls[1], "down");
+        assert_eq!(endpoints.active(), urls[2]);
+        endpoints.report_failure(&urls[2], "down");
+        assert_eq!(endpoints.active(), urls[0]);
+    }
+
+    #[test]
+    fn ignores_other_urls_and_single_endpoints() {
+        let (endpoints, events) = recorded(unreachable(1));
+        assert!(!endpoints.report_failure("ws://127.0.0.1:9944", "down"));
+        assert!(!endpoints.report_failure(endpoints.urls()[0].as_str(), "down"));
+        assert!(events.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn reports_lagging_endpoints() {
+        let endpoints = Endpoints::new(unreachable(1)).unwrap().max_lag(2);
+        let mut health = EndpointHealth::unchecked("ws://127.0.0.1:1");
+        health.lag = 2;
+        assert_eq!(endpoints.unhealthy(&health), None);
+        health.lag = 3;
+        assert_eq!(
+            endpoints.unhealthy(&health).as_deref(),
+            Some("3 blocks behind")
+        );
+    }
+
+    #[test]
+    fn checks_switch_away_from_failing_endpoints() {
+        let urls = unreachable(2);
+        let (endpoints, events) = recorded(urls.clone());
+        let health = runtime::block_on(endpoints.check());
+        assert!(health.iter().all(|endpoint| endpoint.error.is_some()));
+        assert_eq!(endpoints.active(), urls[1]);
+        assert_eq!(events.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn transport_retries_on_the_next_endpoint() {
+        let urls = unreachable(3);
+        let (endpoints, events) = recorded(urls.clone());
+        let endpoints = Arc::new(endpoints);
+        let transport = WsTransport::with_endpoints(endpoints.clone());
+        assert!(runtime::block_on(transport.best_block()).is_err());
+        // The retry failed as well, on the endpoint the next request fails over from.
+        assert_eq!(endpoints.active(), urls[1]);
+        assert_eq!(transport.node_url(), Some(urls[1].as_str()));
+        assert!(runtime::block_on(transport.best_block()).is_err());
+        assert_eq!(endpoints.active(), urls[2]);
+        assert_eq!(events.lock().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,788 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Context, Result};
+use futures::{Stream, StreamExt};
+use jsonrpsee::core::client::ClientT;
+use jsonrpsee::rpc_params;
+use jsonrpsee::ws_client::WsClientBuilder;
+use scale::{Decode, Encode};
+use sp_core::hashing::blake2_256;
+use sp_core::{H160, H256};
+use sp_weights::Weight;
+use std::convert::TryFrom;
+use std::future::Future;
+use std::sync::Mutex;
+use subxt::blocks::ExtrinsicEvents;
+use subxt::dynamic::{self, Value};
+use subxt::events::{EventDetails, Phase};
+use subxt::tx::{DynamicPayload, SubmittableExtrinsic, TxInBlock, TxPayload, TxStatus};
+use subxt::{Config, Metadata, OnlineClient};
+
+use super::chain::ChainConfig;
+use super::contract::error::ErrorVariant;
+use super::contract::info;
+use super::contract::query::state_call;
+use super::offline::{Mortality, OfflineTxParams};
+use super::signer::{ExtrinsicSigner, SignerProvider};
+use super::{AccountId, Balance, Client, DefaultConfig};
+
+const CONTRACTS_PALLET: &str = "Contracts";
+const REVIVE_PALLET: &str = "Revive";
+const PROXY_PALLET: &str = "Proxy";
+const MULTISIG_PALLET: &str = "Multisig";
+const UTILITY_PALLET: &str = "Utility";
+/// Prefix of the entropy multisig accounts are derived from, in `pallet_multisig`.
+const MULTISIG_ACCOUNT_PREFIX: &[u8; 16] = b"modlpy/utilisuba";
+
+/// Account nonce, as counted by the runtime.
+pub type AccountNonce = u64;
+
+/// An extrinsic included in a finalized block of a chain with config `T`.
+pub struct ExtrinsicOutcome<T: Config = DefaultConfig> {
+    pub block_hash: H256,
+    pub extrinsic_hash: H256,
+    /// Events emitted while executing the extrinsic.
+    pub events: ExtrinsicEvents<T>,
+}
+
+impl<T: Config> ExtrinsicOutcome<T> {
+    /// Contract events of each call of a [`batch_all`] extrinsic, in the order of the
+    /// calls.
+    ///
+    /// The events are split at the `Utility::ItemCompleted` event ending each call.
+    pub fn batch_contract_events(&self) -> Result<Vec<Vec<ContractEmitted>>> {
+        let mut items = Vec::new();
+        let mut current = Vec::new();
+        for event in self.events.iter() {
+            let event = event?;
+            if event.pallet_name() == UTILITY_PALLET && event.variant_name() == "ItemCompleted" {
+                items.push(std::mem::take(&mut current));
+            } else if let Some(event) = ContractEmitted::from_event(&event)? {
+                current.push(event);
+            }
+        }
+        Ok(items)
+    }
+}
+
+/// A `Contracts::ContractEmitted` event, see [`crate::substrate::ContractInstance::decode_events`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractEmitted {
+    pub contract: AccountId,
+    pub topics: Vec<H256>,
+    pub data: Vec<u8>,
+}
+
+impl ContractEmitted {
+    /// Reads `event` if it was emitted by a contract.
+    pub fn from_event<T: Config>(event: &EventDetails<T>) -> Result<Option<Self>> {
+        if event.pallet_name() != CONTRACTS_PALLET || event.variant_name() != "ContractEmitted" {
+            return Ok(None);
+        }
+        let (contract, data) = <(AccountId, Vec<u8>)>::decode(&mut event.field_bytes())?;
+        Ok(Some(Self {
+            contract,
+            topics: event_topics(event)?,
+            data,
+        }))
+    }
+}
+
+/// Topics of `event`, which follow its fields in the event record.
+fn event_topics<T: Config>(event: &EventDetails<T>) -> Result<Vec<H256>> {
+    let mut record = event.bytes();
+    Phase::decode(&mut record)?;
+    // The pallet and variant indices precede the fields.
+    let mut topics = record
+        .get(2 + event.field_bytes().len()..)
+        .context("Truncated event record")?;
+    Ok(Vec::<H256>::decode(&mut topics)?)
+}
+
+/// Signing parameters of an extrinsic, the node's defaults if not set.
+#[derive(Debug, Clone, Default)]
+pub struct TxParams {
+    /// Account nonce, fetched from the node if not set.
+    ///
+    /// See [`NonceTracker`] when submitting several extrinsics in a row.
+    pub nonce: Option<AccountNonce>,
+    /// Tip paid to the block author, on top of the fee.
+    pub tip: Balance,
+    /// Number of blocks the extrinsic stays valid for, immortal if not set.
+    pub mortality: Option<u64>,
+}
+
+impl TxParams {
+    pub fn nonce(mut self, nonce: AccountNonce) -> Self {
+        self.nonce = Some(nonce);
+        self
+    }
+
+    pub fn tip(mut self, tip: Balance) -> Self {
+        self.tip = tip;
+        self
+    }
+
+    pub fn mortality(mut self, period: u64) -> Self {
+        self.mortality = Some(period);
+        self
+    }
+}
+
+/// Signs `call` with `signer`, submits it to the node at `url` and waits until it is
+/// finalized.
+///
+/// A failed dispatch is returned as an error, decoded with the runtime metadata.
+pub async fn submit<T: ChainConfig, Call: TxPayload>(
+    url: &str,
+    call: &Call,
+    signer: &dyn SignerProvider,
+) -> Result<ExtrinsicOutcome<T>, ErrorVariant> {
+    submit_with(url, call, signer, &TxParams::default()).await
+}
+
+/// Same as [`submit`], with explicit nonce, tip and mortality.
+pub async fn submit_with<T: ChainConfig, Call: TxPayload>(
+    url: &str,
+    call: &Call,
+    signer: &dyn SignerProvider,
+    params: &TxParams,
+) -> Result<ExtrinsicOutcome<T>, ErrorVariant> {
+    submit_watched(url, call, signer, params, false, |_| {}).await
+}
+
+/// Progress of a submitted extrinsic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxState {
+    /// Validated by the node and waiting in its pool.
+    Validated,
+    /// Gossiped to the given number of peers.
+    Broadcast(u32),
+    /// Included in a block of the best chain, which may still be retracted.
+    InBlock(H256),
+    /// The including block left the best chain.
+    Retracted,
+    Finalized(H256),
+    /// Dropped from the pool, usually because it was full.
+    Dropped(String),
+    /// Became invalid, e.g. because another extrinsic with the same nonce usurped it,
+    /// or the node stopped watching before the including block was finalized.
+    Invalid(String),
+    /// The node failed to watch the extrinsic.
+    Error(String),
+}
+
+/// Maximum number of times an extrinsic is signed again after being dropped.
+const MAX_RESUBMISSIONS: usize = 3;
+
+/// Same as [`submit_with`], reporting each status change to `on_state`.
+///
+/// With `resubmit`, an extrinsic that is dropped is signed again and resubmitted, at
+/// most [`MAX_RESUBMISSIONS`] times. It keeps its nonce, so that the call executes at
+/// most once even if the dropped extrinsic still makes it into a block.
+pub async fn submit_watched<T: ChainConfig, Call: TxPayload>(
+    url: &str,
+    call: &Call,
+    signer: &dyn SignerProvider,
+    params: &TxParams,
+    resubmit: bool,
+    mut on_state: impl FnMut(&TxState),
+) -> Result<ExtrinsicOutcome<T>, ErrorVariant> {
+    let client = OnlineClient::<T>::from_url(url).await?;
+    let signer = ExtrinsicSigner::new(signer)?;
+    let nonce = match params.nonce {
+        Some(nonce) => nonce,
+        None => next_nonce(url, signer.account_id()).await?,
+    };
+
+    let (client, signer) = (&client, &signer);
+    let in_block = watch_submissions(nonce, resubmit, &mut on_state, |nonce| async move {
+        let params = params.clone().nonce(nonce);
+        let progress = create_signed(client, call, signer, &params)
+            .await?
+            .submit_and_watch()
+            .await?;
+        Ok(progress.map(|status| Ok(tx_state(status?))))
+    })
+    .await?;
+
+    let block_hash = in_block.block_hash();
+    let extrinsic_hash = in_block.extrinsic_hash();
+    let events = in_block.wait_for_success().await?;
+    Ok(ExtrinsicOutcome {
+        block_hash,
+        extrinsic_hash,
+        events,
+    })
+}
+
+/// State of a status reported by subxt, and the including block once finalized.
+fn tx_state<T: ChainConfig>(
+    status: TxStatus<T, OnlineClient<T>>,
+) -> (TxState, Option<TxInBlock<T, OnlineClient<T>>>) {
+    match status {
+        TxStatus::Validated => (TxState::Validated, None),
+        TxStatus::Broadcasted { num_peers } => (TxState::Broadcast(num_peers), None),
+        TxStatus::InBestBlock(in_block) => (TxState::InBlock(in_block.block_hash()), None),
+        TxStatus::NoLongerInBestBlock => (TxState::Retracted, None),
+        TxStatus::InFinalizedBlock(in_block) => {
+            (TxState::Finalized(in_block.block_hash()), Some(in_block))
+        }
+        TxStatus::Dropped { message } => (TxState::Dropped(message), None),
+        TxStatus::Invalid { message } => (TxState::Invalid(message), None),
+        TxStatus::Error { message } => (TxState::Error(message), None),
+    }
+}
+
+/// Follows the statuses of the extrinsic `submit` signs with `nonce` and submits until
+/// it is finalized, submitting it again with the same nonce when dropped if `resubmit`
+/// is set, and returns the including block carried by the finalized status.
+async fn watch_submissions<B, S, F, Fut>(
+    nonce: AccountNonce,
+    resubmit: bool,
+    on_state: &mut impl FnMut(&TxState),
+    mut submit: F,
+) -> Result<B, ErrorVariant>
+where
+    F: FnMut(AccountNonce) -> Fut,
+    Fut: Future<Output = Result<S, ErrorVariant>>,
+    S: Stream<Item = Result<(TxState, Option<B>), ErrorVariant>> + Unpin,
+{
+    let mut resubmissions = 0;
+    'submission: loop {
+        let mut statuses = submit(nonce).await?;
+        while let Some(status) = statuses.next().await {
+            let (state, in_block) = status?;
+            on_state(&state);
+
+            match state {
+                TxState::Finalized(_) => {
+                    return Ok(in_block.expect("Finalized status carries its block"))
+                }
+                TxState::Dropped(_) if resubmit && resubmissions < MAX_RESUBMISSIONS => {
+                    resubmissions += 1;
+                    continue 'submission;
+                }
+                TxState::Dropped(_) | TxState::Invalid(_) | TxState::Error(_) => {
+                    return Err(ErrorVariant::from(
+                        format!("Extrinsic was not included: {state:?}").as_str(),
+                    ))
+                }
+                _ => {}
+            }
+        }
+
+        return Err(ErrorVariant::from(
+            "Transaction status stream ended before finalization",
+        ));
+    }
+}
+
+/// Signs `call` with the nonce set in `params`.
+pub(crate) async fn create_signed<T: ChainConfig, Call: TxPayload>(
+    client: &OnlineClient<T>,
+    call: &Call,
+    signer: &ExtrinsicSigner<'_>,
+    params: &TxParams,
+) -> Result<SubmittableExtrinsic<T, OnlineClient<T>>, ErrorVariant> {
+    let nonce = params
+        .nonce
+        .context("The nonce of an extrinsic must be set before signing it")?;
+    let mut signed_params = OfflineTxParams::new(nonce).tip(params.tip);
+    if let Some(period) = params.mortality {
+        let block = client.blocks().at_latest().await?;
+        signed_params = signed_params.mortality(Mortality {
+            period,
+            block_number: block.number().into(),
+            block_hash: block.hash(),
+        });
+    }
+    Ok(signer.sign(&client.tx(), call, &signed_params).await?)
+}
+
+/// A call encoding to no bytes, whose signer payload holds only the signed extensions.
+pub(crate) struct EmptyCall;
+
+impl TxPayload for EmptyCall {
+    fn encode_call_data_to(&self, _: &Metadata, _: &mut Vec<u8>) -> Result<(), subxt::Error> {
+        Ok(())
+    }
+}
+
+/// Next nonce of `account`, counting its extrinsics waiting in the node's pool.
+pub async fn next_nonce(url: &str, account: &AccountId) -> Result<AccountNonce> {
+    let client = WsClientBuilder::default().build(url).await?;
+    let nonce = client
+        .request("system_accountNextIndex", rpc_params![account.to_string()])
+        .await?;
+    Ok(nonce)
+}
+
+/// Hands out consecutive nonces to an account submitting extrinsics in succession.
+///
+/// An extrinsic only reaches the pool once submitted, so nonces handed out but not
+/// submitted yet are tracked locally on top of [`next_nonce`].
+pub struct NonceTracker {
+    url: String,
+    account: AccountId,
+    next: Mutex<Option<AccountNonce>>,
+}
+
+impl NonceTracker {
+    pub fn new(url: impl Into<String>, account: AccountId) -> Self {
+        Self {
+            url: url.into(),
+            account,
+            next: Mutex::new(None),
+        }
+    }
+
+    /// Reserves the next nonce of the account.
+    pub async fn next(&self) -> Result<AccountNonce> {
+        let on_chain = next_nonce(&self.url, &self.account).await?;
+        let mut next = self
+            .next
+            .lock()
+            .map_err(|_| anyhow!("AccountNonce tracker poisoned"))?;
+        let nonce = next.map_or(on_chain, |next| next.max(on_chain));
+        *next = Some(nonce + 1);
+        Ok(nonce)
+    }
+
+    /// Forgets the locally reserved nonces, e.g. after a submission failed.
+    pub fn reset(&self) {
+        if let Ok(mut next) = self.next.lock() {
+            *next = None;
+        }
+    }
+}
+
+/// Expected cost of an extrinsic, as computed by the runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeEstimate {
+    /// Fee charged for the extrinsic, excluding any tip.
+    ///
+    /// See [`crate::substrate::balance::TokenMetadata::format`] to display it.
+    pub partial_fee: Balance,
+    /// Weight the extrinsic is charged for.
+    pub weight: Weight,
+}
+
+/// `RuntimeDispatchInfo` as returned by `TransactionPaymentApi_query_info`.
+#[derive(Decode)]
+struct RuntimeDispatchInfo {
+    weight: Weight,
+    /// `DispatchClass` variant index.
+    _class: u8,
+    partial_fee: Balance,
+}
+
+/// Estimates the fee of `call` signed by `signer`, without submitting it.
+pub async fn estimate_fee<T: ChainConfig, Call: TxPayload>(
+    url: &str,
+    call: &Call,
+    signer: &dyn SignerProvider,
+) -> Result<FeeEstimate> {
+    let client = OnlineClient::<T>::from_url(url).await?;
+    let signer = ExtrinsicSigner::new(signer)?;
+    let nonce = next_nonce(url, signer.account_id()).await?;
+    let extrinsic = signer
+        .sign(&client.tx(), call, &OfflineTxParams::new(nonce))
+        .await?;
+
+    // `query_info(uxt, len)`: the opaque extrinsic is already length prefixed.
+    let encoded = extrinsic.encoded();
+    let mut args = encoded.to_vec();
+    (encoded.len() as u32).encode_to(&mut args);
+    let info: RuntimeDispatchInfo =
+        state_call(url, "TransactionPaymentApi_query_info", RawArgs(args)).await?;
+
+    Ok(FeeEstimate {
+        partial_fee: info.partial_fee,
+        weight: info.weight,
+    })
+}
+
+/// Pre-encoded runtime API arguments.
+struct RawArgs(Vec<u8>);
+
+impl Encode for RawArgs {
+    fn encode_to<T: scale::Output + ?Sized>(&self, dest: &mut T) {
+        dest.write(&self.0);
+    }
+}
+
+/// `Contracts::call`, executing a message of the contract at `dest`.
+pub fn contract_call(
+    dest: &AccountId,
+    value: Balance,
+    gas_limit: Weight,
+    storage_deposit_limit: Option<Balance>,
+    data: Vec<u8>,
+) -> DynamicPayload {
+    dynamic::tx(
+        CONTRACTS_PALLET,
+        "call",
+        vec![
+            Value::unnamed_variant("Id", vec![Value::from_bytes(dest.0)]),
+            Value::u128(value),
+            weight(gas_limit),
+            optional_balance(storage_deposit_limit),
+            Value::from_bytes(data),
+        ],
+    )
+}
+
+/// `Revive::call`, calling the pallet-revive contract at `dest`.
+///
+/// pallet-revive has no unbounded storage deposit limit, the whole balance of the
+/// signer being the limit if not set.
+pub fn revive_call(
+    dest: &H160,
+    value: Balance,
+    gas_limit: Weight,
+    storage_deposit_limit: Option<Balance>,
+    data: Vec<u8>,
+) -> DynamicPayload {
+    dynamic::tx(
+        REVIVE_PALLET,
+        "call",
+        vec![
+            Value::from_bytes(dest.as_bytes()),
+            Value::u128(value),
+            weight(gas_limit),
+            Value::u128(storage_deposit_limit.unwrap_or(Balance::MAX)),
+            Value::from_bytes(data),
+        ],
+    )
+}
+
+/// `Contracts::instantiate`, instantiating a contract from uploaded code.
+pub fn instantiate(
+    value: Balance,
+    gas_limit: Weight,
+    storage_deposit_limit: Option<Balance>,
+    code_hash: H256,
+    data: Vec<u8>,
+    salt: Vec<u8>,
+) -> DynamicPayload {
+    dynamic::tx(
+        CONTRACTS_PALLET,
+        "instantiate",
+        vec![
+            Value::u128(value),
+            weight(gas_limit),
+            optional_balance(storage_deposit_limit),
+            Value::from_bytes(code_hash),
+            Value::from_bytes(data),
+            Value::from_bytes(salt),
+        ],
+    )
+}
+
+/// `Contracts::instantiate_with_code`, uploading the code and instantiating it.
+pub fn instantiate_with_code(
+    value: Balance,
+    gas_limit: Weight,
+    storage_deposit_limit: Option<Balance>,
+    code: Vec<u8>,
+    data: Vec<u8>,
+    salt: Vec<u8>,
+) -> DynamicPayload {
+    dynamic::tx(
+        CONTRACTS_PALLET,
+        "instantiate_with_code",
+        vec![
+            Value::u128(value),
+            weight(gas_limit),
+            optional_balance(storage_deposit_limit),
+            Value::from_bytes(code),
+            Value::from_bytes(data),
+            Value::from_bytes(salt),
+        ],
+    )
+}
+
+/// `Contracts::upload_code`, requiring deterministic code.
+pub fn upload_code(code: Vec<u8>, storage_deposit_limit: Option<Balance>) -> DynamicPayload {
+    dynamic::tx(
+        CONTRACTS_PALLET,
+        "upload_code",
+        vec![
+            Value::from_bytes(code),
+            optional_balance(storage_deposit_limit),
+            Value::unnamed_variant("Enforced", vec![]),
+        ],
+    )
+}
+
+/// `Utility::batch_all`, dispatching `calls` in order with a single signature, and
+/// reverting all of them if any fails.
+///
+/// See [`ExtrinsicOutcome::batch_contract_events`] to tell their events apart.
+pub fn batch_all(calls: Vec<DynamicPayload>) -> DynamicPayload {
+    let calls = calls
+        .into_iter()
+        .map(DynamicPayload::into_value)
+        .collect::<Vec<_>>();
+    dynamic::tx(
+        UTILITY_PALLET,
+        "batch_all",
+        vec![Value::unnamed_composite(calls)],
+    )
+}
+
+/// `Proxy::proxy`, dispatching `call` as `real` from an account registered as its
+/// proxy, e.g. to operate a contract owned by a DAO.
+///
+/// `proxy_type` restricts the proxy definitions considered to a `ProxyType` variant
+/// of the runtime, e.g. `Any`.
+pub fn proxy(real: &AccountId, proxy_type: Option<&str>, call: DynamicPayload) -> DynamicPayload {
+    let proxy_type = match proxy_type {
+        Some(proxy_type) => {
+            Value::unnamed_variant("Some", vec![Value::unnamed_variant(proxy_type, vec![])])
+        }
+        None => Value::unnamed_variant("None", vec![]),
+    };
+    dynamic::tx(
+        PROXY_PALLET,
+        "proxy",
+        vec![
+            Value::unnamed_variant("Id", vec![Value::from_bytes(real.0)]),
+            proxy_type,
+            call.into_value(),
+        ],
+    )
+}
+
+/// Block height and extrinsic index of the first approval of a multisig operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timepoint {
+    pub height: u32,
+    pub index: u32,
+}
+
+/// A multisig account, dispatching calls approved by `threshold` of its signatories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Multisig {
+    /// Sorted, as `pallet_multisig` expects them.
+    signatories: Vec<AccountId>,
+    threshold: u16,
+}
+
+impl Multisig {
+    pub fn new(mut signatories: Vec<AccountId>, threshold: u16) -> Result<Self> {
+        signatories.sort_by_key(|a| a.0);
+        signatories.dedup();
+        if signatories.len() < 2 {
+            anyhow::bail!("A multisig needs at least 2 distinct signatories");
+        }
+        if threshold < 2 || usize::from(threshold) > signatories.len() {
+            anyhow::bail!(
+                "Multisig threshold must be between 2 and {}, got {threshold}",
+                signatories.len()
+            );
+        }
+        Ok(Self {
+            signatories,
+            threshold,
+        })
+    }
+
+    /// Account the approved calls are dispatched from.
+    pub fn account_id(&self) -> AccountId {
+        let entropy =
+            (MULTISIG_ACCOUNT_PREFIX, &self.signatories, self.threshold).using_encoded(blake2_256);
+        entropy.into()
+    }
+
+    /// `Multisig::as_multi`, approving `call` as `signatory`, which dispatches it once
+    /// `threshold` signatories approved it.
+    ///
+    /// The first approval opens the operation and later ones refer to it by its
+    /// [`Timepoint`], read from the node at `url`. `max_weight` bounds the weight of
+    /// `call` when it is dispatched, e.g. the weight of its dry-run.
+    pub async fn as_multi(
+        &self,
+        url: &str,
+        signatory: &AccountId,
+        call: DynamicPayload,
+        max_weight: Weight,
+    ) -> Result<DynamicPayload> {
+        if !self.signatories.contains(signatory) {
+            anyhow::bail!("{signatory} is not a signatory of the multisig");
+        }
+        let client = Client::from_url(url).await?;
+        let call_hash = blake2_256(&call.encode_call_data(&client.metadata())?);
+        let timepoint = match self.pending(&client, call_hash).await? {
+            Some(timepoint) => Value::unnamed_variant(
+                "Some",
+                vec![Value::named_composite(vec![
+                    ("height", Value::u128(timepoint.height.into())),
+                    ("index", Value::u128(timepoint.index.into())),
+                ])],
+            ),
+            None => Value::unnamed_variant("None", vec![]),
+        };
+        let other_signatories = self
+            .signatories
+            .iter()
+            .filter(|account| *account != signatory)
+            .map(|account| Value::from_bytes(account.0))
+            .collect::<Vec<_>>();
+
+        Ok(dynamic::tx(
+            MULTISIG_PALLET,
+            "as_multi",
+            vec![
+                Value::u128(self.threshold.into()),
+                Value::unnamed_composite(other_signatories),
+                timepoint,
+                call.into_value(),
+                weight(max_weight),
+            ],
+        ))
+    }
+
+    /// Timepoint of the operation dispatching the call hashed to `call_hash`, `None`
+    /// until its first approval.
+    async fn pending(&self, client: &Client, call_hash: [u8; 32]) -> Result<Option<Timepoint>> {
+        let address = dynamic::storage(
+            MULTISIG_PALLET,
+            "Multisigs",
+            vec![
+                Value::from_bytes(self.account_id().0),
+                Value::from_bytes(call_hash),
+            ],
+        );
+        let operation = match client.storage().at_latest().await?.fetch(&address).await? {
+            Some(operation) => operation.to_value()?,
+            None => return Ok(None),
+        };
+        let when = info::field(&operation, "when").context("Multisig has no timepoint")?;
+        let number = |name: &str| {
+            info::field(when, name)
+                .and_then(Value::as_u128)
+                .and_then(|number| u32::try_from(number).ok())
+                .with_context(|| format!("Multisig timepoint has no {name}"))
+        };
+        Ok(Some(Timepoint {
+            height: number("height")?,
+            index: number("index")?,
+        }))
+    }
+}
+
+fn weight(weight: Weight) -> Value {
+    Value::named_composite(vec![
+        ("ref_time", Value::u128(weight.ref_time().into())),
+        ("proof_size", Value::u128(weight.proof_size().into())),
+    ])
+}
+
+fn optional_balance(balance: Option<Balance>) -> Value {
+    match balance {
+        Some(balance) => Value::unnamed_variant("Some", vec![Value::u128(balance)]),
+        None => Value::unnamed_variant("None", vec![]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use futures::stream;
+
+    type Status = Result<(TxState, Option<usize>), ErrorVariant>;
+
+    /// Outcome of [`replay`]: the index of the finalized submission, the nonce of each
+    /// submission and the states reported.
+    type Replay = (Result<usize, ErrorVariant>, Vec<AccountNonce>, Vec<TxState>);
+
+    /// Replays one scripted status stream per submission, the finalized status
+    /// carrying the index of its submission.
+    fn replay(resubmit: bool, scripts: Vec<Vec<TxState>>) -> Replay {
+        let mut scripts = scripts.into_iter();
+        let mut nonces = Vec::new();
+        let mut states = Vec::new();
+        let mut on_state = |state: &TxState| states.push(state.clone());
+        let submit = |nonce| {
+            let submission = nonces.len();
+            nonces.push(nonce);
+            let script = scripts.next().expect("No status script left");
+            async move {
+                let statuses = script.into_iter().map(move |state| -> Status {
+                    let in_block = matches!(state, TxState::Finalized(_)).then_some(submission);
+                    Ok((state, in_block))
+                });
+                Ok(stream::iter(statuses))
+            }
+        };
+        let result = block_on(watch_submissions(7, resubmit, &mut on_state, submit));
+        (result, nonces, states)
+    }
+
+    #[test]
+    fn resubmits_dropped_extrinsics() {
+        let block = H256::repeat_byte(1);
+        let (result, nonces, states) = replay(
+            true,
+            vec![
+                vec![TxState::Validated, TxState::Dropped("full".into())],
+                vec![
+                    TxState::Validated,
+                    TxState::InBlock(block),
+                    TxState::Finalized(block),
+                ],
+            ],
+        );
+        assert_eq!(result.unwrap(), 1);
+        // The resubmission keeps the nonce, so that only one of them can execute.
+        assert_eq!(nonces, [7, 7]);
+        assert_eq!(states.len(), 5);
+    }
+
+    #[test]
+    fn fails_on_dropped_extrinsics_without_resubmit() {
+        let (result, nonces, _) = replay(false, vec![vec![TxState::Dropped("full".into())]]);
+        assert!(result.is_err());
+        assert_eq!(nonces.len(), 1);
+    }
+
+    #[test]
+    fn gives_up_after_max_resubmissions() {
+        let dropped = vec![TxState::Dropped("full".into())];
+        let (result, nonces, _) = replay(true, vec![dropped; MAX_RESUBMISSIONS + 1]);
+        assert!(result.is_err());
+        assert_eq!(nonces.len(), MAX_RESUBMISSIONS + 1);
+    }
+
+    #[test]
+    fn does_not_resubmit_usurped_extrinsics() {
+        // subxt reports an extrinsic usurped by another with its nonce as invalid.
+        let usurped =
+            TxState::Invalid("Transaction was usurped by another with the same nonce".into());
+        let (result, nonces, _) = replay(true, vec![vec![TxState::Validated, usurped]]);
+        assert!(result.is_err());
+        assert_eq!(nonces.len(), 1);
+    }
+
+    #[test]
+    fn fails_when_the_stream_ends_before_finalization() {
+        let block = H256::repeat_byte(1);
+        let (result, _, _) = replay(true, vec![vec![TxState::InBlock(block)]]);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,238 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use toml::value::{Table, Value};
+
+use super::contract::ink::{ContractArtifacts, InkMeta};
+use super::signer::DevAccount;
+use super::testing::TestNode;
+
+const DEFAULT_CONSTRUCTOR: &str = "new";
+/// Chain of the development nodes fixtures are deployed to, see [`super::chain::Chain`].
+const FIXTURE_CHAIN: &str = "local";
+
+/// Contracts to deploy together, e.g. a token and the exchange trading it
+///
+/// ```toml
+/// account = "alice"
+///
+/// [contracts.token]
+/// bundle = "target/ink/token/token.contract"
+/// args = ["1000000"]
+///
+/// [contracts.exchange]
+/// bundle = "target/ink/exchange/exchange.contract"
+/// constructor = "new"
+/// args = ["${token}"]
+/// ```
+///
+/// `${name}` in an argument is replaced with the address of the contract `name`,
+/// which is deployed first.
+#[derive(Debug, Deserialize)]
+pub struct FixtureManifest {
+    /// Development account deploying the contracts, `Alice` if not set.
+    account: Option<String>,
+    contracts: BTreeMap<String, Fixture>,
+    /// Directory the bundle paths are relative to.
+    #[serde(skip)]
+    root: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct Fixture {
+    bundle: PathBuf,
+    #[serde(default = "default_constructor")]
+    constructor: String,
+    #[serde(default)]
+    args: Vec<String>,
+    /// Contracts to deploy first, besides the ones referenced in `args`.
+    #[serde(default)]
+    depends_on: Vec<String>,
+}
+
+fn default_constructor() -> String {
+    DEFAULT_CONSTRUCTOR.to_owned()
+}
+
+impl FixtureManifest {
+    /// Reads the manifest at `path`, whose bundle paths are relative to its directory.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read fixture manifest {}", path.display()))?;
+        let mut manifest: Self = toml::from_str(&content)
+            .with_context(|| format!("Invalid fixture manifest {}", path.display()))?;
+        manifest.root = path.parent().map_or_else(PathBuf::new, Path::to_path_buf);
+        Ok(manifest)
+    }
+
+    /// Names of the contracts, each after the contracts it depends on.
+    pub fn deployment_order(&self) -> Result<Vec<&str>> {
+        let mut order = Vec::with_capacity(self.contracts.len());
+        let mut deployed = BTreeSet::new();
+        let mut visiting = BTreeSet::new();
+        for name in self.contracts.keys() {
+            self.visit(name, &mut visiting, &mut deployed, &mut order)?;
+        }
+        Ok(order)
+    }
+
+    fn visit<'a>(
+        &'a self,
+        name: &'a str,
+        visiting: &mut BTreeSet<&'a str>,
+        deployed: &mut BTreeSet<&'a str>,
+        order: &mut Vec<&'a str>,
+    ) -> Result<()> {
+        if deployed.contains(name) {
+            return Ok(());
+        }
+        if !visiting.insert(name) {
+            anyhow::bail!("Fixture {name} is part of a dependency cycle");
+        }
+        let fixture = &self.contracts[name];
+        for dependency in fixture.dependencies() {
+            let (dependency, _) = self.contracts.get_key_value(dependency).with_context(|| {
+                format!("Fixture {name} depends on unknown fixture {dependency}")
+            })?;
+            self.visit(dependency, visiting, deployed, order)?;
+        }
+        visiting.remove(name);
+        deployed.insert(name);
+        order.push(name);
+        Ok(())
+    }
+
+    /// Deploys the contracts to `node` in dependency order.
+    pub fn deploy(&self, node: &TestNode) -> Result<DeployedFixtures> {
+        let account = match &self.account {
+            Some(account) => DevAccount::from_str(account)?,
+            None => DevAccount::Alice,
+        };
+        let mut deployed = DeployedFixtures {
+            url: node.url().to_owned(),
+            contracts: BTreeMap::new(),
+        };
+        for name in self.deployment_order()? {
+            let fixture = &self.contracts[name];
+            let bundle = self.root.join(&fixture.bundle);
+            let artifacts = ContractArtifacts::from_manifest_or_file(None, Some(&bundle))?;
+            let args = fixture
+                .args
+                .iter()
+                .map(|arg| deployed.substitute(arg))
+                .collect::<Vec<_>>();
+            let contract = node
+                .deploy_as(account, &artifacts, &fixture.constructor, &args)
+                .with_context(|| format!("Failed to deploy fixture {name}"))?;
+            tracing::info!(fixture = name, address = %contract.address(), "Deployed fixture");
+            deployed
+                .contracts
+                .insert(name.to_owned(), (bundle, contract.address().to_string()));
+        }
+        Ok(deployed)
+    }
+}
+
+impl Fixture {
+    /// Fixtures named in `depends_on` or referenced as `${name}` in the arguments.
+    fn dependencies(&self) -> impl Iterator<Item = &str> {
+        let referenced = self.args.iter().flat_map(|arg| references(arg));
+        self.depends_on.iter().map(String::as_str).chain(referenced)
+    }
+}
+
+/// Names referenced as `${name}` in `arg`.
+fn references(arg: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = arg;
+    while let Some(start) = rest.find("${") {
+        let after = &rest[start + 2..];
+        match after.find('}') {
+            Some(end) => {
+                names.push(&after[..end]);
+                rest = &after[end + 1..];
+            }
+            None => break,
+        }
+    }
+    names
+}
+
+/// Addresses of deployed fixtures, by name.
+pub struct DeployedFixtures {
+    url: String,
+    /// Bundle path and address of each contract.
+    contracts: BTreeMap<String, (PathBuf, String)>,
+}
+
+impl DeployedFixtures {
+    /// Address of the contract `name`, if deployed.
+    pub fn address(&self, name: &str) -> Option<&str> {
+        self.contracts
+            .get(name)
+            .map(|(_, address)| address.as_str())
+    }
+
+    /// [`InkMeta`] of the contract `name`, to query it.
+    pub fn meta(&self, name: &str) -> Result<InkMeta> {
+        InkMeta::from_toml(&self.profile(name)?)
+    }
+
+    /// Tables of the deployed contracts by name, in the format read by
+    /// [`InkMeta::from_toml`].
+    pub fn to_toml(&self) -> Result<String> {
+        let profiles = self
+            .contracts
+            .keys()
+            .map(|name| Ok((name.clone(), self.profile(name)?)))
+            .collect::<Result<Table>>()?;
+        Ok(toml::to_string(&profiles)?)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_toml()?)
+            .with_context(|| format!("Failed to write fixture addresses to {}", path.display()))
+    }
+
+    fn profile(&self, name: &str) -> Result<Value> {
+        let (bundle, address) = self
+            .contracts
+            .get(name)
+            .with_context(|| format!("Fixture {name} was not deployed"))?;
+        let mut profile = Table::new();
+        profile.insert(
+            "contract_path".to_owned(),
+            Value::String(bundle.display().to_string()),
+        );
+        profile.insert("url".to_owned(), Value::String(self.url.clone()));
+        profile.insert("chain".to_owned(), Value::String(FIXTURE_CHAIN.to_owned()));
+        profile.insert("ink_contract_id".to_owned(), Value::String(address.clone()));
+        Ok(Value::Table(profile))
+    }
+
+    /// Replaces the `${name}` references of `arg` with the addresses of the contracts.
+    fn substitute(&self, arg: &str) -> String {
+        let mut arg = arg.to_owned();
+        for (name, (_, address)) in &self.contracts {
+            arg = arg.replace(&format!("${{{name}}}"), address);
+        }
+        arg
+    }
+}
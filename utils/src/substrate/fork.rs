@@ -0,0 +1,205 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use jsonrpsee::core::client::ClientT;
+use jsonrpsee::rpc_params;
+use jsonrpsee::ws_client::WsClientBuilder;
+use sp_core::hashing::blake2_128;
+use std::net::TcpListener;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use super::address::Address;
+use super::chain::Chain;
+use super::contract::info;
+use super::contract::ink::InkMeta;
+use super::contract::ContractInstance;
+use super::signer::SignerProvider;
+use super::{runtime, AccountId, BlockHash, Client};
+
+/// Environment variable overriding the Chopsticks command, e.g. a local checkout.
+const CHOPSTICKS_ENV: &str = "CHOPSTICKS";
+const DEFAULT_CHOPSTICKS: &str = "npx @acala-network/chopsticks@latest";
+/// Forking fetches the runtime and initial state of the chain, which takes a while.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(120);
+const STARTUP_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Prefix of the keys of the default child tries, as Chopsticks addresses child storage.
+const CHILD_STORAGE_PREFIX: &[u8] = b":child_storage:default:";
+
+/// A Chopsticks fork of a live chain, to simulate contract calls against its state.
+///
+/// Queries and extrinsics run on the fork as on the chain, and the dev RPCs of
+/// Chopsticks can override any storage, including the storage of contracts, before
+/// they run. A spawned fork is killed when dropped.
+pub struct ChopsticksFork {
+    url: String,
+    chain: Chain,
+    process: Option<Child>,
+}
+
+impl ChopsticksFork {
+    /// Forks the chain at `endpoint` at block `at`, or else its latest block, on a free
+    /// port.
+    ///
+    /// The command is read from `$CHOPSTICKS`, running the latest release with `npx`
+    /// by default.
+    pub fn spawn(endpoint: &str, chain: Chain, at: Option<BlockHash>) -> Result<Self> {
+        let command =
+            std::env::var(CHOPSTICKS_ENV).unwrap_or_else(|_| DEFAULT_CHOPSTICKS.to_owned());
+        let mut words = command.split_whitespace();
+        let program = words.next().context("Empty Chopsticks command")?;
+        let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+
+        let mut process = Command::new(program);
+        process
+            .args(words)
+            .args(["--endpoint", endpoint, "--port", &port.to_string()]);
+        if let Some(at) = at {
+            process.args(["--block", &format!("{at:?}")]);
+        }
+        let process = process
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to run {command}, set ${CHOPSTICKS_ENV}"))?;
+
+        let mut fork = Self {
+            url: format!("ws://127.0.0.1:{port}"),
+            chain,
+            process: Some(process),
+        };
+        fork.wait_until_ready()?;
+        Ok(fork)
+    }
+
+    /// Connects to a running fork, e.g. `ws://127.0.0.1:8000`, of a chain of type `chain`.
+    pub fn connect(url: impl Into<String>, chain: Chain) -> Result<Self> {
+        let url = url.into();
+        runtime::block_on(Client::from_url(&url))
+            .with_context(|| format!("Failed to connect to {url}"))?;
+        Ok(Self {
+            url,
+            chain,
+            process: None,
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The contract deployed at `address` on the forked chain, described by the
+    /// artifact at `file`.
+    pub fn contract(
+        &self,
+        file: impl Into<std::path::PathBuf>,
+        address: Address,
+        signer: Box<dyn SignerProvider>,
+    ) -> ContractInstance {
+        let meta = InkMeta::new(file.into(), self.url.clone(), self.chain, address);
+        ContractInstance::new(meta, signer)
+    }
+
+    /// Overrides raw storage entries, removing those set to `None`, through
+    /// `dev_setStorage`. The changes apply to the next block, see [`ChopsticksFork::new_block`].
+    pub fn set_storage(&self, entries: &[(Vec<u8>, Option<Vec<u8>>)]) -> Result<()> {
+        let entries: Vec<(String, Option<String>)> = entries
+            .iter()
+            .map(|(key, value)| (hex_bytes(key), value.as_deref().map(hex_bytes)))
+            .collect();
+        runtime::block_on(
+            self.request::<serde_json::Value>("dev_setStorage", rpc_params![entries]),
+        )
+        .context("Failed to override the storage of the fork")?;
+        Ok(())
+    }
+
+    /// Overrides entries of the storage of the contract at `address`, keyed by their
+    /// unhashed ink! storage keys, e.g. `0x00000000` for the root of an ink! 4 contract.
+    pub fn set_contract_storage(
+        &self,
+        address: &AccountId,
+        entries: &[(Vec<u8>, Option<Vec<u8>>)],
+    ) -> Result<()> {
+        let info = runtime::block_on(info::get_contract_info(&self.url, address))?
+            .with_context(|| format!("No contract found at {address}"))?;
+        let entries: Vec<_> = entries
+            .iter()
+            .map(|(key, value)| (child_storage_key(&info.trie_id, key), value.clone()))
+            .collect();
+        self.set_storage(&entries)
+    }
+
+    /// Builds `count` blocks, applying the storage overrides and pending extrinsics.
+    pub fn new_block(&self, count: u32) -> Result<BlockHash> {
+        let hash: String = runtime::block_on(self.request(
+            "dev_newBlock",
+            rpc_params![serde_json::json!({ "count": count })],
+        ))
+        .context("Failed to build a block on the fork")?;
+        hash.parse()
+            .with_context(|| format!("Invalid block hash {hash}"))
+    }
+
+    async fn request<R: serde::de::DeserializeOwned>(
+        &self,
+        method: &str,
+        params: jsonrpsee::core::params::ArrayParams,
+    ) -> Result<R> {
+        let client = WsClientBuilder::default().build(&self.url).await?;
+        Ok(client.request(method, params).await?)
+    }
+
+    fn wait_until_ready(&mut self) -> Result<()> {
+        let started = Instant::now();
+        loop {
+            if let Some(process) = self.process.as_mut() {
+                if let Some(status) = process.try_wait()? {
+                    anyhow::bail!("Chopsticks exited during startup with {status}");
+                }
+            }
+            if runtime::block_on(Client::from_url(&self.url)).is_ok() {
+                return Ok(());
+            }
+            if started.elapsed() > STARTUP_TIMEOUT {
+                anyhow::bail!("Fork at {} not ready after {STARTUP_TIMEOUT:?}", self.url);
+            }
+            std::thread::sleep(STARTUP_POLL_INTERVAL);
+        }
+    }
+}
+
+impl Drop for ChopsticksFork {
+    fn drop(&mut self) {
+        if let Some(mut process) = self.process.take() {
+            let _ = process.kill();
+            let _ = process.wait();
+        }
+    }
+}
+
+/// Key of a contract storage entry in the child trie `trie_id`, hashed as
+/// pallet-contracts hashes variable-sized keys, with `Blake2_128Concat`.
+fn child_storage_key(trie_id: &[u8], key: &[u8]) -> Vec<u8> {
+    let mut child_key = CHILD_STORAGE_PREFIX.to_vec();
+    child_key.extend_from_slice(trie_id);
+    child_key.extend_from_slice(&blake2_128(key));
+    child_key.extend_from_slice(key);
+    child_key
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
@@ -0,0 +1,489 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Result};
+use async_graphql::dynamic::{
+    Field, FieldFuture, FieldValue, InputValue, Object, ResolverContext, Scalar, Schema, TypeRef,
+};
+use async_graphql::Value as GraphqlValue;
+use contract_transcode::Value;
+use scale_info::{form::PortableForm, PortableRegistry, Type, TypeDef, TypeDefPrimitive};
+use serde_json::{json, Value as JsonValue};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use super::contract::ink::values::{byte_string_len, json_value, key_string, Collection};
+use super::contract::ContractInstance;
+use super::Nonce;
+
+/// Scalar of the values that have no GraphQL type, such as enums with fields.
+const JSON_SCALAR: &str = "JSON";
+
+/// How a value of the contract is typed and rendered in GraphQL.
+#[derive(Debug, Clone)]
+enum Shape {
+    Bool,
+    /// Integers of up to 32 bits.
+    Int,
+    /// Wider integers, rendered as decimal strings as they do not fit a GraphQL `Int`.
+    BigInt,
+    /// Strings, chars and accounts.
+    Text,
+    /// Byte strings and hashes, rendered as 0x-hex unless they are printable text.
+    Bytes,
+    Json,
+    Option(Box<Shape>),
+    List(Box<Shape>),
+    /// A struct, registered as an object type of that name.
+    Object(String),
+}
+
+impl Shape {
+    fn type_ref(&self) -> TypeRef {
+        // async-graphql only builds lists one level deep, nested ones are left as JSON.
+        match self {
+            Shape::Option(inner) => match &**inner {
+                Shape::List(elem) => match elem.element() {
+                    Some((name, false)) => TypeRef::named_nn_list(name),
+                    Some((name, true)) => TypeRef::named_list(name),
+                    None => TypeRef::named(JSON_SCALAR),
+                },
+                inner => TypeRef::named(inner.element().map_or(JSON_SCALAR, |(name, _)| name)),
+            },
+            Shape::List(elem) => match elem.element() {
+                Some((name, false)) => TypeRef::named_nn_list_nn(name),
+                Some((name, true)) => TypeRef::named_list_nn(name),
+                None => TypeRef::named_nn(JSON_SCALAR),
+            },
+            shape => TypeRef::named_nn(shape.name().unwrap_or(JSON_SCALAR)),
+        }
+    }
+
+    /// The type name of a list element, and whether it is nullable.
+    fn element(&self) -> Option<(&str, bool)> {
+        match self {
+            Shape::Option(inner) => inner.name().map(|name| (name, true)),
+            shape => shape.name().map(|name| (name, false)),
+        }
+    }
+
+    /// The type name of a shape which is neither an option nor a list.
+    fn name(&self) -> Option<&str> {
+        match self {
+            Shape::Bool => Some(TypeRef::BOOLEAN),
+            Shape::Int => Some(TypeRef::INT),
+            Shape::BigInt | Shape::Text | Shape::Bytes => Some(TypeRef::STRING),
+            Shape::Json => Some(JSON_SCALAR),
+            Shape::Object(name) => Some(name),
+            Shape::Option(_) | Shape::List(_) => None,
+        }
+    }
+}
+
+/// Maps the types of a contract's metadata to shapes, collecting its struct types.
+struct Mapper<'a> {
+    registry: &'a PortableRegistry,
+    /// Prefix of the object types, as contracts may have structs of the same name.
+    prefix: String,
+    shapes: HashMap<u32, Shape>,
+    objects: Objects,
+}
+
+impl<'a> Mapper<'a> {
+    fn new(registry: &'a PortableRegistry, prefix: String) -> Self {
+        Self {
+            registry,
+            prefix,
+            shapes: HashMap::new(),
+            objects: BTreeMap::new(),
+        }
+    }
+
+    fn shape(&mut self, id: u32) -> Shape {
+        if let Some(shape) = self.shapes.get(&id) {
+            return shape.clone();
+        }
+        let registry = self.registry;
+        let ty = match registry.resolve(id) {
+            Some(ty) => ty,
+            None => return Shape::Json,
+        };
+        let name = ty.path.segments.last().map(String::as_str);
+
+        let shape = match &ty.type_def {
+            TypeDef::Primitive(primitive) => match primitive {
+                TypeDefPrimitive::Bool => Shape::Bool,
+                TypeDefPrimitive::Char | TypeDefPrimitive::Str => Shape::Text,
+                TypeDefPrimitive::U8
+                | TypeDefPrimitive::U16
+                | TypeDefPrimitive::U32
+                | TypeDefPrimitive::I8
+                | TypeDefPrimitive::I16
+                | TypeDefPrimitive::I32 => Shape::Int,
+                _ => Shape::BigInt,
+            },
+            TypeDef::Compact(compact) => self.shape(compact.type_param.id),
+            TypeDef::Sequence(_) | TypeDef::Array(_) if byte_string_len(registry, id).is_some() => {
+                Shape::Bytes
+            }
+            TypeDef::Sequence(sequence) => {
+                Shape::List(Box::new(self.shape(sequence.type_param.id)))
+            }
+            TypeDef::Array(array) => Shape::List(Box::new(self.shape(array.type_param.id))),
+            TypeDef::Variant(variant) if name == Some("Option") => {
+                match variant
+                    .variants
+                    .iter()
+                    .find(|variant| variant.name == "Some")
+                {
+                    Some(some) if some.fields.len() == 1 => {
+                        Shape::Option(Box::new(self.shape(some.fields[0].ty.id)))
+                    }
+                    _ => Shape::Json,
+                }
+            }
+            TypeDef::Composite(_) if matches!(name, Some("AccountId" | "AccountId32")) => {
+                Shape::Text
+            }
+            TypeDef::Composite(_) if matches!(name, Some("Hash" | "H256")) => Shape::Bytes,
+            TypeDef::Composite(composite) => match Collection::of(registry, ty) {
+                Some(Collection::Set { elem }) => Shape::List(Box::new(self.shape(elem))),
+                Some(Collection::Map { .. }) => Shape::Json,
+                None if composite.fields.len() == 1 && composite.fields[0].name.is_none() => {
+                    self.shape(composite.fields[0].ty.id)
+                }
+                None if !composite.fields.is_empty()
+                    && composite.fields.iter().all(|field| field.name.is_some()) =>
+                {
+                    let object = self.object_name(ty, id);
+                    // Registered before the fields, which may refer to the struct itself.
+                    self.shapes.insert(id, Shape::Object(object.clone()));
+                    let fields = composite
+                        .fields
+                        .iter()
+                        .map(|field| {
+                            let name = field.name.clone().unwrap_or_default();
+                            (name, self.shape(field.ty.id))
+                        })
+                        .collect();
+                    self.objects.insert(object.clone(), fields);
+                    Shape::Object(object)
+                }
+                None => Shape::Json,
+            },
+            _ => Shape::Json,
+        };
+        self.shapes.insert(id, shape.clone());
+        shape
+    }
+
+    fn object_name(&self, ty: &Type<PortableForm>, id: u32) -> String {
+        let name = ty
+            .path
+            .segments
+            .last()
+            .map_or_else(|| "Type".to_owned(), |name| graphql_name(name));
+        let object = format!("{}{name}", self.prefix);
+        if self.objects.contains_key(&object) {
+            // Generic structs instantiated with different parameters share a name.
+            format!("{object}{id}")
+        } else {
+            object
+        }
+    }
+}
+
+/// Fields of the struct types of a contract, by object type name.
+type Objects = BTreeMap<String, Vec<(String, Shape)>>;
+
+/// Message of a contract exposed as a query field.
+struct MessageField {
+    label: String,
+    args: Vec<(String, ArgKind)>,
+    shape: Shape,
+    /// Whether the message returns ink's `MessageResult`, unwrapped in GraphQL.
+    lang_result: bool,
+    objects: Arc<Objects>,
+}
+
+/// How a GraphQL argument is passed to the transcoder.
+#[derive(Debug, Clone, Copy)]
+enum ArgKind {
+    Bool,
+    Int,
+    /// A string, quoted for the transcoder.
+    Str,
+    /// Any other value, given in the transcoder syntax.
+    Literal,
+}
+
+/// Builds a schema whose `Query` has a field per contract, itself with a field per
+/// read-only message.
+///
+/// Arguments and return values are typed from the metadata: booleans, integers of
+/// up to 32 bits, strings, options, sequences and structs map to their GraphQL
+/// counterpart, wider integers to decimal strings, and byte strings to 0x-hex. Enums
+/// with fields and other types are returned as `JSON` and taken as strings in the
+/// transcoder syntax.
+pub fn build_schema(contracts: &BTreeMap<String, Arc<ContractInstance>>) -> Result<Schema> {
+    let mut query = Object::new("Query");
+    let mut builder = Schema::build("Query", None, None).register(Scalar::new(JSON_SCALAR));
+
+    for (name, contract) in contracts {
+        let transcoder = contract.get_transcoder()?;
+        let metadata = transcoder.metadata();
+        let contract_type = format!("{}Contract", graphql_name(name));
+        let mut mapper = Mapper::new(metadata.registry(), graphql_name(name));
+
+        let messages = metadata
+            .spec()
+            .messages()
+            .iter()
+            .filter(|message| !message.mutates())
+            .map(|message| {
+                let args = message
+                    .args()
+                    .iter()
+                    .map(|arg| {
+                        let kind = arg_kind(metadata.registry(), arg.ty().ty().id);
+                        (arg.label().to_owned(), kind)
+                    })
+                    .collect::<Vec<_>>();
+                let (shape, lang_result) = match message.return_type().opt_type() {
+                    Some(ty) => match lang_result_ok(metadata.registry(), ty.ty().id) {
+                        Some(ok) => (mapper.shape(ok), true),
+                        None => (mapper.shape(ty.ty().id), false),
+                    },
+                    None => (Shape::Json, false),
+                };
+                (message.label().to_owned(), args, shape, lang_result)
+            })
+            .collect::<Vec<_>>();
+
+        let objects = Arc::new(mapper.objects);
+        for (object_name, fields) in objects.iter() {
+            let mut struct_object = Object::new(object_name.as_str());
+            for (field, shape) in fields {
+                struct_object = struct_object.field(struct_field(field.clone(), shape));
+            }
+            builder = builder.register(struct_object);
+        }
+
+        let mut object = Object::new(contract_type.as_str());
+        for (label, args, shape, lang_result) in messages {
+            object = object.field(message_field(Arc::new(MessageField {
+                label,
+                args,
+                shape,
+                lang_result,
+                objects: objects.clone(),
+            })));
+        }
+        builder = builder.register(object);
+
+        let contract = contract.clone();
+        query = query.field(Field::new(
+            graphql_name(name),
+            TypeRef::named_nn(contract_type),
+            move |_| {
+                let contract = contract.clone();
+                FieldFuture::new(async move { Ok(Some(FieldValue::owned_any(contract))) })
+            },
+        ));
+    }
+
+    builder
+        .register(query)
+        .finish()
+        .map_err(|err| anyhow!("Invalid GraphQL schema: {err}"))
+}
+
+fn message_field(message: Arc<MessageField>) -> Field {
+    let mut field = Field::new(graphql_name(&message.label), message.shape.type_ref(), {
+        let message = message.clone();
+        move |ctx| {
+            let message = message.clone();
+            FieldFuture::new(async move { resolve_message(ctx, &message).await })
+        }
+    });
+    for (label, kind) in &message.args {
+        let ty = match kind {
+            ArgKind::Bool => TypeRef::BOOLEAN,
+            ArgKind::Int => TypeRef::INT,
+            ArgKind::Str | ArgKind::Literal => TypeRef::STRING,
+        };
+        field = field.argument(InputValue::new(label.as_str(), TypeRef::named_nn(ty)));
+    }
+    field
+}
+
+async fn resolve_message<'a>(
+    ctx: ResolverContext<'a>,
+    message: &MessageField,
+) -> async_graphql::Result<Option<FieldValue<'a>>> {
+    let contract = ctx
+        .parent_value
+        .try_downcast_ref::<Arc<ContractInstance>>()?
+        .clone();
+    let args = message
+        .args
+        .iter()
+        .map(|(label, kind)| {
+            let arg = ctx.args.try_get(label)?;
+            Ok(match kind {
+                ArgKind::Bool => arg.boolean()?.to_string(),
+                ArgKind::Int => arg.i64()?.to_string(),
+                ArgKind::Str => format!("{:?}", arg.string()?),
+                ArgKind::Literal => arg.string()?.to_owned(),
+            })
+        })
+        .collect::<async_graphql::Result<Vec<_>>>()?;
+
+    let label = message.label.clone();
+    let nonce = Some(rand::random::<Nonce>());
+    let value = super::runtime::spawn_blocking(move || contract.call_msg(&label, args, nonce))
+        .await
+        .map_err(|err| async_graphql::Error::new(err.to_string()))?;
+
+    let value = match (&value, message.lang_result) {
+        (Value::Tuple(result), true) => match (result.ident().as_deref(), result.values().next()) {
+            (Some("Ok"), Some(ok)) => ok.clone(),
+            _ => {
+                return Err(async_graphql::Error::new(format!(
+                    "Message failed: {value}"
+                )))
+            }
+        },
+        _ => value,
+    };
+    let json = to_json(&message.objects, &message.shape, &value);
+    Ok(Some(FieldValue::value(GraphqlValue::from_json(json)?)))
+}
+
+fn struct_field(name: String, shape: &Shape) -> Field {
+    Field::new(graphql_name(&name), shape.type_ref(), move |ctx| {
+        let name = name.clone();
+        FieldFuture::new(async move {
+            match ctx.parent_value.as_value() {
+                Some(GraphqlValue::Object(fields)) => {
+                    Ok(fields.get(name.as_str()).cloned().map(FieldValue::value))
+                }
+                _ => Ok(None),
+            }
+        })
+    })
+}
+
+fn arg_kind(registry: &PortableRegistry, ty: u32) -> ArgKind {
+    match registry.resolve(ty).map(|ty| &ty.type_def) {
+        Some(TypeDef::Primitive(TypeDefPrimitive::Bool)) => ArgKind::Bool,
+        Some(TypeDef::Primitive(TypeDefPrimitive::Str)) => ArgKind::Str,
+        Some(TypeDef::Primitive(
+            TypeDefPrimitive::U8
+            | TypeDefPrimitive::U16
+            | TypeDefPrimitive::U32
+            | TypeDefPrimitive::I8
+            | TypeDefPrimitive::I16
+            | TypeDefPrimitive::I32,
+        )) => ArgKind::Int,
+        _ => ArgKind::Literal,
+    }
+}
+
+/// Type of the `Ok` value of ink's `Result<T, LangError>` return type, if `ty` is one.
+fn lang_result_ok(registry: &PortableRegistry, ty: u32) -> Option<u32> {
+    let ty = registry.resolve(ty)?;
+    let variant = match &ty.type_def {
+        TypeDef::Variant(variant) if ty.path.segments.last()? == "Result" => variant,
+        _ => return None,
+    };
+    let field = |name: &str| {
+        variant
+            .variants
+            .iter()
+            .find(|variant| variant.name == name)
+            .and_then(|variant| variant.fields.first())
+            .map(|field| field.ty.id)
+    };
+    let err = registry.resolve(field("Err")?)?;
+    if err.path.segments.last()? != "LangError" {
+        return None;
+    }
+    field("Ok")
+}
+
+/// Renders a decoded value along its shape.
+fn to_json(objects: &Objects, shape: &Shape, value: &Value) -> JsonValue {
+    match (shape, value) {
+        (Shape::Json, value) => json_value(value),
+        (Shape::Option(inner), Value::Tuple(tuple)) => match tuple.values().next() {
+            Some(value) if tuple.ident().as_deref() == Some("Some") => {
+                to_json(objects, inner, value)
+            }
+            _ => JsonValue::Null,
+        },
+        (Shape::Option(_), _) => JsonValue::Null,
+        // Newtypes are decoded as a tuple wrapping their value.
+        (shape, Value::Tuple(tuple)) if tuple.values().count() == 1 => to_json(
+            objects,
+            shape,
+            tuple.values().next().expect("counted above"),
+        ),
+        (Shape::Bool, Value::Bool(value)) => JsonValue::Bool(*value),
+        (Shape::Int, Value::UInt(value)) => json!(*value as i64),
+        (Shape::Int, Value::Int(value)) => json!(*value as i64),
+        (Shape::BigInt, Value::UInt(value)) => JsonValue::String(value.to_string()),
+        (Shape::BigInt, Value::Int(value)) => JsonValue::String(value.to_string()),
+        (Shape::Text | Shape::Bytes, Value::String(text) | Value::Literal(text)) => {
+            JsonValue::String(text.clone())
+        }
+        (Shape::Bytes, Value::Hex(hex)) => {
+            JsonValue::String(format!("0x{}", hex::encode(hex.bytes())))
+        }
+        (Shape::List(inner), Value::Seq(seq)) => {
+            let elems = seq.elems().iter();
+            JsonValue::Array(elems.map(|elem| to_json(objects, inner, elem)).collect())
+        }
+        (Shape::Object(object), Value::Map(map)) => {
+            let fields = objects.get(object).map(Vec::as_slice).unwrap_or_default();
+            JsonValue::Object(
+                map.iter()
+                    .map(|(key, value)| {
+                        let key = key_string(key);
+                        let value = match fields.iter().find(|(field, _)| *field == key) {
+                            Some((_, shape)) => to_json(objects, shape, value),
+                            None => json_value(value),
+                        };
+                        (key, value)
+                    })
+                    .collect(),
+            )
+        }
+        (_, value) => JsonValue::String(value.to_string()),
+    }
+}
+
+/// Turns a contract name, message label or field name into a GraphQL name, e.g.
+/// `PSP22::balance_of` into `PSP22__balance_of`.
+fn graphql_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    let first = name.chars().next();
+    match first {
+        Some(c) if !c.is_ascii_digit() => name,
+        _ => format!("_{name}"),
+    }
+}
@@ -0,0 +1,297 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce as AesNonce};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use sp_core::crypto::Ss58Codec;
+use sp_core::{sr25519, Pair};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+use super::{pair_signer, PairSigner};
+
+const KEY_FILE_EXTENSION: &str = "json";
+const SALT_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 12;
+const KEY_LENGTH: usize = 32;
+
+/// Scrypt cost parameters, stored alongside each key so they can be raised later
+/// without breaking existing files.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+struct ScryptParams {
+    log_n: u8,
+    r: u32,
+    p: u32,
+}
+
+impl Default for ScryptParams {
+    fn default() -> Self {
+        Self {
+            log_n: 15,
+            r: 8,
+            p: 1,
+        }
+    }
+}
+
+/// On-disk representation of a key: the secret URI encrypted with AES-256-GCM,
+/// keyed by a scrypt derivation of the password.
+#[derive(Serialize, Deserialize)]
+struct KeyFile {
+    name: String,
+    address: String,
+    scrypt: ScryptParams,
+    salt: String,
+    nonce: String,
+    encoded: String,
+}
+
+/// Public information about a stored key.
+#[derive(Debug, Clone)]
+pub struct KeyInfo {
+    pub name: String,
+    /// SS58 address of the key.
+    pub address: String,
+}
+
+/// A directory of sr25519 keys encrypted at rest, one file per key.
+pub struct Keystore {
+    dir: PathBuf,
+    /// Cost of the key derivation of newly stored keys.
+    scrypt: ScryptParams,
+}
+
+impl Keystore {
+    /// Opens the keystore at `dir`, creating the directory if needed.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create keystore at {}", dir.display()))?;
+        Ok(Self {
+            dir,
+            scrypt: ScryptParams::default(),
+        })
+    }
+
+    /// Generates a new key from a fresh mnemonic and stores it under `name`.
+    pub fn generate(&self, name: &str, password: &str) -> Result<sr25519::Public> {
+        let (_, phrase, _) = sr25519::Pair::generate_with_phrase(None);
+        self.import(name, &Zeroizing::new(phrase), password)
+    }
+
+    /// Imports an existing secret URI (mnemonic, hex seed or derivation path) under `name`.
+    pub fn import(&self, name: &str, suri: &str, password: &str) -> Result<sr25519::Public> {
+        validate_name(name)?;
+        let path = self.key_path(name);
+        if path.exists() {
+            anyhow::bail!("Key '{name}' already exists in the keystore");
+        }
+
+        let pair =
+            sr25519::Pair::from_string(suri, None).map_err(|_| anyhow!("Secret string error"))?;
+
+        let params = self.scrypt;
+        let salt: [u8; SALT_LENGTH] = rand::random();
+        let nonce: [u8; NONCE_LENGTH] = rand::random();
+        let cipher = cipher(password, &salt, params)?;
+        let encoded = cipher
+            .encrypt(AesNonce::from_slice(&nonce), suri.as_bytes())
+            .map_err(|_| anyhow!("Encrypt key failed"))?;
+
+        let key_file = KeyFile {
+            name: name.to_owned(),
+            address: pair.public().to_ss58check(),
+            scrypt: params,
+            salt: hex::encode(salt),
+            nonce: hex::encode(nonce),
+            encoded: hex::encode(encoded),
+        };
+        write_key_file(&path, &serde_json::to_vec_pretty(&key_file)?)
+            .with_context(|| format!("Failed to write key file {}", path.display()))?;
+
+        Ok(pair.public())
+    }
+
+    /// Lists the stored keys, sorted by name.
+    pub fn list(&self) -> Result<Vec<KeyInfo>> {
+        let mut keys = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(KEY_FILE_EXTENSION) {
+                continue;
+            }
+            let key_file: KeyFile = serde_json::from_slice(&fs::read(&path)?)
+                .with_context(|| format!("Invalid key file {}", path.display()))?;
+            keys.push(KeyInfo {
+                name: key_file.name,
+                address: key_file.address,
+            });
+        }
+        keys.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(keys)
+    }
+
+    /// Removes the key stored under `name`.
+    pub fn remove(&self, name: &str) -> Result<()> {
+        validate_name(name)?;
+        fs::remove_file(self.key_path(name)).with_context(|| format!("Key '{name}' not found"))
+    }
+
+    /// Decrypts the key stored under `name`.
+    pub fn pair(&self, name: &str, password: &str) -> Result<sr25519::Pair> {
+        validate_name(name)?;
+        let path = self.key_path(name);
+        let key_file: KeyFile = serde_json::from_slice(
+            &fs::read(&path).with_context(|| format!("Key '{name}' not found"))?,
+        )
+        .with_context(|| format!("Invalid key file {}", path.display()))?;
+
+        let salt = hex::decode(&key_file.salt)?;
+        let nonce = hex::decode(&key_file.nonce)?;
+        let encoded = hex::decode(&key_file.encoded)?;
+        if nonce.len() != NONCE_LENGTH {
+            anyhow::bail!("Invalid key file {}", path.display());
+        }
+
+        let cipher = cipher(password, &salt, key_file.scrypt)?;
+        let suri = Zeroizing::new(
+            cipher
+                .decrypt(AesNonce::from_slice(&nonce), encoded.as_ref())
+                .map_err(|_| anyhow!("Wrong password for key '{name}'"))?,
+        );
+        let suri = std::str::from_utf8(&suri)?;
+
+        sr25519::Pair::from_string(suri, None).map_err(|_| anyhow!("Secret string error"))
+    }
+
+    /// Prompts for the password of `name` on the terminal and decrypts the key.
+    pub fn prompt_pair(&self, name: &str) -> Result<sr25519::Pair> {
+        let password = rpassword::prompt_password(format!("Password for '{name}': "))?;
+        self.pair(name, &password)
+    }
+
+    /// Prompts for the password of `name` on the terminal and returns its signer.
+    pub fn signer(&self, name: &str) -> Result<PairSigner> {
+        Ok(pair_signer(self.prompt_pair(name)?))
+    }
+
+    fn key_path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{name}.{KEY_FILE_EXTENSION}"))
+    }
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        anyhow::bail!("Invalid key name '{name}': use only letters, digits, '-' and '_'");
+    }
+    Ok(())
+}
+
+/// Writes a new key file, readable by its owner only.
+fn write_key_file(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents)
+}
+
+fn cipher(password: &str, salt: &[u8], params: ScryptParams) -> Result<Aes256Gcm> {
+    let params = scrypt::Params::new(params.log_n, params.r, params.p, KEY_LENGTH)
+        .map_err(|_| anyhow!("Invalid scrypt parameters"))?;
+    let mut key = Zeroizing::new([0u8; KEY_LENGTH]);
+    scrypt::scrypt(password.as_bytes(), salt, &params, key.as_mut())
+        .map_err(|_| anyhow!("Derive key failed"))?;
+    Aes256Gcm::new_from_slice(key.as_ref()).map_err(|_| anyhow!("Invalid key length"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SURI: &str = "//Alice";
+
+    /// A keystore in a fresh temporary directory, with a cheap key derivation.
+    fn keystore() -> Keystore {
+        let dir =
+            std::env::temp_dir().join(format!("ink-queries-keystore-{}", rand::random::<u64>()));
+        let mut keystore = Keystore::open(dir).unwrap();
+        keystore.scrypt = ScryptParams {
+            log_n: 4,
+            ..ScryptParams::default()
+        };
+        keystore
+    }
+
+    #[test]
+    fn decrypts_imported_keys() {
+        let keystore = keystore();
+        let public = keystore.import("alice", SURI, "password").unwrap();
+        let pair = keystore.pair("alice", "password").unwrap();
+        assert_eq!(pair.public(), public);
+        assert_eq!(
+            public,
+            sr25519::Pair::from_string(SURI, None).unwrap().public()
+        );
+
+        let keys = keystore.list().unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].name, "alice");
+        assert_eq!(keys[0].address, public.to_ss58check());
+        fs::remove_dir_all(&keystore.dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_wrong_passwords() {
+        let keystore = keystore();
+        keystore.import("alice", SURI, "password").unwrap();
+        match keystore.pair("alice", "wrong") {
+            Err(err) => assert_eq!(err.to_string(), "Wrong password for key 'alice'"),
+            Ok(_) => panic!("Decrypted a key with the wrong password"),
+        }
+        fs::remove_dir_all(&keystore.dir).unwrap();
+    }
+
+    #[test]
+    fn does_not_overwrite_keys() {
+        let keystore = keystore();
+        keystore.import("alice", SURI, "password").unwrap();
+        assert!(keystore.import("alice", "//Bob", "password").is_err());
+        assert!(keystore.pair("alice", "password").is_ok());
+        fs::remove_dir_all(&keystore.dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn key_files_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let keystore = keystore();
+        keystore.import("alice", SURI, "password").unwrap();
+        let mode = fs::metadata(keystore.key_path("alice"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+        fs::remove_dir_all(&keystore.dir).unwrap();
+    }
+}
@@ -12,22 +12,105 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod address;
+#[cfg(feature = "attestation")]
+pub mod attestation;
+pub mod balance;
+pub mod cancel;
+pub mod chain;
+pub mod cluster;
 mod contract;
+pub mod endpoints;
+pub mod extrinsic;
+#[cfg(feature = "testing")]
+pub mod fixtures;
+pub mod fork;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+pub mod keystore;
+pub mod offline;
 mod phala;
-use anyhow::Result;
+pub mod poller;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+pub mod rate_limit;
+mod redact;
+pub mod runtime;
+pub mod scanner;
+pub mod secrets;
+pub mod signer;
+pub mod sink;
+pub mod subscription;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod tx_queue;
+pub mod typed;
+pub mod uos;
+pub mod watch;
+pub mod weight;
+pub mod xcm;
+use anyhow::{Context, Result};
 use phala_crypto::ecdh::EcdhKey;
 use phala_crypto::CryptoError;
 
 use sp_core::{sr25519, Pair, H256};
+use std::fs;
+use std::path::Path;
 
+pub use cancel::CancellationToken;
+pub use contract::cache::QueryCache;
+pub use contract::cassette::{Cassette, CassetteMode, CassetteTransport};
+pub use contract::compat::CompatibilityReport;
+pub use contract::diff::{DryRunDiff, DryRunOutcome};
+pub use contract::error::ErrorVariant;
+pub use contract::info::ContractInfo;
+pub use contract::ink::{
+    abi::{ContractAbi, SolidityAbi},
+    args::{ArgDiagnostic, ArgOptions, IntegerOverflow},
+    bundle::MetadataExtension,
+    config::ConfigFile,
+    decode::DecodeOptions,
+    events::EventDecoder,
+    registry::MetadataRegistry,
+    resolver::{MetadataResolver, RegistryContract, VerifierService},
+    schema::SchemaGenerator,
+    selectors::{compute_selector, SelectorTable},
+    store::{ArtifactStore, StalePolicy},
+    transcoder::{DisplayHook, DisplayHooks, InkTranscoder, TranscoderBuilder},
+    validation,
+    values::json_value,
+    vectors::{TestVector, VectorGenerator},
+    verify::{BuildRunner, BuildVerification, BuildVerifier},
+    ContractArtifacts, ContractBinary, InkMeta,
+};
+pub use contract::metrics::{MetricsHook, QueryMetrics};
+pub use contract::query::{CallOptions, CallResult, CallTimings, MessageOutcome, MutatingPolicy};
+pub use contract::trace::{CallFrame, CallKind, CallTrace, HostCall};
+#[cfg(feature = "light-client")]
+pub use contract::transport::LightClientTransport;
+pub use contract::transport::{MockTransport, RpcTransport, TransportFuture, WsTransport};
+pub use contract::ContractInstance;
+pub use endpoints::{Endpoints, FailoverEvent, FailoverHook};
+pub use phala::{idempotency_nonce, PayloadReader, PinkResponseCache};
+pub use rate_limit::{set_rate_limit, RateLimit};
+pub use redact::{SecretString, SensitiveArgs};
+pub use scanner::{CheckpointStore, EventScanner};
+pub use sp_weights::Weight;
 pub use subxt::{tx, Config, OnlineClient, PolkadotConfig as DefaultConfig};
+pub use tx_queue::TxQueue;
+pub use weight::{WeightDefaults, WeightLimit};
 
-use contract::{builder::ContractBuilder, ContractInstance};
+use contract::builder::ContractBuilder;
+use keystore::Keystore;
+use signer::{CryptoScheme, DevAccount, EcdsaMode, Keypair, ReadOnlySigner, SignerProvider};
 
 type Client = OnlineClient<DefaultConfig>;
-type Balance = u128;
+pub type AccountId = <DefaultConfig as Config>::AccountId;
+pub type Balance = u128;
 type PairSigner = tx::PairSigner<DefaultConfig, sr25519::Pair>;
 type ContractId = H256;
+/// Hash identifying a block.
+pub type BlockHash = H256;
 type Nonce = [u8; 32];
 
 pub trait KeyExtension {
@@ -46,35 +129,80 @@ impl KeyExtension for sr25519::Pair {
     }
 }
 
+#[derive(Debug)]
 pub struct SubstrateBaseConfig {
     /// Secret key URI of the node's substrate account.
-    suri: String,
+    suri: SecretString,
     /// Password for the secret key.
-    password: Option<String>,
+    password: Option<SecretString>,
+    /// Crypto scheme of the secret key.
+    scheme: CryptoScheme,
+    /// Account and signatures of an ecdsa secret key.
+    ecdsa_mode: EcdsaMode,
 }
 
 impl SubstrateBaseConfig {
     pub fn new(suri: String, password: Option<String>) -> Self {
-        Self { suri, password }
+        Self {
+            suri: suri.into(),
+            password: password.map(SecretString::from),
+            scheme: CryptoScheme::Sr25519,
+            ecdsa_mode: EcdsaMode::Substrate,
+        }
     }
 
-    /// Returns the signer for contract extrinsics.
-    pub fn signer(&self) -> Result<sr25519::Pair> {
-        Pair::from_string(&self.suri, self.password.as_ref().map(String::as_ref))
-            .map_err(|_| anyhow::anyhow!("Secret string error"))
+    /// Takes a secret key URI and password resolved by a
+    /// [`SecretResolver`](secrets::SecretResolver).
+    pub fn from_secrets(suri: SecretString, password: Option<SecretString>) -> Self {
+        Self {
+            suri,
+            password,
+            scheme: CryptoScheme::Sr25519,
+            ecdsa_mode: EcdsaMode::Substrate,
+        }
+    }
+
+    /// Sets the crypto scheme of the secret key, sr25519 by default.
+    pub fn with_scheme(mut self, scheme: CryptoScheme) -> Self {
+        self.scheme = scheme;
+        self
+    }
+
+    /// Sets how an ecdsa secret key derives its account and signs, e.g. for an
+    /// Ethereum account.
+    pub fn with_ecdsa_mode(mut self, mode: EcdsaMode) -> Self {
+        self.ecdsa_mode = mode;
+        self
+    }
+
+    /// Returns the signer for contract queries and extrinsics.
+    pub fn signer(&self) -> Result<Keypair> {
+        let suri = self.suri.expose_secret();
+        let password = self.password.as_ref().map(SecretString::expose_secret);
+        match self.scheme {
+            CryptoScheme::Ecdsa => Keypair::from_ecdsa_suri(self.ecdsa_mode, suri, password),
+            scheme => Keypair::from_suri(scheme, suri, password),
+        }
     }
 }
 
+#[deprecated(note = "`SubstrateContract` is `Clone`, clone it instead of its signer")]
 pub trait PairExtension {
     fn consume_ref(&self) -> PairSigner;
 }
 
+#[allow(deprecated)]
 impl PairExtension for PairSigner {
     fn consume_ref(&self) -> PairSigner {
         pair_signer(self.signer().clone())
     }
 }
 
+/// A contract and the account querying it
+///
+/// Cheap to clone, see [`ContractInstance`]: clone one handle into each task
+/// querying the contract concurrently.
+#[derive(Clone)]
 pub struct SubstrateContract {
     pub instance: ContractInstance,
 }
@@ -83,6 +211,10 @@ impl SubstrateContract {
     pub fn from_account(suri: String, password: Option<String>) -> Result<Self> {
         let config: SubstrateBaseConfig = SubstrateBaseConfig::new(suri, password);
 
+        Self::from_config(config)
+    }
+
+    pub fn from_config(config: SubstrateBaseConfig) -> Result<Self> {
         let instance = ContractBuilder::default()
             .init_config(config)
             .sign()?
@@ -91,8 +223,61 @@ impl SubstrateContract {
         Ok(Self { instance })
     }
 
-    pub fn get_pair_signer(&self) -> PairSigner {
-        self.instance.signer.consume_ref()
+    /// Signs with a well-known development account, for local nodes and tests.
+    pub fn dev(account: DevAccount) -> Result<Self> {
+        Self::from_signer(Box::new(account.pair()))
+    }
+
+    /// Signs with the sr25519 key of a BIP39 mnemonic phrase.
+    pub fn from_mnemonic(phrase: &str, password: Option<&str>) -> Result<Self> {
+        let (pair, _) = sr25519::Pair::from_phrase(phrase, password)
+            .map_err(|err| anyhow::anyhow!("Invalid mnemonic phrase: {err:?}"))?;
+        Self::from_signer(Box::new(pair))
+    }
+
+    /// Signs with the sr25519 key of a hex encoded 32 bytes seed.
+    pub fn from_seed_hex(seed: &str) -> Result<Self> {
+        let seed = contract::ink::try_decode_hex(seed)?;
+        let pair = sr25519::Pair::from_seed_slice(&seed)
+            .map_err(|err| anyhow::anyhow!("Invalid seed: {err:?}"))?;
+        Self::from_signer(Box::new(pair))
+    }
+
+    /// Signs with an account exported from polkadot.js as a JSON file.
+    pub fn from_keystore_json(path: &Path, password: &str) -> Result<Self> {
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read keystore file {}", path.display()))?;
+        let keypair = signer::polkadot_js::keypair_from_json(&json, password)?;
+        Self::from_signer(Box::new(keypair))
+    }
+
+    /// Loads the signer `name` from `keystore`, prompting for its password.
+    pub fn from_keystore(keystore: &Keystore, name: &str) -> Result<Self> {
+        let pair = keystore.prompt_pair(name)?;
+
+        let instance = ContractBuilder::default().with_signer(pair).build()?;
+
+        Ok(Self { instance })
+    }
+
+    /// Builds a contract whose queries are signed by `signer`, e.g. a [`signer::RemoteSigner`].
+    pub fn from_signer(signer: Box<dyn SignerProvider>) -> Result<Self> {
+        let instance = ContractBuilder::default().with_signer(signer).build()?;
+
+        Ok(Self { instance })
+    }
+
+    /// Builds a contract that only dry-runs ink messages as `origin`, without any secret.
+    pub fn read_only(origin: AccountId) -> Result<Self> {
+        let instance = ContractBuilder::default()
+            .with_signer(ReadOnlySigner::new(origin))
+            .build()?;
+
+        Ok(Self { instance })
+    }
+
+    pub fn signer(&self) -> &dyn SignerProvider {
+        self.instance.signer.as_ref()
     }
 }
 
@@ -0,0 +1,166 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use scale::Decode;
+use sp_runtime::MultiSignature;
+use std::convert::TryFrom;
+use subxt::backend::RuntimeVersion;
+use subxt::config::{DefaultExtrinsicParamsBuilder, OtherParamsFor};
+use subxt::ext::frame_metadata::RuntimeMetadataPrefixed;
+use subxt::tx::TxPayload;
+use subxt::{Config, Metadata, OfflineClient};
+
+use super::chain::ChainConfig;
+use super::extrinsic::{AccountNonce, EmptyCall};
+use super::{AccountId, Balance, BlockHash, DefaultConfig};
+
+/// What an extrinsic commits to about its chain, gathered beforehand on a connected
+/// machine, e.g. with `subxt metadata` and the `state_getRuntimeVersion` RPC.
+#[derive(Clone)]
+pub struct OfflineChain {
+    client: OfflineClient<DefaultConfig>,
+}
+
+impl OfflineChain {
+    /// `metadata` is the SCALE encoded runtime metadata, as returned by the
+    /// `state_getMetadata` RPC.
+    pub fn new(
+        genesis_hash: BlockHash,
+        spec_version: u32,
+        transaction_version: u32,
+        metadata: &[u8],
+    ) -> Result<Self> {
+        let metadata = RuntimeMetadataPrefixed::decode(&mut &metadata[..])
+            .context("Invalid runtime metadata")?;
+        let metadata = Metadata::try_from(metadata)?;
+        let runtime_version = RuntimeVersion {
+            spec_version,
+            transaction_version,
+        };
+        Ok(Self {
+            client: OfflineClient::new(genesis_hash, runtime_version, metadata),
+        })
+    }
+
+    /// SCALE encoded call of `call`, e.g. to review it on the signing device.
+    pub fn call_data<Call: TxPayload>(&self, call: &Call) -> Result<Vec<u8>> {
+        Ok(self.client.tx().call_data(call)?)
+    }
+
+    /// Bytes the signer of `call` must sign, hashed with Blake2-256 if longer than
+    /// 256 bytes, as the runtime expects.
+    pub fn signer_payload<Call: TxPayload>(
+        &self,
+        call: &Call,
+        params: &OfflineTxParams,
+    ) -> Result<Vec<u8>> {
+        let partial = self.client.tx().create_partial_signed_with_nonce(
+            call,
+            params.nonce,
+            params.extrinsic_params::<DefaultConfig>(),
+        )?;
+        Ok(partial.signer_payload())
+    }
+
+    /// The encoded extrinsic of `call` signed by `account` with `signature`, the
+    /// signature of its [`OfflineChain::signer_payload`], ready to be submitted with
+    /// `author_submitExtrinsic` from any machine.
+    pub fn signed_extrinsic<Call: TxPayload>(
+        &self,
+        call: &Call,
+        params: &OfflineTxParams,
+        account: &AccountId,
+        signature: MultiSignature,
+    ) -> Result<Vec<u8>> {
+        let address: <DefaultConfig as Config>::Address = account.clone().into();
+        let partial = self.client.tx().create_partial_signed_with_nonce(
+            call,
+            params.nonce,
+            params.extrinsic_params::<DefaultConfig>(),
+        )?;
+        let signature: <DefaultConfig as Config>::Signature = signature.into();
+        let extrinsic = partial.sign_with_address_and_signature(&address, &signature);
+        Ok(extrinsic.encoded().to_vec())
+    }
+
+    pub fn genesis_hash(&self) -> BlockHash {
+        self.client.genesis_hash()
+    }
+
+    /// Encoded signed extensions of an extrinsic followed by the data they commit to,
+    /// which the signer payload appends to the call.
+    pub(crate) fn extensions(&self, params: &OfflineTxParams) -> Result<Vec<u8>> {
+        // The extensions do not depend on the call, and the payload of an empty call
+        // is short enough not to be hashed.
+        let partial = self.client.tx().create_partial_signed_with_nonce(
+            &EmptyCall,
+            params.nonce,
+            params.extrinsic_params::<DefaultConfig>(),
+        )?;
+        Ok(partial.signer_payload())
+    }
+}
+
+/// Block a mortal extrinsic is valid from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mortality {
+    /// Number of blocks the extrinsic stays valid for.
+    pub period: u64,
+    pub block_number: u64,
+    pub block_hash: BlockHash,
+}
+
+/// Parameters of an extrinsic signed offline, which cannot be read from a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OfflineTxParams {
+    pub nonce: AccountNonce,
+    pub tip: Balance,
+    /// Immortal if not set.
+    pub mortality: Option<Mortality>,
+}
+
+impl OfflineTxParams {
+    pub fn new(nonce: AccountNonce) -> Self {
+        Self {
+            nonce,
+            tip: 0,
+            mortality: None,
+        }
+    }
+
+    pub fn tip(mut self, tip: Balance) -> Self {
+        self.tip = tip;
+        self
+    }
+
+    pub fn mortality(mut self, mortality: Mortality) -> Self {
+        self.mortality = Some(mortality);
+        self
+    }
+
+    /// Signed extension parameters set by the signer, for a chain with config `T`.
+    pub(crate) fn extrinsic_params<T: ChainConfig>(&self) -> OtherParamsFor<T> {
+        let params = DefaultExtrinsicParamsBuilder::<T>::new().tip(self.tip);
+        match &self.mortality {
+            Some(mortality) => params.mortal_unchecked(
+                mortality.block_number,
+                mortality.block_hash,
+                mortality.period,
+            ),
+            None => params,
+        }
+        .build()
+    }
+}
@@ -1,4 +1,11 @@
-use crate::substrate::{contract::ink::try_decode_hex, ContractId, KeyExtension, Nonce};
+use crate::substrate::{
+    chain::ChainConfig,
+    contract::{error::ErrorVariant, ink::try_decode_hex, query::CallTimings},
+    extrinsic::{self, ExtrinsicOutcome},
+    runtime,
+    signer::{signature_bytes, CryptoScheme, SignerProvider},
+    Balance, ContractId, KeyExtension, Nonce,
+};
 use anyhow::anyhow;
 use anyhow::Result;
 use phactory_api::prpc::phactory_api_client::PhactoryApiClient;
@@ -10,26 +17,74 @@ use phactory_api::{
 use phala_crypto::aead;
 use phala_crypto::ecdh::EcdhPublicKey;
 use phala_types::contract;
-use scale::{Decode, Encode};
-use sp_core::Pair;
+use scale::{Compact, Decode, Encode};
+use sp_core::hashing::blake2_256;
+use sp_core::{sr25519, Pair};
+use std::collections::HashMap;
 use std::convert::TryFrom as _;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use subxt::dynamic::Value;
 
 const DEPOSIT: u128 = 0;
-const TRANSFER: u128 = 0;
+const PHAT_CONTRACTS_PALLET: &str = "PhalaPhatContracts";
+const PUSH_CONTRACT_MESSAGE: &str = "push_contract_message";
+/// Prefix of the idempotency keys hashed into query nonces.
+const IDEMPOTENCY_CONTEXT: &[u8] = b"ink-queries/idempotency";
 
+#[derive(Clone)]
 struct Worker {
     pubkey: EcdhPublicKey,
 }
 
+/// Client of a pRuntime worker, shared by every query sent to it.
+///
+/// Its HTTP client keeps connections open between queries and across concurrent
+/// ones, multiplexed over HTTP/2 where the worker negotiates it, and the key of the
+/// worker is retrieved once rather than before every query.
 struct PRuntime {
     pr: PhactoryApiClient<RpcRequest>,
+    worker: Mutex<Option<Worker>>,
 }
 
 impl PRuntime {
     fn new(url: &str) -> Self {
         Self {
             pr: phactory_api::pruntime_client::new_pruntime_client(url.to_string()),
+            worker: Mutex::new(None),
+        }
+    }
+
+    /// The client of the worker at `url`, created by the first query to it.
+    fn shared(url: &str) -> Arc<Self> {
+        static CLIENTS: OnceLock<Mutex<HashMap<String, Arc<PRuntime>>>> = OnceLock::new();
+        CLIENTS
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap()
+            .entry(url.to_owned())
+            .or_insert_with(|| Arc::new(PRuntime::new(url)))
+            .clone()
+    }
+
+    async fn worker(self: &Arc<Self>) -> Result<Worker> {
+        if let Some(worker) = self.worker.lock().unwrap().clone() {
+            return Ok(worker);
         }
+        // Concurrent first queries may all retrieve the key, which is harmless.
+        let p_runtime = self.clone();
+        let worker = runtime::detached(async move { p_runtime.retrieve_worker().await }).await?;
+        *self.worker.lock().unwrap() = Some(worker.clone());
+        Ok(worker)
+    }
+
+    /// Forgets the key of the worker after a failed query, in case it changed.
+    fn reset_worker(&self) {
+        *self.worker.lock().unwrap() = None;
     }
 
     async fn retrieve_worker(&self) -> Result<Worker> {
@@ -45,46 +100,315 @@ impl PRuntime {
     }
 }
 
+/// Validity of delegate certificates issued for Phala queries by default.
+pub const DEFAULT_CERTIFICATE_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// A certificate chain delegating query signing from an account to a generated key.
+///
+/// The account signs the certificate once, then every query is signed by the
+/// delegate key, which avoids a signing round-trip to remote or hardware signers.
+#[derive(Clone)]
+pub struct DelegateCertificate {
+    certificate: prpc::Certificate,
+    delegate: sr25519::Pair,
+    expires_at: Instant,
+}
+
+impl DelegateCertificate {
+    /// Generates a delegate key and has `root` certify it for `ttl`.
+    pub fn issue(root: &dyn SignerProvider, ttl: Duration) -> Result<Self> {
+        let root_certificate = prpc::Certificate::new(
+            CertificateBody {
+                pubkey: root.public(),
+                ttl: u32::MAX,
+                config_bits: 0,
+            },
+            None,
+        );
+
+        let delegate = sr25519::Pair::generate().0;
+        let body = CertificateBody {
+            pubkey: Pair::public(&delegate).to_vec(),
+            ttl: u32::try_from(ttl.as_secs()).unwrap_or(u32::MAX),
+            config_bits: 0,
+        };
+        let root_signature = prpc::Signature {
+            signed_by: Some(Box::new(root_certificate)),
+            signature_type: signature_type(root.scheme(), root.wraps_messages()) as _,
+            signature: signature_bytes(&root.sign_message(&body.encode())?),
+        };
+        let certificate = prpc::Certificate::new(body, Some(Box::new(root_signature)));
+
+        Ok(Self {
+            certificate,
+            delegate,
+            expires_at: Instant::now() + ttl,
+        })
+    }
+
+    pub fn is_expired(&self) -> bool {
+        Instant::now() >= self.expires_at
+    }
+
+    fn sign(&self, data: &[u8]) -> prpc::Signature {
+        prpc::Signature {
+            signed_by: Some(Box::new(self.certificate.clone())),
+            signature_type: prpc::SignatureType::Sr25519 as _,
+            signature: Pair::sign(&self.delegate, data).0.to_vec(),
+        }
+    }
+}
+
+/// Keeps the delegate certificate of an account until it expires.
+pub struct CertificateCache {
+    ttl: Duration,
+    current: Mutex<Option<DelegateCertificate>>,
+}
+
+impl CertificateCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            current: Mutex::new(None),
+        }
+    }
+
+    /// Returns the cached certificate, issuing a new one with `root` if it expired.
+    ///
+    /// The cache belongs to a single signer, `root` must not change between calls.
+    pub fn get_or_issue(&self, root: &dyn SignerProvider) -> Result<DelegateCertificate> {
+        let mut current = self
+            .current
+            .lock()
+            .map_err(|_| anyhow!("Certificate cache poisoned"))?;
+        match current.as_ref() {
+            Some(certificate) if !certificate.is_expired() => Ok(certificate.clone()),
+            _ => {
+                let certificate = DelegateCertificate::issue(root, self.ttl)?;
+                *current = Some(certificate.clone());
+                Ok(certificate)
+            }
+        }
+    }
+}
+
+impl Default for CertificateCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CERTIFICATE_TTL)
+    }
+}
+
+/// The nonce of the queries made with the idempotency key `key`.
+///
+/// A query nonce is the only request-scoped value reaching the worker, so retries of
+/// a logical request sharing a key also share their nonce, and can be recognized as
+/// duplicates by whoever sees both, e.g. a [`PinkResponseCache`] or a log.
+pub fn idempotency_nonce(key: &[u8]) -> Nonce {
+    blake2_256(&[IDEMPOTENCY_CONTEXT, key].concat())
+}
+
+/// Contract, call data and transfer of a Phala query.
+type PinkQueryKey = (ContractId, Vec<u8>, Balance);
+
+struct PinkCacheEntry {
+    stored: Instant,
+    payload: Vec<u8>,
+}
+
+/// Responses of Phala queries, reused by identical queries instead of running the
+/// contract again.
+///
+/// Phat contracts may make HTTP requests with side effects, e.g. to a paid API, which
+/// every query repeats. Concurrent identical queries wait for the first one and
+/// share its response, and later ones reuse it until the TTL expires, or
+/// [`PinkResponseCache::clear`] without TTL. Failed queries are not cached.
+#[derive(Default)]
+pub struct PinkResponseCache {
+    ttl: Option<Duration>,
+    entries: Mutex<HashMap<PinkQueryKey, Arc<tokio::sync::Mutex<Option<PinkCacheEntry>>>>>,
+}
+
+impl PinkResponseCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// The cached response of the query of `payload` to `id`, or the response of
+    /// `live`, cached if it succeeded.
+    pub(crate) async fn get_or_query(
+        &self,
+        id: ContractId,
+        payload: &[u8],
+        transfer: Balance,
+        live: impl Future<Output = Result<Result<Vec<u8>, QueryError>>>,
+    ) -> Result<Result<Vec<u8>, QueryError>> {
+        let slot = {
+            let mut entries = self.entries.lock().unwrap();
+            if let Some(ttl) = self.ttl {
+                entries.retain(|_, slot| match slot.try_lock() {
+                    Ok(entry) => entry
+                        .as_ref()
+                        .is_none_or(|entry| entry.stored.elapsed() < ttl),
+                    // Held by a query in flight.
+                    Err(_) => true,
+                });
+            }
+            entries
+                .entry((id, payload.to_vec(), transfer))
+                .or_default()
+                .clone()
+        };
+
+        let mut entry = slot.lock().await;
+        if let Some(cached) = entry.as_ref() {
+            if self.ttl.is_none_or(|ttl| cached.stored.elapsed() < ttl) {
+                tracing::debug!(contract = %id, "Phala query served from the response cache");
+                return Ok(Ok(cached.payload.clone()));
+            }
+        }
+        let response = live.await?;
+        if let Ok(payload) = &response {
+            *entry = Some(PinkCacheEntry {
+                stored: Instant::now(),
+                payload: payload.clone(),
+            });
+        }
+        Ok(response)
+    }
+}
+
 // Copied from phat-poller crate for phat contract queries
 
-pub async fn pink_query_raw(
+/// Sends a Phala query, recording the time spent connecting to the worker,
+/// waiting for it and decrypting its response in `timings`.
+pub(crate) async fn pink_query_timed(
     url: &str,
     id: ContractId,
     call_data: Vec<u8>,
-    key: &sp_core::sr25519::Pair,
+    certificate: &DelegateCertificate,
     nonce: Nonce,
+    transfer: Balance,
+    timings: &mut CallTimings,
 ) -> Result<Result<Vec<u8>, QueryError>> {
     let query = PinkQuery::InkMessage {
         payload: call_data,
-        deposit: DEPOSIT,
-        transfer: TRANSFER,
+        // The caller must be able to afford the transfer to a payable message.
+        deposit: DEPOSIT + transfer,
+        transfer,
         estimating: false,
     };
-    let result: Result<Response, QueryError> = contract_query(url, id, query, key, nonce).await?;
+    let result: Result<Response, QueryError> =
+        contract_query_timed(url, id, query, certificate, nonce, timings).await?;
     Ok(result.map(|r| {
         let Response::Payload(payload) = r;
         payload
     }))
 }
 
-pub async fn contract_query<Request: Encode, Response: Decode>(
+/// Sends a state-changing message to a Phat contract, through the chain at `node_url`.
+///
+/// The command is pushed to the contract's message queue with
+/// `PhalaPhatContracts::push_contract_message` and executed by the cluster workers
+/// once the extrinsic is finalized. It is sent unencrypted.
+pub async fn pink_command_raw<T: ChainConfig>(
+    node_url: &str,
+    id: ContractId,
+    call_data: Vec<u8>,
+    signer: &dyn SignerProvider,
+    transfer: Balance,
+    gas_limit: u64,
+    storage_deposit_limit: Option<Balance>,
+) -> Result<ExtrinsicOutcome<T>, ErrorVariant> {
+    let command = PinkCommand::InkMessage {
+        nonce: rand::random::<Nonce>().to_vec(),
+        message: call_data,
+        transfer,
+        gas_limit,
+        storage_deposit_limit,
+    };
+    let payload = CommandPayload::Plain(command.encode());
+
+    let call = subxt::dynamic::tx(
+        PHAT_CONTRACTS_PALLET,
+        PUSH_CONTRACT_MESSAGE,
+        vec![
+            Value::from_bytes(id),
+            Value::from_bytes(payload.encode()),
+            Value::u128(DEPOSIT + transfer),
+        ],
+    );
+    extrinsic::submit(node_url, &call, signer).await
+}
+
+/// Sends `query` to a Phat contract and reads the payload of its response in place.
+///
+/// Suited to sidevm and HTTP-style contracts returning large payloads: the payload
+/// is read out of the decrypted response buffer instead of being copied out of it.
+pub async fn pink_query_stream(
+    url: &str,
+    id: ContractId,
+    query: PinkQuery,
+    certificate: &DelegateCertificate,
+    nonce: Nonce,
+) -> Result<Result<PayloadReader, QueryError>> {
+    let mut timings = CallTimings::default();
+    let data = contract_query_raw(url, id, query, certificate, nonce, &mut timings).await?;
+    PayloadReader::from_response(data, &nonce)
+}
+
+async fn contract_query_timed<Request: Encode, Response: Decode>(
     url: &str,
     id: ContractId,
     data: Request,
-    key: &sp_core::sr25519::Pair,
+    certificate: &DelegateCertificate,
     nonce: Nonce,
+    timings: &mut CallTimings,
 ) -> Result<Response> {
+    let data = contract_query_raw(url, id, data, certificate, nonce, timings).await?;
+
+    // 7. Decode the response.
+    let response: contract::ContractQueryResponse<Response> = Decode::decode(&mut &data[..])?;
+
+    // 8. check the nonce is match the one we sent.
+    if response.nonce != nonce {
+        return Err(anyhow!("nonce mismatch"));
+    }
+
+    Ok(response.result)
+}
+
+/// Encrypts and sends a contract query, returning the decrypted response.
+async fn contract_query_raw<Request: Encode>(
+    url: &str,
+    id: ContractId,
+    data: Request,
+    certificate: &DelegateCertificate,
+    nonce: Nonce,
+    timings: &mut CallTimings,
+) -> Result<Vec<u8>> {
     // 2. Make ContractQuery
     let head = contract::ContractQueryHead { id, nonce };
     let query = contract::ContractQuery { head, data };
 
-    let p_runtime = PRuntime::new(url);
+    let connecting = Instant::now();
+    let p_runtime = PRuntime::shared(url);
 
-    let worker = p_runtime.retrieve_worker().await?;
+    let worker = p_runtime.worker().await?;
+    timings.connect = connecting.elapsed();
 
     // 3. Encrypt the ContractQuery.
+    let encrypting = Instant::now();
 
-    let ecdh_key = sp_core::sr25519::Pair::generate()
+    let ecdh_key = sr25519::Pair::generate()
         .0
         .derive_ecdh_key()
         .map_err(|_| anyhow!("Derive ecdh key failed"))?;
@@ -93,38 +417,97 @@ pub async fn contract_query<Request: Encode, Response: Decode>(
     let encrypted_data = EncryptedData::encrypt(&ecdh_key, &worker.pubkey, iv, &query.encode())
         .map_err(|_| anyhow!("Encrypt data failed"))?;
 
-    let data_cert_body = CertificateBody {
-        pubkey: key.public().to_vec(),
-        ttl: u32::MAX,
-        config_bits: 0,
-    };
-    let data_cert = prpc::Certificate::new(data_cert_body, None);
-    let data_signature = prpc::Signature {
-        signed_by: Some(Box::new(data_cert)),
-        signature_type: prpc::SignatureType::Sr25519 as _,
-        signature: key.sign(&encrypted_data.encode()).0.to_vec(),
-    };
+    let data_signature = certificate.sign(&encrypted_data.encode());
 
     let request = prpc::ContractQueryRequest::new(encrypted_data, Some(data_signature));
+    let encrypted = encrypting.elapsed();
 
     // 5. Do the RPC call.
-    let response = p_runtime.pr.contract_query(request).await?;
+    let sending = Instant::now();
+    let querying = p_runtime.clone();
+    let response = runtime::detached(async move { querying.pr.contract_query(request).await })
+        .await
+        .inspect_err(|_| p_runtime.reset_worker())?;
+    timings.rpc = sending.elapsed();
 
     // 6. Decrypt the response.
+    let decrypting = Instant::now();
     let encrypted_data = response.decode_encrypted_data()?;
-    let data = encrypted_data
-        .decrypt(&ecdh_key)
-        .map_err(|_| anyhow!("Decrypt data failed"))?;
+    let data = encrypted_data.decrypt(&ecdh_key).map_err(|_| {
+        p_runtime.reset_worker();
+        anyhow!("Decrypt data failed")
+    })?;
+    timings.decrypt = encrypted + decrypting.elapsed();
+    Ok(data)
+}
 
-    // 7. Decode the response.
-    let response: contract::ContractQueryResponse<Response> = Decode::decode(&mut &data[..])?;
+/// Reader over the payload of a query response, kept in the decrypted buffer.
+pub struct PayloadReader {
+    data: Vec<u8>,
+    position: usize,
+}
 
-    // 8. check the nonce is match the one we sent.
-    if response.nonce != nonce {
-        return Err(anyhow!("nonce mismatch"));
+impl PayloadReader {
+    /// Locates the payload of an encoded `ContractQueryResponse<Result<Response, QueryError>>`.
+    fn from_response(mut data: Vec<u8>, nonce: &Nonce) -> Result<Result<Self, QueryError>> {
+        let mut input = &data[..];
+        if Nonce::decode(&mut input)? != *nonce {
+            return Err(anyhow!("nonce mismatch"));
+        }
+        match u8::decode(&mut input)? {
+            0 => {}
+            1 => return Ok(Err(QueryError::decode(&mut input)?)),
+            tag => anyhow::bail!("Invalid query result variant {tag}"),
+        }
+        // `Response::Payload` is the only response variant.
+        match u8::decode(&mut input)? {
+            0 => {}
+            tag => anyhow::bail!("Invalid query response variant {tag}"),
+        }
+        let len = Compact::<u32>::decode(&mut input)?.0 as usize;
+        if input.len() < len {
+            anyhow::bail!("Query payload is truncated");
+        }
+
+        let position = data.len() - input.len();
+        data.truncate(position + len);
+        Ok(Ok(Self { data, position }))
     }
 
-    Ok(response.result)
+    /// Number of bytes left to read.
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+}
+
+impl io::Read for PayloadReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.remaining());
+        buf[..len].copy_from_slice(&self.data[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+impl futures_io::AsyncRead for PayloadReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(io::Read::read(self.get_mut(), buf))
+    }
+}
+
+fn signature_type(scheme: CryptoScheme, wrapped: bool) -> prpc::SignatureType {
+    match (scheme, wrapped) {
+        (CryptoScheme::Sr25519, false) => prpc::SignatureType::Sr25519,
+        (CryptoScheme::Ed25519, false) => prpc::SignatureType::Ed25519,
+        (CryptoScheme::Ecdsa, false) => prpc::SignatureType::Ecdsa,
+        (CryptoScheme::Sr25519, true) => prpc::SignatureType::Sr25519WrapBytes,
+        (CryptoScheme::Ed25519, true) => prpc::SignatureType::Ed25519WrapBytes,
+        (CryptoScheme::Ecdsa, true) => prpc::SignatureType::EcdsaWrapBytes,
+    }
 }
 
 #[derive(Debug, Encode, Decode)]
@@ -148,6 +531,27 @@ pub enum PinkQuery {
     SidevmQuery(Vec<u8>),
 }
 
+// Copied from the pink command types in phala-blockchain/crates/phactory
+
+#[derive(Debug, Encode, Decode)]
+pub enum PinkCommand {
+    InkMessage {
+        nonce: Vec<u8>,
+        message: Vec<u8>,
+        /// Amount of tokens transfer from the caller to the target contract.
+        transfer: u128,
+        gas_limit: u64,
+        storage_deposit_limit: Option<u128>,
+    },
+}
+
+/// Payload of a contract message. Only plain payloads are built here, encrypted
+/// ones would follow as the second variant.
+#[derive(Debug, Encode, Decode)]
+pub enum CommandPayload {
+    Plain(Vec<u8>),
+}
+
 #[derive(Debug, Encode, Decode)]
 pub enum QueryError {
     BadOrigin,
@@ -0,0 +1,216 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use contract_transcode::Value;
+use rand::Rng;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::contract::error::ErrorVariant;
+use super::contract::ContractInstance;
+use super::Nonce;
+
+/// Number of queries run at the same time by default.
+const DEFAULT_CONCURRENCY: usize = 4;
+
+/// Index of a query registered with [`Poller::register`].
+pub type PollId = usize;
+
+/// A new value, or an error, read by a recurring query.
+#[derive(Debug)]
+pub struct PollUpdate {
+    pub id: PollId,
+    pub message: String,
+    pub value: Result<Value, ErrorVariant>,
+}
+
+struct PollTask {
+    contract: Arc<ContractInstance>,
+    message: String,
+    args: Vec<String>,
+    interval: Duration,
+    /// Set while a query of the task runs, so that slow queries do not pile up.
+    in_flight: AtomicBool,
+    /// Last value sent, to skip unchanged values.
+    last: Mutex<Option<Value>>,
+}
+
+/// Runs registered queries at their interval and sends the decoded values on a
+/// channel.
+///
+/// A value equal to the previous one of the same query is not sent again, while
+/// every error is. Phala queries are sent with a fresh random nonce.
+pub struct Poller {
+    tasks: Vec<PollTask>,
+    concurrency: usize,
+    jitter: Duration,
+}
+
+impl Default for Poller {
+    fn default() -> Self {
+        Self {
+            tasks: Vec::new(),
+            concurrency: DEFAULT_CONCURRENCY,
+            jitter: Duration::ZERO,
+        }
+    }
+}
+
+impl Poller {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of queries running at the same time.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Delays each query by a random duration up to `jitter`, to spread the load of
+    /// queries sharing an interval.
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Queries `message` of `contract` with `args` every `interval`.
+    pub fn register(
+        &mut self,
+        contract: Arc<ContractInstance>,
+        message: impl Into<String>,
+        args: Vec<String>,
+        interval: Duration,
+    ) -> PollId {
+        self.tasks.push(PollTask {
+            contract,
+            message: message.into(),
+            args,
+            interval,
+            in_flight: AtomicBool::new(false),
+            last: Mutex::new(None),
+        });
+        self.tasks.len() - 1
+    }
+
+    /// Starts polling, until the returned handle is dropped.
+    pub fn start(self) -> (PollerHandle, Receiver<PollUpdate>) {
+        let tasks = Arc::new(self.tasks);
+        let (updates_tx, updates_rx) = mpsc::channel();
+        let (jobs_tx, jobs_rx) = mpsc::channel::<PollId>();
+        let jobs_rx = Arc::new(Mutex::new(jobs_rx));
+        let (stop_tx, stop_rx) = mpsc::channel();
+
+        let mut threads = Vec::with_capacity(self.concurrency + 1);
+        for _ in 0..self.concurrency {
+            let tasks = tasks.clone();
+            let jobs_rx = jobs_rx.clone();
+            let updates_tx = updates_tx.clone();
+            threads.push(thread::spawn(move || loop {
+                let id = match jobs_rx.lock().unwrap().recv() {
+                    Ok(id) => id,
+                    Err(_) => return,
+                };
+                run_task(id, &tasks[id], &updates_tx);
+            }));
+        }
+        let jitter = self.jitter;
+        threads.push(thread::spawn(move || {
+            schedule(&tasks, jitter, &jobs_tx, &stop_rx)
+        }));
+
+        let handle = PollerHandle {
+            stop: Some(stop_tx),
+            threads,
+        };
+        (handle, updates_rx)
+    }
+}
+
+/// Stops the [`Poller`] when dropped, waiting for running queries to finish.
+pub struct PollerHandle {
+    stop: Option<Sender<()>>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl PollerHandle {
+    /// Stops polling, same as dropping the handle.
+    pub fn stop(self) {}
+}
+
+impl Drop for PollerHandle {
+    fn drop(&mut self) {
+        // Disconnecting the stop channel ends the scheduler, whose job channel then
+        // disconnects and ends the workers.
+        self.stop.take();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn schedule(tasks: &[PollTask], jitter: Duration, jobs: &Sender<PollId>, stop: &Receiver<()>) {
+    let mut queue: BinaryHeap<_> = (0..tasks.len())
+        .map(|id| Reverse((Instant::now() + random_delay(jitter), id)))
+        .collect();
+
+    while let Some(Reverse((due, id))) = queue.peek().copied() {
+        let wait = due.saturating_duration_since(Instant::now());
+        match stop.recv_timeout(wait) {
+            Err(RecvTimeoutError::Timeout) => {}
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        queue.pop();
+        let task = &tasks[id];
+        if !task.in_flight.swap(true, Ordering::AcqRel) && jobs.send(id).is_err() {
+            return;
+        }
+        let next = Instant::now() + task.interval + random_delay(jitter);
+        queue.push(Reverse((next, id)));
+    }
+}
+
+fn run_task(id: PollId, task: &PollTask, updates: &Sender<PollUpdate>) {
+    let nonce = rand::random::<Nonce>();
+    let value = task
+        .contract
+        .call_msg(&task.message, task.args.clone(), Some(nonce));
+    task.in_flight.store(false, Ordering::Release);
+
+    if let Ok(value) = &value {
+        let mut last = task.last.lock().unwrap();
+        if last.as_ref() == Some(value) {
+            return;
+        }
+        *last = Some(value.clone());
+    }
+    let _ = updates.send(PollUpdate {
+        id,
+        message: task.message.clone(),
+        value,
+    });
+}
+
+fn random_delay(jitter: Duration) -> Duration {
+    if jitter.is_zero() {
+        return Duration::ZERO;
+    }
+    rand::thread_rng().gen_range(Duration::ZERO..=jitter)
+}
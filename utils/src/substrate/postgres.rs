@@ -0,0 +1,495 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use contract_transcode::Value;
+use scale_info::{PortableRegistry, TypeDef, TypeDefPrimitive};
+use std::convert::TryFrom;
+use std::time::{Duration, UNIX_EPOCH};
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{Client as PgClient, NoTls};
+
+use super::contract::ink::values::{byte_string_len, json_value, key_string};
+use super::contract::ContractInstance;
+use super::runtime;
+use super::scanner::{Checkpoint, CheckpointFuture, CheckpointStore};
+use super::sink::EventRecord;
+
+/// Columns of every event table, before the event fields.
+const RECORD_COLUMNS: &str = "id BIGSERIAL PRIMARY KEY, \
+    block_number BIGINT, \
+    block_hash BYTEA NOT NULL, \
+    block_timestamp TIMESTAMPTZ, \
+    contract TEXT NOT NULL";
+
+/// SQL type of an event field column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Boolean,
+    Integer,
+    BigInt,
+    /// Integers beyond the `BIGINT` range, e.g. balances.
+    Numeric,
+    Text,
+    Bytea,
+    /// Any other type, stored as the JSON rendering of its decoded value.
+    Jsonb,
+}
+
+impl ColumnType {
+    fn of(registry: &PortableRegistry, id: u32) -> Self {
+        let ty = match registry.resolve(id) {
+            Some(ty) => ty,
+            None => return ColumnType::Jsonb,
+        };
+        let name = ty.path.segments.last().map(String::as_str);
+        match &ty.type_def {
+            TypeDef::Primitive(primitive) => match primitive {
+                TypeDefPrimitive::Bool => ColumnType::Boolean,
+                TypeDefPrimitive::Char | TypeDefPrimitive::Str => ColumnType::Text,
+                TypeDefPrimitive::U8
+                | TypeDefPrimitive::U16
+                | TypeDefPrimitive::I8
+                | TypeDefPrimitive::I16
+                | TypeDefPrimitive::I32 => ColumnType::Integer,
+                TypeDefPrimitive::U32 | TypeDefPrimitive::I64 => ColumnType::BigInt,
+                _ => ColumnType::Numeric,
+            },
+            TypeDef::Compact(compact) => Self::of(registry, compact.type_param.id),
+            TypeDef::Sequence(_) | TypeDef::Array(_) if byte_string_len(registry, id).is_some() => {
+                ColumnType::Bytea
+            }
+            // Columns are nullable, `None` is stored as `NULL`.
+            TypeDef::Variant(variant) if name == Some("Option") => {
+                match variant
+                    .variants
+                    .iter()
+                    .find(|variant| variant.name == "Some")
+                {
+                    Some(some) if some.fields.len() == 1 => {
+                        Self::of(registry, some.fields[0].ty.id)
+                    }
+                    _ => ColumnType::Jsonb,
+                }
+            }
+            TypeDef::Composite(_) if matches!(name, Some("AccountId" | "AccountId32")) => {
+                ColumnType::Text
+            }
+            TypeDef::Composite(_) if matches!(name, Some("Hash" | "H256")) => ColumnType::Bytea,
+            TypeDef::Composite(composite) if composite.fields.len() == 1 => {
+                Self::of(registry, composite.fields[0].ty.id)
+            }
+            _ => ColumnType::Jsonb,
+        }
+    }
+
+    fn sql(&self) -> &'static str {
+        match self {
+            ColumnType::Boolean => "BOOLEAN",
+            ColumnType::Integer => "INTEGER",
+            ColumnType::BigInt => "BIGINT",
+            ColumnType::Numeric => "NUMERIC",
+            ColumnType::Text => "TEXT",
+            ColumnType::Bytea => "BYTEA",
+            ColumnType::Jsonb => "JSONB",
+        }
+    }
+
+    /// Placeholder of the parameter `index`, `NUMERIC` and `JSONB` values being sent
+    /// as text.
+    fn placeholder(&self, index: usize) -> String {
+        match self {
+            ColumnType::Numeric | ColumnType::Jsonb => {
+                format!("CAST(${index}::TEXT AS {})", self.sql())
+            }
+            _ => format!("${index}"),
+        }
+    }
+
+    fn param(&self, value: Option<&Value>) -> Result<Box<dyn ToSql + Sync + Send>> {
+        let value = value.and_then(unwrap_option);
+        let mismatch = || format!("Cannot store {value:?} as {}", self.sql());
+        Ok(match self {
+            ColumnType::Boolean => Box::new(match value {
+                Some(Value::Bool(value)) => Some(*value),
+                None => None,
+                Some(_) => anyhow::bail!(mismatch()),
+            }),
+            ColumnType::Integer => Box::new(match value {
+                Some(value) => Some(integer::<i32>(value).with_context(mismatch)?),
+                None => None,
+            }),
+            ColumnType::BigInt => Box::new(match value {
+                Some(value) => Some(integer::<i64>(value).with_context(mismatch)?),
+                None => None,
+            }),
+            ColumnType::Bytea => Box::new(match value {
+                Some(value) => Some(bytes(value).with_context(mismatch)?),
+                None => None,
+            }),
+            ColumnType::Numeric => Box::new(value.map(|value| match unwrap_newtype(value) {
+                Value::UInt(value) => value.to_string(),
+                Value::Int(value) => value.to_string(),
+                value => json_value(value).to_string(),
+            })),
+            ColumnType::Text => Box::new(value.map(|value| match unwrap_newtype(value) {
+                Value::String(value) | Value::Literal(value) => value.clone(),
+                value => value.to_string(),
+            })),
+            ColumnType::Jsonb => Box::new(value.map(|value| json_value(value).to_string())),
+        })
+    }
+}
+
+/// Table of an event, with a column per field.
+#[derive(Debug, Clone)]
+pub struct EventTable {
+    /// Label of the event in the contract metadata, e.g. `Transfer`.
+    pub event: String,
+    pub table: String,
+    /// Fields of the event, by label, and their column types.
+    pub columns: Vec<(String, ColumnType)>,
+}
+
+impl EventTable {
+    /// `CREATE TABLE` statement of the table, a no-op if it exists.
+    pub fn to_sql(&self) -> String {
+        let fields: String = self
+            .columns
+            .iter()
+            .map(|(field, ty)| format!(", {} {}", quote(&column_name(field)), ty.sql()))
+            .collect();
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} ({RECORD_COLUMNS}{fields});",
+            quote(&self.table)
+        )
+    }
+
+    fn insert(&self, record: &EventRecord) -> Result<(String, Vec<Box<dyn ToSql + Sync + Send>>)> {
+        let timestamp = record
+            .timestamp
+            .map(|millis| UNIX_EPOCH + Duration::from_millis(millis));
+        let mut params: Vec<Box<dyn ToSql + Sync + Send>> = vec![
+            Box::new(record.block_number.map(i64::from)),
+            Box::new(record.block.as_bytes().to_vec()),
+            Box::new(timestamp),
+            Box::new(record.contract.to_string()),
+        ];
+        let mut columns = vec![
+            "block_number".to_owned(),
+            "block_hash".to_owned(),
+            "block_timestamp".to_owned(),
+            "contract".to_owned(),
+        ];
+        let mut placeholders: Vec<String> = (1..=params.len()).map(|i| format!("${i}")).collect();
+
+        for (field, ty) in &self.columns {
+            let value = event_field(&record.event, field);
+            params.push(
+                ty.param(value)
+                    .with_context(|| format!("Invalid field {field} of {}", self.event))?,
+            );
+            columns.push(quote(&column_name(field)));
+            placeholders.push(ty.placeholder(params.len()));
+        }
+        let statement = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            quote(&self.table),
+            columns.join(", "),
+            placeholders.join(", ")
+        );
+        Ok((statement, params))
+    }
+}
+
+/// SQL schema of the events of a contract, one table per event.
+///
+/// Fields of scalar types get typed columns, e.g. `NUMERIC` for balances and `BYTEA`
+/// for hashes, and the others are stored as `JSONB`. Every table also records the
+/// block and the contract that emitted the event.
+#[derive(Debug, Clone)]
+pub struct EventSchema {
+    pub tables: Vec<EventTable>,
+}
+
+impl EventSchema {
+    /// Schema of the events of `contract`, its tables being named `<prefix>_<event>`
+    /// in snake case, e.g. `psp22_transfer`.
+    pub fn from_contract(contract: &ContractInstance, prefix: &str) -> Result<Self> {
+        let transcoder = contract.get_transcoder()?;
+        let metadata = transcoder.metadata();
+        let registry = metadata.registry();
+        let tables = metadata
+            .spec()
+            .events()
+            .iter()
+            .map(|event| EventTable {
+                event: event.label().to_owned(),
+                table: table_name(prefix, event.label()),
+                columns: event
+                    .args()
+                    .iter()
+                    .map(|arg| {
+                        let ty = ColumnType::of(registry, arg.ty().ty().id);
+                        (arg.label().to_owned(), ty)
+                    })
+                    .collect(),
+            })
+            .collect();
+        Ok(Self { tables })
+    }
+
+    /// `CREATE TABLE` statements of all the tables.
+    pub fn to_sql(&self) -> String {
+        self.tables
+            .iter()
+            .map(EventTable::to_sql)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Table of the event labelled `event`.
+    pub fn table(&self, event: &str) -> Option<&EventTable> {
+        self.tables.iter().find(|table| table.event == event)
+    }
+}
+
+/// Writes decoded events to PostgreSQL, in the tables of an [`EventSchema`].
+pub struct PostgresSink {
+    client: PgClient,
+    schema: EventSchema,
+}
+
+impl PostgresSink {
+    /// Connects to the database at `config`, e.g. `host=localhost user=indexer`, and
+    /// creates the tables of `schema` unless they exist.
+    pub async fn connect(config: &str, schema: EventSchema) -> Result<Self> {
+        let (client, connection) = tokio_postgres::connect(config, NoTls)
+            .await
+            .context("Failed to connect to PostgreSQL")?;
+        runtime::spawn(async move {
+            if let Err(err) = connection.await {
+                tracing::warn!(%err, "PostgreSQL connection closed");
+            }
+        });
+        client
+            .batch_execute(&schema.to_sql())
+            .await
+            .context("Failed to create the event tables")?;
+        Ok(Self { client, schema })
+    }
+
+    pub fn schema(&self) -> &EventSchema {
+        &self.schema
+    }
+
+    /// Inserts `record` into the table of its event.
+    pub async fn insert(&self, record: &EventRecord) -> Result<()> {
+        let (statement, params) = self.statement(record)?;
+        self.client
+            .execute(&statement, &param_refs(&params))
+            .await?;
+        Ok(())
+    }
+
+    /// Inserts `records` in a single transaction, e.g. the events of a block.
+    pub async fn insert_all(&mut self, records: &[EventRecord]) -> Result<()> {
+        let statements = records
+            .iter()
+            .map(|record| self.statement(record))
+            .collect::<Result<Vec<_>>>()?;
+        let transaction = self.client.transaction().await?;
+        for (statement, params) in &statements {
+            transaction.execute(statement, &param_refs(params)).await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    fn statement(
+        &self,
+        record: &EventRecord,
+    ) -> Result<(String, Vec<Box<dyn ToSql + Sync + Send>>)> {
+        let event = record.name();
+        self.schema
+            .table(&event)
+            .with_context(|| format!("No table for event {event}"))?
+            .insert(record)
+    }
+}
+
+/// Stores the checkpoints of event scans in a `scan_checkpoints` table, keyed by the
+/// name of the scan, e.g. next to the events it indexes.
+pub struct PostgresCheckpointStore {
+    client: PgClient,
+    name: String,
+}
+
+impl PostgresCheckpointStore {
+    /// Connects to the database at `config` to store the checkpoint of the scan `name`.
+    pub async fn connect(config: &str, name: impl Into<String>) -> Result<Self> {
+        let (client, connection) = tokio_postgres::connect(config, NoTls)
+            .await
+            .context("Failed to connect to PostgreSQL")?;
+        runtime::spawn(async move {
+            if let Err(err) = connection.await {
+                tracing::warn!(%err, "PostgreSQL connection closed");
+            }
+        });
+        client
+            .batch_execute(
+                "CREATE TABLE IF NOT EXISTS scan_checkpoints \
+                 (name TEXT PRIMARY KEY, checkpoint JSONB NOT NULL, updated_at TIMESTAMPTZ NOT NULL);",
+            )
+            .await
+            .context("Failed to create the checkpoint table")?;
+        Ok(Self {
+            client,
+            name: name.into(),
+        })
+    }
+}
+
+impl CheckpointStore for PostgresCheckpointStore {
+    fn load(&self) -> CheckpointFuture<'_, Option<Checkpoint>> {
+        Box::pin(async move {
+            let row = self
+                .client
+                .query_opt(
+                    "SELECT checkpoint::TEXT FROM scan_checkpoints WHERE name = $1",
+                    &[&self.name],
+                )
+                .await?;
+            match row {
+                Some(row) => {
+                    let checkpoint: String = row.try_get(0)?;
+                    Ok(Some(serde_json::from_str(&checkpoint)?))
+                }
+                None => Ok(None),
+            }
+        })
+    }
+
+    fn save<'a>(&'a self, checkpoint: &'a Checkpoint) -> CheckpointFuture<'a, ()> {
+        Box::pin(async move {
+            let checkpoint = serde_json::to_string(checkpoint)?;
+            self.client
+                .execute(
+                    "INSERT INTO scan_checkpoints (name, checkpoint, updated_at) \
+                     VALUES ($1, CAST($2::TEXT AS JSONB), now()) \
+                     ON CONFLICT (name) DO UPDATE \
+                     SET checkpoint = EXCLUDED.checkpoint, updated_at = EXCLUDED.updated_at",
+                    &[&self.name, &checkpoint],
+                )
+                .await
+                .context("Failed to save the scan checkpoint")?;
+            Ok(())
+        })
+    }
+}
+
+fn param_refs(params: &[Box<dyn ToSql + Sync + Send>]) -> Vec<&(dyn ToSql + Sync)> {
+    params
+        .iter()
+        .map(|param| &**param as &(dyn ToSql + Sync))
+        .collect()
+}
+
+/// Value of the field `name` of a decoded event.
+fn event_field<'a>(event: &'a Value, name: &str) -> Option<&'a Value> {
+    match event {
+        Value::Map(map) => map
+            .iter()
+            .find(|(key, _)| key_string(key) == name)
+            .map(|(_, value)| value),
+        _ => None,
+    }
+}
+
+/// The value of a `Some`, `None` for a `None`.
+fn unwrap_option(value: &Value) -> Option<&Value> {
+    match value {
+        Value::Tuple(tuple) if tuple.ident().as_deref() == Some("None") => None,
+        Value::Tuple(tuple) if tuple.ident().as_deref() == Some("Some") => tuple.values().next(),
+        value => Some(value),
+    }
+}
+
+/// The field of single-field structs, e.g. the bytes of a `Hash`.
+fn unwrap_newtype(value: &Value) -> &Value {
+    match value {
+        Value::Tuple(tuple) if tuple.values().count() == 1 => {
+            unwrap_newtype(tuple.values().next().expect("one value"))
+        }
+        Value::Map(map) if map.iter().count() == 1 => {
+            unwrap_newtype(map.iter().next().expect("one entry").1)
+        }
+        value => value,
+    }
+}
+
+fn integer<T: TryFrom<u128> + TryFrom<i128>>(value: &Value) -> Option<T> {
+    match unwrap_newtype(value) {
+        Value::UInt(value) => T::try_from(*value).ok(),
+        Value::Int(value) => T::try_from(*value).ok(),
+        _ => None,
+    }
+}
+
+fn bytes(value: &Value) -> Option<Vec<u8>> {
+    match unwrap_newtype(value) {
+        Value::Hex(hex) => Some(hex.bytes().to_vec()),
+        Value::String(text) => Some(text.as_bytes().to_vec()),
+        Value::Seq(seq) => seq
+            .elems()
+            .iter()
+            .map(|elem| match elem {
+                Value::UInt(byte) => u8::try_from(*byte).ok(),
+                _ => None,
+            })
+            .collect(),
+        _ => None,
+    }
+}
+
+fn table_name(prefix: &str, event: &str) -> String {
+    let event = column_name(event);
+    match prefix {
+        "" => event,
+        prefix => format!("{}_{event}", column_name(prefix)),
+    }
+}
+
+/// Snake case identifier, e.g. `Transfer` becomes `transfer` and `OwnershipTransferred`
+/// becomes `ownership_transferred`.
+fn column_name(name: &str) -> String {
+    let mut column = String::new();
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            if !column.is_empty() && !column.ends_with('_') {
+                column.push('_');
+            }
+            column.push(c.to_ascii_lowercase());
+        } else if c.is_ascii_alphanumeric() {
+            column.push(c);
+        } else if !column.ends_with('_') {
+            column.push('_');
+        }
+    }
+    column
+}
+
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
@@ -0,0 +1,190 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use super::runtime;
+
+/// Limits of the requests sent to a node, unlimited by default.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RateLimit {
+    pub requests_per_second: Option<f64>,
+    /// Requests that may be sent at once after an idle period, 1 by default.
+    pub burst: u32,
+    /// Requests awaiting their response at any time.
+    pub max_in_flight: Option<usize>,
+}
+
+impl RateLimit {
+    pub fn per_second(requests_per_second: f64) -> Self {
+        Self {
+            requests_per_second: Some(requests_per_second),
+            burst: 1,
+            max_in_flight: None,
+        }
+    }
+
+    pub fn burst(mut self, burst: u32) -> Self {
+        self.burst = burst;
+        self
+    }
+
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = Some(max_in_flight);
+        self
+    }
+}
+
+/// Counters of a [`RateLimiter`], e.g. to export as Prometheus metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ThrottleStats {
+    pub requests: u64,
+    /// Requests delayed by the limits.
+    pub throttled: u64,
+    /// Total delay of the throttled requests.
+    pub throttled_time: Duration,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token bucket and in-flight cap applied to the requests sent to a node.
+pub struct RateLimiter {
+    limit: RateLimit,
+    bucket: Mutex<Bucket>,
+    in_flight: Option<Arc<Semaphore>>,
+    requests: AtomicU64,
+    throttled: AtomicU64,
+    throttled_micros: AtomicU64,
+}
+
+/// Held while a request is in flight.
+pub struct RatePermit {
+    _in_flight: Option<OwnedSemaphorePermit>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            bucket: Mutex::new(Bucket {
+                tokens: f64::from(limit.burst.max(1)),
+                updated: Instant::now(),
+            }),
+            in_flight: limit
+                .max_in_flight
+                .map(|max| Arc::new(Semaphore::new(max.max(1)))),
+            requests: AtomicU64::new(0),
+            throttled: AtomicU64::new(0),
+            throttled_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub fn limit(&self) -> RateLimit {
+        self.limit
+    }
+
+    /// Waits until a request may be sent, which it may until the permit is dropped.
+    pub async fn acquire(&self) -> RatePermit {
+        let started = Instant::now();
+        let in_flight = match &self.in_flight {
+            Some(semaphore) => Some(
+                semaphore
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("Rate limiter semaphore is never closed"),
+            ),
+            None => None,
+        };
+        while let Some(wait) = self.take_token() {
+            runtime::sleep(wait).await;
+        }
+
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        let waited = started.elapsed();
+        if waited >= Duration::from_millis(1) {
+            self.throttled.fetch_add(1, Ordering::Relaxed);
+            self.throttled_micros
+                .fetch_add(waited.as_micros() as u64, Ordering::Relaxed);
+            tracing::trace!(?waited, "Request throttled");
+        }
+        RatePermit {
+            _in_flight: in_flight,
+        }
+    }
+
+    pub fn stats(&self) -> ThrottleStats {
+        ThrottleStats {
+            requests: self.requests.load(Ordering::Relaxed),
+            throttled: self.throttled.load(Ordering::Relaxed),
+            throttled_time: Duration::from_micros(self.throttled_micros.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Takes a token from the bucket, or returns how long until one is available.
+    fn take_token(&self) -> Option<Duration> {
+        let rate = match self.limit.requests_per_second {
+            Some(rate) if rate > 0.0 => rate,
+            _ => return None,
+        };
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        let refilled = bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate;
+        bucket.tokens = refilled.min(f64::from(self.limit.burst.max(1)));
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        }
+    }
+}
+
+fn limiters() -> &'static Mutex<HashMap<String, Arc<RateLimiter>>> {
+    static LIMITERS: OnceLock<Mutex<HashMap<String, Arc<RateLimiter>>>> = OnceLock::new();
+    LIMITERS.get_or_init(Default::default)
+}
+
+/// Limits the requests sent to the node at `url` by queries, event scans and
+/// subscriptions, across all their connections, replacing any previous limit.
+pub fn set_rate_limit(url: &str, limit: RateLimit) -> Arc<RateLimiter> {
+    let limiter = Arc::new(RateLimiter::new(limit));
+    limiters()
+        .lock()
+        .unwrap()
+        .insert(url.to_owned(), limiter.clone());
+    limiter
+}
+
+/// The limiter of the node at `url`, e.g. to read its [`ThrottleStats`].
+pub fn rate_limiter(url: &str) -> Option<Arc<RateLimiter>> {
+    limiters().lock().unwrap().get(url).cloned()
+}
+
+/// Waits until a request may be sent to the node at `url`, `None` if it is not
+/// limited.
+pub async fn acquire(url: &str) -> Option<RatePermit> {
+    match rate_limiter(url) {
+        Some(limiter) => Some(limiter.acquire().await),
+        None => None,
+    }
+}
@@ -0,0 +1,155 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt;
+use zeroize::Zeroize;
+
+/// Characters of an argument or outcome kept in query logs.
+pub(crate) const MAX_LOGGED_LEN: usize = 128;
+/// Placeholder of the values kept out of logs and errors.
+pub(crate) const REDACTED: &str = "<redacted>";
+
+/// A secret key URI, seed or password, never printed and wiped from memory on drop.
+#[derive(Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(secret.into())
+    }
+
+    /// Returns the secret itself, to be handed to a key derivation and nothing else.
+    pub fn expose_secret(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString(<redacted>)")
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(secret: String) -> Self {
+        Self(secret)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(secret: &str) -> Self {
+        Self(secret.to_owned())
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// Message arguments whose values are kept out of query logs, error messages and
+/// serialized [`ErrorVariant`](super::ErrorVariant)s, e.g. personal data in
+/// compliance-sensitive deployments.
+///
+/// Values are replaced with `<redacted>` wherever they appear, so a short value,
+/// e.g. `true`, also hides its other occurrences in the same error.
+#[derive(Debug, Clone, Default)]
+pub struct SensitiveArgs {
+    all: bool,
+    labels: HashSet<String>,
+    message_args: HashSet<(String, String)>,
+}
+
+impl SensitiveArgs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every argument of every message.
+    pub fn all() -> Self {
+        Self {
+            all: true,
+            ..Self::default()
+        }
+    }
+
+    /// The arguments labelled `label`, in any message.
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.labels.insert(label.into());
+        self
+    }
+
+    /// The argument labelled `label` of `message`.
+    pub fn message_arg(mut self, message: impl Into<String>, label: impl Into<String>) -> Self {
+        self.message_args.insert((message.into(), label.into()));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.all && self.labels.is_empty() && self.message_args.is_empty()
+    }
+
+    /// Whether each of the `count` arguments of `message` is sensitive, given the
+    /// labels of its parameters. Unknown parameters are all deemed sensitive.
+    pub(crate) fn mask(&self, message: &str, labels: Option<&[String]>, count: usize) -> Vec<bool> {
+        if self.is_empty() {
+            return vec![false; count];
+        }
+        (0..count)
+            .map(|i| match labels.and_then(|labels| labels.get(i)) {
+                Some(label) => {
+                    self.all
+                        || self.labels.contains(label)
+                        || self
+                            .message_args
+                            .contains(&(message.to_owned(), label.clone()))
+                }
+                None => true,
+            })
+            .collect()
+    }
+}
+
+/// `text` with every occurrence of `values`, quoted or not, replaced by
+/// `<redacted>`.
+pub(crate) fn blind(text: &str, values: &[&str]) -> String {
+    let mut needles: Vec<&str> = values
+        .iter()
+        .flat_map(|value| [*value, value.trim(), value.trim().trim_matches('"')])
+        .filter(|needle| !needle.is_empty())
+        .collect();
+    // Longest first, so that a value containing another one is replaced whole.
+    needles.sort_by_key(|needle| std::cmp::Reverse(needle.len()));
+    needles.dedup();
+    needles.into_iter().fold(text.to_owned(), |text, needle| {
+        text.replace(needle, REDACTED)
+    })
+}
+
+/// Cuts `text` to at most `max` characters, noting how many were left out.
+pub(crate) fn truncate(text: &str, max: usize) -> Cow<'_, str> {
+    match text.char_indices().nth(max) {
+        None => Cow::Borrowed(text),
+        Some((end, _)) => Cow::Owned(format!(
+            "{}… ({} more chars)",
+            &text[..end],
+            text[end..].chars().count()
+        )),
+    }
+}
@@ -0,0 +1,222 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+use std::time::Duration;
+
+#[cfg(not(any(feature = "rt-tokio", feature = "rt-async-std")))]
+compile_error!("ink-queries needs a runtime, enable either `rt-tokio` or `rt-async-std`");
+
+/// Runtime driving blocking calls made outside of any tokio runtime, shared by all of
+/// them instead of starting one per call.
+#[cfg(any(
+    feature = "light-client",
+    all(feature = "rt-tokio", not(feature = "rt-async-std"))
+))]
+fn shared_runtime() -> &'static tokio::runtime::Runtime {
+    static RUNTIME: std::sync::OnceLock<tokio::runtime::Runtime> = std::sync::OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .thread_name("ink-queries")
+            .enable_all()
+            .build()
+            .expect("Failed to start the tokio runtime of ink-queries")
+    })
+}
+
+/// Enters the shared tokio runtime, for dependencies spawning their background tasks
+/// with `tokio::spawn`, whichever runtime drives the blocking API.
+#[cfg(feature = "light-client")]
+pub(crate) fn enter_tokio() -> tokio::runtime::EnterGuard<'static> {
+    shared_runtime().enter()
+}
+
+#[cfg(all(feature = "rt-tokio", not(feature = "rt-async-std")))]
+mod imp {
+    use super::shared_runtime;
+    use std::future::Future;
+    use std::time::Duration;
+    use tokio::runtime::{Handle, RuntimeFlavor};
+
+    /// The caller's runtime, unless it has a single worker, which a blocking call
+    /// holds until it returns.
+    fn caller_runtime() -> Option<Handle> {
+        Handle::try_current()
+            .ok()
+            .filter(|handle| handle.runtime_flavor() == RuntimeFlavor::MultiThread)
+    }
+
+    pub fn block_on<F>(future: F) -> F::Output
+    where
+        F: Future + Send,
+        F::Output: Send,
+    {
+        match Handle::try_current() {
+            // Runs on the caller's runtime, handing its other tasks to the other workers.
+            Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                tokio::task::block_in_place(|| handle.block_on(future))
+            }
+            // Nothing spawned on the caller's only worker runs until the call returns,
+            // so the shared runtime runs it from another thread.
+            Ok(_) => std::thread::scope(|scope| {
+                let blocking = scope.spawn(|| shared_runtime().block_on(future));
+                match blocking.join() {
+                    Ok(output) => output,
+                    Err(panic) => std::panic::resume_unwind(panic),
+                }
+            }),
+            Err(_) => shared_runtime().block_on(future),
+        }
+    }
+
+    pub fn spawn<F>(future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        match caller_runtime() {
+            Some(handle) => drop(handle.spawn(future)),
+            None => drop(shared_runtime().spawn(future)),
+        }
+    }
+
+    pub async fn detached<F>(future: F) -> F::Output
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        match Handle::try_current() {
+            Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::CurrentThread => {
+                match shared_runtime().spawn(future).await {
+                    Ok(output) => output,
+                    Err(err) => std::panic::resume_unwind(err.into_panic()),
+                }
+            }
+            _ => future.await,
+        }
+    }
+
+    pub async fn sleep(duration: Duration) {
+        tokio::time::sleep(duration).await
+    }
+
+    pub async fn spawn_blocking<F, T>(f: F) -> T
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        match tokio::task::spawn_blocking(f).await {
+            Ok(value) => value,
+            Err(err) => std::panic::resume_unwind(err.into_panic()),
+        }
+    }
+}
+
+#[cfg(feature = "rt-async-std")]
+mod imp {
+    use std::future::Future;
+    use std::time::Duration;
+
+    pub fn block_on<F>(future: F) -> F::Output
+    where
+        F: Future + Send,
+        F::Output: Send,
+    {
+        async_std::task::block_on(future)
+    }
+
+    pub fn spawn<F>(future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        drop(async_std::task::spawn(future));
+    }
+
+    pub async fn detached<F>(future: F) -> F::Output
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        future.await
+    }
+
+    pub async fn sleep(duration: Duration) {
+        async_std::task::sleep(duration).await
+    }
+
+    pub async fn spawn_blocking<F, T>(f: F) -> T
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        async_std::task::spawn_blocking(f).await
+    }
+}
+
+/// Runs `future` to completion for the blocking API, on tokio, or on async-std with
+/// the `rt-async-std` feature.
+///
+/// With tokio, calls made from a multi-thread runtime run on it rather than on a
+/// nested runtime, and other calls share a single lazily started runtime. Calls made
+/// from a current-thread runtime, e.g. `#[tokio::test]`, run on the shared runtime
+/// from another thread, as the caller's thread cannot drive anything until they
+/// complete.
+pub fn block_on<F>(future: F) -> F::Output
+where
+    F: Future + Send,
+    F::Output: Send,
+{
+    // Query futures are large enough in debug builds to overflow the stack of a
+    // spawned thread if polled in place.
+    imp::block_on(Box::pin(future))
+}
+
+/// Runs `future` in the background on the selected runtime, e.g. to drive a
+/// connection.
+///
+/// With tokio, tasks spawned from a current-thread runtime run on the shared runtime,
+/// so that blocking calls made on that runtime do not stall them.
+pub fn spawn<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    imp::spawn(future)
+}
+
+/// Runs `future`, which opens or uses connections kept across calls, where blocking
+/// calls cannot stall them.
+///
+/// With tokio, a future awaited on a current-thread runtime runs on the shared
+/// runtime: the background tasks of the connections it opens would otherwise live on
+/// the caller's only worker, which a later blocking call holds until it returns.
+pub async fn detached<F>(future: F) -> F::Output
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    imp::detached(future).await
+}
+
+/// Waits for `duration` without blocking the runtime.
+pub async fn sleep(duration: Duration) {
+    imp::sleep(duration).await
+}
+
+/// Runs the blocking `f` off the async workers of the selected runtime.
+pub async fn spawn_blocking<F, T>(f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    imp::spawn_blocking(f).await
+}
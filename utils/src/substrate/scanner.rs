@@ -0,0 +1,372 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use jsonrpsee::core::client::ClientT;
+use jsonrpsee::rpc_params;
+use jsonrpsee::ws_client::{WsClient, WsClientBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use subxt::dynamic;
+
+use super::cancel::{self, CancellationToken};
+use super::contract::ContractInstance;
+use super::extrinsic::ContractEmitted;
+use super::rate_limit;
+use super::sink::{EventRecord, EventSink};
+use super::{AccountId, BlockHash, Client};
+
+/// Blocks scanned per batch, each batch ending with a checkpoint.
+const DEFAULT_BATCH_SIZE: u32 = 100;
+
+pub type CheckpointFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// A scanned block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockCursor {
+    pub number: u32,
+    pub hash: BlockHash,
+}
+
+/// Progress of an [`EventScanner`], from which it resumes after a restart.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Last block whose events were all processed, `None` before the first one.
+    pub block: Option<BlockCursor>,
+    /// Last block processed for each contract, by address, so that contracts added
+    /// to the scanner later catch up from their own start block.
+    pub contracts: BTreeMap<String, u32>,
+}
+
+/// Where an [`EventScanner`] persists its [`Checkpoint`]s.
+pub trait CheckpointStore: Send + Sync {
+    /// The last saved checkpoint, `None` if the scan never ran.
+    fn load(&self) -> CheckpointFuture<'_, Option<Checkpoint>>;
+
+    fn save<'a>(&'a self, checkpoint: &'a Checkpoint) -> CheckpointFuture<'a, ()>;
+}
+
+/// Stores the checkpoint as a JSON file, replaced atomically on every save.
+pub struct FileCheckpointStore {
+    path: PathBuf,
+}
+
+impl FileCheckpointStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl CheckpointStore for FileCheckpointStore {
+    fn load(&self) -> CheckpointFuture<'_, Option<Checkpoint>> {
+        Box::pin(async move {
+            if !self.path.exists() {
+                return Ok(None);
+            }
+            let content = std::fs::read(&self.path)
+                .with_context(|| format!("Failed to read checkpoint {}", self.path.display()))?;
+            Ok(Some(serde_json::from_slice(&content).with_context(
+                || format!("Invalid checkpoint {}", self.path.display()),
+            )?))
+        })
+    }
+
+    fn save<'a>(&'a self, checkpoint: &'a Checkpoint) -> CheckpointFuture<'a, ()> {
+        Box::pin(async move {
+            // Written aside and renamed, a crash never leaves a truncated checkpoint.
+            let temp = self.path.with_extension("tmp");
+            std::fs::write(&temp, serde_json::to_vec_pretty(checkpoint)?)?;
+            std::fs::rename(&temp, &self.path)
+                .with_context(|| format!("Failed to save checkpoint {}", self.path.display()))
+        })
+    }
+}
+
+/// Decoded events of a range of blocks, see [`EventScanner::next_batch`].
+#[derive(Debug, Clone)]
+pub struct ScanBatch {
+    pub records: Vec<EventRecord>,
+    /// Progress once the records are written.
+    pub checkpoint: Checkpoint,
+}
+
+struct ScannedContract {
+    contract: Arc<ContractInstance>,
+    address: AccountId,
+    start: u32,
+}
+
+/// Scans the finalized blocks of a chain for the events of contracts, resuming from
+/// the checkpoint of its [`CheckpointStore`].
+///
+/// Records are delivered at least once: the checkpoint of a batch is saved after its
+/// records are written, and a batch interrupted in between is scanned again.
+pub struct EventScanner {
+    url: String,
+    store: Box<dyn CheckpointStore>,
+    contracts: Vec<ScannedContract>,
+    batch_size: u32,
+    checkpoint: Option<Checkpoint>,
+    timeout: Option<Duration>,
+    cancellation: Option<CancellationToken>,
+}
+
+impl EventScanner {
+    /// Scans the chain at `url`, persisting its progress to `store`.
+    pub fn new(url: impl Into<String>, store: Box<dyn CheckpointStore>) -> Self {
+        Self {
+            url: url.into(),
+            store,
+            contracts: Vec::new(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            checkpoint: None,
+            timeout: None,
+            cancellation: None,
+        }
+    }
+
+    /// Scans the events of `contract` from block `start`, e.g. the block it was
+    /// instantiated in, unless the checkpoint has a cursor for it.
+    pub fn contract(mut self, contract: Arc<ContractInstance>, start: u32) -> Result<Self> {
+        let address = contract
+            .address()
+            .as_ink()
+            .context("Event scans require an ink contract address")?
+            .clone();
+        self.contracts.push(ScannedContract {
+            contract,
+            address,
+            start,
+        });
+        Ok(self)
+    }
+
+    pub fn batch_size(mut self, batch_size: u32) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Time scanning a batch may take before it fails, e.g. on a node that stopped
+    /// answering, unbounded if not set.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Stops the scan once `token` is cancelled, the batch in progress failing
+    /// without being committed.
+    pub fn cancel_on(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Progress of the scan, once loaded from the store.
+    pub fn checkpoint(&self) -> Option<&Checkpoint> {
+        self.checkpoint.as_ref()
+    }
+
+    /// Decodes the events of the next blocks, `None` once the scan reached the
+    /// finalized head.
+    ///
+    /// The batch must be passed to [`EventScanner::commit`] once its records are
+    /// written, and is returned again until then.
+    pub async fn next_batch(&mut self) -> Result<Option<ScanBatch>> {
+        let cancellation = self.cancellation.clone();
+        let timeout = self.timeout;
+        cancel::run_until(self.scan_batch(), cancellation.as_ref(), timeout).await?
+    }
+
+    async fn scan_batch(&mut self) -> Result<Option<ScanBatch>> {
+        let rpc = WsClientBuilder::default().build(&self.url).await?;
+        let checkpoint = self.load(&rpc).await?;
+        let next = self
+            .contracts
+            .iter()
+            .map(|scanned| cursor(&checkpoint, scanned).map_or(scanned.start, |block| block + 1))
+            .min()
+            .context("No contract to scan")?;
+        let finalized = {
+            let _permit = rate_limit::acquire(&self.url).await;
+            finalized_number(&rpc).await?
+        };
+        if next > finalized {
+            return Ok(None);
+        }
+        let last = finalized.min(next.saturating_add(self.batch_size - 1));
+
+        let client = Client::from_url(&self.url).await?;
+        let mut records = Vec::new();
+        let mut checkpoint = checkpoint;
+        for number in next..=last {
+            let hash = {
+                let _permit = rate_limit::acquire(&self.url).await;
+                block_hash(&rpc, number).await?
+            };
+            let pending: Vec<&ScannedContract> = self
+                .contracts
+                .iter()
+                .filter(|scanned| {
+                    scanned.start <= number
+                        && cursor(&checkpoint, scanned).is_none_or(|block| block < number)
+                })
+                .collect();
+            if !pending.is_empty() {
+                let _permit = rate_limit::acquire(&self.url).await;
+                records.extend(scan_block(&client, hash, number, &pending).await?);
+            }
+            for scanned in pending {
+                checkpoint
+                    .contracts
+                    .insert(scanned.address.to_string(), number);
+            }
+            checkpoint.block = Some(BlockCursor { number, hash });
+        }
+        Ok(Some(ScanBatch {
+            records,
+            checkpoint,
+        }))
+    }
+
+    /// Saves the progress of `batch`, whose records were written.
+    pub async fn commit(&mut self, batch: ScanBatch) -> Result<()> {
+        self.store.save(&batch.checkpoint).await?;
+        self.checkpoint = Some(batch.checkpoint);
+        Ok(())
+    }
+
+    /// Scans up to the finalized head, writing the records to `sink` and flushing it
+    /// before every checkpoint.
+    ///
+    /// Returns early, without error, once the scan is cancelled.
+    pub async fn run(&mut self, sink: &mut dyn EventSink) -> Result<()> {
+        loop {
+            let batch = match self.next_batch().await {
+                Ok(Some(batch)) => batch,
+                Ok(None) => break,
+                Err(_) if self.is_cancelled() => break,
+                Err(err) => return Err(err),
+            };
+            for record in &batch.records {
+                sink.write(record)?;
+            }
+            sink.flush()?;
+            self.commit(batch).await?;
+        }
+        Ok(())
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// The current checkpoint, loaded from the store on first use and checked
+    /// against the chain, e.g. in case the store belongs to another network.
+    async fn load(&mut self, rpc: &WsClient) -> Result<Checkpoint> {
+        if let Some(checkpoint) = &self.checkpoint {
+            return Ok(checkpoint.clone());
+        }
+        let checkpoint = self.store.load().await?.unwrap_or_default();
+        if let Some(block) = &checkpoint.block {
+            let hash = {
+                let _permit = rate_limit::acquire(&self.url).await;
+                block_hash(rpc, block.number).await?
+            };
+            if hash != block.hash {
+                anyhow::bail!(
+                    "Checkpoint block {} is {:?}, but the chain has {hash:?}",
+                    block.number,
+                    block.hash
+                );
+            }
+        }
+        self.checkpoint = Some(checkpoint.clone());
+        Ok(checkpoint)
+    }
+}
+
+fn cursor(checkpoint: &Checkpoint, scanned: &ScannedContract) -> Option<u32> {
+    checkpoint
+        .contracts
+        .get(&scanned.address.to_string())
+        .copied()
+}
+
+async fn scan_block(
+    client: &Client,
+    hash: BlockHash,
+    number: u32,
+    contracts: &[&ScannedContract],
+) -> Result<Vec<EventRecord>> {
+    let events = client.events().at(hash).await?;
+    let mut emitted = Vec::new();
+    for event in events.iter() {
+        if let Some(event) = ContractEmitted::from_event(&event?)? {
+            emitted.push(event);
+        }
+    }
+    if emitted.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let timestamp = block_timestamp(client, hash).await?;
+    let mut records = Vec::new();
+    for scanned in contracts {
+        for event in scanned.contract.decode_events(&emitted)? {
+            let record =
+                EventRecord::new(hash, scanned.address.clone(), event).block_number(number);
+            records.push(match timestamp {
+                Some(timestamp) => record.timestamp(timestamp),
+                None => record,
+            });
+        }
+    }
+    Ok(records)
+}
+
+/// `Timestamp::Now` at block `hash`, in milliseconds.
+async fn block_timestamp(client: &Client, hash: BlockHash) -> Result<Option<u64>> {
+    let address = dynamic::storage("Timestamp", "Now", Vec::<dynamic::Value>::new());
+    let now = client.storage().at(hash).fetch(&address).await?;
+    Ok(match now {
+        Some(now) => now
+            .to_value()?
+            .as_u128()
+            .and_then(|now| u64::try_from(now).ok()),
+        None => None,
+    })
+}
+
+async fn block_hash(rpc: &WsClient, number: u32) -> Result<BlockHash> {
+    let hash: Option<BlockHash> = rpc
+        .request("chain_getBlockHash", rpc_params![number])
+        .await?;
+    hash.with_context(|| format!("No block {number}"))
+}
+
+async fn finalized_number(rpc: &WsClient) -> Result<u32> {
+    let hash: BlockHash = rpc.request("chain_getFinalizedHead", rpc_params![]).await?;
+    let header: serde_json::Value = rpc.request("chain_getHeader", rpc_params![hash]).await?;
+    let number = header["number"]
+        .as_str()
+        .context("Block header without number")?;
+    Ok(u32::from_str_radix(number.trim_start_matches("0x"), 16)?)
+}
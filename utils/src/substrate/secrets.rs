@@ -0,0 +1,154 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Result;
+use std::str::FromStr;
+
+use super::redact::SecretString;
+
+/// Keyring service of the secrets referenced as `keyring:<account>` by default.
+pub const DEFAULT_KEYRING_SERVICE: &str = "ink-queries";
+/// Prefix of the dev account URIs, e.g. `//Alice`, which are no secrets.
+const DEV_URI_PREFIX: &str = "//";
+
+/// Where a secret of a config file is read from, instead of the file itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretRef {
+    /// `env:<name>`, an environment variable.
+    Env(String),
+    /// `keyring:<account>`, an entry of the OS keyring, e.g. the macOS Keychain or
+    /// the Secret Service on Linux.
+    Keyring(String),
+}
+
+impl SecretRef {
+    /// The reference `value` is, or `None` if it is the secret itself.
+    ///
+    /// Secret key URIs, mnemonics and seeds never start with `<scheme>:`, so values
+    /// that do are references, and unknown schemes are errors rather than secrets.
+    pub fn parse(value: &str) -> Result<Option<Self>> {
+        let (scheme, name) = match value.split_once(':') {
+            Some((scheme, name))
+                if !scheme.is_empty() && scheme.chars().all(|c| c.is_ascii_lowercase()) =>
+            {
+                (scheme, name.trim())
+            }
+            _ => return Ok(None),
+        };
+        if name.is_empty() {
+            anyhow::bail!("Secret reference {value} names no {scheme} entry");
+        }
+        match scheme {
+            "env" => Ok(Some(SecretRef::Env(name.to_owned()))),
+            "keyring" => Ok(Some(SecretRef::Keyring(name.to_owned()))),
+            _ => anyhow::bail!(
+                "Unknown secret reference {scheme}:, expected env:<name> or keyring:<account>"
+            ),
+        }
+    }
+}
+
+impl FromStr for SecretRef {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)?.ok_or_else(|| {
+            anyhow::anyhow!("Expected env:<name> or keyring:<account>, got a secret")
+        })
+    }
+}
+
+/// Resolves the secrets of config files, e.g. `suri = "env:CONTRACT_SURI"`, so that
+/// no seed has to be written to them.
+#[derive(Debug, Clone)]
+pub struct SecretResolver {
+    keyring_service: String,
+    allow_plaintext: bool,
+}
+
+impl Default for SecretResolver {
+    fn default() -> Self {
+        Self {
+            keyring_service: DEFAULT_KEYRING_SERVICE.to_owned(),
+            allow_plaintext: true,
+        }
+    }
+}
+
+impl SecretResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Service of the keyring entries, `ink-queries` by default.
+    pub fn keyring_service(mut self, service: impl Into<String>) -> Self {
+        self.keyring_service = service.into();
+        self
+    }
+
+    /// Rejects secrets written in config files, other than dev account URIs, which
+    /// are otherwise accepted with a warning.
+    pub fn deny_plaintext(mut self) -> Self {
+        self.allow_plaintext = false;
+        self
+    }
+
+    /// The secret `value` refers to, or `value` itself if it is no reference.
+    pub fn resolve(&self, value: &str) -> Result<SecretString> {
+        match SecretRef::parse(value)? {
+            Some(reference) => self.resolve_ref(&reference),
+            None if value.starts_with(DEV_URI_PREFIX) => Ok(SecretString::from(value)),
+            None if self.allow_plaintext => {
+                tracing::warn!(
+                    "Plaintext secret in config, use env:<name> or keyring:<account> instead"
+                );
+                Ok(SecretString::from(value))
+            }
+            None => anyhow::bail!(
+                "Plaintext secrets are not allowed, use env:<name> or keyring:<account>"
+            ),
+        }
+    }
+
+    pub fn resolve_ref(&self, reference: &SecretRef) -> Result<SecretString> {
+        match reference {
+            SecretRef::Env(name) => std::env::var(name)
+                .map(SecretString::from)
+                .map_err(|err| anyhow::anyhow!("Failed to read secret env:{name}: {err}")),
+            SecretRef::Keyring(account) => self.keyring_secret(account),
+        }
+    }
+
+    #[cfg(feature = "keyring")]
+    fn keyring_secret(&self, account: &str) -> Result<SecretString> {
+        keyring::Entry::new(&self.keyring_service, account)
+            .and_then(|entry| entry.get_password())
+            .map(SecretString::from)
+            .map_err(|err| {
+                anyhow::anyhow!(
+                    "Failed to read secret keyring:{account} of service {}: {err}",
+                    self.keyring_service
+                )
+            })
+    }
+
+    #[cfg(not(feature = "keyring"))]
+    fn keyring_secret(&self, account: &str) -> Result<SecretString> {
+        anyhow::bail!(
+            "Secret keyring:{account} of service {} requires the keyring feature, use \
+             env:<name> instead",
+            self.keyring_service
+        )
+    }
+}
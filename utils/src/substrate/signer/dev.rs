@@ -0,0 +1,74 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Result};
+use sp_core::{sr25519, Pair};
+use std::str::FromStr;
+
+use super::SignerProvider;
+use crate::substrate::AccountId;
+
+/// Well-known development accounts, funded on local and test chains.
+///
+/// Their keys are derived from the public development phrase: never use them on a
+/// chain holding real funds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DevAccount {
+    Alice,
+    Bob,
+    Charlie,
+    Dave,
+    Eve,
+    Ferdie,
+}
+
+impl DevAccount {
+    /// Secret URI of the account, e.g. `//Alice`.
+    pub fn suri(&self) -> &'static str {
+        match self {
+            DevAccount::Alice => "//Alice",
+            DevAccount::Bob => "//Bob",
+            DevAccount::Charlie => "//Charlie",
+            DevAccount::Dave => "//Dave",
+            DevAccount::Eve => "//Eve",
+            DevAccount::Ferdie => "//Ferdie",
+        }
+    }
+
+    pub fn pair(&self) -> sr25519::Pair {
+        sr25519::Pair::from_string(self.suri(), None)
+            .expect("Development account URIs are valid secret URIs")
+    }
+
+    pub fn account_id(&self) -> AccountId {
+        self.pair().account_id()
+    }
+}
+
+impl FromStr for DevAccount {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let account = match s.trim_start_matches("//").to_ascii_lowercase().as_str() {
+            "alice" => DevAccount::Alice,
+            "bob" => DevAccount::Bob,
+            "charlie" => DevAccount::Charlie,
+            "dave" => DevAccount::Dave,
+            "eve" => DevAccount::Eve,
+            "ferdie" => DevAccount::Ferdie,
+            _ => return Err(anyhow!("Unknown development account {s}")),
+        };
+        Ok(account)
+    }
+}
@@ -0,0 +1,264 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Context, Result};
+use aws_sdk_kms::error::DisplayErrorContext;
+use aws_sdk_kms::primitives::Blob;
+use aws_sdk_kms::types::{MessageType, SigningAlgorithmSpec};
+use aws_sdk_kms::Client;
+use sp_core::crypto::ByteArray;
+use sp_core::hashing::blake2_256;
+use sp_core::{ecdsa, ed25519};
+use sp_runtime::MultiSignature;
+use std::convert::TryFrom;
+
+use super::{account_from_public, CryptoScheme, SignFuture, SignerProvider};
+use crate::substrate::{runtime, AccountId};
+
+const ECDSA_KEY_SPEC: &str = "ECC_SECG_P256K1";
+const ED25519_KEY_SPEC: &str = "ECC_NIST_EDWARDS25519";
+/// Pure EdDSA, not yet named by the SDK.
+const ED25519_ALGORITHM: &str = "ED25519_SHA_512";
+/// Order of the secp256k1 group.
+const SECP256K1_ORDER: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+    0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x41,
+];
+/// Tags of the DER encoding of ecdsa signatures.
+const DER_SEQUENCE: u8 = 0x30;
+const DER_INTEGER: u8 = 0x02;
+/// Tag of an uncompressed elliptic curve point.
+const UNCOMPRESSED_POINT: u8 = 0x04;
+
+/// Signs with an asymmetric key of AWS KMS, so that the key never leaves KMS.
+///
+/// Supports secp256k1 keys, `ECC_SECG_P256K1`, as ecdsa accounts and ed25519 keys,
+/// `ECC_NIST_EDWARDS25519`, as ed25519 accounts. Credentials and region are read
+/// like the AWS CLI does, e.g. from `AWS_PROFILE` or `AWS_ACCESS_KEY_ID`. Phala
+/// query certificates are signed by KMS like extrinsics.
+pub struct KmsSigner {
+    client: Client,
+    key_id: String,
+    scheme: CryptoScheme,
+    public: Vec<u8>,
+    account_id: AccountId,
+}
+
+impl KmsSigner {
+    /// Loads the public key of the KMS key `key_id`, its id, ARN or alias, which must
+    /// be of `scheme`.
+    pub fn connect(key_id: &str, scheme: CryptoScheme) -> Result<Self> {
+        if scheme == CryptoScheme::Sr25519 {
+            anyhow::bail!("AWS KMS has no sr25519 keys, expected ed25519 or ecdsa");
+        }
+        let client = Client::new(&runtime::block_on(aws_config::load_from_env()));
+        let mut signer = Self {
+            client,
+            key_id: key_id.to_owned(),
+            scheme,
+            public: Vec::new(),
+            account_id: AccountId::from([0u8; 32]),
+        };
+        signer.public = runtime::block_on(signer.load_public())?;
+        signer.account_id = account_from_public(scheme, &signer.public)?;
+        Ok(signer)
+    }
+
+    async fn load_public(&self) -> Result<Vec<u8>> {
+        let request = self.client.get_public_key().key_id(&self.key_id);
+        let output = runtime::detached(request.send()).await.map_err(|err| {
+            anyhow!(
+                "Failed to load KMS key {}: {}",
+                self.key_id,
+                DisplayErrorContext(err)
+            )
+        })?;
+        let expected = match self.scheme {
+            CryptoScheme::Ecdsa => ECDSA_KEY_SPEC,
+            _ => ED25519_KEY_SPEC,
+        };
+        let spec = output
+            .key_spec()
+            .map(|spec| spec.as_str())
+            .unwrap_or_default();
+        if spec != expected {
+            anyhow::bail!("KMS key {} is {spec}, expected {expected}", self.key_id);
+        }
+        let spki = output
+            .public_key()
+            .context("KMS returned no public key")?
+            .as_ref();
+        // The key is the bit string ending the DER encoded SubjectPublicKeyInfo.
+        let key_len = match self.scheme {
+            CryptoScheme::Ecdsa => 65,
+            _ => ed25519::Public::LEN,
+        };
+        let key = spki
+            .len()
+            .checked_sub(key_len)
+            .map(|start| &spki[start..])
+            .context("KMS returned an invalid public key")?;
+        match self.scheme {
+            CryptoScheme::Ecdsa if key[0] == UNCOMPRESSED_POINT => {
+                let mut public = Vec::with_capacity(ecdsa::Public::LEN);
+                public.push(if key[64] % 2 == 0 { 0x02 } else { 0x03 });
+                public.extend_from_slice(&key[1..33]);
+                Ok(public)
+            }
+            CryptoScheme::Ecdsa => anyhow::bail!("KMS returned a compressed public key"),
+            _ => Ok(key.to_vec()),
+        }
+    }
+
+    async fn sign_remote(&self, payload: &[u8]) -> Result<MultiSignature> {
+        // ecdsa accounts sign the BLAKE2 hash of payloads, which KMS signs as is.
+        let digest = blake2_256(payload);
+        let request = self.client.sign().key_id(&self.key_id);
+        let request = match self.scheme {
+            CryptoScheme::Ecdsa => request
+                .message(Blob::new(digest.to_vec()))
+                .message_type(MessageType::Digest)
+                .signing_algorithm(SigningAlgorithmSpec::EcdsaSha256),
+            _ => request
+                .message(Blob::new(payload.to_vec()))
+                .message_type(MessageType::Raw)
+                .signing_algorithm(SigningAlgorithmSpec::from(ED25519_ALGORITHM)),
+        };
+        let output = runtime::detached(request.send())
+            .await
+            .map_err(|err| anyhow!("KMS failed to sign: {}", DisplayErrorContext(err)))?;
+        let signature = output
+            .signature()
+            .context("KMS returned no signature")?
+            .as_ref();
+
+        match self.scheme {
+            CryptoScheme::Ecdsa => {
+                let public = ecdsa::Public::from_slice(&self.public)
+                    .map_err(|_| anyhow!("Invalid ecdsa public key"))?;
+                Ok(recoverable_signature(signature, &digest, &public)?.into())
+            }
+            _ => {
+                let signature = <[u8; 64]>::try_from(signature).map_err(|_| {
+                    anyhow!("KMS returned a signature of {} bytes", signature.len())
+                })?;
+                Ok(ed25519::Signature::from_raw(signature).into())
+            }
+        }
+    }
+}
+
+impl SignerProvider for KmsSigner {
+    fn scheme(&self) -> CryptoScheme {
+        self.scheme
+    }
+
+    fn public(&self) -> Vec<u8> {
+        self.public.clone()
+    }
+
+    fn account_id(&self) -> AccountId {
+        self.account_id.clone()
+    }
+
+    fn sign(&self, payload: &[u8]) -> Result<MultiSignature> {
+        runtime::block_on(self.sign_remote(payload))
+    }
+
+    fn sign_async<'a>(&'a self, payload: &'a [u8]) -> SignFuture<'a> {
+        Box::pin(self.sign_remote(payload))
+    }
+}
+
+/// Converts a DER encoded ecdsa signature of `digest` by `public` to the recoverable
+/// `r || s || v` form of Substrate, with the low `s` Substrate requires.
+fn recoverable_signature(
+    der: &[u8],
+    digest: &[u8; 32],
+    public: &ecdsa::Public,
+) -> Result<ecdsa::Signature> {
+    let (r, s) = parse_der_signature(der).context("KMS returned an invalid signature")?;
+    let s = if s > half_order() {
+        sub(&SECP256K1_ORDER, &s)
+    } else {
+        s
+    };
+    let mut raw = [0u8; 65];
+    raw[..32].copy_from_slice(&r);
+    raw[32..64].copy_from_slice(&s);
+    for v in 0..2 {
+        raw[64] = v;
+        let signature = ecdsa::Signature::from_raw(raw);
+        if signature.recover_prehashed(digest).as_ref() == Some(public) {
+            return Ok(signature);
+        }
+    }
+    anyhow::bail!("KMS signature does not match the public key")
+}
+
+/// `r` and `s` of `SEQUENCE { INTEGER r, INTEGER s }`.
+fn parse_der_signature(der: &[u8]) -> Option<([u8; 32], [u8; 32])> {
+    let (&tag, rest) = der.split_first()?;
+    let (&len, body) = rest.split_first()?;
+    if tag != DER_SEQUENCE || usize::from(len) != body.len() {
+        return None;
+    }
+    let (r, rest) = parse_der_integer(body)?;
+    let (s, rest) = parse_der_integer(rest)?;
+    rest.is_empty().then_some((r, s))
+}
+
+fn parse_der_integer(der: &[u8]) -> Option<([u8; 32], &[u8])> {
+    let (&tag, rest) = der.split_first()?;
+    let (&len, rest) = rest.split_first()?;
+    let len = usize::from(len);
+    if tag != DER_INTEGER || rest.len() < len {
+        return None;
+    }
+    let (value, rest) = rest.split_at(len);
+    // Strips the zero keeping integers with the high bit set positive.
+    let start = value.iter().position(|byte| *byte != 0).unwrap_or(len);
+    let value = &value[start..];
+    if value.len() > 32 {
+        return None;
+    }
+    let mut padded = [0u8; 32];
+    padded[32 - value.len()..].copy_from_slice(value);
+    Some((padded, rest))
+}
+
+fn half_order() -> [u8; 32] {
+    let mut half = [0u8; 32];
+    let mut carry = 0;
+    for (i, byte) in SECP256K1_ORDER.iter().enumerate() {
+        half[i] = (carry << 7) | (byte >> 1);
+        carry = byte & 1;
+    }
+    half
+}
+
+/// `a - b` of big-endian integers, `a` being the larger.
+fn sub(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let mut difference = [0u8; 32];
+    let mut borrow = 0i16;
+    for i in (0..32).rev() {
+        let mut byte = i16::from(a[i]) - i16::from(b[i]) - borrow;
+        borrow = i16::from(byte < 0);
+        if byte < 0 {
+            byte += 256;
+        }
+        difference[i] = byte as u8;
+    }
+    difference
+}
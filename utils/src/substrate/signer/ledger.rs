@@ -0,0 +1,179 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Result};
+use ledger_transport::APDUCommand;
+use ledger_transport_hid::hidapi::HidApi;
+use ledger_transport_hid::TransportNativeHID;
+use scale::Decode;
+use sp_runtime::MultiSignature;
+use std::future;
+
+use super::{account_from_public, CryptoScheme, SignFuture, SignerProvider};
+use crate::substrate::AccountId;
+
+const INS_GET_ADDR: u8 = 0x01;
+const INS_SIGN: u8 = 0x02;
+const INS_SIGN_RAW: u8 = 0x03;
+
+const PAYLOAD_INIT: u8 = 0x00;
+const PAYLOAD_ADD: u8 = 0x01;
+const PAYLOAD_LAST: u8 = 0x02;
+
+const CHUNK_SIZE: usize = 250;
+const HASH_LENGTH: usize = 32;
+const PUBLIC_KEY_LENGTH: usize = 32;
+const HARDENED: u32 = 0x8000_0000;
+const APDU_OK: u16 = 0x9000;
+
+const BYTES_PREFIX: &[u8] = b"<Bytes>";
+const BYTES_POSTFIX: &[u8] = b"</Bytes>";
+
+/// Signs with a key held by a Ledger device running a Substrate app.
+///
+/// Extrinsics are shown and confirmed on the device; other messages, such as Phala
+/// query certificates, are signed as wrapped raw bytes.
+pub struct LedgerSigner {
+    transport: TransportNativeHID,
+    cla: u8,
+    path: Vec<u8>,
+    scheme: CryptoScheme,
+    public: Vec<u8>,
+    account_id: AccountId,
+}
+
+impl LedgerSigner {
+    /// Connects to the first Ledger device and loads the key at
+    /// `m/44'/slip44'/account'/0'/index'` of the app identified by `cla`.
+    pub fn connect(
+        cla: u8,
+        slip44: u32,
+        account: u32,
+        index: u32,
+        scheme: CryptoScheme,
+    ) -> Result<Self> {
+        if scheme == CryptoScheme::Ecdsa {
+            anyhow::bail!("Ledger Substrate apps do not support ecdsa keys");
+        }
+
+        let hid = HidApi::new().map_err(|err| anyhow!("Failed to access HID devices: {err}"))?;
+        let transport = TransportNativeHID::new(&hid)
+            .map_err(|err| anyhow!("Failed to connect to Ledger device: {err}"))?;
+
+        let path = [44, slip44, account, 0, index]
+            .iter()
+            .flat_map(|level| (level | HARDENED).to_le_bytes())
+            .collect::<Vec<_>>();
+
+        let mut signer = Self {
+            transport,
+            cla,
+            path,
+            scheme,
+            public: Vec::new(),
+            account_id: AccountId::from([0u8; 32]),
+        };
+
+        let answer = signer.exchange(INS_GET_ADDR, 0, signer.path.clone())?;
+        if answer.len() < PUBLIC_KEY_LENGTH {
+            anyhow::bail!("Ledger returned a malformed address");
+        }
+        signer.public = answer[..PUBLIC_KEY_LENGTH].to_vec();
+        signer.account_id = account_from_public(scheme, &signer.public)?;
+
+        Ok(signer)
+    }
+
+    fn scheme_param(&self) -> u8 {
+        match self.scheme {
+            CryptoScheme::Ed25519 => 0x00,
+            _ => 0x01,
+        }
+    }
+
+    fn exchange(&self, ins: u8, p1: u8, data: Vec<u8>) -> Result<Vec<u8>> {
+        let command = APDUCommand {
+            cla: self.cla,
+            ins,
+            p1,
+            p2: self.scheme_param(),
+            data,
+        };
+        let answer = self
+            .transport
+            .exchange(&command)
+            .map_err(|err| anyhow!("Ledger communication failed: {err}"))?;
+        if answer.retcode() != APDU_OK {
+            anyhow::bail!("Ledger rejected the request (0x{:04x})", answer.retcode());
+        }
+        Ok(answer.data().to_vec())
+    }
+
+    /// Streams `payload` to the device in chunks and returns the signature.
+    fn sign_chunked(&self, ins: u8, payload: &[u8]) -> Result<MultiSignature> {
+        self.exchange(ins, PAYLOAD_INIT, self.path.clone())?;
+
+        let mut answer = Vec::new();
+        let chunks = payload.chunks(CHUNK_SIZE).collect::<Vec<_>>();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let p1 = if i + 1 == chunks.len() {
+                PAYLOAD_LAST
+            } else {
+                PAYLOAD_ADD
+            };
+            answer = self.exchange(ins, p1, chunk.to_vec())?;
+        }
+
+        MultiSignature::decode(&mut &answer[..])
+            .map_err(|_| anyhow!("Ledger returned a malformed signature"))
+    }
+}
+
+impl SignerProvider for LedgerSigner {
+    fn scheme(&self) -> CryptoScheme {
+        self.scheme
+    }
+
+    fn public(&self) -> Vec<u8> {
+        self.public.clone()
+    }
+
+    fn account_id(&self) -> AccountId {
+        self.account_id.clone()
+    }
+
+    fn sign(&self, payload: &[u8]) -> Result<MultiSignature> {
+        // Encoded extrinsics are longer than a hash, which the app cannot parse.
+        if payload.len() == HASH_LENGTH {
+            return Err(anyhow!(
+                "Ledger devices cannot sign hashed extrinsic payloads, sign the full payload"
+            ));
+        }
+        self.sign_chunked(INS_SIGN, payload)
+    }
+
+    /// The app parses the extrinsic to show it, so long payloads are not hashed.
+    fn sign_extrinsic<'a>(&'a self, payload: &'a [u8]) -> SignFuture<'a> {
+        Box::pin(future::ready(self.sign_chunked(INS_SIGN, payload)))
+    }
+
+    fn sign_message(&self, message: &[u8]) -> Result<MultiSignature> {
+        let wrapped = [BYTES_PREFIX, message, BYTES_POSTFIX].concat();
+        self.sign_chunked(INS_SIGN_RAW, &wrapped)
+    }
+
+    fn wraps_messages(&self) -> bool {
+        true
+    }
+}
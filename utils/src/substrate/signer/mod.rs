@@ -0,0 +1,473 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod dev;
+#[cfg(feature = "kms")]
+mod kms;
+#[cfg(feature = "ledger")]
+mod ledger;
+pub mod polkadot_js;
+mod read_only;
+mod remote;
+#[cfg(feature = "vault")]
+mod vault;
+
+use anyhow::{anyhow, Result};
+use sp_core::crypto::ByteArray;
+use sp_core::hashing::{blake2_256, keccak_256};
+use sp_core::{ecdsa, ed25519, sr25519, Pair, H160};
+use sp_runtime::traits::IdentifyAccount;
+use sp_runtime::{MultiSignature, MultiSigner};
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::str::FromStr;
+use subxt::client::OfflineClientT;
+use subxt::tx::{SubmittableExtrinsic, TxClient, TxPayload};
+
+use super::address::revive_account_id;
+use super::chain::ChainConfig;
+use super::extrinsic::EmptyCall;
+use super::offline::OfflineTxParams;
+use super::AccountId;
+
+pub use dev::DevAccount;
+#[cfg(feature = "kms")]
+pub use kms::KmsSigner;
+#[cfg(feature = "ledger")]
+pub use ledger::LedgerSigner;
+pub use read_only::ReadOnlySigner;
+pub use remote::RemoteSigner;
+#[cfg(feature = "vault")]
+pub use vault::{VaultSigner, DEFAULT_TRANSIT_MOUNT};
+
+/// Crypto scheme of a signing key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CryptoScheme {
+    Sr25519,
+    Ed25519,
+    /// secp256k1 keys. A raw `0x`-prefixed private key, as exported by Ethereum
+    /// wallets, is accepted as secret URI.
+    Ecdsa,
+}
+
+impl FromStr for CryptoScheme {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "sr25519" => Ok(CryptoScheme::Sr25519),
+            "ed25519" => Ok(CryptoScheme::Ed25519),
+            "ecdsa" => Ok(CryptoScheme::Ecdsa),
+            other => Err(anyhow!(
+                "Unknown crypto scheme {other}, expected `sr25519`, `ed25519` or `ecdsa`"
+            )),
+        }
+    }
+}
+
+/// Longest extrinsic payload signed as is, longer ones are signed by their Blake2-256 hash.
+const MAX_UNHASHED_PAYLOAD: usize = 256;
+
+/// How an ecdsa key derives its account and signs payloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EcdsaMode {
+    /// The account is the Blake2-256 hash of the compressed key, and payloads are
+    /// signed by their Blake2-256 hash.
+    #[default]
+    Substrate,
+    /// The account is the Ethereum address of the key, and payloads are signed by their
+    /// Keccak-256 hash, as on chains with Ethereum-compatible accounts.
+    Ethereum,
+}
+
+/// Future returned by [`SignerProvider::sign_async`].
+pub type SignFuture<'a> = Pin<Box<dyn Future<Output = Result<MultiSignature>> + Send + 'a>>;
+
+/// Anything able to sign contract queries and extrinsics on behalf of an account.
+pub trait SignerProvider: Send + Sync {
+    /// Crypto scheme of the signing key.
+    fn scheme(&self) -> CryptoScheme;
+
+    /// Raw public key bytes.
+    fn public(&self) -> Vec<u8>;
+
+    /// Account id of the signer on chain.
+    fn account_id(&self) -> AccountId;
+
+    /// Signs an arbitrary payload.
+    fn sign(&self, payload: &[u8]) -> Result<MultiSignature>;
+
+    /// Same as [`SignerProvider::sign`], without blocking the runtime while signers
+    /// holding their key elsewhere are reached.
+    fn sign_async<'a>(&'a self, payload: &'a [u8]) -> SignFuture<'a> {
+        Box::pin(future::ready(self.sign(payload)))
+    }
+
+    /// Signs the full payload of an extrinsic, its call followed by its signed extensions.
+    ///
+    /// Payloads longer than 256 bytes are signed by their hash, as the runtime expects;
+    /// signers showing the extrinsic to the user sign the full payload instead.
+    fn sign_extrinsic<'a>(&'a self, payload: &'a [u8]) -> SignFuture<'a> {
+        if payload.len() > MAX_UNHASHED_PAYLOAD {
+            let hash = blake2_256(payload);
+            Box::pin(async move { self.sign_async(&hash).await })
+        } else {
+            self.sign_async(payload)
+        }
+    }
+
+    /// Signs a message that is not an extrinsic, such as a Phala query certificate.
+    fn sign_message(&self, message: &[u8]) -> Result<MultiSignature> {
+        self.sign(message)
+    }
+
+    /// Whether [`SignerProvider::sign_message`] wraps the message in `<Bytes>..</Bytes>`
+    /// before signing it, as wallets do to keep raw messages from being valid extrinsics.
+    fn wraps_messages(&self) -> bool {
+        false
+    }
+
+    /// Whether the signer signs as an Ethereum account, whose signatures and padded
+    /// account id are rejected by chains with substrate accounts.
+    fn is_ethereum(&self) -> bool {
+        false
+    }
+}
+
+impl<S: SignerProvider + ?Sized> SignerProvider for Box<S> {
+    fn scheme(&self) -> CryptoScheme {
+        (**self).scheme()
+    }
+
+    fn public(&self) -> Vec<u8> {
+        (**self).public()
+    }
+
+    fn account_id(&self) -> AccountId {
+        (**self).account_id()
+    }
+
+    fn sign(&self, payload: &[u8]) -> Result<MultiSignature> {
+        (**self).sign(payload)
+    }
+
+    fn sign_async<'a>(&'a self, payload: &'a [u8]) -> SignFuture<'a> {
+        (**self).sign_async(payload)
+    }
+
+    fn sign_extrinsic<'a>(&'a self, payload: &'a [u8]) -> SignFuture<'a> {
+        (**self).sign_extrinsic(payload)
+    }
+
+    fn sign_message(&self, message: &[u8]) -> Result<MultiSignature> {
+        (**self).sign_message(message)
+    }
+
+    fn wraps_messages(&self) -> bool {
+        (**self).wraps_messages()
+    }
+
+    fn is_ethereum(&self) -> bool {
+        (**self).is_ethereum()
+    }
+}
+
+impl SignerProvider for sr25519::Pair {
+    fn scheme(&self) -> CryptoScheme {
+        CryptoScheme::Sr25519
+    }
+
+    fn public(&self) -> Vec<u8> {
+        Pair::public(self).0.to_vec()
+    }
+
+    fn account_id(&self) -> AccountId {
+        MultiSigner::from(Pair::public(self)).into_account().into()
+    }
+
+    fn sign(&self, payload: &[u8]) -> Result<MultiSignature> {
+        Ok(Pair::sign(self, payload).into())
+    }
+}
+
+impl SignerProvider for ed25519::Pair {
+    fn scheme(&self) -> CryptoScheme {
+        CryptoScheme::Ed25519
+    }
+
+    fn public(&self) -> Vec<u8> {
+        Pair::public(self).0.to_vec()
+    }
+
+    fn account_id(&self) -> AccountId {
+        MultiSigner::from(Pair::public(self)).into_account().into()
+    }
+
+    fn sign(&self, payload: &[u8]) -> Result<MultiSignature> {
+        Ok(Pair::sign(self, payload).into())
+    }
+}
+
+impl SignerProvider for ecdsa::Pair {
+    fn scheme(&self) -> CryptoScheme {
+        CryptoScheme::Ecdsa
+    }
+
+    fn public(&self) -> Vec<u8> {
+        Pair::public(self).0.to_vec()
+    }
+
+    fn account_id(&self) -> AccountId {
+        MultiSigner::from(Pair::public(self)).into_account().into()
+    }
+
+    fn sign(&self, payload: &[u8]) -> Result<MultiSignature> {
+        Ok(Pair::sign(self, payload).into())
+    }
+}
+
+/// An ecdsa keypair of an Ethereum account, see [`EcdsaMode::Ethereum`].
+///
+/// Its account is the Ethereum address padded as pallet-revive maps it, and its
+/// signatures verify as Ethereum signatures of the payload, not as the ecdsa
+/// signatures of a [`MultiSignature`] verified by substrate runtimes.
+pub struct EthereumPair {
+    pair: ecdsa::Pair,
+    address: H160,
+}
+
+impl EthereumPair {
+    pub fn new(pair: ecdsa::Pair) -> Result<Self> {
+        let address = ethereum_address(&Pair::public(&pair))?;
+        Ok(Self { pair, address })
+    }
+
+    /// Ethereum address of the key, the account id of chains with 20 byte accounts.
+    pub fn address(&self) -> H160 {
+        self.address
+    }
+}
+
+impl SignerProvider for EthereumPair {
+    fn scheme(&self) -> CryptoScheme {
+        CryptoScheme::Ecdsa
+    }
+
+    fn public(&self) -> Vec<u8> {
+        Pair::public(&self.pair).0.to_vec()
+    }
+
+    fn account_id(&self) -> AccountId {
+        revive_account_id(&self.address)
+    }
+
+    fn sign(&self, payload: &[u8]) -> Result<MultiSignature> {
+        Ok(self.pair.sign_prehashed(&keccak_256(payload)).into())
+    }
+
+    fn is_ethereum(&self) -> bool {
+        true
+    }
+}
+
+/// A keypair of any supported crypto scheme.
+pub enum Keypair {
+    Sr25519(sr25519::Pair),
+    Ed25519(Box<ed25519::Pair>),
+    Ecdsa(ecdsa::Pair),
+    Ethereum(Box<EthereumPair>),
+}
+
+impl Keypair {
+    /// Builds a keypair of the given scheme from a secret URI.
+    pub fn from_suri(scheme: CryptoScheme, suri: &str, password: Option<&str>) -> Result<Self> {
+        let err = |_| anyhow!("Secret string error");
+        let keypair = match scheme {
+            CryptoScheme::Sr25519 => {
+                Keypair::Sr25519(Pair::from_string(suri, password).map_err(err)?)
+            }
+            CryptoScheme::Ed25519 => {
+                Keypair::Ed25519(Box::new(Pair::from_string(suri, password).map_err(err)?))
+            }
+            CryptoScheme::Ecdsa => Keypair::Ecdsa(Pair::from_string(suri, password).map_err(err)?),
+        };
+        Ok(keypair)
+    }
+
+    /// Builds the keypair of an ecdsa key used in `mode` from a secret URI.
+    pub fn from_ecdsa_suri(mode: EcdsaMode, suri: &str, password: Option<&str>) -> Result<Self> {
+        let pair =
+            ecdsa::Pair::from_string(suri, password).map_err(|_| anyhow!("Secret string error"))?;
+        match mode {
+            EcdsaMode::Substrate => Ok(Keypair::Ecdsa(pair)),
+            EcdsaMode::Ethereum => Ok(Keypair::Ethereum(Box::new(EthereumPair::new(pair)?))),
+        }
+    }
+
+    fn inner(&self) -> &dyn SignerProvider {
+        match self {
+            Keypair::Sr25519(pair) => pair,
+            Keypair::Ed25519(pair) => &**pair,
+            Keypair::Ecdsa(pair) => pair,
+            Keypair::Ethereum(pair) => &**pair,
+        }
+    }
+}
+
+impl SignerProvider for Keypair {
+    fn scheme(&self) -> CryptoScheme {
+        self.inner().scheme()
+    }
+
+    fn public(&self) -> Vec<u8> {
+        self.inner().public()
+    }
+
+    fn account_id(&self) -> AccountId {
+        self.inner().account_id()
+    }
+
+    fn sign(&self, payload: &[u8]) -> Result<MultiSignature> {
+        self.inner().sign(payload)
+    }
+
+    fn is_ethereum(&self) -> bool {
+        self.inner().is_ethereum()
+    }
+}
+
+impl From<sr25519::Pair> for Keypair {
+    fn from(pair: sr25519::Pair) -> Self {
+        Keypair::Sr25519(pair)
+    }
+}
+
+impl From<ed25519::Pair> for Keypair {
+    fn from(pair: ed25519::Pair) -> Self {
+        Keypair::Ed25519(Box::new(pair))
+    }
+}
+
+impl From<ecdsa::Pair> for Keypair {
+    fn from(pair: ecdsa::Pair) -> Self {
+        Keypair::Ecdsa(pair)
+    }
+}
+
+/// Signs extrinsics with a [`SignerProvider`].
+///
+/// subxt signers are infallible, so the payload is signed before handing the
+/// signature to subxt, and a failing provider (e.g. an unreachable remote signer)
+/// fails the extrinsic instead.
+///
+/// Extrinsics are signed for the substrate accounts of [`ChainConfig`], so
+/// Ethereum keypairs are limited to dry-run origins.
+pub struct ExtrinsicSigner<'a> {
+    provider: &'a dyn SignerProvider,
+    account_id: AccountId,
+}
+
+impl<'a> ExtrinsicSigner<'a> {
+    pub fn new(provider: &'a dyn SignerProvider) -> Result<Self> {
+        if provider.is_ethereum() {
+            return Err(anyhow!(
+                "Ethereum keypairs cannot sign extrinsics, they can only be dry-run origins"
+            ));
+        }
+        Ok(Self {
+            account_id: provider.account_id(),
+            provider,
+        })
+    }
+
+    pub fn account_id(&self) -> &AccountId {
+        &self.account_id
+    }
+
+    /// Signs `call` as the extrinsic of the signer with the given nonce and parameters.
+    pub async fn sign<T: ChainConfig, C: OfflineClientT<T>, Call: TxPayload>(
+        &self,
+        tx: &TxClient<T, C>,
+        call: &Call,
+        params: &OfflineTxParams,
+    ) -> Result<SubmittableExtrinsic<T, C>> {
+        // subxt only hands out the payload hashed when long, the extensions of an
+        // empty call are short enough to be returned as is.
+        let mut payload = tx.call_data(call)?;
+        payload.extend(
+            tx.create_partial_signed_with_nonce(
+                &EmptyCall,
+                params.nonce,
+                params.extrinsic_params::<T>(),
+            )?
+            .signer_payload(),
+        );
+        let signature = self.provider.sign_extrinsic(&payload).await?;
+        // Partial extrinsics are not `Send`, so none is held while signing.
+        let partial = tx.create_partial_signed_with_nonce(
+            call,
+            params.nonce,
+            params.extrinsic_params::<T>(),
+        )?;
+        let address: T::Address = self.account_id.clone().into();
+        let signature: T::Signature = signature.into();
+        Ok(partial.sign_with_address_and_signature(&address, &signature))
+    }
+}
+
+/// Derives the on-chain account id of a raw public key.
+pub fn account_from_public(scheme: CryptoScheme, public: &[u8]) -> Result<AccountId> {
+    let err = |_| anyhow!("Invalid {scheme:?} public key");
+    let signer = match scheme {
+        CryptoScheme::Sr25519 => {
+            MultiSigner::from(sr25519::Public::from_slice(public).map_err(err)?)
+        }
+        CryptoScheme::Ed25519 => {
+            MultiSigner::from(ed25519::Public::from_slice(public).map_err(err)?)
+        }
+        CryptoScheme::Ecdsa => MultiSigner::from(ecdsa::Public::from_slice(public).map_err(err)?),
+    };
+    Ok(signer.into_account().into())
+}
+
+/// Ethereum address of an ecdsa key: the last 20 bytes of the Keccak-256 hash of
+/// the uncompressed key, without its `0x04` tag.
+pub fn ethereum_address(public: &ecdsa::Public) -> Result<H160> {
+    let public = libsecp256k1::PublicKey::parse_compressed(&public.0)
+        .map_err(|_| anyhow!("Invalid ecdsa public key"))?;
+    let hash = keccak_256(&public.serialize()[1..]);
+    Ok(H160::from_slice(&hash[12..]))
+}
+
+/// Splits a signature into its raw bytes.
+pub fn signature_bytes(signature: &MultiSignature) -> Vec<u8> {
+    match signature {
+        MultiSignature::Sr25519(sig) => sig.0.to_vec(),
+        MultiSignature::Ed25519(sig) => sig.0.to_vec(),
+        MultiSignature::Ecdsa(sig) => sig.0.to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ethereum_keypairs_cannot_sign_extrinsics() {
+        let ethereum = Keypair::from_ecdsa_suri(EcdsaMode::Ethereum, "//Alice", None).unwrap();
+        assert!(ExtrinsicSigner::new(&ethereum).is_err());
+        let substrate = Keypair::from_ecdsa_suri(EcdsaMode::Substrate, "//Alice", None).unwrap();
+        assert!(ExtrinsicSigner::new(&substrate).is_ok());
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Context, Result};
+use base64::Engine;
+use crypto_secretbox::aead::{Aead, KeyInit};
+use crypto_secretbox::{Nonce, XSalsa20Poly1305};
+use serde::Deserialize;
+use sp_core::{ed25519, sr25519, Pair};
+use std::convert::TryInto;
+
+use super::Keypair;
+
+const SALT_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 24;
+/// Length of the scrypt output, of which the first half is the secretbox key.
+const SCRYPT_LENGTH: usize = 64;
+const KEY_LENGTH: usize = 32;
+const PKCS8_HEADER: [u8; 16] = [48, 83, 2, 1, 1, 48, 5, 6, 3, 43, 101, 112, 4, 34, 4, 32];
+const PKCS8_DIVIDER: [u8; 5] = [161, 35, 3, 33, 0];
+const SECRET_LENGTH: usize = 64;
+
+/// An account exported from polkadot.js, version 3 of its JSON format.
+#[derive(Deserialize)]
+struct KeystoreJson {
+    encoded: String,
+    encoding: Encoding,
+}
+
+#[derive(Deserialize)]
+struct Encoding {
+    content: Vec<String>,
+    #[serde(rename = "type")]
+    ty: Vec<String>,
+    version: String,
+}
+
+/// Decrypts an account exported from polkadot.js or its extension.
+///
+/// Only sr25519 and ed25519 accounts encrypted with a password are supported.
+pub fn keypair_from_json(json: &str, password: &str) -> Result<Keypair> {
+    let keystore: KeystoreJson =
+        serde_json::from_str(json).context("Invalid polkadot.js keystore file")?;
+    let encoding = &keystore.encoding;
+    if encoding.version != "3" {
+        anyhow::bail!(
+            "Unsupported keystore version {}, expected 3",
+            encoding.version
+        );
+    }
+    if !encoding.ty.iter().any(|ty| ty == "scrypt")
+        || !encoding.ty.iter().any(|ty| ty == "xsalsa20-poly1305")
+    {
+        anyhow::bail!("Unsupported keystore encryption {:?}", encoding.ty);
+    }
+
+    let encoded = base64::engine::general_purpose::STANDARD
+        .decode(&keystore.encoded)
+        .context("Invalid keystore encoding")?;
+    let pkcs8 = decrypt(&encoded, password)?;
+    let secret = secret_key(&pkcs8)?;
+
+    let scheme = encoding
+        .content
+        .get(1)
+        .map(String::as_str)
+        .unwrap_or("sr25519");
+    match scheme {
+        "sr25519" => {
+            let secret = schnorrkel::SecretKey::from_ed25519_bytes(secret)
+                .map_err(|err| anyhow!("Invalid sr25519 secret key: {err}"))?;
+            Ok(Keypair::Sr25519(sr25519::Pair::from(secret)))
+        }
+        "ed25519" => {
+            let pair = ed25519::Pair::from_seed_slice(&secret[..32])
+                .map_err(|_| anyhow!("Invalid ed25519 secret key"))?;
+            Ok(Keypair::from(pair))
+        }
+        other => anyhow::bail!("Unsupported keystore crypto scheme {other}"),
+    }
+}
+
+/// Decrypts `salt | scrypt N, p, r | nonce | ciphertext`.
+fn decrypt(encoded: &[u8], password: &str) -> Result<Vec<u8>> {
+    let header_length = SALT_LENGTH + 12 + NONCE_LENGTH;
+    if encoded.len() < header_length {
+        anyhow::bail!("Keystore data is too short");
+    }
+    let (salt, rest) = encoded.split_at(SALT_LENGTH);
+    let read_u32 = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().expect("4 bytes"));
+    let n = read_u32(&rest[0..4]);
+    let p = read_u32(&rest[4..8]);
+    let r = read_u32(&rest[8..12]);
+    let (nonce, ciphertext) = rest[12..].split_at(NONCE_LENGTH);
+
+    if !n.is_power_of_two() {
+        anyhow::bail!("Invalid scrypt parameter N = {n}");
+    }
+    let log_n = n.trailing_zeros() as u8;
+    let params = scrypt::Params::new(log_n, r, p, SCRYPT_LENGTH)
+        .map_err(|_| anyhow!("Invalid scrypt parameters"))?;
+    let mut derived = [0u8; SCRYPT_LENGTH];
+    scrypt::scrypt(password.as_bytes(), salt, &params, &mut derived)
+        .map_err(|_| anyhow!("Derive key failed"))?;
+
+    let cipher = XSalsa20Poly1305::new_from_slice(&derived[..KEY_LENGTH])
+        .map_err(|_| anyhow!("Invalid key length"))?;
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("Wrong password for keystore file"))
+}
+
+/// Extracts the secret key from the PKCS#8 document stored in the keystore.
+fn secret_key(pkcs8: &[u8]) -> Result<&[u8]> {
+    let secret = pkcs8
+        .strip_prefix(&PKCS8_HEADER[..])
+        .context("Invalid PKCS#8 header in keystore")?;
+    if secret.len() < SECRET_LENGTH + PKCS8_DIVIDER.len()
+        || secret[SECRET_LENGTH..SECRET_LENGTH + PKCS8_DIVIDER.len()] != PKCS8_DIVIDER
+    {
+        anyhow::bail!("Invalid PKCS#8 divider in keystore");
+    }
+    Ok(&secret[..SECRET_LENGTH])
+}
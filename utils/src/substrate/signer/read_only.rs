@@ -0,0 +1,50 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Result;
+use sp_runtime::MultiSignature;
+
+use super::{CryptoScheme, SignerProvider};
+use crate::substrate::AccountId;
+
+/// An origin address without a key, enough for ink dry-runs.
+///
+/// Any attempt to sign fails, so Phala queries and extrinsics are rejected.
+pub struct ReadOnlySigner {
+    origin: AccountId,
+}
+
+impl ReadOnlySigner {
+    pub fn new(origin: AccountId) -> Self {
+        Self { origin }
+    }
+}
+
+impl SignerProvider for ReadOnlySigner {
+    fn scheme(&self) -> CryptoScheme {
+        CryptoScheme::Sr25519
+    }
+
+    fn public(&self) -> Vec<u8> {
+        self.origin.0.to_vec()
+    }
+
+    fn account_id(&self) -> AccountId {
+        self.origin.clone()
+    }
+
+    fn sign(&self, _payload: &[u8]) -> Result<MultiSignature> {
+        anyhow::bail!("Read-only contract has no signing key, only ink queries are supported")
+    }
+}
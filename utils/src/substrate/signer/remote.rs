@@ -0,0 +1,111 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Result};
+use jsonrpsee::core::client::ClientT;
+use jsonrpsee::rpc_params;
+use jsonrpsee::ws_client::{WsClient, WsClientBuilder};
+use scale::Decode;
+use sp_runtime::MultiSignature;
+use std::sync::{Arc, Mutex};
+
+use super::{account_from_public, CryptoScheme, SignFuture, SignerProvider};
+use crate::substrate::{contract::ink::try_decode_hex, runtime, AccountId};
+
+const SIGN_RAW_METHOD: &str = "signer_signRaw";
+
+/// A signer whose key lives in an external process, reached over JSON-RPC.
+///
+/// Each payload is sent to `signer_signRaw` as `[public_key_hex, payload_hex]`; the
+/// service must answer with the hex of the SCALE-encoded `MultiSignature`, which is
+/// the format returned by polkadot-js `signRaw` bridges with `withType` enabled.
+pub struct RemoteSigner {
+    url: String,
+    /// Connection to the service, opened by the first signature and reopened once lost.
+    client: Mutex<Option<Arc<WsClient>>>,
+    scheme: CryptoScheme,
+    public: Vec<u8>,
+    account_id: AccountId,
+}
+
+impl RemoteSigner {
+    pub fn new(url: String, scheme: CryptoScheme, public: Vec<u8>) -> Result<Self> {
+        let account_id = account_from_public(scheme, &public)?;
+        Ok(Self {
+            url,
+            client: Mutex::new(None),
+            scheme,
+            public,
+            account_id,
+        })
+    }
+
+    async fn connection(&self) -> Result<Arc<WsClient>> {
+        let cached = self.client.lock().unwrap().clone();
+        if let Some(client) = cached.filter(|client| client.is_connected()) {
+            return Ok(client);
+        }
+        let url = self.url.clone();
+        let client = runtime::detached(async move { WsClientBuilder::default().build(&url).await });
+        let client = Arc::new(client.await?);
+        *self.client.lock().unwrap() = Some(client.clone());
+        Ok(client)
+    }
+
+    async fn sign_remote(&self, payload: &[u8]) -> Result<MultiSignature> {
+        let client = self.connection().await?;
+        let params = rpc_params![
+            format!("0x{}", hex::encode(&self.public)),
+            format!("0x{}", hex::encode(payload))
+        ];
+        let signature: String = client.request(SIGN_RAW_METHOD, params).await?;
+        let signature = try_decode_hex(&signature)?;
+        let signature = MultiSignature::decode(&mut &signature[..])
+            .map_err(|_| anyhow!("Remote signer returned an invalid signature"))?;
+
+        let scheme_matches = matches!(
+            (self.scheme, &signature),
+            (CryptoScheme::Sr25519, MultiSignature::Sr25519(_))
+                | (CryptoScheme::Ed25519, MultiSignature::Ed25519(_))
+                | (CryptoScheme::Ecdsa, MultiSignature::Ecdsa(_))
+        );
+        if !scheme_matches {
+            anyhow::bail!("Remote signer returned a signature of the wrong scheme");
+        }
+
+        Ok(signature)
+    }
+}
+
+impl SignerProvider for RemoteSigner {
+    fn scheme(&self) -> CryptoScheme {
+        self.scheme
+    }
+
+    fn public(&self) -> Vec<u8> {
+        self.public.clone()
+    }
+
+    fn account_id(&self) -> AccountId {
+        self.account_id.clone()
+    }
+
+    fn sign(&self, payload: &[u8]) -> Result<MultiSignature> {
+        runtime::block_on(self.sign_remote(payload))
+    }
+
+    fn sign_async<'a>(&'a self, payload: &'a [u8]) -> SignFuture<'a> {
+        Box::pin(self.sign_remote(payload))
+    }
+}
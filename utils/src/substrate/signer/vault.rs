@@ -0,0 +1,162 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Context, Result};
+use base64::Engine;
+use serde_json::{json, Value as JsonValue};
+use sp_core::crypto::ByteArray;
+use sp_core::ed25519;
+use sp_runtime::MultiSignature;
+use std::convert::TryFrom;
+
+use super::{account_from_public, CryptoScheme, SignFuture, SignerProvider};
+use crate::substrate::{runtime, AccountId, SecretString};
+
+/// Mount path of the transit secrets engine by default.
+pub const DEFAULT_TRANSIT_MOUNT: &str = "transit";
+const ADDR_ENV: &str = "VAULT_ADDR";
+const TOKEN_ENV: &str = "VAULT_TOKEN";
+const TOKEN_HEADER: &str = "X-Vault-Token";
+const SIGNATURE_PREFIX: &str = "vault:v";
+
+/// Signs with an ed25519 key of the transit secrets engine of a HashiCorp Vault, so
+/// that the key never leaves the Vault.
+///
+/// Transit has no sr25519 or secp256k1 keys, see
+/// [`KmsSigner`](super::KmsSigner) for ecdsa accounts. Phala query certificates are
+/// signed by the Vault like extrinsics.
+pub struct VaultSigner {
+    client: reqwest::Client,
+    addr: String,
+    mount: String,
+    key: String,
+    token: SecretString,
+    public: Vec<u8>,
+    account_id: AccountId,
+}
+
+impl VaultSigner {
+    /// Loads the public key of the transit key `key` of the Vault at `addr`, e.g.
+    /// `https://vault.example.com:8200`, with the transit engine mounted at `mount`.
+    pub fn connect(addr: &str, mount: &str, key: &str, token: SecretString) -> Result<Self> {
+        let mut signer = Self {
+            client: reqwest::Client::new(),
+            addr: addr.trim_end_matches('/').to_owned(),
+            mount: mount.trim_matches('/').to_owned(),
+            key: key.to_owned(),
+            token,
+            public: Vec::new(),
+            account_id: AccountId::from([0u8; 32]),
+        };
+        signer.public = runtime::block_on(signer.load_public())?;
+        signer.account_id = account_from_public(CryptoScheme::Ed25519, &signer.public)?;
+        Ok(signer)
+    }
+
+    /// Connects to the Vault at `VAULT_ADDR` with the token in `VAULT_TOKEN`, the
+    /// variables of the Vault CLI, and the transit engine at its default mount.
+    pub fn from_env(key: &str) -> Result<Self> {
+        let addr = std::env::var(ADDR_ENV).with_context(|| format!("{ADDR_ENV} is not set"))?;
+        let token = std::env::var(TOKEN_ENV).with_context(|| format!("{TOKEN_ENV} is not set"))?;
+        Self::connect(&addr, DEFAULT_TRANSIT_MOUNT, key, token.into())
+    }
+
+    async fn load_public(&self) -> Result<Vec<u8>> {
+        let url = format!("{}/v1/{}/keys/{}", self.addr, self.mount, self.key);
+        let data = self.send(self.client.get(url)).await?;
+        let key_type = data["type"].as_str().unwrap_or_default();
+        if key_type != "ed25519" {
+            anyhow::bail!(
+                "Vault key {} is {key_type}, expected an ed25519 key",
+                self.key
+            );
+        }
+        let version = data["latest_version"]
+            .as_u64()
+            .context("Vault returned no key version")?;
+        let public = data["keys"][version.to_string()]["public_key"]
+            .as_str()
+            .context("Vault returned no public key")?;
+        let public = base64::engine::general_purpose::STANDARD
+            .decode(public)
+            .context("Vault returned an invalid public key")?;
+        if public.len() != ed25519::Public::LEN {
+            anyhow::bail!("Vault returned a public key of {} bytes", public.len());
+        }
+        Ok(public)
+    }
+
+    async fn sign_remote(&self, payload: &[u8]) -> Result<MultiSignature> {
+        let url = format!("{}/v1/{}/sign/{}", self.addr, self.mount, self.key);
+        let body = json!({ "input": base64::engine::general_purpose::STANDARD.encode(payload) });
+        let data = self
+            .send(self.client.post(url).body(body.to_string()))
+            .await?;
+        // Signatures are formatted as `vault:v<key version>:<base64>`.
+        let signature = data["signature"]
+            .as_str()
+            .filter(|signature| signature.starts_with(SIGNATURE_PREFIX))
+            .and_then(|signature| signature.rsplit(':').next())
+            .context("Vault returned no signature")?;
+        let signature = base64::engine::general_purpose::STANDARD
+            .decode(signature)
+            .context("Vault returned an invalid signature")?;
+        let signature = <[u8; 64]>::try_from(signature.as_slice())
+            .map_err(|_| anyhow!("Vault returned a signature of {} bytes", signature.len()))?;
+        Ok(ed25519::Signature::from_raw(signature).into())
+    }
+
+    /// Sends an authenticated request, returning the `data` of the response.
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<JsonValue> {
+        let request = request.header(TOKEN_HEADER, self.token.expose_secret());
+        let addr = self.addr.clone();
+        let (status, bytes) = runtime::detached(async move {
+            let response = request
+                .send()
+                .await
+                .with_context(|| format!("Failed to reach Vault at {addr}"))?;
+            let status = response.status();
+            anyhow::Ok((status, response.bytes().await?))
+        })
+        .await?;
+        let body: JsonValue = serde_json::from_slice(&bytes)
+            .with_context(|| format!("Vault answered {status} with invalid JSON"))?;
+        if !status.is_success() {
+            anyhow::bail!("Vault answered {status}: {}", body["errors"]);
+        }
+        Ok(body["data"].clone())
+    }
+}
+
+impl SignerProvider for VaultSigner {
+    fn scheme(&self) -> CryptoScheme {
+        CryptoScheme::Ed25519
+    }
+
+    fn public(&self) -> Vec<u8> {
+        self.public.clone()
+    }
+
+    fn account_id(&self) -> AccountId {
+        self.account_id.clone()
+    }
+
+    fn sign(&self, payload: &[u8]) -> Result<MultiSignature> {
+        runtime::block_on(self.sign_remote(payload))
+    }
+
+    fn sign_async<'a>(&'a self, payload: &'a [u8]) -> SignFuture<'a> {
+        Box::pin(self.sign_remote(payload))
+    }
+}
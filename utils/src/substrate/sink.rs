@@ -0,0 +1,212 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Result;
+use contract_transcode::Value;
+use std::io::Write;
+
+use super::contract::ink::values::json_value;
+use super::{AccountId, BlockHash};
+
+/// Columns written by the built-in sinks, in order.
+pub const COLUMNS: [&str; 4] = ["block", "contract", "event", "fields"];
+
+/// A decoded contract event, with the block and contract that emitted it.
+#[derive(Debug, Clone)]
+pub struct EventRecord {
+    pub block: BlockHash,
+    pub block_number: Option<u32>,
+    /// Timestamp of the block, in milliseconds since the Unix epoch.
+    pub timestamp: Option<u64>,
+    pub contract: AccountId,
+    /// Decoded event, as returned by [`crate::substrate::ContractInstance::decode_events`].
+    pub event: Value,
+}
+
+impl EventRecord {
+    pub fn new(block: BlockHash, contract: AccountId, event: Value) -> Self {
+        Self {
+            block,
+            block_number: None,
+            timestamp: None,
+            contract,
+            event,
+        }
+    }
+
+    pub fn block_number(mut self, block_number: u32) -> Self {
+        self.block_number = Some(block_number);
+        self
+    }
+
+    pub fn timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Name of the event, e.g. `Transfer`.
+    pub fn name(&self) -> String {
+        let ident = match &self.event {
+            Value::Map(map) => map.ident(),
+            Value::Tuple(tuple) => tuple.ident(),
+            _ => None,
+        };
+        ident.unwrap_or_default()
+    }
+
+    /// Values of the [`COLUMNS`], the event fields being rendered as JSON.
+    pub fn row(&self) -> [String; 4] {
+        [
+            format!("{:?}", self.block),
+            self.contract.to_string(),
+            self.name(),
+            json_value(&self.event).to_string(),
+        ]
+    }
+}
+
+/// Destination of decoded events, e.g. a file for analytics tools.
+pub trait EventSink {
+    /// Writes `record`, possibly buffering it until [`EventSink::flush`].
+    fn write(&mut self, record: &EventRecord) -> Result<()>;
+
+    /// Writes the buffered records out.
+    fn flush(&mut self) -> Result<()>;
+
+    /// Flushes the sink and completes its output, after which it must not be written to.
+    fn finish(&mut self) -> Result<()> {
+        self.flush()
+    }
+}
+
+/// Writes events as CSV rows of the [`COLUMNS`], with a header.
+pub struct CsvSink<W: Write> {
+    writer: csv::Writer<W>,
+}
+
+impl<W: Write> CsvSink<W> {
+    pub fn new(writer: W) -> Result<Self> {
+        let mut writer = csv::Writer::from_writer(writer);
+        writer.write_record(COLUMNS)?;
+        Ok(Self { writer })
+    }
+}
+
+impl CsvSink<std::fs::File> {
+    pub fn create(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        Self::new(std::fs::File::create(path)?)
+    }
+}
+
+impl<W: Write> EventSink for CsvSink<W> {
+    fn write(&mut self, record: &EventRecord) -> Result<()> {
+        self.writer.write_record(record.row())?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+#[cfg(feature = "parquet")]
+pub use self::parquet_sink::ParquetSink;
+
+#[cfg(feature = "parquet")]
+mod parquet_sink {
+    use anyhow::{Context, Result};
+    use parquet::data_type::{ByteArray, ByteArrayType};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use std::io::Write;
+    use std::sync::Arc;
+
+    use super::{EventRecord, EventSink, COLUMNS};
+
+    /// Records buffered before they are written as a row group.
+    const ROW_GROUP_SIZE: usize = 10_000;
+
+    /// Writes events as an Apache Parquet file of UTF-8 [`COLUMNS`], one row group per
+    /// flush.
+    pub struct ParquetSink<W: Write + Send> {
+        writer: Option<SerializedFileWriter<W>>,
+        rows: Vec<[String; 4]>,
+    }
+
+    impl<W: Write + Send> ParquetSink<W> {
+        pub fn new(writer: W) -> Result<Self> {
+            let columns: String = COLUMNS
+                .iter()
+                .map(|column| format!("REQUIRED BYTE_ARRAY {column} (UTF8); "))
+                .collect();
+            let schema = parse_message_type(&format!("message event {{ {columns}}}"))?;
+            let properties = WriterProperties::builder().build();
+            let writer = SerializedFileWriter::new(writer, Arc::new(schema), Arc::new(properties))?;
+            Ok(Self {
+                writer: Some(writer),
+                rows: Vec::new(),
+            })
+        }
+    }
+
+    impl ParquetSink<std::fs::File> {
+        pub fn create(path: impl AsRef<std::path::Path>) -> Result<Self> {
+            Self::new(std::fs::File::create(path)?)
+        }
+    }
+
+    impl<W: Write + Send> EventSink for ParquetSink<W> {
+        fn write(&mut self, record: &EventRecord) -> Result<()> {
+            self.rows.push(record.row());
+            if self.rows.len() >= ROW_GROUP_SIZE {
+                self.flush()?;
+            }
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            if self.rows.is_empty() {
+                return Ok(());
+            }
+            let writer = self.writer.as_mut().context("Parquet sink is finished")?;
+            let mut row_group = writer.next_row_group()?;
+            for column in 0..COLUMNS.len() {
+                let values: Vec<ByteArray> = self
+                    .rows
+                    .iter()
+                    .map(|row| ByteArray::from(row[column].as_str()))
+                    .collect();
+                let mut column_writer = row_group
+                    .next_column()?
+                    .context("Parquet schema has fewer columns than expected")?;
+                column_writer
+                    .typed::<ByteArrayType>()
+                    .write_batch(&values, None, None)?;
+                column_writer.close()?;
+            }
+            row_group.close()?;
+            self.rows.clear();
+            Ok(())
+        }
+
+        fn finish(&mut self) -> Result<()> {
+            self.flush()?;
+            if let Some(writer) = self.writer.take() {
+                writer.close()?;
+            }
+            Ok(())
+        }
+    }
+}
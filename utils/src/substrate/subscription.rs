@@ -0,0 +1,549 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Context, Result};
+use contract_transcode::Value;
+use jsonrpsee::core::client::{ClientT, Subscription, SubscriptionClientT};
+use jsonrpsee::rpc_params;
+use jsonrpsee::ws_client::{WsClient, WsClientBuilder};
+use scale::Decode;
+use serde::Deserialize;
+use sp_core::hashing::{blake2_128, twox_128};
+use sp_core::{Bytes, H256};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use subxt::config::Header as _;
+
+use super::cancel::{self, CancellationToken};
+use super::contract::error::ErrorVariant;
+use super::contract::query::CallOptions;
+use super::contract::ContractInstance;
+use super::extrinsic::ContractEmitted;
+use super::rate_limit;
+use super::{runtime, BlockHash, Client, Config, DefaultConfig};
+
+/// Prefix of the top trie keys holding the roots of child tries, such as the
+/// storage of contracts.
+const CHILD_STORAGE_PREFIX: &[u8] = b":child_storage:default:";
+/// Blocks of the followed chain kept to detect reorgs, deeper reorgs retracting
+/// every kept block.
+const RETAINED_BLOCKS: usize = 256;
+
+type Header = <DefaultConfig as Config>::Header;
+
+/// Blocks a subscription follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Finality {
+    /// Best blocks, which may be reorged out: updates of retracted blocks are sent
+    /// again with `retracted` set, before the updates of the new best chain.
+    #[default]
+    Best,
+    /// Finalized blocks, which are never retracted.
+    Finalized,
+}
+
+impl Finality {
+    fn subscribe_method(&self) -> (&'static str, &'static str) {
+        match self {
+            Finality::Best => ("chain_subscribeNewHeads", "chain_unsubscribeNewHeads"),
+            Finality::Finalized => (
+                "chain_subscribeFinalizedHeads",
+                "chain_unsubscribeFinalizedHeads",
+            ),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct StorageChangeSet {
+    /// Values of the queried keys, `None` for missing keys.
+    #[serde(default)]
+    changes: Vec<(Bytes, Option<Bytes>)>,
+}
+
+/// Recent blocks of the followed chain, from the oldest.
+#[derive(Default)]
+struct ChainFollower {
+    blocks: VecDeque<(u32, BlockHash)>,
+}
+
+impl ChainFollower {
+    /// Makes `head` the head of the chain, returning the retracted blocks, newest
+    /// first, and the enacted blocks, oldest first, including blocks skipped between
+    /// finalized heads.
+    async fn advance(
+        &mut self,
+        client: &WsClient,
+        url: &str,
+        head: Header,
+    ) -> Result<(Vec<BlockHash>, Vec<BlockHash>)> {
+        let mut enacted = Vec::new();
+        let mut header = head;
+        let ancestor = loop {
+            let block = (header.number, header.hash());
+            if self.blocks.contains(&block) {
+                break Some(block.0);
+            }
+            let past_window = self
+                .blocks
+                .front()
+                .is_none_or(|(oldest, _)| block.0 <= *oldest);
+            enacted.push(block);
+            if past_window {
+                break None;
+            }
+            let _permit = rate_limit::acquire(url).await;
+            let parent: Option<Header> = client
+                .request("chain_getHeader", rpc_params![header.parent_hash])
+                .await?;
+            header = parent.with_context(|| format!("Missing block {:?}", header.parent_hash))?;
+        };
+
+        let mut retracted = Vec::new();
+        while let Some(&(number, hash)) = self.blocks.back() {
+            if ancestor.is_some_and(|ancestor| number <= ancestor) {
+                break;
+            }
+            retracted.push(hash);
+            self.blocks.pop_back();
+        }
+        enacted.reverse();
+        self.blocks.extend(enacted.iter().copied());
+        while self.blocks.len() > RETAINED_BLOCKS {
+            self.blocks.pop_front();
+        }
+        Ok((
+            retracted,
+            enacted.into_iter().map(|(_, hash)| hash).collect(),
+        ))
+    }
+
+    fn contains(&self, hash: &BlockHash) -> bool {
+        self.blocks.iter().any(|(_, block)| block == hash)
+    }
+}
+
+/// Value returned by the getter after a storage change.
+#[derive(Debug)]
+pub struct StorageUpdate {
+    /// Block whose state the getter read, `None` if the subscription failed.
+    pub block: Option<BlockHash>,
+    pub value: Result<Value, ErrorVariant>,
+    /// Whether `block` was reorged out, `value` being the value previously sent for
+    /// it. The value of the new best block follows.
+    pub retracted: bool,
+}
+
+/// Updates of a [`subscribe_storage`] subscription, ending when the node closes it
+/// or the subscription is cancelled.
+///
+/// Dropping the subscription cancels it.
+pub struct StorageSubscription {
+    updates: Receiver<StorageUpdate>,
+    cancellation: CancellationToken,
+}
+
+impl StorageSubscription {
+    /// Closes the subscription, even while it waits for a node that stopped
+    /// answering, ending the updates.
+    pub fn cancel(&self) {
+        self.cancellation.cancel();
+    }
+
+    /// Token cancelling the subscription, e.g. from another thread blocked on it.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+}
+
+impl Drop for StorageSubscription {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+impl Iterator for StorageSubscription {
+    type Item = StorageUpdate;
+
+    /// Blocks until the watched storage changes to a new value.
+    fn next(&mut self) -> Option<StorageUpdate> {
+        self.updates.recv().ok()
+    }
+}
+
+/// Re-runs the getter `message` of the ink contract of `contract` whenever its
+/// storage, or one of the top-level storage `keys`, changes in the blocks followed
+/// along `finality`.
+///
+/// Child trie keys cannot be watched individually, so the root of the contract's
+/// child trie is read along the watched keys at each new head, and the getter is
+/// only run when one of them changed. The getter is run on the first head, and a
+/// value is only sent if it differs from the previous one.
+pub fn subscribe_storage(
+    contract: Arc<ContractInstance>,
+    message: impl Into<String>,
+    args: Vec<String>,
+    keys: Vec<Vec<u8>>,
+    finality: Finality,
+) -> Result<StorageSubscription> {
+    let message = message.into();
+    let address = contract
+        .address()
+        .as_ink()
+        .context("Storage subscriptions require an ink contract address")?
+        .clone();
+    let info = contract
+        .get_contract_info(&address)?
+        .with_context(|| format!("No contract found at {address}"))?;
+
+    let mut watched = vec![Bytes([CHILD_STORAGE_PREFIX, &info.trie_id].concat())];
+    watched.extend(keys.into_iter().map(Bytes));
+
+    let (client, heads) = subscribe_heads(contract.url(), finality)?;
+    let (updates_tx, updates_rx) = mpsc::channel();
+    let cancellation = CancellationToken::new();
+    let token = cancellation.clone();
+    thread::spawn(move || {
+        let mut heads = heads;
+        let mut follower = ChainFollower::default();
+        let mut watched_values = None;
+        let mut last: Option<(BlockHash, Value)> = None;
+        loop {
+            let step = runtime::block_on(cancel::run_until(
+                async {
+                    let head = match heads.next().await {
+                        Some(head) => head?,
+                        None => return Ok(None),
+                    };
+                    let (retracted, enacted) =
+                        follower.advance(&client, contract.url(), head).await?;
+                    let head = match enacted.last() {
+                        Some(head) => *head,
+                        None => return Ok(Some((retracted, None))),
+                    };
+                    let _permit = rate_limit::acquire(contract.url()).await;
+                    let values = query_storage_at(&client, &watched, head).await?;
+                    Ok::<_, anyhow::Error>(Some((retracted, Some((head, values)))))
+                },
+                Some(&token),
+                None,
+            ));
+            let (retracted, head) = match step {
+                Ok(Ok(Some(step))) => step,
+                Ok(Ok(None)) | Err(_) => return,
+                Ok(Err(err)) => {
+                    let value = Err(ErrorVariant::from(anyhow!(
+                        "Storage subscription failed: {err}"
+                    )));
+                    let _ = updates_tx.send(StorageUpdate {
+                        block: None,
+                        value,
+                        retracted: false,
+                    });
+                    return;
+                }
+            };
+
+            let retracted_last = match &last {
+                Some((block, _)) => retracted.contains(block),
+                None => false,
+            };
+            if retracted_last {
+                let (block, value) = last.take().expect("retracted value");
+                let update = StorageUpdate {
+                    block: Some(block),
+                    value: Ok(value),
+                    retracted: true,
+                };
+                if updates_tx.send(update).is_err() {
+                    return;
+                }
+                // The values of the new best chain may differ from the retracted ones.
+                watched_values = None;
+            }
+            let (head, values) = match head {
+                Some(head) => head,
+                None => continue,
+            };
+            if watched_values.as_ref() == Some(&values) {
+                continue;
+            }
+            watched_values = Some(values);
+
+            let options = CallOptions::default().at(head).cancel_on(token.clone());
+            let value = contract.call_msg_with(&message, args.clone(), None, options);
+            if token.is_cancelled() {
+                return;
+            }
+            let changed = match &value {
+                Ok(value) => last.as_ref().map(|(_, last)| last) != Some(value),
+                Err(_) => true,
+            };
+            if let Ok(value) = &value {
+                last = Some((head, value.clone()));
+            }
+            let update = StorageUpdate {
+                block: Some(head),
+                value,
+                retracted: false,
+            };
+            if changed && updates_tx.send(update).is_err() {
+                return;
+            }
+        }
+    });
+
+    Ok(StorageSubscription {
+        updates: updates_rx,
+        cancellation,
+    })
+}
+
+/// Events of a [`subscribe_events`] subscription emitted in one block.
+#[derive(Debug)]
+pub struct EventUpdate {
+    /// Block the events were emitted in, `None` if the subscription failed.
+    pub block: Option<BlockHash>,
+    /// Decoded events of the contract, in the order they were emitted.
+    pub events: Result<Vec<Value>, ErrorVariant>,
+    /// Whether `block` was reorged out, the events having to be reverted.
+    pub retracted: bool,
+}
+
+/// Updates of a [`subscribe_events`] subscription, ending when the node closes it
+/// or the subscription is cancelled.
+///
+/// Dropping the subscription cancels it.
+pub struct EventSubscription {
+    updates: Receiver<EventUpdate>,
+    cancellation: CancellationToken,
+}
+
+impl EventSubscription {
+    /// Closes the subscription, even while it waits for a node that stopped
+    /// answering, ending the updates.
+    pub fn cancel(&self) {
+        self.cancellation.cancel();
+    }
+
+    /// Token cancelling the subscription, e.g. from another thread blocked on it.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+}
+
+impl Drop for EventSubscription {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+impl Iterator for EventSubscription {
+    type Item = EventUpdate;
+
+    /// Blocks until the contract emits events with one of the watched topics.
+    fn next(&mut self) -> Option<EventUpdate> {
+        self.updates.recv().ok()
+    }
+}
+
+/// Decodes the events of the ink contract of `contract` carrying any of `topics`,
+/// e.g. the signature topic of an event or the topic of an indexed field, in the
+/// blocks followed along `finality`.
+///
+/// Instead of downloading the events of every block, the subscription reads the
+/// `System::EventTopics` entries of the topics at each block, and only reads the
+/// events of the blocks, and at the indices, they point to.
+pub fn subscribe_events(
+    contract: Arc<ContractInstance>,
+    topics: Vec<H256>,
+    finality: Finality,
+) -> Result<EventSubscription> {
+    let address = contract
+        .address()
+        .as_ink()
+        .context("Event subscriptions require an ink contract address")?
+        .clone();
+    if topics.is_empty() {
+        anyhow::bail!("Event subscriptions require at least one topic");
+    }
+    let watched: Vec<Bytes> = topics
+        .iter()
+        .map(|topic| Bytes(event_topics_key(topic)))
+        .collect();
+
+    let (client, heads) = subscribe_heads(contract.url(), finality)?;
+    let events_client = runtime::block_on(Client::from_url(contract.url()))?;
+    let (updates_tx, updates_rx) = mpsc::channel();
+    let cancellation = CancellationToken::new();
+    let token = cancellation.clone();
+    thread::spawn(move || {
+        let mut heads = heads;
+        let mut follower = ChainFollower::default();
+        // Events sent for the retained blocks, sent again if they are retracted.
+        let mut sent: HashMap<BlockHash, Vec<Value>> = HashMap::new();
+        loop {
+            let step = runtime::block_on(cancel::run_until(
+                async {
+                    let head = match heads.next().await {
+                        Some(head) => head?,
+                        None => return Ok(None),
+                    };
+                    let (retracted, enacted) =
+                        follower.advance(&client, contract.url(), head).await?;
+                    let mut blocks = Vec::new();
+                    for block in enacted {
+                        let _permit = rate_limit::acquire(contract.url()).await;
+                        let values = query_storage_at(&client, &watched, block).await?;
+                        blocks.push((block, values));
+                    }
+                    Ok::<_, anyhow::Error>(Some((retracted, blocks)))
+                },
+                Some(&token),
+                None,
+            ));
+            let (retracted, blocks) = match step {
+                Ok(Ok(Some(step))) => step,
+                Ok(Ok(None)) | Err(_) => return,
+                Ok(Err(err)) => {
+                    let events = Err(ErrorVariant::from(anyhow!(
+                        "Event subscription failed: {err}"
+                    )));
+                    let _ = updates_tx.send(EventUpdate {
+                        block: None,
+                        events,
+                        retracted: false,
+                    });
+                    return;
+                }
+            };
+
+            for block in retracted {
+                if let Some(events) = sent.remove(&block) {
+                    let update = EventUpdate {
+                        block: Some(block),
+                        events: Ok(events),
+                        retracted: true,
+                    };
+                    if updates_tx.send(update).is_err() {
+                        return;
+                    }
+                }
+            }
+
+            for (block, values) in blocks {
+                // Topics are reset at each block, the entries only index its events.
+                let indices: BTreeSet<u32> = values
+                    .iter()
+                    .filter_map(|(_, value)| value.as_ref())
+                    .filter_map(|value| Vec::<(u32, u32)>::decode(&mut value.0.as_slice()).ok())
+                    .flatten()
+                    .map(|(_, index)| index)
+                    .collect();
+                if indices.is_empty() {
+                    continue;
+                }
+
+                let events = runtime::block_on(cancel::run_until(
+                    async {
+                        let _permit = rate_limit::acquire(contract.url()).await;
+                        let events = events_client.events().at(block).await?;
+                        let mut emitted = Vec::new();
+                        for event in events.iter() {
+                            let event = event?;
+                            if !indices.contains(&event.index()) {
+                                continue;
+                            }
+                            if let Some(event) = ContractEmitted::from_event(&event)? {
+                                let has_topic =
+                                    event.topics.iter().any(|topic| topics.contains(topic));
+                                if event.contract == address && has_topic {
+                                    emitted.push(event);
+                                }
+                            }
+                        }
+                        contract.decode_events(&emitted)
+                    },
+                    Some(&token),
+                    None,
+                ));
+                let events = match events {
+                    Ok(events) => events.map_err(ErrorVariant::from),
+                    Err(_) => return,
+                };
+                if matches!(&events, Ok(events) if events.is_empty()) {
+                    continue;
+                }
+                if let (Finality::Best, Ok(events)) = (finality, &events) {
+                    sent.insert(block, events.clone());
+                }
+                let update = EventUpdate {
+                    block: Some(block),
+                    events,
+                    retracted: false,
+                };
+                if updates_tx.send(update).is_err() {
+                    return;
+                }
+            }
+            sent.retain(|block, _| follower.contains(block));
+        }
+    });
+
+    Ok(EventSubscription {
+        updates: updates_rx,
+        cancellation,
+    })
+}
+
+/// Subscribes to the heads of the chain at `url` along `finality`, the subscription
+/// being closed once the returned client is dropped.
+fn subscribe_heads(url: &str, finality: Finality) -> Result<(WsClient, Subscription<Header>)> {
+    runtime::block_on(async {
+        let client = WsClientBuilder::default().build(url).await?;
+        let (subscribe, unsubscribe) = finality.subscribe_method();
+        let heads = client
+            .subscribe(subscribe, rpc_params![], unsubscribe)
+            .await?;
+        Ok((client, heads))
+    })
+}
+
+/// Values of `keys` at block `at`.
+async fn query_storage_at(
+    client: &WsClient,
+    keys: &[Bytes],
+    at: BlockHash,
+) -> Result<Vec<(Bytes, Option<Bytes>)>> {
+    let change_sets: Vec<StorageChangeSet> = client
+        .request("state_queryStorageAt", rpc_params![keys, at])
+        .await?;
+    Ok(change_sets
+        .into_iter()
+        .flat_map(|change_set| change_set.changes)
+        .collect())
+}
+
+/// Key of the `System::EventTopics` entry of `topic`, hashed with `Blake2_128Concat`.
+fn event_topics_key(topic: &H256) -> Vec<u8> {
+    [
+        &twox_128(b"System")[..],
+        &twox_128(b"EventTopics"),
+        &blake2_128(topic.as_bytes()),
+        topic.as_bytes(),
+    ]
+    .concat()
+}
@@ -0,0 +1,178 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Context, Result};
+use scale::Decode;
+use sp_weights::Weight;
+use std::net::TcpListener;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use super::address::Address;
+use super::chain::Chain;
+use super::contract::ink::args::{self, ArgOptions};
+use super::contract::ink::{ContractArtifacts, InkMeta};
+use super::contract::ContractInstance;
+use super::extrinsic::{self, ExtrinsicOutcome};
+use super::signer::DevAccount;
+use super::{runtime, AccountId, Client, DefaultConfig};
+
+/// Environment variable overriding the node binary, as in ink! end-to-end tests.
+const NODE_BINARY_ENV: &str = "CONTRACTS_NODE";
+const DEFAULT_NODE_BINARY: &str = "substrate-contracts-node";
+/// Time given to a spawned node to serve RPC requests.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+const STARTUP_POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// Gas limit of deployments, far above what a development node charges for any
+/// constructor.
+const DEPLOY_GAS_LIMIT: Weight = Weight::from_parts(500_000_000_000, 5 * 1024 * 1024);
+
+/// A `substrate-contracts-node` to run end-to-end tests against.
+///
+/// A spawned node runs in development mode with a temporary database and is killed
+/// when dropped.
+pub struct TestNode {
+    url: String,
+    process: Option<Child>,
+}
+
+impl TestNode {
+    /// Spawns a development node on a free port.
+    ///
+    /// The binary is read from `$CONTRACTS_NODE`, or else looked up on the `PATH`.
+    pub fn spawn() -> Result<Self> {
+        let binary =
+            std::env::var(NODE_BINARY_ENV).unwrap_or_else(|_| DEFAULT_NODE_BINARY.to_owned());
+        let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+        let process = Command::new(&binary)
+            .args(["--dev", "--tmp", "--rpc-port", &port.to_string()])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| {
+                format!("Failed to spawn {binary}, set ${NODE_BINARY_ENV} to its path")
+            })?;
+
+        let mut node = Self {
+            url: format!("ws://127.0.0.1:{port}"),
+            process: Some(process),
+        };
+        node.wait_until_ready()?;
+        Ok(node)
+    }
+
+    /// Connects to a node that is already running, e.g. `ws://127.0.0.1:9944`.
+    pub fn connect(url: impl Into<String>) -> Result<Self> {
+        let url = url.into();
+        runtime::block_on(Client::from_url(&url))
+            .with_context(|| format!("Failed to connect to {url}"))?;
+        Ok(Self { url, process: None })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Deploys the contract of `artifacts` with `Alice`, calling `constructor` with
+    /// `args`.
+    pub fn deploy(
+        &self,
+        artifacts: &ContractArtifacts,
+        constructor: &str,
+        args: &[String],
+    ) -> Result<ContractInstance> {
+        self.deploy_as(DevAccount::Alice, artifacts, constructor, args)
+    }
+
+    /// Same as [`TestNode::deploy`], deploying and then querying the contract with
+    /// `account`.
+    pub fn deploy_as(
+        &self,
+        account: DevAccount,
+        artifacts: &ContractArtifacts,
+        constructor: &str,
+        args: &[String],
+    ) -> Result<ContractInstance> {
+        let code = artifacts
+            .code
+            .as_ref()
+            .context("Contract artifacts have no code to deploy")?;
+        let transcoder = artifacts.contract_transcoder()?;
+        let args =
+            args::prepare_constructor_args(&transcoder, constructor, args, &ArgOptions::default())?;
+        let data = transcoder.encode(constructor, &args)?;
+
+        let call = extrinsic::instantiate_with_code(
+            0,
+            DEPLOY_GAS_LIMIT,
+            None,
+            code.as_bytes().to_vec(),
+            data,
+            rand::random::<[u8; 32]>().to_vec(),
+        );
+        let signer = account.pair();
+        let outcome = runtime::block_on(extrinsic::submit::<DefaultConfig, _>(
+            &self.url, &call, &signer,
+        ))
+        .map_err(|err| anyhow!("Failed to deploy the contract: {err}"))?;
+        let address = instantiated_contract(&outcome)?;
+
+        let meta = InkMeta::new(
+            artifacts.artifact_path().to_path_buf(),
+            self.url.clone(),
+            Chain::Local,
+            Address::Ink(address),
+        );
+        Ok(ContractInstance::new(meta, Box::new(signer)))
+    }
+
+    fn wait_until_ready(&mut self) -> Result<()> {
+        let started = Instant::now();
+        loop {
+            if let Some(process) = self.process.as_mut() {
+                if let Some(status) = process.try_wait()? {
+                    anyhow::bail!("Node exited during startup with {status}");
+                }
+            }
+            if runtime::block_on(Client::from_url(&self.url)).is_ok() {
+                return Ok(());
+            }
+            if started.elapsed() > STARTUP_TIMEOUT {
+                anyhow::bail!("Node at {} not ready after {STARTUP_TIMEOUT:?}", self.url);
+            }
+            std::thread::sleep(STARTUP_POLL_INTERVAL);
+        }
+    }
+}
+
+impl Drop for TestNode {
+    fn drop(&mut self) {
+        if let Some(mut process) = self.process.take() {
+            let _ = process.kill();
+            let _ = process.wait();
+        }
+    }
+}
+
+/// Reads the address of the contract from the `Contracts::Instantiated` event.
+fn instantiated_contract(outcome: &ExtrinsicOutcome) -> Result<AccountId> {
+    for event in outcome.events.iter() {
+        let event = event?;
+        if event.pallet_name() == "Contracts" && event.variant_name() == "Instantiated" {
+            let (_deployer, contract) = <(AccountId, AccountId)>::decode(&mut event.field_bytes())?;
+            return Ok(contract);
+        }
+    }
+    anyhow::bail!("No Contracts::Instantiated event emitted by the deployment")
+}
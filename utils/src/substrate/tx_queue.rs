@@ -0,0 +1,143 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Result;
+use std::sync::Arc;
+use subxt::tx::{TxPayload, TxProgress};
+use tokio::sync::Mutex;
+
+use super::contract::error::ErrorVariant;
+use super::extrinsic::{self, ExtrinsicOutcome, NonceTracker, TxParams};
+use super::signer::{ExtrinsicSigner, SignerProvider};
+use super::{AccountId, Client, DefaultConfig};
+
+/// Times an extrinsic is signed again after the pool rejected its nonce.
+const MAX_NONCE_RETRIES: usize = 3;
+/// Pool error of an extrinsic reusing the nonce of one already waiting in the pool.
+const PRIORITY_TOO_LOW: &str = "Priority is too low";
+/// Pool error of an extrinsic whose nonce was already used on chain.
+const OUTDATED: &str = "Transaction is outdated";
+
+/// Submits the extrinsics of one account concurrently, with consecutive nonces
+///
+/// Extrinsics are signed and handed to the pool one at a time, so that their nonces
+/// follow each other, but are then watched concurrently: many contract calls can be
+/// in flight without waiting for the previous ones to be finalized. Extrinsics
+/// rejected for their nonce, e.g. after another client used it, are signed again
+/// with a fresh one.
+pub struct TxQueue {
+    client: Client,
+    signer: Arc<dyn SignerProvider>,
+    nonces: NonceTracker,
+    params: TxParams,
+    /// Held while an extrinsic is signed and submitted, so that nonces reach the pool
+    /// in order.
+    submitting: Mutex<()>,
+}
+
+impl TxQueue {
+    /// Connects to the node at `url` to submit extrinsics signed by `signer`, e.g. the
+    /// signer of a [`crate::substrate::ContractInstance`].
+    pub async fn connect(url: &str, signer: Arc<dyn SignerProvider>) -> Result<Self> {
+        let client = Client::from_url(url).await?;
+        let nonces = NonceTracker::new(url, signer.account_id());
+        Ok(Self {
+            client,
+            signer,
+            nonces,
+            params: TxParams::default(),
+            submitting: Mutex::new(()),
+        })
+    }
+
+    /// Sets the tip and mortality of the extrinsics, their nonces being assigned by
+    /// the queue.
+    pub fn params(mut self, params: TxParams) -> Self {
+        self.params = TxParams {
+            nonce: None,
+            ..params
+        };
+        self
+    }
+
+    pub fn account_id(&self) -> AccountId {
+        self.signer.account_id()
+    }
+
+    /// Signs and submits `call`, waiting until it is finalized.
+    ///
+    /// A failed dispatch is returned as an error, decoded with the runtime metadata.
+    pub async fn submit<Call: TxPayload>(
+        &self,
+        call: &Call,
+    ) -> Result<ExtrinsicOutcome, ErrorVariant> {
+        let progress = self.sign_and_submit(call).await?;
+        let in_block = match progress.wait_for_finalized().await {
+            Ok(in_block) => in_block,
+            Err(err) => {
+                // A dropped extrinsic leaves a gap the next nonces must fill.
+                self.nonces.reset();
+                return Err(err.into());
+            }
+        };
+        let block_hash = in_block.block_hash();
+        let extrinsic_hash = in_block.extrinsic_hash();
+        let events = in_block.wait_for_success().await?;
+        Ok(ExtrinsicOutcome {
+            block_hash,
+            extrinsic_hash,
+            events,
+        })
+    }
+
+    async fn sign_and_submit<Call: TxPayload>(
+        &self,
+        call: &Call,
+    ) -> Result<TxProgress<DefaultConfig, Client>, ErrorVariant> {
+        let _submitting = self.submitting.lock().await;
+        let signer = ExtrinsicSigner::new(self.signer.as_ref())?;
+        let mut retries = 0;
+        loop {
+            let nonce = self.nonces.next().await?;
+            let params = self.params.clone().nonce(nonce);
+            let signed = match extrinsic::create_signed(&self.client, call, &signer, &params).await
+            {
+                Ok(signed) => signed,
+                Err(err) => {
+                    // The reserved nonce goes unused, later extrinsics must not skip it.
+                    self.nonces.reset();
+                    return Err(err);
+                }
+            };
+            let err = match signed.submit_and_watch().await {
+                Ok(progress) => return Ok(progress),
+                Err(err) => err,
+            };
+
+            let message = err.to_string();
+            let outdated = message.contains(OUTDATED);
+            if !(outdated || message.contains(PRIORITY_TOO_LOW)) || retries == MAX_NONCE_RETRIES {
+                self.nonces.reset();
+                return Err(err.into());
+            }
+            retries += 1;
+            tracing::debug!(nonce, %message, "Nonce rejected by the pool, signing again");
+            // A nonce taken by a pooled extrinsic is skipped by the tracker, a nonce
+            // used on chain is read again.
+            if outdated {
+                self.nonces.reset();
+            }
+        }
+    }
+}
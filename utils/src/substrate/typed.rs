@@ -0,0 +1,393 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{anyhow, Context};
+use std::collections::VecDeque;
+use std::convert::{TryFrom, TryInto};
+use std::str::FromStr;
+use subxt::utils::AccountId32;
+
+use super::contract::error::ErrorVariant;
+use super::contract::query::CallOptions;
+use super::contract::ContractInstance;
+use super::{AccountId, Nonce};
+
+pub use anyhow::{Error, Result};
+pub use contract_transcode::Value;
+
+/// A Rust value passed to a message, rendered in the transcoder syntax.
+pub trait ToArg {
+    fn to_arg(&self) -> String;
+}
+
+/// A Rust value read from a value decoded by the transcoder.
+pub trait FromValue: Sized {
+    fn from_value(value: Value) -> Result<Self>;
+}
+
+/// Queries `message` with typed arguments and return value, for generated clients.
+///
+/// With `lang_result`, the message returns ink's `Result<T, LangError>` and only `T`
+/// is read, a `LangError` failing the query. Phala queries are sent with a random
+/// nonce.
+pub async fn query<T: FromValue>(
+    contract: &ContractInstance,
+    message: &str,
+    args: Vec<String>,
+    lang_result: bool,
+) -> Result<T, ErrorVariant> {
+    let nonce = Some(rand::random::<Nonce>());
+    let result = contract
+        .query_msg_async(message, args, nonce, CallOptions::default())
+        .await?;
+    let value = if lang_result {
+        let (variant, mut fields) = Fields::variant(result.value, "MessageResult")?;
+        match variant.as_str() {
+            "Ok" => fields.next_field()?,
+            _ => {
+                return Err(ErrorVariant::from(anyhow!(
+                    "Message {message} failed: {variant}"
+                )))
+            }
+        }
+    } else {
+        result.value
+    };
+    T::from_value(value)
+        .with_context(|| format!("Unexpected return value of {message}"))
+        .map_err(ErrorVariant::from)
+}
+
+/// Fields of a decoded struct or enum variant, taken by name or in order.
+pub struct Fields {
+    type_name: String,
+    named: Vec<(String, Value)>,
+    positional: VecDeque<Value>,
+}
+
+impl Fields {
+    /// Fields of a struct of type `type_name`.
+    pub fn new(value: Value, type_name: &str) -> Result<Self> {
+        let mut fields = Self {
+            type_name: type_name.to_owned(),
+            named: Vec::new(),
+            positional: VecDeque::new(),
+        };
+        match value {
+            Value::Map(map) => {
+                fields.named = map
+                    .iter()
+                    .map(|(key, value)| (key_name(key), value.clone()))
+                    .collect();
+            }
+            Value::Tuple(tuple) => fields.positional = tuple.values().cloned().collect(),
+            Value::Unit => {}
+            other => anyhow::bail!("Expected a {type_name}, got `{other}`"),
+        }
+        Ok(fields)
+    }
+
+    /// Name and fields of a variant of the enum `type_name`.
+    pub fn variant(value: Value, type_name: &str) -> Result<(String, Self)> {
+        let variant = match &value {
+            Value::Map(map) => map.ident(),
+            Value::Tuple(tuple) => tuple.ident(),
+            Value::Literal(variant) => Some(variant.clone()),
+            _ => None,
+        }
+        .with_context(|| format!("Expected a variant of {type_name}, got `{value}`"))?;
+        let fields = match value {
+            Value::Literal(_) => Self::new(Value::Unit, type_name)?,
+            value => Self::new(value, type_name)?,
+        };
+        Ok((variant, fields))
+    }
+
+    /// Takes the field `name`.
+    pub fn take<T: FromValue>(&mut self, name: &str) -> Result<T> {
+        let index = self
+            .named
+            .iter()
+            .position(|(field, _)| field == name)
+            .with_context(|| format!("Missing field {name} of {}", self.type_name))?;
+        let (_, value) = self.named.remove(index);
+        T::from_value(value).with_context(|| format!("Invalid field {name} of {}", self.type_name))
+    }
+
+    /// Takes the next unnamed field.
+    pub fn next_field<T: FromValue>(&mut self) -> Result<T> {
+        let value = self
+            .positional
+            .pop_front()
+            .with_context(|| format!("Missing field of {}", self.type_name))?;
+        T::from_value(value).with_context(|| format!("Invalid field of {}", self.type_name))
+    }
+}
+
+/// Renders a struct or enum variant with named fields, e.g. `Point { x: 1, y: 2 }`.
+pub fn named_arg(name: &str, fields: &[(&str, String)]) -> String {
+    let fields = fields
+        .iter()
+        .map(|(field, value)| format!("{field}: {value}"))
+        .collect::<Vec<_>>();
+    format!("{name} {{ {} }}", fields.join(", "))
+}
+
+/// Renders a tuple struct or enum variant, e.g. `Some(1)`, or `None` without fields.
+pub fn tuple_arg(name: &str, fields: &[String]) -> String {
+    if fields.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{name}({})", fields.join(", "))
+    }
+}
+
+fn key_name(key: &Value) -> String {
+    match key {
+        Value::String(key) | Value::Literal(key) => key.clone(),
+        key => key.to_string(),
+    }
+}
+
+/// Unwraps newtypes, decoded as a tuple of a single value.
+fn unwrap_newtype(value: Value) -> Value {
+    match value {
+        Value::Tuple(tuple) if tuple.values().count() == 1 => {
+            unwrap_newtype(tuple.values().next().cloned().expect("counted above"))
+        }
+        value => value,
+    }
+}
+
+macro_rules! impl_integer {
+    ($($ty:ty),*) => {
+        $(
+            impl ToArg for $ty {
+                fn to_arg(&self) -> String {
+                    self.to_string()
+                }
+            }
+
+            impl FromValue for $ty {
+                fn from_value(value: Value) -> Result<Self> {
+                    match unwrap_newtype(value) {
+                        Value::UInt(value) => Ok(<$ty>::try_from(value)?),
+                        Value::Int(value) => Ok(<$ty>::try_from(value)?),
+                        other => anyhow::bail!("Expected an integer, got `{other}`"),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_integer!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+/// Values of types without a Rust counterpart are passed as is.
+impl ToArg for Value {
+    fn to_arg(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value) -> Result<Self> {
+        Ok(value)
+    }
+}
+
+impl ToArg for bool {
+    fn to_arg(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value) -> Result<Self> {
+        match unwrap_newtype(value) {
+            Value::Bool(value) => Ok(value),
+            other => anyhow::bail!("Expected a bool, got `{other}`"),
+        }
+    }
+}
+
+impl ToArg for char {
+    fn to_arg(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+impl FromValue for char {
+    fn from_value(value: Value) -> Result<Self> {
+        match unwrap_newtype(value) {
+            Value::Char(value) => Ok(value),
+            other => anyhow::bail!("Expected a char, got `{other}`"),
+        }
+    }
+}
+
+impl ToArg for String {
+    fn to_arg(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Value) -> Result<Self> {
+        match unwrap_newtype(value) {
+            Value::String(value) | Value::Literal(value) => Ok(value),
+            other => anyhow::bail!("Expected a string, got `{other}`"),
+        }
+    }
+}
+
+impl ToArg for () {
+    fn to_arg(&self) -> String {
+        "()".to_owned()
+    }
+}
+
+impl FromValue for () {
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Unit => Ok(()),
+            Value::Tuple(tuple) if tuple.values().count() == 0 => Ok(()),
+            other => anyhow::bail!("Expected (), got `{other}`"),
+        }
+    }
+}
+
+// Named concretely, as coherence takes impls for the `Config` projection of
+// `AccountId` to overlap the impls for `Value`.
+impl ToArg for AccountId32 {
+    fn to_arg(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl FromValue for AccountId32 {
+    fn from_value(value: Value) -> Result<Self> {
+        match unwrap_newtype(value) {
+            Value::Literal(account) | Value::String(account) => AccountId::from_str(&account)
+                .map_err(|err| anyhow!("Invalid account {account}: {err:?}")),
+            value => Ok(AccountId::from(<[u8; 32]>::from_value(value)?)),
+        }
+    }
+}
+
+impl<T: ToArg> ToArg for Vec<T> {
+    fn to_arg(&self) -> String {
+        let elems = self.iter().map(ToArg::to_arg).collect::<Vec<_>>();
+        format!("[{}]", elems.join(", "))
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: Value) -> Result<Self> {
+        match unwrap_newtype(value) {
+            Value::Seq(seq) => seq.elems().iter().cloned().map(T::from_value).collect(),
+            // Byte strings are decoded as hex, or as text when printable.
+            Value::Hex(hex) => bytes_from(hex.bytes()),
+            Value::String(text) => bytes_from(text.as_bytes()),
+            other => anyhow::bail!("Expected a sequence, got `{other}`"),
+        }
+    }
+}
+
+fn bytes_from<T: FromValue>(bytes: &[u8]) -> Result<Vec<T>> {
+    bytes
+        .iter()
+        .map(|byte| T::from_value(Value::UInt(u128::from(*byte))))
+        .collect()
+}
+
+impl<T: ToArg, const N: usize> ToArg for [T; N] {
+    fn to_arg(&self) -> String {
+        let elems = self.iter().map(ToArg::to_arg).collect::<Vec<_>>();
+        format!("[{}]", elems.join(", "))
+    }
+}
+
+impl<T: FromValue, const N: usize> FromValue for [T; N] {
+    fn from_value(value: Value) -> Result<Self> {
+        let elems = Vec::<T>::from_value(value)?;
+        let len = elems.len();
+        elems
+            .try_into()
+            .map_err(|_| anyhow!("Expected {N} elements, got {len}"))
+    }
+}
+
+impl<T: ToArg> ToArg for Option<T> {
+    fn to_arg(&self) -> String {
+        match self {
+            Some(value) => tuple_arg("Some", &[value.to_arg()]),
+            None => tuple_arg("None", &[]),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: Value) -> Result<Self> {
+        let (variant, mut fields) = Fields::variant(value, "Option")?;
+        match variant.as_str() {
+            "Some" => Ok(Some(fields.next_field()?)),
+            "None" => Ok(None),
+            other => anyhow::bail!("Unknown variant {other} of Option"),
+        }
+    }
+}
+
+impl<T: ToArg, E: ToArg> ToArg for std::result::Result<T, E> {
+    fn to_arg(&self) -> String {
+        match self {
+            Ok(value) => tuple_arg("Ok", &[value.to_arg()]),
+            Err(err) => tuple_arg("Err", &[err.to_arg()]),
+        }
+    }
+}
+
+impl<T: FromValue, E: FromValue> FromValue for std::result::Result<T, E> {
+    fn from_value(value: Value) -> Result<Self> {
+        let (variant, mut fields) = Fields::variant(value, "Result")?;
+        match variant.as_str() {
+            "Ok" => Ok(Ok(fields.next_field()?)),
+            "Err" => Ok(Err(fields.next_field()?)),
+            other => anyhow::bail!("Unknown variant {other} of Result"),
+        }
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($name:ident),+) => {
+        impl<$($name: ToArg),+> ToArg for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn to_arg(&self) -> String {
+                let ($($name,)+) = self;
+                format!("({})", [$($name.to_arg()),+].join(", "))
+            }
+        }
+
+        impl<$($name: FromValue),+> FromValue for ($($name,)+) {
+            fn from_value(value: Value) -> Result<Self> {
+                let mut fields = Fields::new(value, "tuple")?;
+                Ok(($(fields.next_field::<$name>()?,)+))
+            }
+        }
+    };
+}
+
+impl_tuple!(A, B);
+impl_tuple!(A, B, C);
+impl_tuple!(A, B, C, D);
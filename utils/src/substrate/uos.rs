@@ -0,0 +1,132 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use scale::{Compact, Decode, Encode};
+use sp_runtime::MultiSignature;
+use std::convert::TryFrom;
+use subxt::tx::TxPayload;
+
+use super::offline::{OfflineChain, OfflineTxParams};
+use super::signer::CryptoScheme;
+
+/// First byte of UOS payloads of Substrate chains.
+const SUBSTRATE_ID: u8 = 0x53;
+/// Signs a mortal extrinsic, shown decoded on the device.
+const CMD_SIGN_TX: u8 = 0x00;
+/// Signs an immortal extrinsic, shown decoded on the device.
+const CMD_SIGN_IMMORTAL_TX: u8 = 0x02;
+/// First byte of the frames of a multipart QR code.
+const MULTIPART: u8 = 0x00;
+/// Bytes of payload per frame, small enough for phone cameras to scan quickly.
+pub const DEFAULT_FRAME_SIZE: usize = 1024;
+
+/// A signing request in the Universal Offline Signatures format of Parity Signer
+/// (now Polkadot Vault), shown to the device as a sequence of QR frames.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UosPayload {
+    bytes: Vec<u8>,
+}
+
+impl UosPayload {
+    /// Request to sign `call` with the key `public`, registered on the device with
+    /// `scheme`. The device decodes the call with the metadata it holds for the chain
+    /// of `chain`, which must match its runtime version.
+    pub fn transaction<Call: TxPayload>(
+        chain: &OfflineChain,
+        call: &Call,
+        params: &OfflineTxParams,
+        scheme: CryptoScheme,
+        public: &[u8],
+    ) -> Result<Self> {
+        let (crypto, public_len) = match scheme {
+            CryptoScheme::Ed25519 => (0x00, 32),
+            CryptoScheme::Sr25519 => (0x01, 32),
+            CryptoScheme::Ecdsa => (0x02, 33),
+        };
+        if public.len() != public_len {
+            anyhow::bail!(
+                "{scheme:?} public keys are {public_len} bytes, got {}",
+                public.len()
+            );
+        }
+        let command = match params.mortality {
+            Some(_) => CMD_SIGN_TX,
+            None => CMD_SIGN_IMMORTAL_TX,
+        };
+        let call_data = chain.call_data(call)?;
+
+        let mut bytes = vec![SUBSTRATE_ID, crypto, command];
+        bytes.extend_from_slice(public);
+        // The device reads the call with its length, unlike the signer payload.
+        Compact(call_data.len() as u32).encode_to(&mut bytes);
+        bytes.extend_from_slice(&call_data);
+        bytes.extend_from_slice(&chain.extensions(params)?);
+        bytes.extend_from_slice(chain.genesis_hash().as_bytes());
+        Ok(Self { bytes })
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The payload split into multipart frames of at most `frame_size` bytes of
+    /// payload each, to be encoded as binary QR codes and shown in a loop.
+    ///
+    /// Each frame starts with the multipart marker, the number of frames and its
+    /// index, both as big-endian `u16`s.
+    pub fn frames(&self, frame_size: usize) -> Result<Vec<Vec<u8>>> {
+        let chunks: Vec<&[u8]> = self.bytes.chunks(frame_size.max(1)).collect();
+        let count =
+            u16::try_from(chunks.len()).context("Payload too large for a multipart QR code")?;
+        Ok(chunks
+            .into_iter()
+            .enumerate()
+            .map(|(index, chunk)| {
+                let mut frame = vec![MULTIPART];
+                frame.extend_from_slice(&count.to_be_bytes());
+                frame.extend_from_slice(&(index as u16).to_be_bytes());
+                frame.extend_from_slice(chunk);
+                frame
+            })
+            .collect())
+    }
+
+    /// The [`UosPayload::frames`] rendered as SVG QR codes.
+    #[cfg(feature = "qr")]
+    pub fn qr_svgs(&self, frame_size: usize) -> Result<Vec<String>> {
+        self.frames(frame_size)?
+            .iter()
+            .map(|frame| {
+                let code = qrcode::QrCode::new(frame)?;
+                Ok(code.render::<qrcode::render::svg::Color>().build())
+            })
+            .collect()
+    }
+}
+
+/// Reads the signature QR code shown by the device once it signed a payload, e.g.
+/// `0x01…`, as scanned: the hex encoded `MultiSignature`.
+pub fn parse_signature(scanned: &str) -> Result<MultiSignature> {
+    let scanned = scanned.trim();
+    let bytes = hex::decode(scanned.strip_prefix("0x").unwrap_or(scanned))
+        .context("Signature QR code is not hex encoded")?;
+    let mut input = bytes.as_slice();
+    let signature =
+        MultiSignature::decode(&mut input).context("Signature QR code is not a signature")?;
+    if !input.is_empty() {
+        anyhow::bail!("Signature QR code has {} trailing bytes", input.len());
+    }
+    Ok(signature)
+}
@@ -0,0 +1,171 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use super::contract::error::ErrorVariant;
+use super::contract::query::{CallOptions, CallResult};
+use super::contract::ContractInstance;
+use super::Nonce;
+
+/// How often the artifacts are checked by default.
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(500);
+/// Files written by `cargo contract build`.
+const ARTIFACT_EXTENSIONS: &[&str] = &["contract", "json", "wasm", "polkavm"];
+
+/// Modification time and size of each artifact.
+type Snapshot = BTreeMap<PathBuf, (SystemTime, u64)>;
+
+/// Watches the artifacts `cargo contract build` writes, typically in `target/ink`,
+/// e.g. to re-run a query whenever the contract is rebuilt.
+///
+/// The directory and its per-contract subdirectories are polled, which works the
+/// same on every platform and filesystem, including mounted volumes.
+pub struct ArtifactWatcher {
+    dir: PathBuf,
+    interval: Duration,
+}
+
+impl ArtifactWatcher {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+
+    /// Watches `target/ink` of the workspace at `workspace_root`.
+    pub fn for_workspace(workspace_root: &Path) -> Self {
+        Self::new(workspace_root.join("target").join("ink"))
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Calls `on_change` with the artifacts that changed each time they change, until
+    /// it returns `false`.
+    ///
+    /// Changes are reported once the artifacts stopped changing for an interval, as
+    /// a build writes the bundle, metadata and code one after the other.
+    pub fn watch(&self, mut on_change: impl FnMut(&[PathBuf]) -> bool) -> Result<()> {
+        let mut last = self.snapshot()?;
+        loop {
+            thread::sleep(self.interval);
+            let mut current = self.snapshot()?;
+            if current == last {
+                continue;
+            }
+            loop {
+                thread::sleep(self.interval);
+                let settled = self.snapshot()?;
+                if settled == current {
+                    break;
+                }
+                current = settled;
+            }
+            let changed = changed_paths(&last, &current);
+            last = current;
+            if !on_change(&changed) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Runs a query of `contract` now and again each time the artifacts change,
+    /// passing each result to `on_result` until it returns `false`.
+    ///
+    /// The metadata is read again by every query, so that the encoding of the
+    /// arguments and the decoding of the result follow the rebuilt contract. Errors,
+    /// e.g. of a message renamed by the change, are passed on rather than ending the
+    /// watch. Phala queries are sent with a fresh random nonce.
+    pub fn watch_query(
+        &self,
+        contract: &ContractInstance,
+        msg_name: &str,
+        args: Vec<String>,
+        options: CallOptions,
+        mut on_result: impl FnMut(Result<CallResult, ErrorVariant>) -> bool,
+    ) -> Result<()> {
+        let query = || {
+            let nonce = rand::random::<Nonce>();
+            contract.query_msg(msg_name, args.clone(), Some(nonce), options.clone())
+        };
+        if !on_result(query()) {
+            return Ok(());
+        }
+        self.watch(|changed| {
+            tracing::info!(
+                ?changed,
+                "Contract artifacts changed, querying {msg_name} again"
+            );
+            on_result(query())
+        })
+    }
+
+    fn snapshot(&self) -> Result<Snapshot> {
+        let mut snapshot = Snapshot::new();
+        let entries = fs::read_dir(&self.dir)
+            .with_context(|| format!("Failed to read {}", self.dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                for nested in fs::read_dir(&path)? {
+                    record(&mut snapshot, nested?.path());
+                }
+            } else {
+                record(&mut snapshot, path);
+            }
+        }
+        Ok(snapshot)
+    }
+}
+
+/// Adds `path` to `snapshot` if it is an artifact, skipping files removed meanwhile.
+fn record(snapshot: &mut Snapshot, path: PathBuf) {
+    let is_artifact = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ARTIFACT_EXTENSIONS.contains(&ext));
+    if !is_artifact {
+        return;
+    }
+    if let Ok(metadata) = fs::metadata(&path) {
+        if let Ok(modified) = metadata.modified() {
+            snapshot.insert(path, (modified, metadata.len()));
+        }
+    }
+}
+
+/// Artifacts added, modified or removed between two snapshots.
+fn changed_paths(before: &Snapshot, after: &Snapshot) -> Vec<PathBuf> {
+    let mut changed: Vec<PathBuf> = after
+        .iter()
+        .filter(|(path, state)| before.get(*path) != Some(*state))
+        .map(|(path, _)| path.clone())
+        .collect();
+    changed.extend(
+        before
+            .keys()
+            .filter(|path| !after.contains_key(*path))
+            .cloned(),
+    );
+    changed
+}
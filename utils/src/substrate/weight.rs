@@ -0,0 +1,115 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sp_weights::Weight;
+
+/// Maximum proof size of a parachain block, `MAX_POV_SIZE` of the relay chain.
+pub const PARACHAIN_MAX_PROOF_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Limit of the weight a contract call may consume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WeightLimit {
+    /// No limit, the call may consume up to the maximum block weight.
+    ///
+    /// Extrinsics then take their limit from a dry-run, see [`WeightDefaults::with_margin`].
+    #[default]
+    Unlimited,
+    /// A V1 weight, limiting the `ref_time` only. The proof size is unbounded in
+    /// dry-runs and set from the [`WeightDefaults`] of the chain in extrinsics, or
+    /// from a dry-run on chains without a known bound.
+    V1(u64),
+    /// A V2 weight, limiting both the `ref_time` and the proof size.
+    V2(Weight),
+}
+
+impl WeightLimit {
+    /// Gas limit of a dry-run, `None` for the maximum block weight.
+    pub fn dry_run(&self) -> Option<Weight> {
+        match self {
+            WeightLimit::Unlimited => None,
+            WeightLimit::V1(ref_time) => Some(Weight::from_parts(*ref_time, u64::MAX)),
+            WeightLimit::V2(weight) => Some(*weight),
+        }
+    }
+
+    /// Gas limit of an extrinsic, `None` if it must be taken from a dry-run, see
+    /// [`WeightLimit::with_dry_run`].
+    pub fn extrinsic(&self, defaults: &WeightDefaults) -> Option<Weight> {
+        match self {
+            WeightLimit::Unlimited => None,
+            WeightLimit::V1(_) if defaults.max_proof_size == u64::MAX => None,
+            WeightLimit::V1(ref_time) => {
+                Some(Weight::from_parts(*ref_time, defaults.max_proof_size))
+            }
+            WeightLimit::V2(weight) => Some(*weight),
+        }
+    }
+
+    /// Gas limit of an extrinsic whose dry-run required `required`, when
+    /// [`WeightLimit::extrinsic`] has none. A V1 limit keeps its `ref_time`.
+    pub fn with_dry_run(&self, defaults: &WeightDefaults, required: Weight) -> Weight {
+        let estimated = defaults.with_margin(required);
+        match self {
+            WeightLimit::V1(ref_time) => Weight::from_parts(*ref_time, estimated.proof_size()),
+            _ => estimated,
+        }
+    }
+
+    /// The `ref_time` limit, e.g. for Phala contracts, which are not charged for proof size.
+    pub fn ref_time(&self) -> Option<u64> {
+        match self {
+            WeightLimit::Unlimited => None,
+            WeightLimit::V1(ref_time) => Some(*ref_time),
+            WeightLimit::V2(weight) => Some(weight.ref_time()),
+        }
+    }
+}
+
+impl From<Weight> for WeightLimit {
+    fn from(weight: Weight) -> Self {
+        WeightLimit::V2(weight)
+    }
+}
+
+/// How the weights of a chain are limited, see [`crate::substrate::chain::Chain::weight_defaults`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeightDefaults {
+    /// Proof size of V1 limits and upper bound of the proof size of extrinsic limits,
+    /// `u64::MAX` on chains without a known bound.
+    pub max_proof_size: u64,
+    /// Margin added to the weight required by a dry-run when using it as the limit of
+    /// an extrinsic, in percent, as the state can change before the extrinsic executes.
+    pub margin_percent: u64,
+}
+
+impl Default for WeightDefaults {
+    fn default() -> Self {
+        Self {
+            max_proof_size: u64::MAX,
+            margin_percent: 10,
+        }
+    }
+}
+
+impl WeightDefaults {
+    /// Extrinsic limit for a call whose dry-run required `required`.
+    pub fn with_margin(&self, required: Weight) -> Weight {
+        let margin =
+            |value: u64| value.saturating_add(value.saturating_mul(self.margin_percent) / 100);
+        Weight::from_parts(
+            margin(required.ref_time()),
+            margin(required.proof_size()).min(self.max_proof_size),
+        )
+    }
+}
@@ -0,0 +1,278 @@
+// Copyright (C) 2022-2023 <company>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::{Context, Result};
+use scale::{Decode, Encode};
+use sp_weights::Weight;
+use subxt::dynamic::{self, Value};
+use subxt::tx::{DynamicPayload, TxPayload};
+
+use super::{Balance, Client, RpcTransport, WsTransport};
+
+/// Chain sending an XCM message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XcmSender {
+    /// The relay chain, sending with `XcmPallet`.
+    RelayChain,
+    /// Parachain with this id, sending with `PolkadotXcm`.
+    Parachain(u32),
+}
+
+impl XcmSender {
+    fn pallet(&self) -> &'static str {
+        match self {
+            XcmSender::RelayChain => "XcmPallet",
+            XcmSender::Parachain(_) => "PolkadotXcm",
+        }
+    }
+
+    /// Location of parachain `para_id` seen from the sender.
+    fn destination(&self, para_id: u32) -> Value {
+        match self {
+            XcmSender::RelayChain => location(0, Some(para_id)),
+            XcmSender::Parachain(_) => location(1, Some(para_id)),
+        }
+    }
+
+    /// Location of the sender seen from a parachain.
+    fn location(&self) -> Value {
+        match self {
+            XcmSender::RelayChain => location(1, None),
+            XcmSender::Parachain(para_id) => location(1, Some(*para_id)),
+        }
+    }
+}
+
+/// Origin the remote chain dispatches a `Transact` call with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OriginKind {
+    /// The native origin of the sender, e.g. a parachain origin.
+    Native,
+    /// The sovereign account of the sender on the remote chain.
+    SovereignAccount,
+    /// Root, if the remote chain trusts the sender as much.
+    Superuser,
+    /// The XCM location of the sender, for pallets reading it.
+    Xcm,
+}
+
+impl OriginKind {
+    fn name(&self) -> &'static str {
+        match self {
+            OriginKind::Native => "Native",
+            OriginKind::SovereignAccount => "SovereignAccount",
+            OriginKind::Superuser => "Superuser",
+            OriginKind::Xcm => "Xcm",
+        }
+    }
+}
+
+/// A call of a remote parachain wrapped in an XCM `Transact`, e.g. to govern its
+/// contracts from another chain.
+///
+/// The message is sent with the `send` call of the XCM pallet of the sender. With fees,
+/// the remote chain buys execution with its native token withdrawn from the sender's
+/// sovereign account, and deposits what is left back into it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XcmTransact {
+    sender: XcmSender,
+    para_id: u32,
+    call: Vec<u8>,
+    origin_kind: OriginKind,
+    weight: Option<Weight>,
+    fees: Option<Balance>,
+}
+
+impl XcmTransact {
+    /// Wraps `call`, a call encoded for the runtime of parachain `para_id`, sent by
+    /// `sender`.
+    pub fn new(sender: XcmSender, para_id: u32, call: Vec<u8>) -> Self {
+        Self {
+            sender,
+            para_id,
+            call,
+            origin_kind: OriginKind::SovereignAccount,
+            weight: None,
+            fees: None,
+        }
+    }
+
+    /// Wraps `payload`, encoded with the metadata of the chain at `remote_url`.
+    pub async fn from_payload<Call: TxPayload>(
+        sender: XcmSender,
+        remote_url: &str,
+        para_id: u32,
+        payload: &Call,
+    ) -> Result<Self> {
+        let client = Client::from_url(remote_url).await?;
+        let call = client.tx().call_data(payload)?;
+        Ok(Self::new(sender, para_id, call))
+    }
+
+    pub fn origin_kind(mut self, origin_kind: OriginKind) -> Self {
+        self.origin_kind = origin_kind;
+        self
+    }
+
+    /// Maximum weight the remote call may consume, see [`XcmTransact::estimate_weight`].
+    pub fn weight(mut self, weight: Weight) -> Self {
+        self.weight = Some(weight);
+        self
+    }
+
+    /// Native tokens of the remote chain paying for the execution of the message.
+    pub fn fees(mut self, fees: Balance) -> Self {
+        self.fees = Some(fees);
+        self
+    }
+
+    pub fn call(&self) -> &[u8] {
+        &self.call
+    }
+
+    /// Sets the weight of the call to what the chain at `remote_url` charges for it.
+    ///
+    /// The weight of `Contracts::call` includes its gas limit, which must therefore
+    /// be set, e.g. from a dry-run as [`ContractInstance::call_payload`](crate::substrate::ContractInstance::call_payload)
+    /// does.
+    pub async fn estimate_weight(mut self, remote_url: &str) -> Result<Self> {
+        self.weight = Some(estimate_weight(remote_url, &self.call).await?);
+        Ok(self)
+    }
+
+    /// The `send` extrinsic to submit on the local chain.
+    pub fn payload(&self) -> Result<DynamicPayload> {
+        let weight = self
+            .weight
+            .context("The weight of the remote call must be set or estimated")?;
+        let mut instructions = Vec::new();
+        if let Some(fees) = self.fees {
+            instructions.push(Value::unnamed_variant(
+                "WithdrawAsset",
+                vec![Value::unnamed_composite(vec![native_asset(fees)])],
+            ));
+            instructions.push(Value::named_variant(
+                "BuyExecution",
+                vec![
+                    ("fees", native_asset(fees)),
+                    ("weight_limit", Value::unnamed_variant("Unlimited", vec![])),
+                ],
+            ));
+        }
+        instructions.push(Value::named_variant(
+            "Transact",
+            vec![
+                (
+                    "origin_kind",
+                    Value::unnamed_variant(self.origin_kind.name(), vec![]),
+                ),
+                ("require_weight_at_most", weight_value(weight)),
+                (
+                    "call",
+                    Value::named_composite(vec![("encoded", Value::from_bytes(&self.call))]),
+                ),
+            ],
+        ));
+        if self.fees.is_some() {
+            instructions.push(Value::unnamed_variant("RefundSurplus", vec![]));
+            instructions.push(Value::named_variant(
+                "DepositAsset",
+                vec![
+                    (
+                        "assets",
+                        Value::unnamed_variant(
+                            "Wild",
+                            vec![Value::unnamed_variant("AllCounted", vec![Value::u128(1)])],
+                        ),
+                    ),
+                    ("beneficiary", self.sender.location()),
+                ],
+            ));
+        }
+
+        Ok(dynamic::tx(
+            self.sender.pallet(),
+            "send",
+            vec![
+                Value::unnamed_variant("V3", vec![self.sender.destination(self.para_id)]),
+                Value::unnamed_variant(
+                    "V3",
+                    vec![Value::unnamed_composite(vec![Value::unnamed_composite(
+                        instructions,
+                    )])],
+                ),
+            ],
+        ))
+    }
+}
+
+/// Weight the chain at `url` charges for `call`, from the `TransactionPaymentCallApi`.
+pub async fn estimate_weight(url: &str, call: &[u8]) -> Result<Weight> {
+    // The call is passed as is, followed by its length.
+    let mut args = call.to_vec();
+    (call.len() as u32).encode_to(&mut args);
+    let bytes = WsTransport::new(url.to_owned())
+        .state_call("TransactionPaymentCallApi_query_call_info", args, None)
+        .await
+        .context("Failed to estimate the weight of the remote call")?;
+    let info = RuntimeDispatchInfo::decode(&mut bytes.as_slice())?;
+    Ok(info.weight)
+}
+
+/// Dispatch info of a call, as returned by `TransactionPaymentCallApi_query_call_info`.
+#[derive(Decode)]
+struct RuntimeDispatchInfo {
+    weight: Weight,
+    _class: u8,
+    _partial_fee: Balance,
+}
+
+/// `MultiLocation` of the chain `parents` levels up, then down to parachain `para_id`.
+fn location(parents: u8, para_id: Option<u32>) -> Value {
+    let interior = match para_id {
+        Some(para_id) => Value::unnamed_variant(
+            "X1",
+            vec![Value::unnamed_variant(
+                "Parachain",
+                vec![Value::u128(para_id.into())],
+            )],
+        ),
+        None => Value::unnamed_variant("Here", vec![]),
+    };
+    Value::named_composite(vec![
+        ("parents", Value::u128(parents.into())),
+        ("interior", interior),
+    ])
+}
+
+/// `amount` of the native token of the chain executing the message.
+fn native_asset(amount: Balance) -> Value {
+    Value::named_composite(vec![
+        (
+            "id",
+            Value::unnamed_variant("Concrete", vec![location(0, None)]),
+        ),
+        (
+            "fun",
+            Value::unnamed_variant("Fungible", vec![Value::u128(amount)]),
+        ),
+    ])
+}
+
+fn weight_value(weight: Weight) -> Value {
+    Value::named_composite(vec![
+        ("ref_time", Value::u128(weight.ref_time().into())),
+        ("proof_size", Value::u128(weight.proof_size().into())),
+    ])
+}